@@ -0,0 +1,67 @@
+//! Concurrent blocklist checks behind a `Mutex` against a `RwLock`, as the
+//! proxy's request handlers make them. Run with `cargo bench --bench blocker_lock`.
+
+use std::sync::{Mutex, RwLock};
+use std::thread;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use detrack_proxy::tracker_blocker::TrackerBlocker;
+
+const LIST_SIZE: usize = 50_000;
+
+/// Checks each thread makes per iteration
+const CHECKS_PER_THREAD: usize = 1_000;
+
+const HOSTS: [&str; 4] = [
+    "tracker49999.ads-network46.com",
+    "cdn.eu.tracker123.ads-network26.com",
+    "static.images.example-news-site.co.uk",
+    "www.example.org",
+];
+
+fn large_blocker() -> TrackerBlocker {
+    let trackers: Vec<String> = (0..LIST_SIZE)
+        .map(|i| format!("tracker{}.ads-network{}.com", i, i % 97))
+        .collect();
+
+    let dir = std::env::temp_dir().join(format!("detrack-bench-lock-{}", std::process::id()));
+    let path = dir.join("trackers.txt");
+    std::fs::create_dir_all(&dir).expect("failed to create bench directory");
+    std::fs::write(&path, trackers.join("\n")).expect("failed to write tracker list");
+
+    TrackerBlocker::new(&path).expect("failed to load tracker list")
+}
+
+/// Run `CHECKS_PER_THREAD` checks on each of `threads` threads at once
+fn check_concurrently(threads: usize, check: impl Fn(&str) -> bool + Sync) {
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                for i in 0..CHECKS_PER_THREAD {
+                    black_box(check(black_box(HOSTS[i % HOSTS.len()])));
+                }
+            });
+        }
+    });
+}
+
+fn bench_locks(c: &mut Criterion) {
+    let mutex = Mutex::new(large_blocker());
+    let rwlock = RwLock::new(large_blocker());
+
+    let mut group = c.benchmark_group("blocker_lock");
+    for threads in [1, 4, 8] {
+        group.throughput(Throughput::Elements((threads * CHECKS_PER_THREAD) as u64));
+        group.bench_with_input(BenchmarkId::new("mutex", threads), &threads, |b, &threads| {
+            b.iter(|| check_concurrently(threads, |host| mutex.lock().unwrap().is_blocked(host)))
+        });
+        group.bench_with_input(BenchmarkId::new("rwlock", threads), &threads, |b, &threads| {
+            b.iter(|| check_concurrently(threads, |host| rwlock.read().unwrap().is_blocked(host)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_locks);
+criterion_main!(benches);
@@ -0,0 +1,122 @@
+//! Per-request hot paths: blocklist matching, URL cleaning and the AI
+//! heuristics. Run with `cargo bench --bench hot_paths`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use detrack_proxy::ai_tracker::AITracker;
+use detrack_proxy::tracker_blocker::TrackerBlocker;
+
+/// Requests as a browser sends them while loading a typical news page.
+const REQUESTS: &[(&str, &str, Option<&str>)] = &[
+    (
+        "https://www.example-news.com/world/2024/05/article-title?utm_source=twitter&utm_medium=social&utm_campaign=spring",
+        "www.example-news.com",
+        None,
+    ),
+    (
+        "https://cdn.example-news.com/assets/app.3f9a1c.js",
+        "cdn.example-news.com",
+        Some("https://www.example-news.com/world/2024/05/article-title"),
+    ),
+    (
+        "https://www.google-analytics.com/g/collect?v=2&tid=G-ABC123&cid=555.666&_gid=123&en=page_view&dl=https%3A%2F%2Fwww.example-news.com%2F",
+        "www.google-analytics.com",
+        Some("https://www.example-news.com/"),
+    ),
+    (
+        "https://px.ads.linkedin.com/collect/?pid=123456&fmt=gif&url=https%3A%2F%2Fwww.example-news.com%2F",
+        "px.ads.linkedin.com",
+        Some("https://www.example-news.com/"),
+    ),
+    (
+        "https://a8f3k2x9q.cloudfront.net/pixel/1x1.gif?uid=8837461923&ts=1715000000",
+        "a8f3k2x9q.cloudfront.net",
+        Some("https://www.example-news.com/"),
+    ),
+    (
+        "https://fonts.gstatic.com/s/roboto/v30/KFOmCnqEu92Fr1Mu4mxK.woff2",
+        "fonts.gstatic.com",
+        Some("https://www.example-news.com/"),
+    ),
+    (
+        "https://shop.example.org/products/12345678?ref=newsletter&fbclid=IwAR0abc&color=blue&size=m",
+        "shop.example.org",
+        Some("https://mail.example.net/"),
+    ),
+];
+
+/// A blocker loaded with `size` generated domains plus a few real-world ones.
+fn blocker_with_list(size: usize) -> TrackerBlocker {
+    let mut trackers: Vec<String> = (0..size)
+        .map(|i| format!("tracker{}.ads-network{}.com", i, i % 97))
+        .collect();
+    trackers.extend(
+        ["google-analytics.com", "ads.linkedin.com", "doubleclick.net", "facebook.net"]
+            .iter()
+            .map(|s| s.to_string()),
+    );
+
+    let dir = std::env::temp_dir().join(format!("detrack-bench-hot-{}", std::process::id()));
+    let path = dir.join("trackers.txt");
+    std::fs::create_dir_all(&dir).expect("failed to create bench directory");
+    std::fs::write(&path, trackers.join("\n")).expect("failed to write tracker list");
+
+    TrackerBlocker::new(&path).expect("failed to load tracker list")
+}
+
+fn bench_blocklist(c: &mut Criterion) {
+    let mut group = c.benchmark_group("blocklist_matching");
+    group.throughput(Throughput::Elements(REQUESTS.len() as u64));
+    for size in [1_000, 50_000, 200_000] {
+        let blocker = blocker_with_list(size);
+        group.bench_function(format!("{}_entries", size), |b| {
+            b.iter(|| {
+                for (_, host, _) in REQUESTS {
+                    black_box(blocker.matching_tracker(black_box(host)));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_clean_url(c: &mut Criterion) {
+    let blocker = blocker_with_list(0);
+
+    let mut group = c.benchmark_group("clean_url");
+    group.throughput(Throughput::Elements(REQUESTS.len() as u64));
+    group.bench_function("page_load", |b| {
+        b.iter(|| {
+            for (url, _, _) in REQUESTS {
+                black_box(blocker.clean_url(black_box(url)));
+            }
+        })
+    });
+    group.finish();
+}
+
+fn bench_entropy(c: &mut Criterion) {
+    let mut group = c.benchmark_group("entropy");
+    for host in ["www.example-news.com", "a8f3k2x9q.cloudfront.net"] {
+        group.bench_function(host, |b| b.iter(|| AITracker::calculate_entropy(black_box(host))));
+    }
+    group.finish();
+}
+
+fn bench_ai_features(c: &mut Criterion) {
+    let tracker = AITracker::new();
+
+    let mut group = c.benchmark_group("ai_score");
+    group.throughput(Throughput::Elements(REQUESTS.len() as u64));
+    group.bench_function("page_load", |b| {
+        b.iter(|| {
+            for (url, host, referer) in REQUESTS {
+                black_box(tracker.score(black_box(url), black_box(host), black_box(*referer)));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_blocklist, bench_clean_url, bench_entropy, bench_ai_features);
+criterion_main!(benches);
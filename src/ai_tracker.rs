@@ -1,8 +1,39 @@
 use url::Url;
+use chrono::{DateTime, Utc};
 use std::collections::HashMap;
 use std::fs;
 use std::io::{self, Read, Write};
 use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+/// Where the AI model is kept between runs
+pub const AI_MODEL_PATH: &str = "ai_model.json";
+
+/// How long "Snooze" keeps a host out of the suggestion queue
+pub const AI_SNOOZE_HOURS: i64 = 24;
+
+/// How far one piece of feedback moves the feature weights
+const LEARNING_RATE: f32 = 0.05;
+
+/// Longest URL analyzed; longer ones are scored as having no features
+pub const MAX_ANALYZED_URL_LEN: usize = 8192;
+
+/// Characters of a host considered for entropy, the DNS limit
+pub const MAX_ENTROPY_INPUT_LEN: usize = 253;
+
+/// Shortest hex path segment taken for an encoded identifier; shorter hex
+/// is more often a version or short hash
+const MIN_HEX_PAYLOAD_LEN: usize = 32;
+
+/// Shortest base64 path segment taken for an encoded identifier
+const MIN_BASE64_PAYLOAD_LEN: usize = 24;
+
+/// Files whose paths often carry content hashes for cache busting, so long
+/// hex or base64 segments in them aren't identifiers
+const STATIC_ASSET_EXTENSIONS: &[&str] = &[
+    "js", "mjs", "css", "map", "png", "jpg", "jpeg", "gif", "webp", "avif", "svg", "ico",
+    "woff", "woff2", "ttf", "otf", "eot", "mp4", "webm", "wasm",
+];
 
 /// AI Tracker Detection module for DeTrack Proxy
 /// Uses fingerprinting and heuristic methods to identify potential trackers
@@ -12,6 +43,9 @@ pub struct AITracker {
     enabled: bool,
     confidence_threshold: f32,
     
+    // Treat requests to suspicious paths that carry no Referer as third-party
+    no_referer_heuristic: bool,
+    
     // Model parameters (would be learned/tuned over time)
     feature_weights: FeatureWeights,
     
@@ -22,22 +56,46 @@ pub struct AITracker {
     // Cache for previous decisions to improve performance
     decision_cache: HashMap<String, bool>,
     
+    // Features of the last analyzed request per host, used to learn from feedback
+    recent_features: HashMap<String, RequestFeatures>,
+    
+    // Hosts not to suggest again until the given time, without a verdict either way
+    snoozed: HashMap<String, DateTime<Utc>>,
+    
     // Statistics
     detection_count: usize,
     false_positive_count: usize,
     false_negative_count: usize,
 }
 
-#[derive(Debug, Clone)]
-struct FeatureWeights {
-    tracking_param_weight: f32,
-    suspicious_path_weight: f32,
-    numeric_id_weight: f32,
-    domain_entropy_weight: f32,
-    third_party_weight: f32,
-    suspicious_keywords_weight: f32,
-    path_depth_weight: f32,
-    query_count_weight: f32,
+/// How much each request feature contributes to the confidence score.
+/// Adjusted by user feedback, always within 0.0 to 1.0.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeatureWeights {
+    pub tracking_param_weight: f32,
+    pub suspicious_path_weight: f32,
+    pub numeric_id_weight: f32,
+    pub domain_entropy_weight: f32,
+    pub third_party_weight: f32,
+    pub suspicious_keywords_weight: f32,
+    pub path_depth_weight: f32,
+    pub query_count_weight: f32,
+    /// Models saved before this feature existed start from the default
+    #[serde(default = "default_encoded_payload_weight")]
+    pub encoded_payload_weight: f32,
+}
+
+fn default_encoded_payload_weight() -> f32 {
+    0.4
+}
+
+/// The parts of an `AITracker` that are saved to disk
+#[derive(Serialize, Deserialize)]
+struct SavedModel {
+    confidence_threshold: f32,
+    feature_weights: FeatureWeights,
+    known_trackers: Vec<String>,
+    known_legitimate: Vec<String>,
 }
 
 impl Default for FeatureWeights {
@@ -51,12 +109,13 @@ impl Default for FeatureWeights {
             suspicious_keywords_weight: 0.8,
             path_depth_weight: 0.2,
             query_count_weight: 0.3,
+            encoded_payload_weight: default_encoded_payload_weight(),
         }
     }
 }
 
-#[derive(Debug)]
-struct RequestFeatures {
+#[derive(Debug, Clone)]
+pub(crate) struct RequestFeatures {
     has_tracking_params: bool,
     has_suspicious_path: bool,
     has_numeric_id: bool,
@@ -65,6 +124,7 @@ struct RequestFeatures {
     has_suspicious_keywords: bool,
     path_depth: usize,
     query_param_count: usize,
+    has_encoded_payload: bool,
 }
 
 impl AITracker {
@@ -73,30 +133,46 @@ impl AITracker {
         Self {
             enabled: true,
             confidence_threshold: 0.65,
+            no_referer_heuristic: false,
             feature_weights: FeatureWeights::default(),
             known_trackers: Vec::new(),
             known_legitimate: Vec::new(),
             decision_cache: HashMap::new(),
+            recent_features: HashMap::new(),
+            snoozed: HashMap::new(),
             detection_count: 0,
             false_positive_count: 0,
             false_negative_count: 0,
         }
     }
     
-    /// Load AI tracker from file - simplified version without serde
+    /// Load a model saved by [`save`](Self::save)
     pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
-        // Skip loading for now - just create a new instance
-        // In a real implementation, you would parse a custom format here
-        println!("Note: AI model loading from file not implemented in this version");
-        Ok(Self::new())
+        let mut content = String::new();
+        fs::File::open(path)?.read_to_string(&mut content)?;
+        let saved: SavedModel = serde_json::from_str(&content)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        
+        Ok(Self {
+            confidence_threshold: saved.confidence_threshold,
+            feature_weights: saved.feature_weights,
+            known_trackers: saved.known_trackers,
+            known_legitimate: saved.known_legitimate,
+            ..Self::new()
+        })
     }
     
-    /// Save AI tracker to file - simplified version without serde
+    /// Save the learned weights, threshold and known domains as JSON
     pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
-        // Skip saving for now
-        // In a real implementation, you would serialize to a custom format here
-        println!("Note: AI model saving to file not implemented in this version");
-        Ok(())
+        let saved = SavedModel {
+            confidence_threshold: self.confidence_threshold,
+            feature_weights: self.feature_weights.clone(),
+            known_trackers: self.known_trackers.clone(),
+            known_legitimate: self.known_legitimate.clone(),
+        };
+        let content = serde_json::to_string_pretty(&saved)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::File::create(path)?.write_all(content.as_bytes())
     }
     
     /// Enable AI detection
@@ -124,6 +200,18 @@ impl AITracker {
         self.confidence_threshold
     }
     
+    /// Treat requests to suspicious paths (`/pixel`, `/collect`, ...) that
+    /// carry no Referer as third-party, since trackers are often loaded with
+    /// the Referer stripped to dodge referer-based rules.
+    pub fn set_no_referer_heuristic(&mut self, enabled: bool) {
+        self.no_referer_heuristic = enabled;
+        self.decision_cache.clear();
+    }
+    
+    pub fn is_no_referer_heuristic_enabled(&self) -> bool {
+        self.no_referer_heuristic
+    }
+    
     /// Analyze if a request is likely a tracker
     pub fn is_likely_tracker(&mut self, url: &str, host: &str, referer: Option<&str>) -> bool {
         if !self.enabled {
@@ -148,15 +236,18 @@ impl AITracker {
             return false;
         }
         
-        // Extract features from the request
+        // Score the request from its features
         let features = self.extract_features(url, host, referer);
-        
-        // Calculate confidence score
         let confidence = self.calculate_confidence(&features);
         
         // Make decision based on confidence threshold
         let is_tracker = confidence >= self.confidence_threshold;
         
+        // Keep the features of flagged requests so feedback on them can be learned from
+        if is_tracker {
+            self.recent_features.insert(host.to_string(), features);
+        }
+        
         // Cache the decision
         self.decision_cache.insert(url.to_string(), is_tracker);
         
@@ -168,6 +259,13 @@ impl AITracker {
         is_tracker
     }
     
+    /// Confidence score (0.0 to 1.0) for a request, without consulting or
+    /// updating the decision cache
+    pub fn score(&self, url: &str, host: &str, referer: Option<&str>) -> f32 {
+        let features = self.extract_features(url, host, referer);
+        self.calculate_confidence(&features)
+    }
+    
     /// Report a false positive (something that was marked as tracker but isn't)
     pub fn report_false_positive(&mut self, domain: &str) {
         self.false_positive_count += 1;
@@ -180,6 +278,9 @@ impl AITracker {
         // Remove from known trackers if present
         self.known_trackers.retain(|d| d != domain);
         
+        // Make the features that fired count for less
+        self.learn(domain, false);
+        
         // Clear cache entry
         self.decision_cache.remove(domain);
     }
@@ -196,14 +297,92 @@ impl AITracker {
         // Remove from known legitimate if present
         self.known_legitimate.retain(|d| d != domain);
         
+        // Make the features that fired count for more
+        self.learn(domain, true);
+        
         // Clear cache entry
         self.decision_cache.remove(domain);
     }
     
+    /// Keep `domain` out of the suggestion queue until `until`, without
+    /// learning anything from it.
+    pub fn snooze(&mut self, domain: &str, until: DateTime<Utc>) {
+        self.snoozed.insert(domain.to_string(), until);
+    }
+    
+    /// Whether suggestions for `domain` are snoozed at `now`. Expired
+    /// snoozes are forgotten.
+    pub fn is_snoozed(&mut self, domain: &str, now: DateTime<Utc>) -> bool {
+        self.snoozed.retain(|_, until| *until > now);
+        self.snoozed.contains_key(domain)
+    }
+    
+    /// Snoozed hosts and when each snooze ends
+    pub fn snoozed_domains(&self) -> Vec<(String, DateTime<Utc>)> {
+        let mut snoozed: Vec<_> = self.snoozed.iter().map(|(d, until)| (d.clone(), *until)).collect();
+        snoozed.sort_by_key(|(_, until)| *until);
+        snoozed
+    }
+    
+    /// Current feature weights
+    pub fn feature_weights(&self) -> &FeatureWeights {
+        &self.feature_weights
+    }
+    
+    /// Nudge the weights towards the right answer for the last request seen
+    /// from `domain`: each weight moves by the error times how strongly its
+    /// feature fired, so features that weren't present are left alone.
+    fn learn(&mut self, domain: &str, is_tracker: bool) {
+        let Some(features) = self.recent_features.remove(domain) else {
+            return;
+        };
+        
+        let target = if is_tracker { 1.0 } else { 0.0 };
+        let error = target - self.calculate_confidence(&features);
+        let activations = Self::activations(&features);
+        let weights = &mut self.feature_weights;
+        let slots = [
+            &mut weights.tracking_param_weight,
+            &mut weights.suspicious_path_weight,
+            &mut weights.numeric_id_weight,
+            &mut weights.domain_entropy_weight,
+            &mut weights.third_party_weight,
+            &mut weights.suspicious_keywords_weight,
+            &mut weights.path_depth_weight,
+            &mut weights.query_count_weight,
+            &mut weights.encoded_payload_weight,
+        ];
+        
+        for (weight, activation) in slots.into_iter().zip(activations) {
+            *weight = (*weight + LEARNING_RATE * error * activation).clamp(0.0, 1.0);
+        }
+    }
+    
+    /// How strongly each feature fired (0.0 to 1.0), in `FeatureWeights` field order
+    fn activations(features: &RequestFeatures) -> [f32; 9] {
+        let flag = |b: bool| if b { 1.0 } else { 0.0 };
+        [
+            flag(features.has_tracking_params),
+            flag(features.has_suspicious_path),
+            flag(features.has_numeric_id),
+            (features.domain_entropy / 4.5).min(1.0),
+            flag(features.is_third_party),
+            flag(features.has_suspicious_keywords),
+            (features.path_depth as f32 / 10.0).min(1.0),
+            (features.query_param_count as f32 / 20.0).min(1.0),
+            flag(features.has_encoded_payload),
+        ]
+    }
+    
     /// Extract features from a request
-    fn extract_features(&self, url: &str, host: &str, referer: Option<&str>) -> RequestFeatures {
-        // Parse URL
-        let parsed_url = match Url::parse(url) {
+    pub(crate) fn extract_features(&self, url: &str, host: &str, referer: Option<&str>) -> RequestFeatures {
+        // Parse URL; absurdly long ones aren't worth the work and get no features
+        let parsed = if url.len() > MAX_ANALYZED_URL_LEN {
+            Err(url::ParseError::Overflow)
+        } else {
+            Url::parse(url)
+        };
+        let parsed_url = match parsed {
             Ok(url) => url,
             Err(_) => return RequestFeatures {
                 has_tracking_params: false,
@@ -214,6 +393,7 @@ impl AITracker {
                 has_suspicious_keywords: false,
                 path_depth: 0,
                 query_param_count: 0,
+                has_encoded_payload: false,
             },
         };
         
@@ -234,8 +414,11 @@ impl AITracker {
         // Count query parameters
         let query_param_count = query_pairs.len();
         
-        // Check path for suspicious patterns
+        // Check path for suspicious patterns. Matching is done on the decoded,
+        // lowercased path so `/%70ixel` or `/Pixel` can't slip past.
         let path = parsed_url.path();
+        let decoded_path = percent_decode(path);
+        let matched_path = decoded_path.to_lowercase();
         let path_segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
         let path_depth = path_segments.len();
         
@@ -244,13 +427,18 @@ impl AITracker {
             .any(|segment| segment.chars().all(|c| c.is_numeric()) && segment.len() > 5);
         
         // Check for suspicious path patterns
-        let has_suspicious_path = path.contains("/pixel") || 
-                                path.contains("/track") || 
-                                path.contains("/collect") ||
-                                path.contains("/beacon") ||
-                                path.contains("/1x1.gif") ||
-                                path.contains("/1x1.png") ||
-                                path.contains("/impression");
+        let has_suspicious_path = matched_path.contains("/pixel") || 
+                                matched_path.contains("/track") || 
+                                matched_path.contains("/collect") ||
+                                matched_path.contains("/beacon") ||
+                                matched_path.contains("/1x1.gif") ||
+                                matched_path.contains("/1x1.png") ||
+                                matched_path.contains("/impression");
+        
+        // Check for identifiers stuffed into the path as hex or base64,
+        // except in static assets, where long hashes are cache busting
+        let has_encoded_payload = !is_static_asset(&matched_path)
+            && decoded_path.split('/').any(looks_encoded);
         
         // Calculate domain entropy (more random = more likely to be a tracker)
         let domain_entropy = Self::calculate_entropy(host);
@@ -268,11 +456,12 @@ impl AITracker {
                     true
                 }
             },
-            None => false, // Can't determine without referer
+            // Can't tell without a referer, unless the request looks like a beacon
+            None => self.no_referer_heuristic && has_suspicious_path,
         };
         
         // Check for suspicious keywords
-        let url_lower = url.to_lowercase();
+        let url_lower = percent_decode(url).to_lowercase();
         let has_suspicious_keywords = ["analytics", "tracker", "pixel", "stat", "metrics", "telemetry", "beacon", "counter"]
             .iter()
             .any(|&keyword| url_lower.contains(keyword));
@@ -286,6 +475,7 @@ impl AITracker {
             has_suspicious_keywords,
             path_depth,
             query_param_count,
+            has_encoded_payload,
         }
     }
     
@@ -326,15 +516,20 @@ impl AITracker {
         let normalized_query_count = (features.query_param_count as f32 / 20.0).min(1.0);
         confidence += normalized_query_count * self.feature_weights.query_count_weight;
         
+        if features.has_encoded_payload {
+            confidence += self.feature_weights.encoded_payload_weight;
+        }
+        
         // Normalize final confidence to 0-1 range
         confidence = (confidence / 3.0).min(1.0);
         
         confidence
     }
     
-    /// Calculate Shannon entropy of a string
-    fn calculate_entropy(text: &str) -> f32 {
-        let text = text.to_lowercase();
+    /// Calculate Shannon entropy of a string. Only the first
+    /// [`MAX_ENTROPY_INPUT_LEN`] characters are considered.
+    pub fn calculate_entropy(text: &str) -> f32 {
+        let text: String = text.chars().take(MAX_ENTROPY_INPUT_LEN).collect::<String>().to_lowercase();
         let len = text.len() as f32;
         
         if len == 0.0 {
@@ -388,4 +583,180 @@ impl Default for AITracker {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+
+/// Whether `path` names a static asset, by its file extension
+fn is_static_asset(path: &str) -> bool {
+    let file = path.rsplit('/').next().unwrap_or_default();
+    file.rsplit_once('.')
+        .is_some_and(|(_, extension)| STATIC_ASSET_EXTENSIONS.contains(&extension))
+}
+
+/// Whether a path segment looks like an encoded identifier: long hex, or
+/// long base64 mixing upper and lower case letters and digits, which plain
+/// words and slugs don't
+fn looks_encoded(segment: &str) -> bool {
+    let hex = segment.len() >= MIN_HEX_PAYLOAD_LEN
+        && segment.chars().all(|c| c.is_ascii_hexdigit())
+        && segment.chars().any(|c| c.is_ascii_digit())
+        && segment.chars().any(|c| c.is_ascii_alphabetic());
+    if hex {
+        return true;
+    }
+
+    let body = segment.trim_end_matches('=');
+    body.len() >= MIN_BASE64_PAYLOAD_LEN
+        && body.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '_'))
+        && body.chars().any(|c| c.is_ascii_uppercase())
+        && body.chars().any(|c| c.is_ascii_lowercase())
+        && body.chars().any(|c| c.is_ascii_digit())
+}
+
+/// Decode `%XX` escapes in `text`. Malformed escapes are kept as they are.
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| text.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn features(url: &str, referer: Option<&str>) -> RequestFeatures {
+        let host = Url::parse(url).unwrap().host_str().unwrap().to_string();
+        AITracker::new().extract_features(url, &host, referer)
+    }
+
+    #[test]
+    fn encoded_and_capitalized_paths_are_suspicious() {
+        assert!(features("https://cdn.test/%70ixel", None).has_suspicious_path);
+        assert!(features("https://cdn.test/%63OLLECT?id=1", None).has_suspicious_path);
+        assert!(features("https://cdn.test/Pixel", None).has_suspicious_path);
+        assert!(features("https://cdn.test/%62eacon", None).has_suspicious_keywords);
+        assert!(!features("https://cdn.test/%70ictures", None).has_suspicious_path);
+    }
+
+    #[test]
+    fn malformed_escapes_are_kept() {
+        assert_eq!(percent_decode("/a%2"), "/a%2");
+        assert_eq!(percent_decode("/%zz%41"), "/%zzA");
+    }
+
+    #[test]
+    fn tracking_params_are_detected() {
+        assert!(features("https://shop.test/item?utm_source=x", None).has_tracking_params);
+        assert!(features("https://shop.test/item?fbclid=abc", None).has_tracking_params);
+        assert!(features("https://shop.test/item?GCLID=abc", None).has_tracking_params);
+        assert!(!features("https://shop.test/item?color=red", None).has_tracking_params);
+    }
+
+    #[test]
+    fn query_params_are_counted() {
+        assert_eq!(features("https://shop.test/item?a=1&b=2&c=3", None).query_param_count, 3);
+        assert_eq!(features("https://shop.test/item", None).query_param_count, 0);
+    }
+
+    #[test]
+    fn suspicious_paths_are_detected() {
+        assert!(features("https://cdn.test/pixel", None).has_suspicious_path);
+        assert!(features("https://cdn.test/g/collect?v=2", None).has_suspicious_path);
+        assert!(features("https://cdn.test/img/1x1.gif", None).has_suspicious_path);
+        assert!(!features("https://cdn.test/images/logo.png", None).has_suspicious_path);
+    }
+
+    #[test]
+    fn long_numeric_segments_count_as_ids() {
+        let found = features("https://news.test/article/1234567/comments", None);
+        assert!(found.has_numeric_id);
+        assert_eq!(found.path_depth, 3);
+        // Short numbers are usually pages or years, not identifiers
+        assert!(!features("https://news.test/2024/05/story", None).has_numeric_id);
+    }
+
+    #[test]
+    fn encoded_path_segments_raise_confidence() {
+        let tracker = AITracker::new();
+        let encoded = features("https://cdn.test/v/eyJ1aWQiOiI4ZjNhMmIiLCJzIjo0Mn0=/img", None);
+        let words = features("https://cdn.test/v/spring-collection-highlights/img", None);
+        assert!(encoded.has_encoded_payload);
+        assert!(!words.has_encoded_payload);
+        assert!(tracker.calculate_confidence(&encoded) > tracker.calculate_confidence(&words));
+
+        assert!(features("https://cdn.test/u/5f4dcc3b5aa765d61d8327deb882cf99", None).has_encoded_payload);
+        // Short hex is more likely a version or commit
+        assert!(!features("https://cdn.test/build/3f2a9b8c/", None).has_encoded_payload);
+    }
+
+    #[test]
+    fn hashed_asset_paths_are_not_encoded_payloads() {
+        assert!(!features("https://cdn.test/assets/5f4dcc3b5aa765d61d8327deb882cf99/app.js", None).has_encoded_payload);
+        assert!(!features("https://cdn.test/static/Xk9mQ2pLr7TzWb4NvY8aHc1E.woff2", None).has_encoded_payload);
+    }
+
+    #[test]
+    fn models_saved_without_the_encoded_payload_weight_load() {
+        let mut saved: serde_json::Value = serde_json::to_value(FeatureWeights::default()).unwrap();
+        saved.as_object_mut().unwrap().remove("encoded_payload_weight");
+        let weights: FeatureWeights = serde_json::from_value(saved).unwrap();
+        assert_eq!(weights, FeatureWeights::default());
+    }
+
+    #[test]
+    fn cross_host_referer_is_third_party() {
+        assert!(features("https://ads.test/x", Some("https://news.test/story")).is_third_party);
+        assert!(!features("https://cdn.news.test/x", Some("https://news.test/story")).is_third_party);
+        assert!(!features("https://news.test/x", Some("https://www.news.test/")).is_third_party);
+        assert!(features("https://ads.test/x", Some("not a url")).is_third_party);
+    }
+
+    #[test]
+    fn missing_referer_is_third_party_only_with_heuristic() {
+        let mut tracker = AITracker::new();
+        assert!(!tracker.extract_features("https://ads.test/beacon", "ads.test", None).is_third_party);
+
+        tracker.set_no_referer_heuristic(true);
+        assert!(tracker.extract_features("https://ads.test/beacon", "ads.test", None).is_third_party);
+        assert!(!tracker.extract_features("https://ads.test/home", "ads.test", None).is_third_party);
+    }
+
+    #[test]
+    fn keywords_anywhere_in_the_url_are_detected() {
+        assert!(features("https://metrics.shop.test/", None).has_suspicious_keywords);
+        assert!(features("https://shop.test/?src=telemetry", None).has_suspicious_keywords);
+        assert!(!features("https://shop.test/cart", None).has_suspicious_keywords);
+    }
+
+    #[test]
+    fn random_looking_hosts_have_higher_entropy() {
+        let plain = features("https://aaaa.test/", None).domain_entropy;
+        let random = features("https://x7kq2zp9vw.test/", None).domain_entropy;
+        assert!(random > plain, "{} vs {}", random, plain);
+    }
+
+    #[test]
+    fn unparsable_urls_have_no_features() {
+        let found = AITracker::new().extract_features("not a url", "shop.test", Some("https://other.test/"));
+        assert!(!found.has_tracking_params);
+        assert!(!found.is_third_party);
+        assert_eq!(found.domain_entropy, 0.0);
+        assert_eq!(found.path_depth, 0);
+    }
+}
@@ -0,0 +1,92 @@
+//! Heartbeat beacon detection: the same host and path requested over and
+//! over at a steady interval, as analytics pings and session heartbeats are.
+//!
+//! This is a timing signal, so it catches trackers whose URLs look harmless
+//! to the URL-based AI features.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use chrono::{DateTime, Duration, Utc};
+
+/// How far back requests are counted
+pub const BEACON_WINDOW_SECS: i64 = 60;
+
+/// Identical requests per minute that count as a beacon unless configured otherwise
+pub const DEFAULT_BEACON_THRESHOLD: usize = 6;
+
+/// Most intervals may stray from the average by this fraction and still be
+/// regular. Loose enough for timer jitter, tight enough to skip bursts of
+/// page loads.
+const MAX_INTERVAL_DEVIATION: f64 = 0.5;
+
+/// Most (host, path) pairs kept before idle ones are dropped
+const MAX_TRACKED_PATHS: usize = 4096;
+
+/// Most flagged hosts remembered before they're forgotten, after which a
+/// host may be reported again
+const MAX_FLAGGED_HOSTS: usize = 4096;
+
+/// Recent request times per host and path
+#[derive(Default)]
+pub struct BeaconDetector {
+    requests: HashMap<(String, String), VecDeque<DateTime<Utc>>>,
+    /// Hosts already reported, so each is flagged once
+    flagged: HashSet<String>,
+}
+
+impl BeaconDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a request and report whether it completes a beacon pattern:
+    /// at least `threshold` requests for `path` on `host` within the window,
+    /// spaced at regular intervals. Returns the request count the first
+    /// time a host is caught, `None` otherwise.
+    pub fn record(&mut self, host: &str, path: &str, now: DateTime<Utc>, threshold: usize) -> Option<usize> {
+        if threshold < 2 || self.flagged.contains(host) {
+            return None;
+        }
+
+        let window_start = now - Duration::seconds(BEACON_WINDOW_SECS);
+        if self.requests.len() >= MAX_TRACKED_PATHS {
+            self.requests.retain(|_, times| times.back().is_some_and(|last| *last > window_start));
+        }
+
+        let times = self.requests.entry((host.to_string(), path.to_string())).or_default();
+        times.push_back(now);
+        while times.front().is_some_and(|first| *first <= window_start) {
+            times.pop_front();
+        }
+
+        if times.len() < threshold || !is_regular(times) {
+            return None;
+        }
+
+        let count = times.len();
+        if self.flagged.len() >= MAX_FLAGGED_HOSTS {
+            self.flagged.clear();
+        }
+        self.flagged.insert(host.to_string());
+        self.requests.retain(|(h, _), _| h != host);
+        Some(count)
+    }
+}
+
+/// Whether the gaps between `times` are all close to their average
+fn is_regular(times: &VecDeque<DateTime<Utc>>) -> bool {
+    let intervals: Vec<f64> = times
+        .iter()
+        .zip(times.iter().skip(1))
+        .map(|(a, b)| (*b - *a).num_milliseconds() as f64)
+        .collect();
+    if intervals.is_empty() {
+        return false;
+    }
+
+    let mean = intervals.iter().sum::<f64>() / intervals.len() as f64;
+    if mean <= 0.0 {
+        return false;
+    }
+    intervals.iter().all(|interval| (interval - mean).abs() <= mean * MAX_INTERVAL_DEVIATION)
+}
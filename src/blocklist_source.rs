@@ -0,0 +1,207 @@
+//! Storage backends for the tracker blocklist.
+//!
+//! The text-file [`TrackerBlocker`](crate::tracker_blocker::TrackerBlocker)
+//! is the default; with the `sqlite` feature enabled,
+//! [`SqliteBlocklist`](crate::sqlite_blocklist::SqliteBlocklist) keeps very
+//! large lists in an indexed table instead.
+
+use std::fmt;
+use std::io;
+use std::net::IpAddr;
+
+use crate::list_formats::ListFormat;
+
+/// How a host matched a blocklist entry
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchKind {
+    /// The host is the entry itself
+    Exact,
+    /// The host is a subdomain of the entry
+    Subdomain,
+    /// The entry is a bare top-level domain, such as `zip`, covering every
+    /// host under it
+    TopLevelDomain,
+}
+
+/// Which blocklist entry matched a host, and how.
+///
+/// Entries are host names only (a leading `*.` is dropped when a list is
+/// read), so there are no separate wildcard or path matches.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatchExplanation {
+    pub host: String,
+    pub rule: String,
+    pub kind: MatchKind,
+}
+
+impl MatchExplanation {
+    /// Classify a match of `host` by the listed entry `rule`
+    pub fn new(host: &str, rule: &str) -> Self {
+        let kind = if host == rule {
+            MatchKind::Exact
+        } else if !rule.contains('.') {
+            MatchKind::TopLevelDomain
+        } else {
+            MatchKind::Subdomain
+        };
+        Self { host: host.to_string(), rule: rule.to_string(), kind }
+    }
+
+    /// Short machine-readable form, e.g. `suffix-match:doubleclick.net`
+    pub fn code(&self) -> String {
+        let kind = match self.kind {
+            MatchKind::Exact => "exact-match",
+            MatchKind::Subdomain => "suffix-match",
+            MatchKind::TopLevelDomain => "tld-match",
+        };
+        format!("{}:{}", kind, self.rule)
+    }
+}
+
+impl fmt::Display for MatchExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            MatchKind::Exact => write!(f, "{} is on the blocklist", self.host),
+            MatchKind::Subdomain => write!(f, "{} is a subdomain of {}", self.host, self.rule),
+            MatchKind::TopLevelDomain => write!(f, "{} is under the blocked top-level domain .{}", self.host, self.rule),
+        }
+    }
+}
+
+/// What merging a list into the blocklist changed
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ImportDiff {
+    /// Domains that weren't on the blocklist before, sorted
+    pub added: Vec<String>,
+    /// Domains that were already on the blocklist
+    pub duplicates: usize,
+    /// Lines of the imported file that weren't valid entries
+    pub malformed: usize,
+    /// Rules that don't block a whole domain, such as cosmetic filters
+    pub ignored: usize,
+    /// The format the imported file was read as
+    pub format: ListFormat,
+}
+
+impl ImportDiff {
+    /// Diff of merging `domains`, of which `added` were new
+    pub fn new(mut added: Vec<String>, domains: usize) -> Self {
+        added.sort();
+        let duplicates = domains.saturating_sub(added.len());
+        Self { added, duplicates, ..Self::default() }
+    }
+
+    /// One-line summary, e.g. "Added 342 new domains, skipped 1203 duplicates"
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "Added {} new domains, skipped {} duplicates",
+            self.added.len(),
+            self.duplicates
+        );
+        let mut skipped = Vec::new();
+        if self.malformed > 0 {
+            skipped.push(format!("{} malformed lines", self.malformed));
+        }
+        if self.ignored > 0 {
+            skipped.push(format!("{} unsupported rules", self.ignored));
+        }
+        match skipped.as_slice() {
+            [] => {}
+            [one] => summary.push_str(&format!(" and {}", one)),
+            [malformed, ignored] => summary.push_str(&format!(", {} and {}", malformed, ignored)),
+            _ => unreachable!(),
+        }
+        summary
+    }
+}
+
+/// A set of blocked domains, matched against hosts and their parent domains.
+pub trait BlocklistSource: Send + Sync {
+    /// Whether `host` or one of its parent domains is on the list
+    fn contains(&self, host: &str) -> bool;
+
+    /// Whether `domain` itself is on the list, ignoring its parent domains.
+    /// Sources with an index should override the default scan.
+    fn is_listed(&self, domain: &str) -> bool {
+        self.iter().any(|listed| listed == domain)
+    }
+
+    /// Add a domain, persisting the change
+    fn add(&mut self, domain: &str) -> io::Result<()>;
+
+    /// Add several domains, persisting once. Returns which were new.
+    fn merge(&mut self, domains: &[String]) -> io::Result<ImportDiff> {
+        let mut added = Vec::new();
+        for domain in domains {
+            if !self.is_listed(domain) {
+                self.add(domain)?;
+                added.push(domain.clone());
+            }
+        }
+        Ok(ImportDiff::new(added, domains.len()))
+    }
+
+    /// Remove a domain, persisting the change
+    fn remove(&mut self, domain: &str) -> io::Result<()>;
+
+    /// All listed domains, in no particular order
+    fn iter(&self) -> Box<dyn Iterator<Item = String> + '_>;
+
+    /// Re-read the list from its backing store, dropping unsaved state
+    fn reload(&mut self) -> io::Result<()>;
+
+    /// Write the list back out if its backing store was deleted. Returns
+    /// whether it had to.
+    fn restore_if_missing(&mut self) -> io::Result<bool> {
+        Ok(false)
+    }
+
+    /// The entry that makes `host` blocked, if any. `host` must be normalized.
+    fn explain_match(&self, host: &str) -> Option<MatchExplanation> {
+        // The host itself, then each parent domain, so the most specific
+        // entry wins
+        let mut candidate = host;
+        loop {
+            if self.is_listed(candidate) {
+                return Some(MatchExplanation::new(host, candidate));
+            }
+            match candidate.split_once('.') {
+                Some((_, parent)) => candidate = parent,
+                None => return None,
+            }
+        }
+    }
+
+    /// Number of listed domains
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Block every address in `cidr`, e.g. `203.0.113.0/24`. Ranges are kept
+    /// in memory only. Returns whether the range is new.
+    fn add_blocked_cidr(&mut self, _cidr: &str) -> io::Result<bool> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "This blocklist doesn't support IP ranges"))
+    }
+
+    /// Stop blocking `cidr`, returning whether it was blocked
+    fn remove_blocked_cidr(&mut self, _cidr: &str) -> io::Result<bool> {
+        Ok(false)
+    }
+
+    /// Blocked IP ranges, in the order they were added
+    fn blocked_cidrs(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Whether any IP ranges are blocked
+    fn has_blocked_cidrs(&self) -> bool {
+        false
+    }
+
+    /// Whether `ip` is in a blocked IP range
+    fn is_ip_blocked(&self, _ip: IpAddr) -> bool {
+        false
+    }
+}
@@ -0,0 +1,121 @@
+//! Command-line blocklist management, for scripts and cron jobs.
+//!
+//! Subcommands work on the tracker file directly and exit without opening
+//! a window. With no subcommand DeTrack starts as usual.
+
+use std::ffi::OsString;
+use std::io;
+use std::path::PathBuf;
+
+use clap::{Arg, ArgAction, Command};
+
+use crate::tracker_blocker::{normalize_host, TrackerBlocker};
+
+/// A blocklist change or query requested on the command line
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    Add(String),
+    Remove(String),
+    List,
+    Import(PathBuf),
+    Export(PathBuf),
+}
+
+/// Parsed command line
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cli {
+    /// Run the proxy without a window
+    pub headless: bool,
+    /// Named profile to use instead of the one in the config
+    pub profile: Option<String>,
+    /// `None` starts the proxy
+    pub action: Option<Action>,
+}
+
+pub fn command() -> Command {
+    let domain = || Arg::new("domain").required(true).help("Domain, e.g. doubleclick.net");
+    let file = || Arg::new("file").required(true).value_parser(clap::value_parser!(PathBuf));
+    Command::new("detrack")
+        .about("DeTrack Proxy: a local proxy that blocks trackers")
+        .arg(
+            Arg::new("headless")
+                .long("headless")
+                .action(ArgAction::SetTrue)
+                .help("Run the proxy without a window until Ctrl-C"),
+        )
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .value_name("NAME")
+                .global(true)
+                .help("Use the named profile's settings, blocklist and AI model"),
+        )
+        .subcommand(Command::new("add").about("Add a domain to the blocklist").arg(domain()))
+        .subcommand(Command::new("remove").about("Remove a domain from the blocklist").arg(domain()))
+        .subcommand(Command::new("list").about("Print the blocklist, one domain per line"))
+        .subcommand(Command::new("import").about("Add the domains listed in a file").arg(file()))
+        .subcommand(Command::new("export").about("Write the blocklist to a file").arg(file()))
+}
+
+/// Parse `args`, including the program name.
+pub fn parse<I, T>(args: I) -> Result<Cli, clap::Error>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let matches = command().try_get_matches_from(args)?;
+    let domain = |m: &clap::ArgMatches| m.get_one::<String>("domain").cloned().unwrap_or_default();
+    let file = |m: &clap::ArgMatches| m.get_one::<PathBuf>("file").cloned().unwrap_or_default();
+    let action = match matches.subcommand() {
+        Some(("add", m)) => Some(Action::Add(domain(m))),
+        Some(("remove", m)) => Some(Action::Remove(domain(m))),
+        Some(("list", _)) => Some(Action::List),
+        Some(("import", m)) => Some(Action::Import(file(m))),
+        Some(("export", m)) => Some(Action::Export(file(m))),
+        _ => None,
+    };
+    Ok(Cli {
+        headless: matches.get_flag("headless"),
+        profile: matches.get_one::<String>("profile").cloned(),
+        action,
+    })
+}
+
+impl Action {
+    /// Apply the action to `blocker` and describe the outcome.
+    pub fn run(&self, blocker: &mut TrackerBlocker) -> io::Result<String> {
+        match self {
+            Action::Add(domain) => {
+                let domain = normalize_host(domain);
+                if blocker.get_trackers().contains(&domain) {
+                    return Ok(format!("ℹ️ {} is already on the blocklist", domain));
+                }
+                blocker.add_tracker(&domain)?;
+                Ok(format!("➕ Added {}", domain))
+            }
+            Action::Remove(domain) => {
+                let domain = normalize_host(domain);
+                if !blocker.get_trackers().contains(&domain) {
+                    return Ok(format!("ℹ️ {} isn't on the blocklist", domain));
+                }
+                blocker.remove_tracker(&domain)?;
+                Ok(format!("➖ Removed {}", domain))
+            }
+            Action::List => Ok(blocker.get_trackers().join("\n")),
+            Action::Import(path) => {
+                let diff = blocker.import_trackers(path)?;
+                Ok(format!(
+                    "📥 Imported {} new domains from {}, skipped {} duplicates ({})",
+                    diff.added.len(),
+                    path.display(),
+                    diff.duplicates,
+                    diff.format
+                ))
+            }
+            Action::Export(path) => {
+                let count = blocker.export_trackers(path)?;
+                Ok(format!("📤 Exported {} domains to {}", count, path.display()))
+            }
+        }
+    }
+}
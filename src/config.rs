@@ -0,0 +1,221 @@
+//! Settings that persist between runs, stored as JSON.
+//!
+//! A missing config file means DeTrack has not been set up yet, which is
+//! what triggers the first-run wizard.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ai_tracker::AI_MODEL_PATH;
+use crate::logging::DEFAULT_TIMESTAMP_FORMAT;
+use crate::shared_state::{DEFAULT_ASSUMED_SPEED_MBPS, DEFAULT_BLOCK_HEADER_PREFIX, DEFAULT_BYPASS_HOSTS};
+use crate::verdict::AiConflictPolicy;
+
+/// Where the config is read from and written to, relative to the working directory
+pub const CONFIG_PATH: &str = "detrack_config.json";
+
+/// Port the proxy listens on unless configured otherwise
+pub const DEFAULT_LISTEN_PORT: u16 = 8100;
+
+/// Tracker list used unless another one was picked during setup
+pub const DEFAULT_TRACKER_LIST: &str = "tracker_lists/test_trackers.txt";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Set once the first-run wizard has been completed
+    pub setup_complete: bool,
+    /// Port on 127.0.0.1 the proxy listens on
+    pub listen_port: u16,
+    /// Path of the tracker list to load
+    pub tracker_list: String,
+    /// Start listening as soon as DeTrack opens, rather than from the UI
+    pub start_proxy_on_launch: bool,
+    /// Use tighter UI spacing
+    pub compact_ui: bool,
+    /// Use status colors that don't rely on telling red from green
+    pub colorblind_palette: bool,
+    /// Where AI feedback is saved
+    pub ai_model_path: String,
+    /// Connection speed used to estimate time saved, in Mbit/s
+    pub assumed_speed_mbps: u32,
+    /// `strftime` pattern for log timestamps
+    pub log_timestamp_format: String,
+    /// Write log timestamps in UTC rather than local time
+    pub log_timestamps_utc: bool,
+    /// Named profile to start with; only read from the top-level config
+    pub profile: Option<String>,
+    /// Script consulted about requests no built-in rule matched, empty for none
+    pub decision_script: String,
+    /// Fill an empty blocklist with the default preset on startup, rather
+    /// than only warning that nothing is blocked
+    pub seed_empty_blocklist: bool,
+    /// IP ranges blocked once a host is resolved, e.g. `203.0.113.0/24`
+    pub blocked_cidrs: Vec<String>,
+    /// Allowlisting a domain also allows its subdomains, rather than only
+    /// the exact host
+    pub allowlist_subdomains: bool,
+    /// Show byte sizes in KiB/MiB (powers of 1024) rather than KB/MB
+    pub binary_byte_units: bool,
+    /// Prefix of the headers explaining block responses, e.g. `X-DeTrack`
+    /// for `X-DeTrack-Reason`; empty to leave them out
+    pub block_header_prefix: String,
+    /// Queue log entries and add them in batches, so request floods don't
+    /// hold up the window
+    pub batch_log_appends: bool,
+    /// What to do when the AI flags a host the blocklist allows
+    pub ai_conflict_policy: AiConflictPolicy,
+    /// Reuse the decision script's answer for every request to a host,
+    /// rather than asking again for each path
+    pub decision_cache_per_host: bool,
+    /// Intranet names that skip filtering, each with its subdomains
+    pub bypass_hosts: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            setup_complete: false,
+            listen_port: DEFAULT_LISTEN_PORT,
+            tracker_list: DEFAULT_TRACKER_LIST.to_string(),
+            start_proxy_on_launch: true,
+            compact_ui: false,
+            colorblind_palette: false,
+            ai_model_path: AI_MODEL_PATH.to_string(),
+            assumed_speed_mbps: DEFAULT_ASSUMED_SPEED_MBPS,
+            log_timestamp_format: DEFAULT_TIMESTAMP_FORMAT.to_string(),
+            log_timestamps_utc: false,
+            profile: None,
+            decision_script: String::new(),
+            seed_empty_blocklist: false,
+            blocked_cidrs: Vec::new(),
+            allowlist_subdomains: false,
+            binary_byte_units: false,
+            block_header_prefix: DEFAULT_BLOCK_HEADER_PREFIX.to_string(),
+            batch_log_appends: false,
+            ai_conflict_policy: AiConflictPolicy::default(),
+            decision_cache_per_host: false,
+            bypass_hosts: DEFAULT_BYPASS_HOSTS.iter().map(|h| h.to_string()).collect(),
+        }
+    }
+}
+
+impl Config {
+    /// Load the config, or `None` if the file doesn't exist yet.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Option<Self>> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        serde_json::from_str(&content)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Write the config, replacing any existing file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, content)
+    }
+}
+
+/// Environment variable that overrides the listen port
+pub const ENV_PORT: &str = "DETRACK_PORT";
+/// Environment variable that overrides the tracker list path
+pub const ENV_BLOCKLIST: &str = "DETRACK_BLOCKLIST";
+/// Environment variable that turns AI detection on or off
+pub const ENV_AI: &str = "DETRACK_AI";
+/// Environment variable that turns blocking on or off
+pub const ENV_BLOCKING: &str = "DETRACK_BLOCKING";
+/// Environment variable that runs the proxy without a window, like `--headless`
+pub const ENV_HEADLESS: &str = "DETRACK_HEADLESS";
+
+/// Settings taken from `DETRACK_*` environment variables, which win over
+/// the config file, e.g. for containers and scripted setups.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EnvOverrides {
+    pub listen_port: Option<u16>,
+    pub tracker_list: Option<String>,
+    pub ai_enabled: Option<bool>,
+    pub blocking_enabled: Option<bool>,
+    pub headless: Option<bool>,
+}
+
+impl EnvOverrides {
+    /// Read the overrides from the process environment.
+    pub fn from_env() -> io::Result<Self> {
+        Self::from_vars(std::env::vars())
+    }
+
+    /// Read the overrides from `vars`, ignoring unrelated names. Empty
+    /// values count as unset; anything else that doesn't parse is an error.
+    pub fn from_vars<I, K, V>(vars: I) -> io::Result<Self>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let mut overrides = Self::default();
+        for (name, value) in vars {
+            let value = value.as_ref().trim();
+            if value.is_empty() {
+                continue;
+            }
+            match name.as_ref() {
+                ENV_PORT => {
+                    overrides.listen_port = Some(value.parse().map_err(|_| invalid(ENV_PORT, value))?);
+                }
+                ENV_BLOCKLIST => overrides.tracker_list = Some(value.to_string()),
+                ENV_AI => overrides.ai_enabled = Some(parse_flag(value).ok_or_else(|| invalid(ENV_AI, value))?),
+                ENV_BLOCKING => {
+                    overrides.blocking_enabled = Some(parse_flag(value).ok_or_else(|| invalid(ENV_BLOCKING, value))?);
+                }
+                ENV_HEADLESS => {
+                    overrides.headless = Some(parse_flag(value).ok_or_else(|| invalid(ENV_HEADLESS, value))?);
+                }
+                _ => {}
+            }
+        }
+        Ok(overrides)
+    }
+
+    /// `NAME=value` for each override that is set, for logging
+    pub fn describe(&self) -> Vec<String> {
+        let mut set = Vec::new();
+        if let Some(port) = self.listen_port {
+            set.push(format!("{}={}", ENV_PORT, port));
+        }
+        if let Some(path) = &self.tracker_list {
+            set.push(format!("{}={}", ENV_BLOCKLIST, path));
+        }
+        if let Some(enabled) = self.ai_enabled {
+            set.push(format!("{}={}", ENV_AI, enabled));
+        }
+        if let Some(enabled) = self.blocking_enabled {
+            set.push(format!("{}={}", ENV_BLOCKING, enabled));
+        }
+        if let Some(headless) = self.headless {
+            set.push(format!("{}={}", ENV_HEADLESS, headless));
+        }
+        set
+    }
+}
+
+/// `1`/`0`, `true`/`false`, `yes`/`no` or `on`/`off`, in any case
+fn parse_flag(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+fn invalid(name: &str, value: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("invalid {}: '{}'", name, value))
+}
@@ -0,0 +1,222 @@
+//! Control endpoints served by the proxy itself under `/__detrack/`.
+//!
+//! They are reached by pointing a browser at the proxy address, e.g.
+//! `http://127.0.0.1:8100/__detrack/report`, and only answer clients on the
+//! loopback interface.
+//!
+//! `GET /__detrack/blocklist` lets a browser extension keep in sync with the
+//! proxy. It answers with both lists, sorted:
+//!
+//! ```json
+//! {"blocklist": ["ads.example.com"], "allowlist": ["cdn.example.com"]}
+//! ```
+//!
+//! Entries are changed with `POST /__detrack/blocklist/add?domain=...`, and
+//! likewise `blocklist/remove`, `allowlist/add` and `allowlist/remove`, which
+//! answer with the updated lists in the same form. Changes from web pages,
+//! which send an `http` or `https` `Origin`, are refused.
+
+use std::net::{IpAddr, SocketAddr};
+
+use bytes::Bytes;
+use http_body_util::Full;
+use hyper::header::{HeaderValue, ALLOW, CONTENT_TYPE, ORIGIN};
+use hyper::{Method, Request, Response, StatusCode, Uri};
+use serde_json::json;
+
+use crate::shared_state::{DomainStat, SharedState};
+
+/// Path prefix of all control endpoints
+pub const CONTROL_PREFIX: &str = "/__detrack/";
+
+/// Whether a request is for a control endpoint rather than a site to proxy.
+///
+/// Only requests addressed to a loopback host count, so a real site with a
+/// `/__detrack/` path is still proxied normally.
+pub fn is_control_request(uri: &Uri) -> bool {
+    let loopback_host = match uri.host() {
+        Some("localhost") => true,
+        Some(host) => host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+            .map(|ip| ip.is_loopback())
+            .unwrap_or(false),
+        None => false,
+    };
+    loopback_host && uri.path().starts_with(CONTROL_PREFIX)
+}
+
+/// Answer a control request from `client`.
+///
+/// Errors are returned as JSON objects of the form
+/// `{ "error": "...", "code": 404 }` so scripts can handle them uniformly.
+pub fn handle<B>(req: &Request<B>, client: SocketAddr, state: &SharedState) -> Response<Full<Bytes>> {
+    if !client.ip().is_loopback() {
+        return error(StatusCode::FORBIDDEN, "Control endpoints are only available from this computer");
+    }
+
+    let method = req.method();
+    match &req.uri().path()[CONTROL_PREFIX.len()..] {
+        "report" => match *method {
+            Method::GET | Method::HEAD => html(report(state)),
+            _ => method_not_allowed("GET, HEAD"),
+        },
+        "blocklist" => match *method {
+            Method::GET | Method::HEAD => lists(state),
+            _ => method_not_allowed("GET, HEAD"),
+        },
+        path @ ("blocklist/add" | "blocklist/remove" | "allowlist/add" | "allowlist/remove") => {
+            if *method != Method::POST {
+                return method_not_allowed("POST");
+            }
+            if from_web_page(req) {
+                return error(StatusCode::FORBIDDEN, "Lists can't be changed from a web page");
+            }
+            let Some(domain) = domain_param(req.uri()) else {
+                return error(StatusCode::BAD_REQUEST, "Missing domain parameter");
+            };
+            let result = match path {
+                "blocklist/add" => state.add_tracker(&domain),
+                "blocklist/remove" => state.remove_tracker(&domain),
+                "allowlist/add" => {
+                    state.add_allowed_host(&domain);
+                    Ok(())
+                }
+                _ => {
+                    state.remove_allowed_host(&domain);
+                    Ok(())
+                }
+            };
+            match result {
+                Ok(()) => lists(state),
+                Err(e) => error(StatusCode::BAD_REQUEST, &e),
+            }
+        }
+        _ => error(StatusCode::NOT_FOUND, "Unknown DeTrack endpoint"),
+    }
+}
+
+/// JSON error response with the given status.
+pub fn error(status: StatusCode, message: &str) -> Response<Full<Bytes>> {
+    let body = json!({ "error": message, "code": status.as_u16() });
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Full::new(Bytes::from(body.to_string())))
+        .unwrap()
+}
+
+fn method_not_allowed(allowed: &'static str) -> Response<Full<Bytes>> {
+    let mut resp = error(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed");
+    resp.headers_mut().insert(ALLOW, HeaderValue::from_static(allowed));
+    resp
+}
+
+fn json(body: serde_json::Value) -> Response<Full<Bytes>> {
+    Response::builder()
+        .header(CONTENT_TYPE, "application/json")
+        .body(Full::new(Bytes::from(body.to_string())))
+        .unwrap()
+}
+
+/// Both lists, in the form documented at the top of this module.
+fn lists(state: &SharedState) -> Response<Full<Bytes>> {
+    match state.get_trackers() {
+        Ok(mut blocklist) => {
+            blocklist.sort();
+            json(json!({ "blocklist": blocklist, "allowlist": state.get_allowlist() }))
+        }
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, &e),
+    }
+}
+
+/// The non-empty `domain` query parameter, if given.
+fn domain_param(uri: &Uri) -> Option<String> {
+    url::form_urlencoded::parse(uri.query()?.as_bytes())
+        .find(|(key, _)| key == "domain")
+        .map(|(_, value)| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// Whether a browser sent the request on behalf of a web page. Extensions
+/// send their own origin scheme, and scripts usually none at all.
+fn from_web_page<B>(req: &Request<B>) -> bool {
+    req.headers()
+        .get(ORIGIN)
+        .and_then(|origin| origin.to_str().ok())
+        .map(|origin| origin.starts_with("http://") || origin.starts_with("https://") || origin == "null")
+        .unwrap_or(false)
+}
+
+fn html(body: String) -> Response<Full<Bytes>> {
+    Response::builder()
+        .header(CONTENT_TYPE, "text/html; charset=utf-8")
+        .body(Full::new(Bytes::from(body)))
+        .unwrap()
+}
+
+/// Self-contained HTML page summarizing blocked domains, busiest first.
+fn report(state: &SharedState) -> String {
+    let mut stats: Vec<DomainStat> = state
+        .get_stats()
+        .into_values()
+        .filter(|stat| stat.blocked > 0)
+        .collect();
+    stats.sort_by(|a, b| b.blocked.cmp(&a.blocked).then_with(|| a.domain.cmp(&b.domain)));
+
+    let rows: String = stats
+        .iter()
+        .map(|stat| {
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                escape_html(&stat.domain),
+                stat.blocked,
+                stat.requests,
+                stat.last_seen.format("%Y-%m-%d %H:%M:%S UTC"),
+            )
+        })
+        .collect();
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>DeTrack Proxy report</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em; }}
+table {{ border-collapse: collapse; }}
+th, td {{ border: 1px solid #ccc; padding: 4px 8px; text-align: left; }}
+</style>
+</head>
+<body>
+<h1>DeTrack Proxy report</h1>
+<p>Generated {generated}</p>
+<ul>
+<li>Blocked requests: {blocked}</li>
+<li>Allowed requests: {allowed}</li>
+<li>Bandwidth saved: {saved:.2} MB</li>
+</ul>
+<h2>Blocked domains ({domains})</h2>
+<table>
+<tr><th>Domain</th><th>Blocked</th><th>Requests</th><th>Last seen</th></tr>
+{rows}</table>
+</body>
+</html>
+"#,
+        generated = chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+        blocked = state.get_blocked_count(),
+        allowed = state.get_allowed_count(),
+        saved = state.get_bandwidth_saved() as f64 / 1_000_000.0,
+        domains = stats.len(),
+        rows = rows,
+    )
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
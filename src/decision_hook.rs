@@ -0,0 +1,175 @@
+//! User-provided block decisions, for rules the built-in ones can't express.
+//!
+//! Requests that no built-in rule matched are passed to a user script. The
+//! script is run once per request with a JSON object on stdin:
+//!
+//! ```json
+//! {"host": "ads.example.com", "path": "/pixel.gif", "referer": "https://news.example/"}
+//! ```
+//!
+//! and answers on stdout with `{"block": true}` or `{"block": false}`.
+//! Answers are cached for a minute, per request or per host, and a script
+//! that doesn't answer within the timeout leaves the request allowed. At
+//! most [`MAX_CONCURRENT_RUNS`] copies of the script run at once.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::Semaphore;
+
+/// How long the script gets to answer unless configured otherwise
+pub const DEFAULT_HOOK_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How long an answer is reused for the same request
+const HOOK_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Most answers cached before the cache is reset
+const MAX_CACHED_DECISIONS: usize = 10_000;
+
+/// Most script processes running at once; further requests wait their turn
+/// within the timeout
+pub const MAX_CONCURRENT_RUNS: usize = 8;
+
+/// Shortest gap between logged script failures
+const FAILURE_LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+/// What the script is told about a request
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct HookRequest {
+    pub host: String,
+    /// Empty for CONNECT tunnels
+    pub path: String,
+    pub referer: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct HookResponse {
+    block: bool,
+}
+
+/// A user script consulted after the built-in rules
+pub struct DecisionHook {
+    program: PathBuf,
+    timeout: Duration,
+    cache: Mutex<HashMap<HookRequest, (bool, Instant)>>,
+    cache_per_host: AtomicBool,
+    runs: Semaphore,
+    /// When a failure was last logged, and how many weren't since
+    failures: Mutex<(Option<Instant>, usize)>,
+}
+
+impl DecisionHook {
+    pub fn new(program: impl Into<PathBuf>, timeout: Duration) -> Self {
+        Self {
+            program: program.into(),
+            timeout,
+            cache: Mutex::new(HashMap::new()),
+            cache_per_host: AtomicBool::new(false),
+            runs: Semaphore::new(MAX_CONCURRENT_RUNS),
+            failures: Mutex::new((None, 0)),
+        }
+    }
+
+    /// Reuse an answer for every request to the same host, rather than only
+    /// for the same host, path and referer. Clears cached answers.
+    pub fn set_cache_per_host(&self, enabled: bool) {
+        self.cache_per_host.store(enabled, Ordering::Relaxed);
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.clear();
+        }
+    }
+
+    pub fn is_cache_per_host(&self) -> bool {
+        self.cache_per_host.load(Ordering::Relaxed)
+    }
+
+    pub fn program(&self) -> &Path {
+        &self.program
+    }
+
+    /// Whether the script wants `request` blocked. Errors and timeouts aren't
+    /// cached, so a fixed script is picked up on the next request.
+    pub async fn decide(&self, request: &HookRequest) -> Result<bool, String> {
+        let key = self.cache_key(request);
+        if let Some(block) = self.cached(&key) {
+            return Ok(block);
+        }
+
+        let run = async {
+            let _permit = self.runs.acquire().await.map_err(|e| e.to_string())?;
+            self.run(request).await
+        };
+        let block = tokio::time::timeout(self.timeout, run)
+            .await
+            .map_err(|_| format!("no answer within {} ms", self.timeout.as_millis()))??;
+
+        if let Ok(mut cache) = self.cache.lock() {
+            if cache.len() >= MAX_CACHED_DECISIONS {
+                cache.clear();
+            }
+            cache.insert(key, (block, Instant::now()));
+        }
+        Ok(block)
+    }
+
+    /// Count a failed decision, returning whether it should be logged now
+    /// and how many failures went unlogged before it. Failures are logged
+    /// at most once per [`FAILURE_LOG_INTERVAL`], so a broken script doesn't
+    /// flood the log.
+    pub fn note_failure(&self) -> Option<usize> {
+        let mut failures = self.failures.lock().ok()?;
+        let (last_logged, suppressed) = &mut *failures;
+        if last_logged.is_some_and(|at| at.elapsed() < FAILURE_LOG_INTERVAL) {
+            *suppressed += 1;
+            return None;
+        }
+        *last_logged = Some(Instant::now());
+        Some(std::mem::take(suppressed))
+    }
+
+    fn cache_key(&self, request: &HookRequest) -> HookRequest {
+        if self.is_cache_per_host() {
+            HookRequest { host: request.host.clone(), path: String::new(), referer: None }
+        } else {
+            request.clone()
+        }
+    }
+
+    fn cached(&self, key: &HookRequest) -> Option<bool> {
+        let cache = self.cache.lock().ok()?;
+        let (block, at) = cache.get(key)?;
+        (at.elapsed() < HOOK_CACHE_TTL).then_some(*block)
+    }
+
+    async fn run(&self, request: &HookRequest) -> Result<bool, String> {
+        let input = serde_json::to_vec(request).map_err(|e| e.to_string())?;
+        let mut child = Command::new(&self.program)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            // Dropped on timeout, which should not leave the script running
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("couldn't start {}: {}", self.program.display(), e))?;
+
+        // A script that decides without reading its input may close stdin early
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(&input).await;
+        }
+
+        let output = child.wait_with_output().await.map_err(|e| e.to_string())?;
+        if !output.status.success() {
+            return Err(format!("exited with {}", output.status));
+        }
+        serde_json::from_slice::<HookResponse>(&output.stdout)
+            .map(|response| response.block)
+            .map_err(|e| format!("invalid answer: {}", e))
+    }
+}
@@ -0,0 +1,61 @@
+//! Short, readable forms of large counts and byte sizes for the UI, e.g.
+//! `1.2K` requests or `3.4 MB` saved.
+
+/// Suffixes for counts, each 1000 times the previous
+const COUNT_SUFFIXES: [&str; 5] = ["", "K", "M", "B", "T"];
+
+const DECIMAL_UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+const BINARY_UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+/// Whether byte sizes step by 1000 (`KB`) or 1024 (`KiB`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ByteUnits {
+    #[default]
+    Decimal,
+    Binary,
+}
+
+impl ByteUnits {
+    fn base(self) -> f64 {
+        match self {
+            ByteUnits::Decimal => 1000.0,
+            ByteUnits::Binary => 1024.0,
+        }
+    }
+
+    fn names(self) -> &'static [&'static str; 5] {
+        match self {
+            ByteUnits::Decimal => &DECIMAL_UNITS,
+            ByteUnits::Binary => &BINARY_UNITS,
+        }
+    }
+}
+
+/// `value` scaled down by `base` until it shows as less than `base` with one
+/// decimal, as `(scaled, step)`. Rounding is accounted for, so 999,950
+/// becomes `1.0M` rather than `1000.0K`.
+fn scale(value: u64, base: f64, steps: usize) -> (f64, usize) {
+    let mut scaled = value as f64;
+    let mut step = 0;
+    while step + 1 < steps && (scaled * 10.0).round() / 10.0 >= base {
+        scaled /= base;
+        step += 1;
+    }
+    (scaled, step)
+}
+
+/// `1234` as `1.2K` and `3400000` as `3.4M`; below 1000 as is
+pub fn format_count(count: u64) -> String {
+    match scale(count, 1000.0, COUNT_SUFFIXES.len()) {
+        (_, 0) => count.to_string(),
+        (scaled, step) => format!("{:.1}{}", scaled, COUNT_SUFFIXES[step]),
+    }
+}
+
+/// `1234567` bytes as `1.2 MB`, or `1.2 MiB` in binary units
+pub fn format_bytes(bytes: u64, units: ByteUnits) -> String {
+    match scale(bytes, units.base(), units.names().len()) {
+        (_, 0) => format!("{} B", bytes),
+        (scaled, step) => format!("{:.1} {}", scaled, units.names()[step]),
+    }
+}
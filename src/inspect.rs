@@ -0,0 +1,177 @@
+//! Streaming inspection of message bodies.
+//!
+//! The forwarding path never buffers whole bodies. Features that need to
+//! look at a body wrap it in an [`InspectableBody`], which passes every
+//! frame on as soon as it arrives and keeps a copy of at most
+//! `max_buffer_bytes` from the start of the body for the [`Inspector`].
+//! Once the inspector is done or the cap is reached, the copy is dropped
+//! and the rest of the body streams through untouched, so a large download
+//! costs no more memory than a small one.
+//!
+//! [`CountingBody`] measures a body the same way, without buffering at all.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use hyper::body::{Body, Frame, SizeHint};
+
+/// Buffer cap for inspection features that don't pick their own
+pub const DEFAULT_MAX_BUFFER_BYTES: usize = 64 * 1024;
+
+/// Looks at the leading bytes of a body as it streams through.
+pub trait Inspector: Send + Sync {
+    /// Called with everything buffered so far each time data arrives.
+    /// Return true once there's nothing more to learn.
+    fn inspect(&mut self, buffered: &[u8]) -> bool {
+        let _ = buffered;
+        false
+    }
+
+    /// Called exactly once: when `inspect` returns true, the buffer cap is
+    /// reached or the body ends, whichever comes first.
+    fn finish(self: Box<Self>, buffered: &[u8]);
+}
+
+/// Body that passes frames through unchanged while showing up to
+/// `max_buffer_bytes` of its start to an [`Inspector`].
+pub struct InspectableBody<B> {
+    inner: B,
+    buffered: Vec<u8>,
+    max_buffer_bytes: usize,
+    inspector: Option<Box<dyn Inspector>>,
+}
+
+impl<B> InspectableBody<B> {
+    pub fn new(inner: B, max_buffer_bytes: usize, inspector: impl Inspector + 'static) -> Self {
+        Self {
+            inner,
+            buffered: Vec::new(),
+            max_buffer_bytes,
+            inspector: Some(Box::new(inspector)),
+        }
+    }
+
+    /// Whether the inspector is still looking at the body
+    pub fn is_inspecting(&self) -> bool {
+        self.inspector.is_some()
+    }
+
+    /// Bytes currently held for the inspector, never more than the cap
+    pub fn buffered_len(&self) -> usize {
+        self.buffered.len()
+    }
+
+    fn observe(&mut self, data: &[u8]) {
+        let Some(inspector) = self.inspector.as_mut() else {
+            return;
+        };
+        let wanted = self.max_buffer_bytes - self.buffered.len();
+        self.buffered.extend_from_slice(&data[..data.len().min(wanted)]);
+        if inspector.inspect(&self.buffered) || self.buffered.len() >= self.max_buffer_bytes {
+            self.finish();
+        }
+    }
+
+    fn finish(&mut self) {
+        if let Some(inspector) = self.inspector.take() {
+            inspector.finish(&self.buffered);
+            self.buffered = Vec::new();
+        }
+    }
+}
+
+impl<B> Body for InspectableBody<B>
+where
+    B: Body<Data = Bytes> + Unpin,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Frame<Bytes>, B::Error>>> {
+        let polled = Pin::new(&mut self.inner).poll_frame(cx);
+        match &polled {
+            Poll::Ready(Some(Ok(frame))) => {
+                if let Some(data) = frame.data_ref() {
+                    let data = data.clone();
+                    self.observe(&data);
+                }
+            }
+            Poll::Ready(_) => self.finish(),
+            Poll::Pending => {}
+        }
+        polled
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+type ByteCountCallback = Box<dyn FnOnce(u64) + Send + Sync>;
+
+/// Body that passes frames through unchanged while counting their bytes.
+///
+/// `on_end` is called once with the total, when the body ends or, if the
+/// reader gives up early, when it's dropped.
+pub struct CountingBody<B> {
+    inner: B,
+    bytes: u64,
+    on_end: Option<ByteCountCallback>,
+}
+
+impl<B> CountingBody<B> {
+    pub fn new(inner: B, on_end: impl FnOnce(u64) + Send + Sync + 'static) -> Self {
+        Self {
+            inner,
+            bytes: 0,
+            on_end: Some(Box::new(on_end)),
+        }
+    }
+
+    fn finish(&mut self) {
+        if let Some(on_end) = self.on_end.take() {
+            on_end(self.bytes);
+        }
+    }
+}
+
+impl<B> Drop for CountingBody<B> {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+impl<B> Body for CountingBody<B>
+where
+    B: Body<Data = Bytes> + Unpin,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Frame<Bytes>, B::Error>>> {
+        let polled = Pin::new(&mut self.inner).poll_frame(cx);
+        match &polled {
+            Poll::Ready(Some(Ok(frame))) => {
+                if let Some(data) = frame.data_ref() {
+                    self.bytes += data.len() as u64;
+                }
+            }
+            Poll::Ready(_) => self.finish(),
+            Poll::Pending => {}
+        }
+        polled
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
@@ -0,0 +1,75 @@
+//! CIDR ranges, such as `203.0.113.0/24`, for blocking by resolved address.
+
+use std::fmt;
+use std::io;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// A block of IPv4 or IPv6 addresses. A bare address is a range of one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct IpRange {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpRange {
+    /// Whether `ip` is in the range. IPv4-mapped IPv6 addresses count as
+    /// their IPv4 address.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+            ip => ip,
+        };
+        match (self.network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                mask_v4(u32::from(ip), self.prefix_len) == u32::from(network)
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                mask_v6(u128::from(ip), self.prefix_len) == u128::from(network)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// `bits` with all but the leading `prefix_len` bits cleared
+fn mask_v4(bits: u32, prefix_len: u8) -> u32 {
+    u32::MAX.checked_shl(32 - prefix_len as u32).map_or(0, |mask| bits & mask)
+}
+
+fn mask_v6(bits: u128, prefix_len: u8) -> u128 {
+    u128::MAX.checked_shl(128 - prefix_len as u32).map_or(0, |mask| bits & mask)
+}
+
+impl FromStr for IpRange {
+    type Err = io::Error;
+
+    /// Parse `address/prefix` or a bare address. Host bits are cleared, so
+    /// `10.1.2.3/8` is `10.0.0.0/8`.
+    fn from_str(text: &str) -> io::Result<Self> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid IP range: {:?}", text));
+        let text = text.trim();
+        let (address, prefix) = match text.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (text, None),
+        };
+        let address: IpAddr = address.parse().map_err(|_| invalid())?;
+        let max_len = if address.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix {
+            Some(prefix) => prefix.parse::<u8>().ok().filter(|len| *len <= max_len).ok_or_else(invalid)?,
+            None => max_len,
+        };
+
+        let network = match address {
+            IpAddr::V4(v4) => IpAddr::V4(mask_v4(u32::from(v4), prefix_len).into()),
+            IpAddr::V6(v6) => IpAddr::V6(mask_v6(u128::from(v6), prefix_len).into()),
+        };
+        Ok(Self { network, prefix_len })
+    }
+}
+
+impl fmt::Display for IpRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix_len)
+    }
+}
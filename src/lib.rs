@@ -1,4 +1,24 @@
 pub mod shared_state;
 pub mod tracker_blocker;
+pub mod blocklist_source;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_blocklist;
 pub mod run_proxy;
-pub mod ai_tracker;
\ No newline at end of file
+pub mod ai_tracker;
+pub mod verdict;
+pub mod system_proxy;
+pub mod public_suffix;
+pub mod logging;
+pub mod config;
+pub mod control;
+pub mod inspect;
+pub mod pixel;
+pub mod summary_image;
+pub mod beacon;
+pub mod presets;
+pub mod profiles;
+pub mod decision_hook;
+pub mod ip_range;
+pub mod list_formats;
+pub mod humanize;
+pub mod cli;
@@ -0,0 +1,272 @@
+//! Blocklist formats of other blockers, so existing lists can be imported
+//! as they are.
+//!
+//! Besides DeTrack's own one-domain-per-line lists, imports recognize:
+//!
+//! - hosts files, as used by Pi-hole adlists: `0.0.0.0 ads.example.com`
+//! - adblock filter lists and uBlock Origin's "My filters": `||ads.example.com^`
+//! - uBlock Origin settings backups (JSON), using the user's filters and
+//!   `* host * block` dynamic rules
+//! - Pi-hole Teleporter domain list exports (JSON), using the enabled exact
+//!   deny entries
+//!
+//! Rules that can't be expressed as a blocked domain, such as cosmetic
+//! filters, exceptions and regexes, are counted as ignored.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::net::IpAddr;
+
+use serde_json::Value;
+
+use crate::tracker_blocker::{parse_tracker_line, parse_tracker_list, SkippedLine};
+
+/// Lines looked at when sniffing a text list's format
+const SNIFF_LINES: usize = 50;
+
+/// Names hosts files map to local addresses, which aren't trackers
+const LOCAL_HOSTNAMES: &[&str] = &[
+    "localhost",
+    "localhost.localdomain",
+    "local",
+    "broadcasthost",
+    "ip6-localhost",
+    "ip6-loopback",
+    "ip6-localnet",
+    "ip6-mcastprefix",
+    "ip6-allnodes",
+    "ip6-allrouters",
+    "ip6-allhosts",
+    "0.0.0.0",
+];
+
+/// Adblock rule options that still block the whole domain
+const DOMAIN_WIDE_OPTIONS: &[&str] = &["third-party", "3p", "important", "all", "document", "doc"];
+
+/// Pi-hole domain list type for exact deny entries
+const PIHOLE_EXACT_DENY: u64 = 1;
+
+/// The format a list was read as
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ListFormat {
+    /// One domain per line
+    #[default]
+    Plain,
+    Hosts,
+    Adblock,
+    UblockBackup,
+    PiholeTeleporter,
+}
+
+impl fmt::Display for ListFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ListFormat::Plain => "domain list",
+            ListFormat::Hosts => "hosts file",
+            ListFormat::Adblock => "adblock filter list",
+            ListFormat::UblockBackup => "uBlock Origin backup",
+            ListFormat::PiholeTeleporter => "Pi-hole Teleporter export",
+        })
+    }
+}
+
+/// Domains read from a list in any supported format
+#[derive(Clone, Debug, Default)]
+pub struct ImportedList {
+    pub format: ListFormat,
+    pub trackers: HashSet<String>,
+    /// Entries that aren't valid, numbered by line, or by position in JSON
+    pub skipped: Vec<SkippedLine>,
+    /// Valid rules that don't block a whole domain
+    pub ignored: usize,
+}
+
+impl ImportedList {
+    fn new(format: ListFormat) -> Self {
+        Self { format, ..Self::default() }
+    }
+
+    fn add(&mut self, number: usize, entry: &str) {
+        match parse_tracker_line(entry) {
+            Some(Ok(host)) => {
+                self.trackers.insert(host);
+            }
+            Some(Err(reason)) => self.skipped.push(SkippedLine {
+                number,
+                line: entry.trim().to_string(),
+                reason,
+            }),
+            None => {}
+        }
+    }
+}
+
+/// Guess the format of `content` from its structure
+pub fn detect_format(content: &str) -> ListFormat {
+    let trimmed = content.trim_start();
+    if trimmed.starts_with('[') || trimmed.starts_with('{') {
+        match serde_json::from_str::<Value>(trimmed) {
+            Ok(Value::Array(entries)) if entries.iter().any(|entry| entry.get("domain").is_some()) => {
+                return ListFormat::PiholeTeleporter;
+            }
+            Ok(Value::Object(backup))
+                if backup.contains_key("userFilters") || backup.contains_key("selectedFilterLists") =>
+            {
+                return ListFormat::UblockBackup;
+            }
+            _ => {}
+        }
+    }
+
+    let mut adblock = 0;
+    let mut hosts = 0;
+    let lines = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .take(SNIFF_LINES);
+    for line in lines {
+        if line.starts_with("[Adblock") {
+            return ListFormat::Adblock;
+        }
+        if line.starts_with("||") || line.starts_with("@@") || line.starts_with('!') || line.contains("##") {
+            adblock += 1;
+        } else if is_hosts_line(line) {
+            hosts += 1;
+        }
+    }
+    if adblock > 0 && adblock >= hosts {
+        ListFormat::Adblock
+    } else if hosts > 0 {
+        ListFormat::Hosts
+    } else {
+        ListFormat::Plain
+    }
+}
+
+fn is_hosts_line(line: &str) -> bool {
+    let mut fields = line.split_whitespace();
+    fields.next().is_some_and(|address| address.parse::<IpAddr>().is_ok()) && fields.next().is_some()
+}
+
+/// Read `content` in whichever format it's in
+pub fn parse_list(content: &str) -> ImportedList {
+    match detect_format(content) {
+        ListFormat::Plain => {
+            let parsed = parse_tracker_list(content);
+            ImportedList {
+                format: ListFormat::Plain,
+                trackers: parsed.trackers,
+                skipped: parsed.skipped,
+                ignored: 0,
+            }
+        }
+        ListFormat::Hosts => parse_hosts(content),
+        ListFormat::Adblock => {
+            let mut list = ImportedList::new(ListFormat::Adblock);
+            parse_adblock_into(&mut list, content);
+            list
+        }
+        ListFormat::UblockBackup => parse_ublock_backup(content),
+        ListFormat::PiholeTeleporter => parse_pihole_teleporter(content),
+    }
+}
+
+fn parse_hosts(content: &str) -> ImportedList {
+    let mut list = ImportedList::new(ListFormat::Hosts);
+    for (i, line) in content.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut fields = line.split_whitespace().peekable();
+        // Some lists mix in bare domains
+        if fields.peek().is_some_and(|first| first.parse::<IpAddr>().is_ok()) {
+            fields.next();
+        }
+        for name in fields {
+            if !LOCAL_HOSTNAMES.contains(&name.to_ascii_lowercase().as_str()) {
+                list.add(i + 1, name);
+            }
+        }
+    }
+    list
+}
+
+fn parse_adblock_into(list: &mut ImportedList, content: &str) {
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('!') || line.starts_with('[') || line.starts_with('#') {
+            continue;
+        }
+        match adblock_domain(line) {
+            Some(domain) => list.add(i + 1, domain),
+            None => list.ignored += 1,
+        }
+    }
+}
+
+/// The domain a filter rule blocks outright, if that's all it does
+fn adblock_domain(rule: &str) -> Option<&str> {
+    if rule.starts_with("@@") || rule.contains("##") || rule.contains("#@#") || rule.contains("#?#") || rule.contains("#$#") {
+        return None;
+    }
+    let (pattern, options) = match rule.split_once('$') {
+        Some((pattern, options)) => (pattern, Some(options)),
+        None => (rule, None),
+    };
+    if let Some(options) = options {
+        let domain_wide = options.split(',').all(|option| DOMAIN_WIDE_OPTIONS.contains(&option.trim()));
+        if !domain_wide {
+            return None;
+        }
+    }
+    let domain = match pattern.strip_prefix("||") {
+        Some(rest) => {
+            let rest = rest.trim_end_matches('|');
+            rest.strip_suffix('^').unwrap_or(rest)
+        }
+        // uBlock treats a bare host name as `||host^`
+        None => pattern,
+    };
+    let is_domain = !domain.is_empty() && !domain.contains(['/', '*', '^', '|', '?', '=']);
+    is_domain.then_some(domain)
+}
+
+fn parse_ublock_backup(content: &str) -> ImportedList {
+    let mut list = ImportedList::new(ListFormat::UblockBackup);
+    let Ok(backup) = serde_json::from_str::<Value>(content) else {
+        return list;
+    };
+    if let Some(filters) = backup.get("userFilters").and_then(Value::as_str) {
+        parse_adblock_into(&mut list, filters);
+    }
+    // Dynamic rules are "source destination type action", e.g. "* ads.example.com * block"
+    let rules = backup.get("dynamicFilteringString").and_then(Value::as_str).unwrap_or_default();
+    for (i, rule) in rules.lines().enumerate() {
+        match rule.split_whitespace().collect::<Vec<_>>()[..] {
+            ["*", destination, "*", "block"] if destination != "*" => list.add(i + 1, destination),
+            [] => {}
+            _ => list.ignored += 1,
+        }
+    }
+    list
+}
+
+fn parse_pihole_teleporter(content: &str) -> ImportedList {
+    let mut list = ImportedList::new(ListFormat::PiholeTeleporter);
+    let entries = match serde_json::from_str::<Value>(content) {
+        Ok(Value::Array(entries)) => entries,
+        _ => return list,
+    };
+    for (i, entry) in entries.iter().enumerate() {
+        let enabled = match entry.get("enabled") {
+            Some(Value::Bool(enabled)) => *enabled,
+            Some(value) => value.as_u64() != Some(0),
+            None => true,
+        };
+        let exact_deny = entry.get("type").and_then(Value::as_u64) == Some(PIHOLE_EXACT_DENY);
+        match entry.get("domain").and_then(Value::as_str) {
+            Some(domain) if enabled && exact_deny => list.add(i + 1, domain),
+            _ => list.ignored += 1,
+        }
+    }
+    list
+}
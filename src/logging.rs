@@ -0,0 +1,397 @@
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufWriter, Write};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local, Utc};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+
+use crate::public_suffix;
+
+/// Directory that cleared logs are archived to
+pub const LOG_ARCHIVE_DIR: &str = "logs";
+
+/// What a log entry is about
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum LogKind {
+    /// General status messages
+    Info,
+    /// A request that was let through
+    Request,
+    /// A request that was blocked
+    Blocked,
+    /// Connection, upstream or tunnel errors
+    Error,
+    /// AI tracker detection
+    Ai,
+}
+
+/// Categories of events whose logging can be switched off independently
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LogEvent {
+    /// Allowed requests
+    Request,
+    /// Blocked requests
+    Block,
+    /// Connection, upstream and tunnel errors
+    Error,
+    /// AI detections and suggestion decisions
+    AiSuggestion,
+    /// CONNECT tunnels opening and closing
+    Tunnel,
+}
+
+impl LogEvent {
+    pub const ALL: [LogEvent; 5] = [
+        LogEvent::Request,
+        LogEvent::Block,
+        LogEvent::Error,
+        LogEvent::AiSuggestion,
+        LogEvent::Tunnel,
+    ];
+
+    /// The event category entries of `kind` belong to, if any
+    pub fn of_kind(kind: LogKind) -> Option<LogEvent> {
+        match kind {
+            LogKind::Info => None,
+            LogKind::Request => Some(LogEvent::Request),
+            LogKind::Blocked => Some(LogEvent::Block),
+            LogKind::Error => Some(LogEvent::Error),
+            LogKind::Ai => Some(LogEvent::AiSuggestion),
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            LogEvent::Request => "Allowed requests",
+            LogEvent::Block => "Blocked requests",
+            LogEvent::Error => "Errors",
+            LogEvent::AiSuggestion => "AI suggestions",
+            LogEvent::Tunnel => "Tunnel open/close",
+        }
+    }
+}
+
+/// How log entries are written to stdout
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Messages as shown in the GUI, emoji included
+    #[default]
+    Emoji,
+    /// ASCII tags such as `[BLOCK]` in place of emoji, for terminals and grep
+    Plain,
+}
+
+/// Longest log message kept; longer ones are truncated
+pub const MAX_LOG_MESSAGE_LEN: usize = 1024;
+
+/// Longest host kept with a log entry, the DNS limit
+pub const MAX_LOGGED_HOST_LEN: usize = 253;
+
+/// `text` cut to at most `max` bytes on a character boundary, noting how
+/// much was dropped, so absurdly long hosts and URLs can't flood the log.
+pub fn truncate_for_log(text: &str, max: usize) -> Cow<'_, str> {
+    if text.len() <= max {
+        return Cow::Borrowed(text);
+    }
+    let mut end = max;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    Cow::Owned(format!("{}… [truncated {} bytes]", &text[..end], text.len() - end))
+}
+
+/// Timestamp format used unless configured otherwise
+pub const DEFAULT_TIMESTAMP_FORMAT: &str = "%H:%M:%S";
+
+/// How log timestamps are written: a `strftime` pattern, in local time or UTC
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TimestampFormat {
+    pattern: String,
+    utc: bool,
+}
+
+impl TimestampFormat {
+    /// Check `pattern` before using it, since chrono panics on bad patterns
+    /// when formatting.
+    pub fn new(pattern: &str, utc: bool) -> Result<Self, String> {
+        if pattern.trim().is_empty() {
+            return Err("Timestamp format can't be empty".to_string());
+        }
+        if StrftimeItems::new(pattern).any(|item| matches!(item, Item::Error)) {
+            return Err(format!("Invalid timestamp format: {}", pattern));
+        }
+        Ok(Self { pattern: pattern.to_string(), utc })
+    }
+
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn is_utc(&self) -> bool {
+        self.utc
+    }
+
+    pub fn apply(&self, timestamp: &DateTime<Local>) -> String {
+        if self.utc {
+            timestamp.with_timezone(&Utc).format(&self.pattern).to_string()
+        } else {
+            timestamp.format(&self.pattern).to_string()
+        }
+    }
+}
+
+impl Default for TimestampFormat {
+    fn default() -> Self {
+        Self { pattern: DEFAULT_TIMESTAMP_FORMAT.to_string(), utc: false }
+    }
+}
+
+/// A single entry in the request log
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// Unique, increasing id
+    pub id: u64,
+    pub timestamp: DateTime<Local>,
+    pub kind: LogKind,
+    /// The host the entry is about, if any
+    pub host: Option<String>,
+    pub message: String,
+    /// HTTP status returned to the client, for request entries
+    pub status: Option<u16>,
+    /// The proxied request, for request entries
+    #[serde(default)]
+    pub request: Option<RequestInfo>,
+}
+
+/// The request a log entry is about, kept so it can be replayed
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequestInfo {
+    pub method: String,
+    /// Absolute request URL
+    pub url: String,
+    /// Request headers, only filled in when header capture is on
+    pub headers: Vec<(String, String)>,
+}
+
+impl RequestInfo {
+    /// A `curl` command line that repeats the request.
+    pub fn to_curl(&self) -> String {
+        let mut command = String::from("curl");
+        if self.method != "GET" {
+            command.push_str(&format!(" -X {}", self.method));
+        }
+        command.push(' ');
+        command.push_str(&shell_quote(&self.url));
+        for (name, value) in &self.headers {
+            command.push_str(" -H ");
+            command.push_str(&shell_quote(&format!("{}: {}", name, value)));
+        }
+        command
+    }
+}
+
+/// Quote `text` as a single POSIX shell word.
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+/// Outcome of a log entry, used to pick its display color
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LogClass {
+    /// Status messages and AI notes
+    Info,
+    /// A request that went through and got a 1xx-3xx response
+    Allowed,
+    /// A request that got a 4xx response from upstream
+    ClientError,
+    /// A 5xx response or a proxy error
+    ServerError,
+    /// A request DeTrack blocked
+    Blocked,
+}
+
+impl LogEntry {
+    pub fn class(&self) -> LogClass {
+        match (self.kind, self.status) {
+            (LogKind::Blocked, _) => LogClass::Blocked,
+            (LogKind::Error, _) => LogClass::ServerError,
+            (_, Some(status)) if status >= 500 => LogClass::ServerError,
+            (_, Some(status)) if status >= 400 => LogClass::ClientError,
+            (LogKind::Request, _) => LogClass::Allowed,
+            (LogKind::Info | LogKind::Ai, _) => LogClass::Info,
+        }
+    }
+
+    /// Whether `other` says the same thing, ignoring id and timestamp.
+    pub fn is_repeat_of(&self, other: &LogEntry) -> bool {
+        self.kind == other.kind
+            && self.host == other.host
+            && self.message == other.message
+            && self.status == other.status
+    }
+
+    /// ASCII tag naming the entry's kind, used by [`LogFormat::Plain`]
+    pub fn tag(&self) -> &'static str {
+        match self.kind {
+            LogKind::Info => "INFO",
+            LogKind::Request => "ALLOW",
+            LogKind::Blocked => "BLOCK",
+            LogKind::Error => "ERROR",
+            LogKind::Ai => "AI",
+        }
+    }
+
+    /// Format the entry for output in the given format.
+    ///
+    /// The plain format swaps the message's leading emoji for the entry's tag.
+    pub fn format(&self, format: LogFormat, timestamps: &TimestampFormat) -> String {
+        match format {
+            LogFormat::Emoji => format!("[{}] {}", timestamps.apply(&self.timestamp), self.message),
+            LogFormat::Plain => format!(
+                "[{}] [{}] {}",
+                timestamps.apply(&self.timestamp),
+                self.tag(),
+                self.message.trim_start_matches(|c: char| !c.is_ascii() || c.is_whitespace())
+            ),
+        }
+    }
+
+    /// Format the entry with its host replaced by [`redact_host`].
+    pub fn redacted(&self, timestamps: &TimestampFormat) -> String {
+        match &self.host {
+            Some(host) => format!(
+                "[{}] {}",
+                timestamps.apply(&self.timestamp),
+                self.message.replace(host.as_str(), &redact_host(host))
+            ),
+            None => self.format(LogFormat::Emoji, timestamps),
+        }
+    }
+}
+
+impl fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.timestamp.format(DEFAULT_TIMESTAMP_FORMAT), self.message)
+    }
+}
+
+/// Merge runs of consecutive repeated entries into one, keeping the latest
+/// entry of each run and how many entries it stands for.
+pub fn coalesce(entries: Vec<LogEntry>) -> Vec<(LogEntry, usize)> {
+    let mut merged: Vec<(LogEntry, usize)> = Vec::with_capacity(entries.len());
+    for entry in entries {
+        match merged.last_mut() {
+            Some((last, count)) if last.is_repeat_of(&entry) => {
+                *last = entry;
+                *count += 1;
+            }
+            _ => merged.push((entry, 1)),
+        }
+    }
+    merged
+}
+
+/// How [`group_by_host`] orders its groups
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GroupOrder {
+    /// Hosts with the most entries first
+    #[default]
+    Count,
+    /// Hosts seen most recently first
+    Recency,
+}
+
+/// The log entries about one host
+#[derive(Clone, Debug)]
+pub struct HostGroup {
+    pub host: String,
+    /// Newest first
+    pub entries: Vec<LogEntry>,
+}
+
+/// Group entries by host, skipping entries without one. `entries` are
+/// expected oldest first, as the log keeps them.
+pub fn group_by_host(entries: Vec<LogEntry>, order: GroupOrder) -> Vec<HostGroup> {
+    let mut groups: Vec<HostGroup> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for entry in entries.into_iter().rev() {
+        let Some(host) = entry.host.clone() else {
+            continue;
+        };
+        match index.get(&host) {
+            Some(&i) => groups[i].entries.push(entry),
+            None => {
+                index.insert(host.clone(), groups.len());
+                groups.push(HostGroup { host, entries: vec![entry] });
+            }
+        }
+    }
+
+    // Groups are already in order of their newest entry
+    if order == GroupOrder::Count {
+        groups.sort_by_key(|group| std::cmp::Reverse(group.entries.len()));
+    }
+    groups
+}
+
+/// Write `entries` to a new timestamped `.jsonl.gz` file in `dir`, one JSON
+/// object per line, and return its path.
+pub fn archive_logs(entries: &[LogEntry], dir: &Path) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!(
+        "detrack-logs-{}.jsonl.gz",
+        Local::now().format("%Y%m%d-%H%M%S%.3f")
+    ));
+
+    let mut writer = BufWriter::new(GzEncoder::new(File::create(&path)?, Compression::default()));
+    for entry in entries {
+        serde_json::to_writer(&mut writer, entry)?;
+        writer.write_all(b"\n")?;
+    }
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())?
+        .finish()?;
+
+    Ok(path)
+}
+
+/// Hide a host for display while keeping it recognisable across entries.
+///
+/// The registrable domain is replaced by a short stable hash and any
+/// subdomain labels by `***`, keeping the public suffix:
+/// `ads.example.co.uk` becomes e.g. `***.5c1e0a.co.uk`. IP addresses become
+/// `ip-<hash>`.
+pub fn redact_host(host: &str) -> String {
+    if host.parse::<IpAddr>().is_ok() {
+        return format!("ip-{}", short_hash(host));
+    }
+
+    match public_suffix::registrable_domain(host) {
+        Some(domain) => {
+            let suffix = domain.split_once('.').map(|(_, suffix)| suffix).unwrap_or("");
+            let hashed = format!("{}.{}", short_hash(&domain), suffix);
+            if domain.len() < host.len() {
+                format!("***.{}", hashed)
+            } else {
+                hashed
+            }
+        }
+        None => short_hash(host),
+    }
+}
+
+fn short_hash(text: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    text.to_lowercase().hash(&mut hasher);
+    format!("{:06x}", hasher.finish() & 0xff_ffff)
+}
@@ -1,74 +1,860 @@
 use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
-use std::path::Path;
+use std::time::{Duration, Instant};
+use std::path::{Path, PathBuf};
 use eframe::{egui, App, Frame, CreationContext};
 use egui::{Color32, RichText, Ui};
 use image;
 
 use detrack_proxy::{
-    shared_state::SharedState,
+    shared_state::{
+        BlocklistSimulation, ConnectionKind, DomainStat, PathStat, ProtectionScore, SharedState, SiteSummary, DASHBOARD_STATS_INTERVAL,
+        LOG_DRAIN_INTERVAL, LOG_QUEUE_CAPACITY, MAX_HEADER_COUNT_LIMIT, MAX_PATH_HOSTS, TEMPORARY_ALLOW_SECS,
+    },
+    logging::{coalesce, group_by_host, redact_host, GroupOrder, LogClass, LogEntry, LogEvent, LogFormat, LogKind, DEFAULT_TIMESTAMP_FORMAT, LOG_ARCHIVE_DIR},
     tracker_blocker::TrackerBlocker,
     run_proxy::run_proxy,
+    system_proxy,
+    config::{Config, EnvOverrides, CONFIG_PATH, DEFAULT_TRACKER_LIST},
+    ai_tracker::{AI_MODEL_PATH, AI_SNOOZE_HOURS},
+    summary_image::Summary,
+    cli,
+    presets::{default_preset, PRESETS},
+    decision_hook::DEFAULT_HOOK_TIMEOUT,
+    profiles::{list_profiles, Profile, DEFAULT_PROFILE, PROFILES_DIR},
+    blocklist_source::ImportDiff,
+    humanize::{format_bytes, format_count, ByteUnits},
+    verdict::AiConflictPolicy,
 };
 
+/// Window title, shown alone or followed by the session block count
+const WINDOW_TITLE: &str = "DeTrack Proxy";
+
+/// Shortest time between window title updates
+const TITLE_UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How far the log can be scrolled from the newest entries before it freezes
+const LOG_FREEZE_SCROLL_OFFSET: f32 = 20.0;
+
+/// Tracker lists offered by the setup wizard: label, path, description
+const STARTER_LISTS: &[(&str, &str, &str)] = &[
+    ("Bundled list", DEFAULT_TRACKER_LIST, "A short list of common ad and analytics hosts"),
+    ("Empty list", "tracker_lists/trackers.txt", "Start from scratch and add trackers yourself"),
+];
+
 // Add derive for PartialEq to fix comparison issues
 #[derive(PartialEq)]
 enum Tab {
     Dashboard,
     Logs,
+    Errors,
     BlockList,
     Settings,
     About,
     AI,
 }
 
+/// Fraction of the normal gaps between sections kept in compact mode
+const COMPACT_SPACE_SCALE: f32 = 0.4;
+
+/// Where compact mode is remembered in egui's memory, for [`space`]
+fn compact_id() -> egui::Id {
+    egui::Id::new("detrack_compact_ui")
+}
+
+/// Switch between the default and compact layout density.
+fn apply_density(ctx: &egui::Context, compact: bool) {
+    let default = egui::Style::default().spacing;
+    ctx.style_mut(|style| {
+        let spacing = &mut style.spacing;
+        if compact {
+            spacing.item_spacing = egui::vec2(6.0, 2.0);
+            spacing.button_padding = egui::vec2(4.0, 1.0);
+            spacing.indent = 14.0;
+            spacing.interact_size.y = 16.0;
+            spacing.window_margin = egui::Margin::same(4);
+        } else {
+            spacing.item_spacing = default.item_spacing;
+            spacing.button_padding = default.button_padding;
+            spacing.indent = default.indent;
+            spacing.interact_size = default.interact_size;
+            spacing.window_margin = default.window_margin;
+        }
+    });
+    ctx.data_mut(|data| data.insert_temp(compact_id(), compact));
+}
+
+/// Add a gap between sections, narrower in compact mode
+fn space(ui: &mut Ui, amount: f32) {
+    let compact = ui.ctx().data(|data| data.get_temp::<bool>(compact_id())).unwrap_or(false);
+    ui.add_space(if compact { amount * COMPACT_SPACE_SCALE } else { amount });
+}
+
+/// Colors for good, bad, warning and blocked states
+#[derive(Clone, Copy)]
+struct Palette {
+    good: Color32,
+    bad: Color32,
+    warning: Color32,
+    blocked: Color32,
+}
+
+const DEFAULT_PALETTE: Palette = Palette {
+    good: Color32::GREEN,
+    bad: Color32::RED,
+    warning: Color32::from_rgb(255, 165, 0),
+    blocked: Color32::from_rgb(180, 110, 255),
+};
+
+/// Okabe-Ito colors, which stay apart for the common kinds of color blindness
+const COLORBLIND_PALETTE: Palette = Palette {
+    good: Color32::from_rgb(86, 180, 233),
+    bad: Color32::from_rgb(230, 159, 0),
+    warning: Color32::from_rgb(240, 228, 66),
+    blocked: Color32::from_rgb(204, 121, 167),
+};
+
+/// Where the color-blind palette choice is remembered in egui's memory
+fn colorblind_id() -> egui::Id {
+    egui::Id::new("detrack_colorblind_palette")
+}
+
+/// Switch between the default and color-blind-safe palettes.
+fn apply_palette(ctx: &egui::Context, colorblind: bool) {
+    let palette = if colorblind { COLORBLIND_PALETTE } else { DEFAULT_PALETTE };
+    let default = egui::Style::default().visuals;
+    ctx.style_mut(|style| {
+        style.visuals.error_fg_color = if colorblind { palette.bad } else { default.error_fg_color };
+        style.visuals.warn_fg_color = if colorblind { palette.warning } else { default.warn_fg_color };
+    });
+    ctx.data_mut(|data| data.insert_temp(colorblind_id(), colorblind));
+}
+
+/// The palette in use
+fn palette(ctx: &egui::Context) -> Palette {
+    if ctx.data(|data| data.get_temp::<bool>(colorblind_id())).unwrap_or(false) {
+        COLORBLIND_PALETTE
+    } else {
+        DEFAULT_PALETTE
+    }
+}
+
+/// Where the byte unit choice is remembered in egui's memory
+fn byte_units_id() -> egui::Id {
+    egui::Id::new("detrack_byte_units")
+}
+
+/// Show byte sizes in binary (KiB) or decimal (KB) units.
+fn apply_byte_units(ctx: &egui::Context, binary: bool) {
+    let units = if binary { ByteUnits::Binary } else { ByteUnits::Decimal };
+    ctx.data_mut(|data| data.insert_temp(byte_units_id(), units));
+}
+
+/// The byte units in use
+fn byte_units(ctx: &egui::Context) -> ByteUnits {
+    ctx.data(|data| data.get_temp::<ByteUnits>(byte_units_id())).unwrap_or_default()
+}
+
+/// Color for each kind of log line
+fn log_color(class: LogClass, palette: Palette) -> Color32 {
+    match class {
+        LogClass::Info => Color32::GRAY,
+        LogClass::Allowed => palette.good,
+        LogClass::ClientError => palette.warning,
+        LogClass::ServerError => palette.bad,
+        LogClass::Blocked => palette.blocked,
+    }
+}
+
+/// Pages of the first-run setup wizard
+#[derive(Clone, Copy, PartialEq)]
+enum SetupStep {
+    Port,
+    Browser,
+    SystemProxy,
+    Blocklist,
+}
+
 struct RequestViewerApp {
     state: Arc<SharedState>,
     selected_tab: Tab,
     log_filter: String,
     new_domain: String,
+    new_allowed_domain: String,
+    new_blocked_cidr: String,
+    blocked_cidr_error: Option<String>,
+    new_bypass_host: String,
+    new_stun_turn_host: String,
+    new_allowed_port: String,
+    /// Outcome of the last "Reload from file", shown under the button
+    blocklist_reload_status: Option<Result<String, String>>,
     show_blocked_only: bool,
     max_logs: usize,
     auto_scroll: bool,
+    /// Newest log entry shown while the log is frozen for reading
+    log_frozen_at: Option<u64>,
+    /// Scroll to the newest entries on the next frame, after unfreezing
+    log_jump_to_latest: bool,
+    merge_duplicate_logs: bool,
+    group_stats_by_domain: bool,
+    /// Order domain statistics by data transferred instead of request count
+    sort_stats_by_data: bool,
+    privacy_mode: bool,
     ai_suggestions_showing: bool,
     logo_texture: Option<egui::TextureHandle>,
+    /// How many errors the Errors tab shows
+    errors_shown: usize,
+    /// Id of the newest error seen on the Errors tab, for the unseen count
+    errors_seen_up_to: u64,
+    /// Show the Logs tab grouped by host instead of as one stream
+    group_logs_by_host: bool,
+    log_group_order: GroupOrder,
+    /// Profile in use, whose config file `config` is saved to
+    profile: Profile,
+    config: Config,
+    /// Name typed for a new profile
+    new_profile_name: String,
+    /// Start listening again once the listener has stopped, after a
+    /// profile switch changed the port
+    restart_listener: bool,
+    /// Current wizard page, `None` once setup is complete
+    setup_step: Option<SetupStep>,
+    /// Index into `PRESETS` merged into the list when the wizard finishes
+    setup_preset: Option<usize>,
+    /// Why the AI model couldn't be saved when closing, shown until resolved
+    ai_save_error: Option<String>,
+    /// Why the timestamp format being typed can't be used
+    timestamp_format_error: Option<String>,
+    /// What the last import changed, shown until dismissed
+    import_diff: Option<(PathBuf, ImportDiff)>,
+    /// Candidate list pasted for a blocking preview
+    simulation_input: String,
+    simulation: Option<BlocklistSimulation>,
+    /// Close without retrying the AI model save
+    exit_without_saving: bool,
+    /// Show the session block count in the window title
+    count_in_title: bool,
+    /// Title last sent to the window, and when
+    window_title: String,
+    title_updated_at: Instant,
 }
 
 impl RequestViewerApp {
-    fn new(state: Arc<SharedState>) -> Self {
+    fn new(state: Arc<SharedState>, profile: Profile, config: Config) -> Self {
+        let setup_step = (!config.setup_complete).then_some(SetupStep::Port);
         Self {
             state,
             selected_tab: Tab::Dashboard,
             log_filter: String::new(),
             new_domain: String::new(),
+            new_allowed_domain: String::new(),
+            new_blocked_cidr: String::new(),
+            blocked_cidr_error: None,
+            new_bypass_host: String::new(),
+            new_stun_turn_host: String::new(),
+            new_allowed_port: String::new(),
+            blocklist_reload_status: None,
             show_blocked_only: false,
             max_logs: 1000,
             auto_scroll: true,
+            log_frozen_at: None,
+            log_jump_to_latest: false,
+            merge_duplicate_logs: true,
+            group_stats_by_domain: false,
+            sort_stats_by_data: false,
+            privacy_mode: false,
             ai_suggestions_showing: true,
             logo_texture: None,
+            group_logs_by_host: false,
+            errors_shown: 50,
+            errors_seen_up_to: 0,
+            log_group_order: GroupOrder::default(),
+            profile,
+            config,
+            new_profile_name: String::new(),
+            restart_listener: false,
+            setup_step,
+            setup_preset: None,
+            ai_save_error: None,
+            timestamp_format_error: None,
+            import_diff: None,
+            simulation_input: String::new(),
+            simulation: None,
+            exit_without_saving: false,
+            count_in_title: false,
+            window_title: WINDOW_TITLE.to_string(),
+            title_updated_at: Instant::now(),
+        }
+    }
+
+    /// Begin listening if the accept loop isn't running, and enable filtering.
+    fn start_proxy(&self) {
+        if !self.state.is_listener_running() {
+            start_proxy(Arc::clone(&self.state));
+        }
+        self.state.enable_proxy();
+    }
+
+    /// Keep the window title in sync with the session block count, at most
+    /// once per `TITLE_UPDATE_INTERVAL`.
+    fn update_window_title(&mut self, ctx: &egui::Context) {
+        let title = if self.count_in_title {
+            format!("{} — {} blocked", WINDOW_TITLE, self.state.get_session_blocked_count())
+        } else {
+            WINDOW_TITLE.to_string()
+        };
+        // Turning the count off takes effect right away
+        let throttled = self.count_in_title && self.title_updated_at.elapsed() < TITLE_UPDATE_INTERVAL;
+        if title != self.window_title && !throttled {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Title(title.clone()));
+            self.window_title = title;
+            self.title_updated_at = Instant::now();
+        }
+    }
+
+    /// First-run wizard, shown as a modal window until setup is complete
+    fn render_setup_wizard(&mut self, ctx: &egui::Context) {
+        let Some(step) = self.setup_step else {
+            return;
+        };
+
+        egui::Window::new("Welcome to DeTrack Proxy")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.set_width(420.0);
+
+                match step {
+                    SetupStep::Port => {
+                        ui.heading("1. Listen port");
+                        ui.label("DeTrack runs a proxy on your computer. Pick the port it listens on.");
+                        space(ui, 8.0);
+                        ui.horizontal(|ui| {
+                            ui.label("Port:");
+                            ui.add(egui::DragValue::new(&mut self.config.listen_port).range(1024..=65535));
+                        });
+                        ui.label("The default, 8100, is fine unless another program uses it.");
+                    }
+                    SetupStep::Browser => {
+                        let address = format!("127.0.0.1:{}", self.config.listen_port);
+                        ui.heading("2. Configure your browser");
+                        ui.label("Set your browser's HTTP and HTTPS proxy to this address:");
+                        space(ui, 8.0);
+                        ui.horizontal(|ui| {
+                            ui.label(RichText::new(&address).monospace().strong());
+                            if ui.button("📋 Copy").clicked() {
+                                ui.ctx().copy_text(address.clone());
+                            }
+                        });
+                        space(ui, 8.0);
+                        ui.label("Chrome / Edge: Settings -> System -> Open your computer's proxy settings");
+                        ui.label("Firefox: Settings -> General -> Network Settings -> Manual proxy configuration");
+                    }
+                    SetupStep::SystemProxy => {
+                        ui.heading("3. System proxy (optional)");
+                        if system_proxy::is_supported() {
+                            ui.label("DeTrack can point the system proxy at itself, so browsers using system settings need no changes. The previous setting is restored when DeTrack exits.");
+                            space(ui, 8.0);
+                            if self.state.is_system_proxy_configured() {
+                                ui.label(RichText::new("System proxy is pointed at DeTrack").color(palette(ui.ctx()).good));
+                            } else if ui.button("🖥️ Configure system proxy automatically").clicked() {
+                                let address = format!("127.0.0.1:{}", self.config.listen_port);
+                                if let Err(e) = self.state.enable_system_proxy(&address) {
+                                    self.state.append_log(format!("❌ {}", e));
+                                }
+                            }
+                        } else {
+                            ui.label("Automatic system proxy configuration isn't available on this platform. Configure your browser as shown in the previous step.");
+                        }
+                    }
+                    SetupStep::Blocklist => {
+                        ui.heading("4. Starter blocklist");
+                        ui.label("Choose the tracker list to start with. You can edit it later in the Blocklist tab.");
+                        space(ui, 8.0);
+                        for (label, path, description) in STARTER_LISTS {
+                            ui.radio_value(&mut self.config.tracker_list, path.to_string(), *label)
+                                .on_hover_text(*path);
+                            ui.label(RichText::new(*description).small());
+                        }
+                        space(ui, 8.0);
+                        ui.label("Add a curated preset to it:");
+                        ui.radio_value(&mut self.setup_preset, None, "No preset");
+                        for (i, preset) in PRESETS.iter().enumerate() {
+                            ui.radio_value(
+                                &mut self.setup_preset,
+                                Some(i),
+                                format!("{} ({} domains)", preset.name, preset.domain_count()),
+                            );
+                            ui.label(RichText::new(preset.description).small());
+                        }
+                    }
+                }
+
+                space(ui, 16.0);
+                ui.separator();
+                ui.horizontal(|ui| {
+                    let previous = match step {
+                        SetupStep::Port => None,
+                        SetupStep::Browser => Some(SetupStep::Port),
+                        SetupStep::SystemProxy => Some(SetupStep::Browser),
+                        SetupStep::Blocklist => Some(SetupStep::SystemProxy),
+                    };
+                    let next = match step {
+                        SetupStep::Port => Some(SetupStep::Browser),
+                        SetupStep::Browser => Some(SetupStep::SystemProxy),
+                        SetupStep::SystemProxy => Some(SetupStep::Blocklist),
+                        SetupStep::Blocklist => None,
+                    };
+
+                    if let Some(previous) = previous {
+                        if ui.button("⬅ Back").clicked() {
+                            self.setup_step = Some(previous);
+                        }
+                    }
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        match next {
+                            Some(next) => {
+                                if ui.button("Next ➡").clicked() {
+                                    self.setup_step = Some(next);
+                                }
+                            }
+                            None => {
+                                if ui.button("✅ Finish").clicked() {
+                                    self.finish_setup();
+                                }
+                            }
+                        }
+                    });
+                });
+            });
+    }
+
+    /// Apply the wizard choices, save the config and start the proxy
+    fn finish_setup(&mut self) {
+        if self.config.tracker_list != DEFAULT_TRACKER_LIST {
+            match TrackerBlocker::new(&self.config.tracker_list) {
+                Ok(blocker) => {
+                    if let Err(e) = self.state.replace_blocker(blocker) {
+                        self.state.append_log(format!("❌ {}", e));
+                    }
+                }
+                Err(e) => self.state.append_log(format!("❌ Failed to load {}: {}", self.config.tracker_list, e)),
+            }
+        }
+        if let Some(preset) = self.setup_preset.and_then(|i| PRESETS.get(i)) {
+            if let Err(e) = self.state.apply_preset(preset) {
+                self.state.append_log(format!("❌ {}", e));
+            }
+        }
+
+        self.config.setup_complete = true;
+        if let Err(e) = self.config.save(&self.profile.config_path) {
+            self.state.append_log(format!("❌ Failed to save config: {}", e));
+        }
+
+        self.state.set_listen_port(self.config.listen_port);
+        start_proxy(Arc::clone(&self.state));
+        self.setup_step = None;
+    }
+
+    /// On close, retry a failed AI model save. If it fails again, keep the
+    /// window open and ask what to do.
+    fn check_unsaved_ai_model(&mut self, ctx: &egui::Context) {
+        if ctx.input(|i| i.viewport().close_requested()) && !self.exit_without_saving {
+            if let Err(e) = self.state.flush_ai_model() {
+                ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+                self.ai_save_error = Some(e);
+            }
+        }
+
+        let Some(error) = self.ai_save_error.clone() else {
+            return;
+        };
+        egui::Window::new("AI model not saved")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(RichText::new(format!("❌ {}", error)).color(palette(ui.ctx()).bad));
+                ui.label("Approvals and rejections since the last successful save will be lost if you quit now.");
+                space(ui, 8.0);
+                ui.horizontal(|ui| {
+                    if ui.button("🔄 Retry").clicked() {
+                        match self.state.flush_ai_model() {
+                            Ok(()) => self.close_after_ai_save(ctx),
+                            Err(e) => self.ai_save_error = Some(e),
+                        }
+                    }
+                    if ui.button("📁 Save Elsewhere…").clicked() {
+                        if let Some(path) = pick_ai_model_path() {
+                            match self.state.change_ai_model_path(path.clone()) {
+                                Ok(()) => {
+                                    self.remember_ai_model_path(&path);
+                                    self.close_after_ai_save(ctx);
+                                }
+                                Err(e) => self.ai_save_error = Some(e),
+                            }
+                        }
+                    }
+                    if ui.button("Quit Without Saving").clicked() {
+                        self.close_after_ai_save(ctx);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.ai_save_error = None;
+                    }
+                });
+            });
+    }
+
+    fn close_after_ai_save(&mut self, ctx: &egui::Context) {
+        self.ai_save_error = None;
+        self.exit_without_saving = true;
+        ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+    }
+
+    /// Keep a newly chosen AI model path for later runs
+    /// Save the bypass list after an edit
+    fn save_bypass_hosts(&mut self) {
+        self.config.bypass_hosts = self.state.get_bypass_hosts();
+        if let Err(e) = self.config.save(&self.profile.config_path) {
+            self.state.append_log(format!("❌ Failed to save config: {}", e));
+        }
+    }
+
+    fn remember_ai_model_path(&mut self, path: &Path) {
+        self.config.ai_model_path = path.display().to_string();
+        if let Err(e) = self.config.save(&self.profile.config_path) {
+            self.state.append_log(format!("❌ Failed to save config: {}", e));
+        }
+    }
+
+    /// Profile dropdown, and a field to create a new profile from the defaults
+    fn render_profile_picker(&mut self, ui: &mut Ui) {
+        let root = Path::new(PROFILES_DIR);
+        let mut switch_to = None;
+        ui.horizontal(|ui| {
+            ui.label("Profile:");
+            egui::ComboBox::from_id_salt("profile")
+                .selected_text(self.profile.display_name())
+                .show_ui(ui, |ui| {
+                    if ui.selectable_label(self.profile.name.is_none(), DEFAULT_PROFILE).clicked() {
+                        switch_to = Some(Profile::default_profile());
+                    }
+                    let names = list_profiles(root).unwrap_or_else(|e| {
+                        ui.label(format!("❌ {}", e));
+                        Vec::new()
+                    });
+                    for name in names {
+                        let selected = self.profile.name.as_deref() == Some(name.as_str());
+                        if ui.selectable_label(selected, &name).clicked() {
+                            switch_to = Profile::named(root, &name).ok();
+                        }
+                    }
+                });
+            
+            ui.text_edit_singleline(&mut self.new_profile_name);
+            if ui.button("➕ New Profile")
+                .on_hover_text("Create a profile with default settings and its own blocklist, allowlist and AI model")
+                .clicked() {
+                let created = Profile::named(root, &self.new_profile_name)
+                    .and_then(|profile| profile.create(&Config::default()).map(|_| profile));
+                match created {
+                    Ok(profile) => {
+                        self.new_profile_name.clear();
+                        switch_to = Some(profile);
+                    }
+                    Err(e) => self.state.append_log(format!("❌ Failed to create profile: {}", e)),
+                }
+            }
+        });
+        
+        if let Some(profile) = switch_to.filter(|profile| *profile != self.profile) {
+            self.switch_profile(ui.ctx(), profile);
+        }
+    }
+
+    /// Load `profile` in place of the current one and remember it for the next launch
+    fn switch_profile(&mut self, ctx: &egui::Context, profile: Profile) {
+        let config = match profile.load_config() {
+            Ok(Some(config)) => config,
+            Ok(None) => {
+                self.state.append_log(format!("❌ Profile {} has no settings file", profile.display_name()));
+                return;
+            }
+            Err(e) => {
+                self.state.append_log(format!("❌ Failed to read profile {}: {}", profile.display_name(), e));
+                return;
+            }
+        };
+        
+        let old_port = self.state.get_listen_port();
+        if let Err(e) = self.state.load_profile(&profile, &config) {
+            self.state.append_log(format!("❌ {}", e));
+            return;
+        }
+        if config.listen_port != old_port && self.state.is_listener_running() {
+            self.state.stop_listener();
+            self.restart_listener = true;
+        }
+        
+        apply_density(ctx, config.compact_ui);
+        apply_palette(ctx, config.colorblind_palette);
+        apply_byte_units(ctx, config.binary_byte_units);
+        if let Err(e) = self.state.set_timestamp_format(&config.log_timestamp_format, config.log_timestamps_utc) {
+            self.state.append_log(format!("⚠️ {}; using {}", e, DEFAULT_TIMESTAMP_FORMAT));
+        }
+        
+        if decision_script(&config) != self.state.get_decision_hook().map(|hook| hook.program().to_path_buf()) {
+            self.state.set_decision_hook(decision_script(&config), DEFAULT_HOOK_TIMEOUT);
+        }
+        if let Err(e) = remember_profile(profile.name.clone()) {
+            self.state.append_log(format!("❌ Failed to save config: {}", e));
+        }
+        self.profile = profile;
+        self.config = config;
+        if self.profile.name.is_none() {
+            self.config.profile = None;
+        }
+    }
+
+    /// Show what the last import added
+    fn render_import_diff(&mut self, ctx: &egui::Context) {
+        let Some((path, diff)) = &self.import_diff else {
+            return;
+        };
+        let mut open = true;
+        egui::Window::new("Import Results")
+            .collapsible(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.label(format!("📥 {}", path.display()));
+                ui.label(format!("Detected: {}", diff.format));
+                ui.label(diff.summary());
+                if !diff.added.is_empty() {
+                    ui.collapsing(format!("New domains ({})", diff.added.len()), |ui| {
+                        egui::ScrollArea::vertical().max_height(300.0).show_rows(
+                            ui,
+                            ui.text_style_height(&egui::TextStyle::Body),
+                            diff.added.len(),
+                            |ui, rows| {
+                                for domain in &diff.added[rows] {
+                                    ui.label(domain);
+                                }
+                            },
+                        );
+                    });
+                }
+            });
+        if !open {
+            self.import_diff = None;
+        }
+    }
+
+    /// Use the configured timestamp format if it's valid
+    fn apply_timestamp_format(&mut self) {
+        match self.state.set_timestamp_format(&self.config.log_timestamp_format, self.config.log_timestamps_utc) {
+            Ok(()) => self.timestamp_format_error = None,
+            Err(e) => self.timestamp_format_error = Some(e),
+        }
+    }
+
+    /// Log entry text as displayed, with hosts hidden in privacy mode
+    fn log_text(&self, entry: &LogEntry) -> String {
+        let timestamps = self.state.get_timestamp_format();
+        if self.privacy_mode {
+            entry.redacted(&timestamps)
+        } else {
+            entry.format(LogFormat::Emoji, &timestamps)
+        }
+    }
+
+    /// One line of the live feed, with an "allow once" button on blocked requests
+    fn render_log_entry(&self, ui: &mut Ui, entry: &LogEntry, log: String) {
+        let text = RichText::new(&log).color(log_color(entry.class(), palette(ui.ctx())));
+        let label = egui::Label::new(text).sense(egui::Sense::click());
+
+        let response = match (&entry.kind, &entry.host) {
+            (LogKind::Blocked, Some(host)) => {
+                ui.horizontal(|ui| {
+                    let response = ui.add(label);
+                    if ui.small_button("⏳ Allow once")
+                        .on_hover_text(format!("Let requests through for {} seconds", TEMPORARY_ALLOW_SECS))
+                        .clicked()
+                    {
+                        self.state.allow_once(host);
+                    }
+                    response
+                }).inner
+            }
+            _ => ui.add(label),
+        };
+
+        response.context_menu(|ui| {
+            if ui.button("📋 Copy line").clicked() {
+                ui.ctx().copy_text(log.clone());
+                ui.close_menu();
+            }
+            if self.state.is_log_entry_pinned(entry.id) {
+                if ui.button("📌 Unpin").clicked() {
+                    self.state.unpin_log_entry(entry.id);
+                    ui.close_menu();
+                }
+            } else if ui.button("📌 Pin").on_hover_text("Keep this entry at the top of the Logs tab").clicked() {
+                if let Err(e) = self.state.pin_log_entry(entry.id) {
+                    self.state.append_log(format!("⚠️ {}", e));
+                }
+                ui.close_menu();
+            }
+            if let Some(request) = &entry.request {
+                if ui.button("📋 Copy as curl").clicked() {
+                    ui.ctx().copy_text(request.to_curl());
+                    ui.close_menu();
+                }
+            }
+        });
+    }
+
+    /// Score gauge, with what would raise it
+    fn render_protection_score(&self, ui: &mut Ui, protection: &ProtectionScore) {
+        let colors = palette(ui.ctx());
+        let color = match protection.score {
+            80.. => colors.good,
+            50..=79 => colors.warning,
+            _ => colors.bad,
+        };
+        
+        ui.heading("Protection Score");
+        space(ui, 8.0);
+        ui.add(egui::ProgressBar::new(protection.score as f32 / 100.0)
+            .desired_width(300.0)
+            .fill(color)
+            .text(RichText::new(format!("{} / 100", protection.score)).strong()))
+            .on_hover_text(protection.factors.iter()
+                .map(|f| format!("{}: {}/{}", f.name, f.points, f.max))
+                .collect::<Vec<_>>()
+                .join("\n"));
+        for factor in protection.weak_factors() {
+            ui.label(format!("• {} ({}/{}): {}", factor.name, factor.points, factor.max, factor.hint));
+        }
+    }
+
+    /// Preview of what a candidate list would block among the hosts seen
+    /// so far, leaving the live blocklist alone
+    fn render_simulation(&mut self, ui: &mut Ui) {
+        ui.heading("Simulate a List");
+        space(ui, 8.0);
+        ui.label("Paste a tracker list, or load one, to see which of the hosts you've visited it would block.");
+        
+        egui::ScrollArea::vertical().id_salt("simulation_input").max_height(120.0).show(ui, |ui| {
+            ui.add(egui::TextEdit::multiline(&mut self.simulation_input)
+                .hint_text("ads.example.com\ntracker.example.net")
+                .code_editor()
+                .desired_rows(4)
+                .desired_width(f32::INFINITY));
+        });
+        ui.horizontal(|ui| {
+            if ui.button("📂 Load file…").clicked() {
+                if let Some(path) = rfd::FileDialog::new().add_filter("Tracker list", &["txt"]).pick_file() {
+                    match std::fs::read_to_string(&path) {
+                        Ok(content) => self.simulation_input = content,
+                        Err(e) => self.state.append_log(format!("❌ Failed to read {}: {}", path.display(), e)),
+                    }
+                }
+            }
+            if ui.add_enabled(!self.simulation_input.trim().is_empty(), egui::Button::new("▶ Simulate")).clicked() {
+                self.simulation = Some(self.state.simulate_blocklist(&self.simulation_input));
+            }
+            if self.simulation.is_some() && ui.button("Clear").clicked() {
+                self.simulation = None;
+            }
+        });
+        
+        let Some(simulation) = &self.simulation else {
+            return;
+        };
+        let colors = palette(ui.ctx());
+        space(ui, 8.0);
+        ui.label(format!(
+            "{} entries ({} malformed lines skipped). Of the hosts seen, {} would be newly blocked ({} requests) and {} are already blocked.",
+            simulation.candidate_size,
+            simulation.malformed,
+            simulation.newly_blocked.len(),
+            simulation.newly_blocked_requests(),
+            simulation.already_blocked,
+        ));
+        if !simulation.allowlist_overlaps.is_empty() {
+            ui.label(RichText::new(format!(
+                "⚠ Lists {} allowlisted hosts, which would stay allowed: {}",
+                simulation.allowlist_overlaps.len(),
+                simulation.allowlist_overlaps.join(", "),
+            )).color(colors.warning));
+        }
+        egui::ScrollArea::vertical().id_salt("simulation_results").max_height(200.0).show(ui, |ui| {
+            for stat in &simulation.newly_blocked {
+                ui.label(RichText::new(format!(
+                    "{} — {} requests",
+                    self.display_host(&stat.domain), stat.requests
+                )).color(colors.blocked));
+            }
+        });
+    }
+
+    fn save_blocked_cidrs(&mut self) {
+        self.config.blocked_cidrs = self.state.get_blocked_cidrs();
+        if let Err(e) = self.config.save(&self.profile.config_path) {
+            self.state.append_log(format!("❌ Failed to save config: {}", e));
+        }
+    }
+
+    /// Host as displayed, hidden in privacy mode
+    fn display_host(&self, host: &str) -> String {
+        if self.privacy_mode {
+            redact_host(host)
+        } else {
+            host.to_string()
         }
     }
 
     fn render_dashboard(&mut self, ui: &mut Ui) {
         ui.heading("Dashboard");
-        ui.add_space(10.0);
+        space(ui, 10.0);
+
+        // An empty list blocks nothing, e.g. after a list file failed to load
+        if self.state.is_proxy_enabled() && self.state.is_blocklist_empty() {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("⚠ Blocklist is empty — nothing is being blocked")
+                    .strong()
+                    .color(palette(ui.ctx()).warning));
+                if ui.button(format!("Add the {} list", default_preset().name)).clicked() {
+                    if let Err(e) = self.state.seed_empty_blocklist() {
+                        self.state.append_log(format!("❌ {}", e));
+                    }
+                }
+            });
+            space(ui, 10.0);
+        }
 
         // Status and controls
         ui.horizontal(|ui| {
             let enabled = self.state.is_proxy_enabled();
-            let status_text = if enabled {
-                RichText::new("🟢 Proxy Running").color(Color32::GREEN)
+            let listening = self.state.is_listener_running();
+            let status_text = if enabled && listening {
+                RichText::new("✔ Proxy Running").color(palette(ui.ctx()).good)
+            } else if listening {
+                RichText::new("✖ Proxy Stopped").color(palette(ui.ctx()).bad)
             } else {
-                RichText::new("🔴 Proxy Stopped").color(Color32::RED)
+                RichText::new("⚪ Not Listening").color(Color32::GRAY)
             };
             ui.label(status_text);
             
-            if ui.button(if enabled { "🚫 Stop Proxy" } else { "▶️ Start Proxy" }).clicked() {
-                if enabled {
+            let running = enabled && listening;
+            if ui.button(if running { "🚫 Stop Proxy" } else { "▶️ Start Proxy" }).clicked() {
+                if running {
                     self.state.disable_proxy();
                 } else {
-                    self.state.enable_proxy();
+                    self.start_proxy();
                 }
             }
             
@@ -86,108 +872,406 @@ impl RequestViewerApp {
             }
         });
         
-        ui.add_space(16.0);
+        space(ui, 16.0);
+        
+        let cached = self.state.get_cached_dashboard_stats();
+        self.render_protection_score(ui, &cached.protection);
+        space(ui, 16.0);
         
         // Stats overview
-        ui.heading("Request Statistics");
+        ui.horizontal(|ui| {
+            ui.heading("Request Statistics");
+            let age = (chrono::Utc::now() - cached.computed_at).num_seconds().max(0);
+            ui.label(RichText::new(format!("domain figures updated {}s ago", age)).small().color(Color32::GRAY));
+        });
         
-        egui::Grid::new("stats_grid").num_columns(2).spacing([40.0, 8.0]).show(ui, |ui| {
+        egui::Grid::new("stats_grid").num_columns(3).spacing([40.0, 8.0]).show(ui, |ui| {
             // Get stats
+            let colors = palette(ui.ctx());
             let allowed = self.state.get_allowed_count();
             let blocked = self.state.get_blocked_count();
             let total = allowed + blocked;
             
+            let session_allowed = self.state.get_session_allowed_count();
+            let session_blocked = self.state.get_session_blocked_count();
+            let session_total = session_allowed + session_blocked;
+            
+            let block_rate = |blocked: usize, total: usize| if total > 0 {
+                (blocked as f32 / total as f32) * 100.0
+            } else {
+                0.0
+            };
+            
+            ui.label("");
+            ui.label(RichText::new("This Session").strong());
+            ui.label(RichText::new("All Time").strong());
+            ui.end_row();
+            
             ui.label("Total Requests:");
-            ui.label(format!("{}", total));
+            ui.label(format_count(session_total as u64));
+            ui.label(format_count(total as u64));
             ui.end_row();
             
             ui.label("Allowed Requests:");
-            ui.label(RichText::new(format!("{}", allowed)).color(Color32::GREEN));
+            ui.label(RichText::new(format_count(session_allowed as u64)).color(colors.good));
+            ui.label(RichText::new(format_count(allowed as u64)).color(colors.good));
             ui.end_row();
             
             ui.label("Blocked Requests:");
-            ui.label(RichText::new(format!("{}", blocked)).color(Color32::RED));
+            ui.label(RichText::new(format_count(session_blocked as u64)).color(colors.bad));
+            ui.label(RichText::new(format_count(blocked as u64)).color(colors.bad));
             ui.end_row();
             
             ui.label("Block Rate:");
-            let block_rate = if total > 0 {
-                (blocked as f32 / total as f32) * 100.0
-            } else {
-                0.0
-            };
-            ui.label(format!("{:.1}%", block_rate));
+            ui.label(format!("{:.1}%", block_rate(session_blocked, session_total)));
+            ui.label(format!("{:.1}%", block_rate(blocked, total)));
             ui.end_row();
             
-            // Get domain stats
-            let domain_stats = self.state.get_stats();
-            
             ui.label("Unique Domains:");
-            ui.label(format!("{}", domain_stats.len()));
+            ui.label("");
+            ui.label(format_count(cached.domains.len() as u64));
+            ui.end_row();
+            
+            ui.label("Active Tunnels:");
+            ui.label(format!("{} / {}", self.state.get_active_tunnels(), self.state.get_max_tunnels()));
+            ui.label("");
+            ui.end_row();
+            
+            let (hits, misses) = self.state.get_verdict_cache_stats();
+            ui.label("Verdict Cache Hit Rate:");
+            if hits + misses > 0 {
+                ui.label(format!("{:.1}%", hits as f64 / (hits + misses) as f64 * 100.0));
+            } else {
+                ui.label("-");
+            }
+            ui.label("");
             ui.end_row();
         });
         
-        ui.add_space(16.0);
+        space(ui, 16.0);
+        
+        // Per-domain statistics
+        ui.horizontal(|ui| {
+            ui.heading("Domain Statistics");
+            ui.checkbox(&mut self.group_stats_by_domain, "Group by registrable domain");
+            ui.checkbox(&mut self.sort_stats_by_data, "Sort by data transferred");
+        });
+        space(ui, 8.0);
+        
+        egui::ScrollArea::vertical().id_salt("domain_stats").max_height(200.0).show(ui, |ui| {
+            if self.group_stats_by_domain {
+                let mut groups = cached.groups.clone();
+                if self.sort_stats_by_data {
+                    groups.sort_by(|a, b| b.data_transferred.cmp(&a.data_transferred).then_with(|| a.domain.cmp(&b.domain)));
+                    for group in &mut groups {
+                        group.hosts.sort_by(|a, b| b.data_transferred().cmp(&a.data_transferred()).then_with(|| a.domain.cmp(&b.domain)));
+                    }
+                }
+                for group in groups {
+                    let title = format!(
+                        "{} — {} requests, {} blocked, {} ({} hosts)",
+                        self.display_host(&group.domain), format_count(group.requests as u64), format_count(group.blocked as u64),
+                        format_bytes(group.data_transferred, byte_units(ui.ctx())), group.hosts.len()
+                    );
+                    egui::CollapsingHeader::new(title)
+                        .id_salt(&group.domain)
+                        .show(ui, |ui| {
+                            render_domain_rows(ui, &group.domain, &group.hosts, &self.state, self.privacy_mode);
+                        });
+                }
+            } else {
+                let mut stats: Vec<DomainStat> = cached.domains.clone();
+                if self.sort_stats_by_data {
+                    stats.sort_by(|a, b| b.data_transferred().cmp(&a.data_transferred()).then_with(|| a.domain.cmp(&b.domain)));
+                } else {
+                    stats.sort_by(|a, b| b.requests.cmp(&a.requests).then_with(|| a.domain.cmp(&b.domain)));
+                }
+                render_domain_rows(ui, "domain_stats_grid", &stats, &self.state, self.privacy_mode);
+            }
+        });
+        
+        space(ui, 16.0);
+
+        self.render_site_summary(ui, &cached.sites);
+
+        space(ui, 16.0);
+
+        self.render_top_paths(ui, &cached.top_paths);
+
+        space(ui, 16.0);
+
+        self.render_connections(ui);
+
+        space(ui, 16.0);
         
         // Recent activity
         ui.heading("Recent Activity");
-        ui.add_space(8.0);
+        space(ui, 8.0);
         
         egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
-            let logs = self.state.get_logs();
+            let logs = self.state.get_log_entries();
             let logs_to_show = logs.iter().rev().take(10);
             
-            for log in logs_to_show {
-                let text = if log.contains("Blocked") || log.contains("🚫") {
-                    RichText::new(log.clone()).color(Color32::RED)
-                } else if log.contains("Allowed") || log.contains("✅") {
-                    RichText::new(log.clone()).color(Color32::GREEN)
-                } else {
-                    RichText::new(log.clone())
-                };
-                ui.label(text);
+            for entry in logs_to_show {
+                self.render_log_entry(ui, entry, self.log_text(entry));
             }
         });
 
 
         // Bandwidth section
-        ui.add_space(16.0);
+        space(ui, 16.0);
 
         ui.heading("Bandwidth Savings");
-        ui.add_space(8.0);
+        space(ui, 8.0);
 
         let saved_bytes = self.state.get_bandwidth_saved();
-        ui.label(format!("Total Saved: {:.2} MB", 
-        saved_bytes as f64 / 1_000_000.0));
-    }
+        ui.label(format!("Total Saved: {}", format_bytes(saved_bytes, byte_units(ui.ctx()))));
 
-    fn render_logs(&mut self, ui: &mut Ui) {
-        ui.heading("Request Logs");
-        ui.add_space(10.0);
-        
-        // Log controls
+        let time_saved = self.state.estimated_time_saved(self.config.assumed_speed_mbps);
         ui.horizontal(|ui| {
-            ui.label("Filter:");
-            ui.text_edit_singleline(&mut self.log_filter);
-            
-            ui.checkbox(&mut self.show_blocked_only, "Blocked Only");
-            
-            ui.label("Max logs:");
+            ui.label(format!("Estimated Time Saved: ~{:.1} s", time_saved.as_secs_f64()))
+                .on_hover_text("An estimate: blocked bytes at the assumed speed, plus ~50 ms of latency per blocked request");
+            ui.label("at");
+            let speed = ui.add(egui::DragValue::new(&mut self.config.assumed_speed_mbps)
+                .range(1..=10_000)
+                .suffix(" Mbit/s"));
+            if speed.changed() {
+                if let Err(e) = self.config.save(&self.profile.config_path) {
+                    self.state.append_log(format!("❌ Failed to save config: {}", e));
+                }
+            }
+        });
+
+        space(ui, 8.0);
+        if ui.button("🖼 Save Summary Image").on_hover_text("A PNG of these stats for sharing").clicked() {
+            self.save_summary_image();
+        }
+    }
+
+    /// Ask where to save, then write the all-time stats as a PNG
+    fn save_summary_image(&self) {
+        let Some(path) = rfd::FileDialog::new()
+            .set_file_name("detrack-summary.png")
+            .add_filter("PNG image", &["png"])
+            .save_file()
+        else {
+            return;
+        };
+
+        let mut summary = Summary::from_state(&self.state);
+        if self.privacy_mode {
+            for (domain, _) in &mut summary.top_domains {
+                *domain = redact_host(domain);
+            }
+        }
+
+        // Draw with the same font the UI uses
+        let fonts = egui::FontDefinitions::default();
+        let result = match fonts.font_data.get("Ubuntu-Light") {
+            Some(data) => summary.save_png(&data.font, &path),
+            None => Err(std::io::Error::new(std::io::ErrorKind::NotFound, "no font to draw with")),
+        };
+        match result {
+            Ok(()) => self.state.append_log(format!("🖼 Saved summary image to {}", path.display())),
+            Err(e) => self.state.append_log(format!("❌ Failed to save summary image: {}", e)),
+        }
+    }
+
+    /// Busiest request paths across hosts, which can reveal tracking
+    /// endpoints such as `/collect` served from many domains
+    fn render_top_paths(&mut self, ui: &mut Ui, paths: &[PathStat]) {
+        ui.horizontal(|ui| {
+            ui.heading("Top Paths");
+            let mut enabled = self.state.is_path_stats_enabled();
+            if ui.checkbox(&mut enabled, "Count requests by path")
+                .on_hover_text("IDs in paths are replaced by :id, so /u/123/collect and /u/456/collect count together")
+                .changed() {
+                self.state.set_path_stats_enabled(enabled);
+            }
+        });
+        space(ui, 8.0);
+
+        if paths.is_empty() {
+            ui.label(if self.state.is_path_stats_enabled() {
+                "No requests counted yet."
+            } else {
+                "Turn on counting by path to see which endpoints are requested most."
+            });
+            return;
+        }
+
+        let colors = palette(ui.ctx());
+        egui::ScrollArea::vertical().id_salt("top_paths").max_height(200.0).show(ui, |ui| {
+            egui::Grid::new("top_paths_grid").num_columns(4).striped(true).show(ui, |ui| {
+                ui.strong("Path");
+                ui.strong("Requests");
+                ui.strong("Blocked");
+                ui.strong("Hosts");
+                ui.end_row();
+                for stat in paths {
+                    let color = if stat.blocked > 0 { colors.blocked } else { colors.good };
+                    ui.label(RichText::new(&stat.path).monospace().color(color));
+                    ui.label(format_count(stat.requests as u64));
+                    ui.label(format_count(stat.blocked as u64));
+                    let hosts = if stat.hosts.len() >= MAX_PATH_HOSTS {
+                        format!("{}+", stat.hosts.len())
+                    } else {
+                        stat.hosts.len().to_string()
+                    };
+                    let preview: Vec<String> = stat.hosts.iter().take(10).map(|host| self.display_host(host)).collect();
+                    ui.label(hosts).on_hover_text(preview.join("\n"));
+                    ui.end_row();
+                }
+            });
+        });
+    }
+
+    /// Sites visited this session, each with the trackers contacted from it
+    fn render_site_summary(&mut self, ui: &mut Ui, summary: &[SiteSummary]) {
+        ui.heading("Sites Visited");
+        space(ui, 8.0);
+
+        if summary.is_empty() {
+            ui.label("No third-party requests with a referring page yet this session.");
+            return;
+        }
+
+        let colors = palette(ui.ctx());
+        egui::ScrollArea::vertical().id_salt("site_summary").max_height(200.0).show(ui, |ui| {
+            for site in summary {
+                let title = format!(
+                    "{} — {} trackers blocked, {} third-party requests",
+                    self.display_host(&site.site), site.blocked, site.requests
+                );
+                egui::CollapsingHeader::new(title)
+                    .id_salt(("site_summary", &site.site))
+                    .show(ui, |ui| {
+                        for contact in &site.contacts {
+                            let text = format!(
+                                "{} — {} of {} blocked",
+                                self.display_host(&contact.host), contact.blocked, contact.requests
+                            );
+                            let color = if contact.blocked > 0 { colors.blocked } else { colors.good };
+                            ui.label(RichText::new(text).color(color));
+                        }
+                    });
+            }
+        });
+    }
+
+    /// Open tunnels and upstream connections, each with a button to close it
+    fn render_connections(&mut self, ui: &mut Ui) {
+        ui.heading("Connections");
+        space(ui, 8.0);
+
+        let connections = self.state.get_connections();
+        if connections.is_empty() {
+            ui.label("No open connections");
+            return;
+        }
+
+        let now = chrono::Utc::now();
+        egui::ScrollArea::vertical().id_salt("connections").max_height(150.0).show(ui, |ui| {
+            egui::Grid::new("connections_grid").num_columns(5).striped(true).spacing([24.0, 4.0]).show(ui, |ui| {
+                ui.label(RichText::new("Host").strong());
+                ui.label(RichText::new("Kind").strong());
+                ui.label(RichText::new("Open For").strong());
+                ui.label(RichText::new("Transferred").strong());
+                ui.label("");
+                ui.end_row();
+
+                for connection in connections {
+                    ui.label(self.display_host(&connection.host));
+                    ui.label(match connection.kind {
+                        ConnectionKind::Tunnel => "Tunnel",
+                        ConnectionKind::Forward => "HTTP",
+                    });
+                    ui.label(format!("{}s", (now - connection.started).num_seconds().max(0)));
+                    ui.label(format!("{:.1} KB", connection.bytes as f64 / 1000.0));
+                    if ui.button("✂ Close").clicked() {
+                        self.state.close_connection(connection.id);
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+    }
+
+    fn render_logs(&mut self, ui: &mut Ui) {
+        ui.heading("Request Logs");
+        space(ui, 10.0);
+        
+        // Log controls
+        ui.horizontal(|ui| {
+            ui.label("Filter:");
+            ui.text_edit_singleline(&mut self.log_filter);
+            
+            ui.checkbox(&mut self.show_blocked_only, "Blocked Only");
+            
+            ui.label("Max logs:");
             ui.add(egui::Slider::new(&mut self.max_logs, 10..=10000).logarithmic(true));
             
-            ui.checkbox(&mut self.auto_scroll, "Auto-scroll");
+            ui.checkbox(&mut self.auto_scroll, "Auto-scroll")
+                .on_hover_text("Keep the newest entries in view. Scrolling down to read older ones freezes the log.");
+            
+            match self.log_frozen_at {
+                Some(frozen_at) => {
+                    let new = self.state.get_log_entries().iter().filter(|entry| entry.id > frozen_at).count();
+                    if ui.button(format!("▶ Resume ({} new)", new)).clicked() {
+                        self.log_frozen_at = None;
+                        self.log_jump_to_latest = true;
+                    }
+                }
+                None => {
+                    if ui.button("⏸ Freeze")
+                        .on_hover_text("Stop new entries from moving the log while you read; they're still collected")
+                        .clicked() {
+                        self.log_frozen_at = Some(self.state.get_log_entries().last().map_or(0, |entry| entry.id));
+                    }
+                }
+            }
+            
+            ui.checkbox(&mut self.merge_duplicate_logs, "Merge duplicates")
+                .on_hover_text("Show repeated consecutive lines once, with a (xN) count");
+            
+            ui.checkbox(&mut self.group_logs_by_host, "Group by domain")
+                .on_hover_text("Show each host once, with its requests underneath");
+            if self.group_logs_by_host {
+                ui.radio_value(&mut self.log_group_order, GroupOrder::Count, "Most requests");
+                ui.radio_value(&mut self.log_group_order, GroupOrder::Recency, "Most recent");
+            }
             
             if ui.button("💨 Clear Logs").clicked() {
                 self.state.clear_logs();
             }
         });
         
-        ui.add_space(8.0);
+        space(ui, 8.0);
+        self.render_pinned_logs(ui);
         
-        // Log viewer
-        let logs = self.state.get_logs();
-        let filtered_logs: Vec<&String> = logs.iter()
-            .filter(|log| {
-                if self.show_blocked_only && !log.contains("Blocked") && !log.contains("🚫") {
+        // Log viewer; while frozen, later entries are held back
+        let mut entries = self.state.get_log_entries();
+        let latest_id = entries.last().map_or(0, |entry| entry.id);
+        if let Some(frozen_at) = self.log_frozen_at {
+            entries.retain(|entry| entry.id <= frozen_at);
+        }
+        let entries: Vec<(LogEntry, usize)> = if self.merge_duplicate_logs {
+            coalesce(entries)
+        } else {
+            entries.into_iter().map(|entry| (entry, 1)).collect()
+        };
+        let logs: Vec<(LogEntry, String)> = entries.into_iter()
+            .map(|(entry, count)| {
+                let mut text = self.log_text(&entry);
+                if count > 1 {
+                    text.push_str(&format!(" (x{})", count));
+                }
+                (entry, text)
+            })
+            .collect();
+        let filtered_logs: Vec<&(LogEntry, String)> = logs.iter()
+            .filter(|(entry, log)| {
+                if self.show_blocked_only && entry.class() != LogClass::Blocked {
                     return false;
                 }
                 if !self.log_filter.is_empty() {
@@ -200,31 +1284,157 @@ impl RequestViewerApp {
             .collect();
         
         let log_panel_height = ui.available_height() - 50.0;
-        let scroll_area = egui::ScrollArea::vertical()
+        let mut scroll_area = egui::ScrollArea::vertical()
             .auto_shrink([false; 2])
             .max_height(log_panel_height);
+        // The newest entries are at the top
+        if self.log_jump_to_latest || self.auto_scroll && self.log_frozen_at.is_none() {
+            scroll_area = scroll_area.vertical_scroll_offset(0.0);
+            self.log_jump_to_latest = false;
+        }
         
-        scroll_area.show(ui, |ui| {
-            for log in &filtered_logs {
-                // Fix the dereference issue by cloning the string
-                let log_text = (*log).clone();
-                let text = if log_text.contains("Blocked") || log_text.contains("🚫") {
-                    RichText::new(log_text).color(Color32::RED)
-                } else if log_text.contains("Allowed") || log_text.contains("✅") {
-                    RichText::new(log_text).color(Color32::GREEN)
-                } else {
-                    RichText::new(log_text)
-                };
-                ui.label(text);
-            }
-        });
+        let scroll_offset = if self.group_logs_by_host {
+            // Back to oldest first, as grouping expects
+            let entries = filtered_logs.iter().rev().map(|(entry, _)| entry.clone()).collect();
+            let groups = group_by_host(entries, self.log_group_order);
+            scroll_area.show(ui, |ui| {
+                for group in &groups {
+                    let title = format!("{} ({})", self.display_host(&group.host), group.entries.len());
+                    egui::CollapsingHeader::new(title)
+                        .id_salt(("log_group", &group.host))
+                        .show(ui, |ui| {
+                            for entry in &group.entries {
+                                self.render_log_entry(ui, entry, self.log_text(entry));
+                            }
+                        });
+                }
+            }).state.offset.y
+        } else {
+            scroll_area.show(ui, |ui| {
+                for (entry, log) in &filtered_logs {
+                    self.render_log_entry(ui, entry, log.clone());
+                }
+            }).state.offset.y
+        };
+        
+        // Scrolling away from the newest entries means the user is reading
+        if self.auto_scroll && self.log_frozen_at.is_none() && scroll_offset > LOG_FREEZE_SCROLL_OFFSET {
+            self.log_frozen_at = Some(latest_id);
+        }
         
         ui.label(format!("Displaying {} of {} logs", filtered_logs.len(), logs.len()));
     }
 
+    /// Pinned entries above the log, unaffected by its filters
+    fn render_pinned_logs(&self, ui: &mut Ui) {
+        let pinned = self.state.get_pinned_logs();
+        if pinned.is_empty() {
+            return;
+        }
+        egui::CollapsingHeader::new(format!("📌 Pinned ({})", pinned.len()))
+            .id_salt("pinned_logs")
+            .default_open(true)
+            .show(ui, |ui| {
+                egui::ScrollArea::vertical().id_salt("pinned_logs_scroll").max_height(150.0).show(ui, |ui| {
+                    for entry in &pinned {
+                        ui.horizontal(|ui| {
+                            if ui.small_button("✖").on_hover_text("Unpin").clicked() {
+                                self.state.unpin_log_entry(entry.id);
+                            }
+                            self.render_log_entry(ui, entry, self.log_text(entry));
+                        });
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let mut clear_unpins = self.state.is_clear_unpins_enabled();
+                    if ui.checkbox(&mut clear_unpins, "Clearing logs also unpins").changed() {
+                        self.state.set_clear_unpins(clear_unpins);
+                    }
+                    if ui.button("Unpin all").clicked() {
+                        self.state.clear_pinned_logs();
+                    }
+                });
+            });
+        space(ui, 8.0);
+    }
+
+    /// Errors shown on the Errors tab that arrived since it was last viewed
+    fn unseen_error_count(&self) -> usize {
+        self.state
+            .get_recent_errors(self.errors_shown)
+            .iter()
+            .filter(|entry| entry.id > self.errors_seen_up_to)
+            .count()
+    }
+
+    fn render_errors(&mut self, ui: &mut Ui) {
+        ui.heading("Recent Errors");
+        space(ui, 10.0);
+        
+        ui.horizontal(|ui| {
+            ui.label("Show the last");
+            ui.add(egui::DragValue::new(&mut self.errors_shown).range(1..=1000));
+            ui.label("errors");
+        });
+        space(ui, 8.0);
+        
+        let errors = self.state.get_recent_errors(self.errors_shown);
+        if let Some(latest) = errors.first() {
+            self.errors_seen_up_to = self.errors_seen_up_to.max(latest.id);
+        }
+        if errors.is_empty() {
+            ui.label("No errors. Handshake, connection and tunnel failures show up here.");
+            return;
+        }
+        
+        egui::ScrollArea::vertical().auto_shrink([false; 2]).show(ui, |ui| {
+            for entry in &errors {
+                self.render_log_entry(ui, entry, self.log_text(entry));
+                let mut details = Vec::new();
+                if let Some(host) = &entry.host {
+                    details.push(format!("Host: {}", self.display_host(host)));
+                }
+                if let Some(status) = entry.status {
+                    details.push(format!("Status: {}", status));
+                }
+                if let Some(request) = entry.request.as_ref().filter(|_| !self.privacy_mode) {
+                    details.push(format!("{} {}", request.method, request.url));
+                }
+                details.push(entry.timestamp.format("%Y-%m-%d %H:%M:%S").to_string());
+                ui.label(RichText::new(details.join("  ·  ")).small().weak());
+                space(ui, 4.0);
+            }
+        });
+    }
+
     fn render_blocklist(&mut self, ui: &mut Ui) {
         ui.heading("Tracker Blocklist");
-        ui.add_space(16.0);
+        space(ui, 16.0);
+        
+        ui.horizontal(|ui| {
+            if ui.button("🔄 Reload from file")
+                .on_hover_text("Pick up changes made to the list file outside DeTrack")
+                .clicked() {
+                self.blocklist_reload_status = Some(self.state.reload_trackers().map(|(before, after)| {
+                    format!("Reloaded: {} → {} entries", before, after)
+                }));
+            }
+            match &self.blocklist_reload_status {
+                Some(Ok(message)) => { ui.label(RichText::new(message).color(palette(ui.ctx()).good)); }
+                Some(Err(e)) => { ui.label(RichText::new(format!("❌ {}", e)).color(palette(ui.ctx()).bad)); }
+                None => {}
+            }
+        });
+        
+        let mut registrable = self.state.is_registrable_matching_enabled();
+        if ui.checkbox(&mut registrable, "Match whole sites (registrable domain)")
+            .on_hover_text("An entry like ads.example.co.uk blocks all of example.co.uk, \
+                and entries that are public suffixes (co.uk, github.io) block nothing")
+            .changed() {
+            self.state.set_registrable_matching(registrable);
+        }
+        
+        space(ui, 8.0);
         
         // Add new domain
         ui.horizontal(|ui| {
@@ -248,7 +1458,20 @@ impl RequestViewerApp {
             }
         });
         
-        ui.add_space(16.0);
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Add preset:");
+            for preset in PRESETS {
+                if ui.button(format!("➕ {} ({})", preset.name, preset.domain_count()))
+                    .on_hover_text(preset.description)
+                    .clicked() {
+                    if let Err(e) = self.state.apply_preset(preset) {
+                        self.state.append_log(format!("❌ {}", e));
+                    }
+                }
+            }
+        });
+        
+        space(ui, 16.0);
         
         // Blocklist viewer
         match self.state.get_trackers() {
@@ -269,120 +1492,679 @@ impl RequestViewerApp {
                         });
                     }
                 });
-            },
-            Err(e) => {
-                ui.label(RichText::new(format!("❌ Error loading trackers: {}", e)).color(Color32::RED));
+            },
+            Err(e) => {
+                ui.label(RichText::new(format!("❌ Error loading trackers: {}", e)).color(palette(ui.ctx()).bad));
+            }
+        }
+        
+        space(ui, 16.0);
+        ui.separator();
+        space(ui, 16.0);
+        
+        // Allowlist - hosts that are never blocked
+        ui.heading("Allowlist");
+        space(ui, 8.0);
+        
+        ui.horizontal(|ui| {
+            ui.label("Allow domain:");
+            let response = ui.text_edit_singleline(&mut self.new_allowed_domain);
+            
+            let add_pressed = ui.button("Allow").clicked();
+            if (add_pressed || response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)))
+                && !self.new_allowed_domain.is_empty() {
+                self.state.add_allowed_host(&self.new_allowed_domain);
+                self.new_allowed_domain.clear();
+            }
+        });
+        
+        if ui.checkbox(&mut self.config.allowlist_subdomains, "Also allow subdomains")
+            .on_hover_text("Allowing example.com also allows sub.example.com, as blocking does")
+            .changed() {
+            self.state.set_allowlist_subdomains(self.config.allowlist_subdomains);
+            if let Err(e) = self.config.save(&self.profile.config_path) {
+                self.state.append_log(format!("❌ Failed to save config: {}", e));
+            }
+        }
+        
+        let allowlist = self.state.get_allowlist();
+        ui.label(format!("Allowed domains: {}", allowlist.len()));
+        for domain in &allowlist {
+            ui.horizontal(|ui| {
+                ui.label(domain);
+                if ui.button("❌").clicked() {
+                    self.state.remove_allowed_host(domain);
+                }
+            });
+        }
+
+        let temporary = self.state.get_temporary_allows();
+        if !temporary.is_empty() {
+            space(ui, 8.0);
+            ui.label(format!("Temporarily allowed: {}", temporary.len()));
+            let now = chrono::Utc::now();
+            for (host, expiry) in &temporary {
+                let remaining = (*expiry - now).num_seconds().max(0);
+                ui.label(format!("⏳ {} ({}s left)", self.display_host(host), remaining));
+            }
+        }
+        
+        space(ui, 16.0);
+        ui.separator();
+        space(ui, 16.0);
+        
+        // Address ranges, checked once a host has been resolved
+        ui.heading("Blocked IP Ranges");
+        space(ui, 8.0);
+        ui.label("Blocks hosts that resolve into these ranges, e.g. 203.0.113.0/24. Each request is resolved first, so leave this empty unless you need it.");
+        
+        ui.horizontal(|ui| {
+            ui.label("Block range:");
+            let response = ui.text_edit_singleline(&mut self.new_blocked_cidr);
+            
+            let add_pressed = ui.button("Block").clicked();
+            if (add_pressed || response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)))
+                && !self.new_blocked_cidr.is_empty() {
+                match self.state.add_blocked_cidr(&self.new_blocked_cidr) {
+                    Ok(()) => {
+                        self.new_blocked_cidr.clear();
+                        self.blocked_cidr_error = None;
+                        self.save_blocked_cidrs();
+                    }
+                    Err(e) => self.blocked_cidr_error = Some(e),
+                }
+            }
+        });
+        if let Some(error) = &self.blocked_cidr_error {
+            ui.label(RichText::new(format!("❌ {}", error)).color(palette(ui.ctx()).bad));
+        }
+        
+        for cidr in self.state.get_blocked_cidrs() {
+            ui.horizontal(|ui| {
+                ui.label(&cidr);
+                if ui.button("❌").clicked() {
+                    if let Err(e) = self.state.remove_blocked_cidr(&cidr) {
+                        self.state.append_log(format!("❌ {}", e));
+                    }
+                    self.save_blocked_cidrs();
+                }
+            });
+        }
+        
+        space(ui, 16.0);
+        ui.separator();
+        space(ui, 16.0);
+        
+        self.render_simulation(ui);
+        
+        space(ui, 16.0);
+        ui.separator();
+        space(ui, 16.0);
+        
+        // Third-party hosts that got through without any rule deciding
+        ui.heading("Recently Allowed Third Parties");
+        space(ui, 8.0);
+        
+        let review = self.state.get_third_party_review();
+        ui.label(format!("Pending review: {}", review.len()))
+            .on_hover_text("Hosts contacted from other sites that no rule or AI check caught. Block the ones you recognize as trackers.");
+        if review.is_empty() {
+            ui.label("Nothing to review yet.");
+        } else {
+            egui::ScrollArea::vertical().id_salt("third_party_review").max_height(200.0).show(ui, |ui| {
+                for host in review.iter().rev() {
+                    ui.horizontal(|ui| {
+                        ui.label(self.display_host(host));
+                        if ui.button("🚫 Block").clicked() {
+                            if let Err(e) = self.state.block_reviewed_host(host) {
+                                self.state.append_log(format!("❌ Error blocking {}: {}", host, e));
+                            }
+                        }
+                        if ui.button("Dismiss").clicked() {
+                            self.state.dismiss_reviewed_host(host);
+                        }
+                    });
+                }
+            });
+            if ui.button("Dismiss All").clicked() {
+                self.state.clear_third_party_review();
+            }
+        }
+        
+        space(ui, 16.0);
+        ui.separator();
+        space(ui, 16.0);
+        
+        // Import/Export controls
+        ui.heading("Import/Export");
+        
+        ui.horizontal(|ui| {
+            if ui.button("Import Trackers")
+                .on_hover_text("Add the domains from another tracker list file")
+                .clicked() {
+                if let Some(path) = rfd::FileDialog::new().add_filter("Tracker list", &["txt"]).pick_file() {
+                    match self.state.import_trackers(&path) {
+                        Ok(diff) => self.import_diff = Some((path, diff)),
+                        Err(e) => self.state.append_log(format!("❌ {}", e)),
+                    }
+                }
+            }
+            
+            if ui.button("Export Trackers").clicked() {
+                if let Some(path) = rfd::FileDialog::new().add_filter("Tracker list", &["txt"]).save_file() {
+                    if let Err(e) = self.state.export_trackers(&path) {
+                        self.state.append_log(format!("❌ {}", e));
+                    }
+                }
+            }
+        });
+    }
+
+    fn render_settings(&mut self, ui: &mut Ui) {
+        ui.heading("Proxy Settings");
+        space(ui, 16.0);
+        
+        self.render_profile_picker(ui);
+        space(ui, 8.0);
+        
+        // Proxy status
+        let enabled = self.state.is_proxy_enabled();
+        ui.horizontal(|ui| {
+            ui.label("Proxy Status:");
+            let status_text = if !self.state.is_listener_running() {
+                RichText::new("Not listening").color(Color32::GRAY)
+            } else if enabled {
+                RichText::new("✔ Running").color(palette(ui.ctx()).good)
+            } else {
+                RichText::new("✖ Stopped").color(palette(ui.ctx()).bad)
+            };
+            ui.label(status_text);
+        });
+        
+        space(ui, 8.0);
+        
+        // Proxy controls
+        let listening = self.state.is_listener_running();
+        ui.horizontal(|ui| {
+            let running = enabled && listening;
+            if ui.button(if running { "🚫 Stop Proxy" } else { "▶️ Start Proxy" })
+                .on_hover_text("Stopping keeps the port open and passes HTTPS through unfiltered")
+                .clicked() {
+                if running {
+                    self.state.disable_proxy();
+                } else {
+                    self.start_proxy();
+                }
+            }
+            if listening && ui.button("⏹ Stop Listening")
+                .on_hover_text("Close the proxy port entirely; browsers using the proxy will fail to connect")
+                .clicked() {
+                self.state.stop_listener();
+            }
+        });
+        
+        if ui.checkbox(&mut self.config.start_proxy_on_launch, "Start proxy when DeTrack opens").changed() {
+            if let Err(e) = self.config.save(&self.profile.config_path) {
+                self.state.append_log(format!("❌ Failed to save config: {}", e));
+            }
+        }
+        
+        space(ui, 8.0);
+        ui.checkbox(&mut self.count_in_title, "Show blocked count in window title")
+            .on_hover_text("e.g. \"DeTrack Proxy — 142 blocked\", for feedback while the window is in the background");
+        
+        if ui.checkbox(&mut self.config.seed_empty_blocklist, "Fill an empty blocklist on startup")
+            .on_hover_text(format!("Use the built-in {} list when the blocklist has no entries, instead of only warning", default_preset().name))
+            .changed() {
+            if let Err(e) = self.config.save(&self.profile.config_path) {
+                self.state.append_log(format!("❌ Failed to save config: {}", e));
+            }
+        }
+        
+        if ui.checkbox(&mut self.config.compact_ui, "Compact layout")
+            .on_hover_text("Tighter spacing, to fit more logs and stats on small screens")
+            .changed() {
+            apply_density(ui.ctx(), self.config.compact_ui);
+            if let Err(e) = self.config.save(&self.profile.config_path) {
+                self.state.append_log(format!("❌ Failed to save config: {}", e));
+            }
+        }
+        
+        if ui.checkbox(&mut self.config.binary_byte_units, "Binary byte units (KiB, MiB)")
+            .on_hover_text("Show sizes in powers of 1024 rather than 1000")
+            .changed() {
+            apply_byte_units(ui.ctx(), self.config.binary_byte_units);
+            if let Err(e) = self.config.save(&self.profile.config_path) {
+                self.state.append_log(format!("❌ Failed to save config: {}", e));
+            }
+        }
+        
+        if ui.checkbox(&mut self.config.colorblind_palette, "Color-blind friendly colors")
+            .on_hover_text("Use blue and orange instead of green and red for status colors")
+            .changed() {
+            apply_palette(ui.ctx(), self.config.colorblind_palette);
+            if let Err(e) = self.config.save(&self.profile.config_path) {
+                self.state.append_log(format!("❌ Failed to save config: {}", e));
+            }
+        }
+        
+        space(ui, 16.0);
+        ui.separator();
+        space(ui, 16.0);
+        
+        // Logging settings
+        ui.heading("Logging Settings");
+        space(ui, 8.0);
+        
+        let logging = self.state.is_logging_enabled();
+        ui.horizontal(|ui| {
+            ui.label("Logging Status:");
+            let status_text = if logging {
+                RichText::new("✔ Enabled").color(palette(ui.ctx()).good)
+            } else {
+                RichText::new("✖ Disabled").color(palette(ui.ctx()).bad)
+            };
+            ui.label(status_text);
+        });
+        
+        space(ui, 8.0);
+        
+        // Logging controls
+        if ui.button(if logging { "📴 Disable Logging" } else { "📡 Enable Logging" }).clicked() {
+            if logging {
+                self.state.disable_logging();
+            } else {
+                self.state.enable_logging();
+            }
+        }
+        
+        ui.label("Log these events:");
+        ui.horizontal_wrapped(|ui| {
+            for event in LogEvent::ALL {
+                let mut logged = self.state.is_event_logged(event);
+                if ui.checkbox(&mut logged, event.label()).changed() {
+                    self.state.set_event_logging(event, logged);
+                }
+            }
+        });
+        
+        let mut archive_on_clear = self.state.get_log_archive_dir().is_some();
+        if ui.checkbox(&mut archive_on_clear, "Archive logs when clearing")
+            .on_hover_text(format!("Save the log to a compressed file in {}/ before clearing it", LOG_ARCHIVE_DIR))
+            .changed() {
+            self.state.set_log_archive_dir(archive_on_clear.then(|| PathBuf::from(LOG_ARCHIVE_DIR)));
+        }
+        
+        if ui.checkbox(&mut self.config.batch_log_appends, "Batch log writes")
+            .on_hover_text(format!("Queue log entries and add them every {} ms, so request floods don't slow the window. \
+                Entries beyond {} per batch are dropped and counted.", LOG_DRAIN_INTERVAL.as_millis(), LOG_QUEUE_CAPACITY))
+            .changed() {
+            self.state.set_log_batching(self.config.batch_log_appends);
+            if let Err(e) = self.config.save(&self.profile.config_path) {
+                self.state.append_log(format!("❌ Failed to save config: {}", e));
+            }
+        }
+        
+        let mut capture_headers = self.state.is_capturing_headers();
+        if ui.checkbox(&mut capture_headers, "Capture request headers")
+            .on_hover_text("Include headers in \"Copy as curl\". Headers may contain cookies and credentials.")
+            .changed() {
+            self.state.set_capture_headers(capture_headers);
+        }
+        
+        ui.horizontal(|ui| {
+            ui.label("Timestamps:");
+            let pattern = ui.add(egui::TextEdit::singleline(&mut self.config.log_timestamp_format)
+                .desired_width(140.0))
+                .on_hover_text("strftime format, e.g. %Y-%m-%d %H:%M:%S for full dates");
+            let utc = ui.checkbox(&mut self.config.log_timestamps_utc, "UTC");
+            if pattern.changed() || utc.changed() {
+                self.apply_timestamp_format();
+            }
+            // Shown as it's typed, but only saved once editing is done
+            if (pattern.lost_focus() || utc.changed()) && self.timestamp_format_error.is_none() {
+                if let Err(e) = self.config.save(&self.profile.config_path) {
+                    self.state.append_log(format!("❌ Failed to save config: {}", e));
+                }
+            }
+        });
+        if let Some(error) = &self.timestamp_format_error {
+            ui.label(RichText::new(format!("⚠ {}; keeping the previous format", error)).color(palette(ui.ctx()).warning));
+        }
+        
+        let mut plain_text = self.state.get_log_format() == LogFormat::Plain;
+        if ui.checkbox(&mut plain_text, "Plain text console logs")
+            .on_hover_text("Write [BLOCK], [ALLOW] and [INFO] tags instead of emoji to the terminal")
+            .changed() {
+            self.state.set_log_format(if plain_text { LogFormat::Plain } else { LogFormat::Emoji });
+        }
+        
+        if ui.button("💨 Clear Logs").clicked() {
+            self.state.clear_logs();
+        }
+        
+        space(ui, 16.0);
+        ui.separator();
+        space(ui, 16.0);
+        
+        // Security settings
+        ui.heading("Security Settings");
+        space(ui, 8.0);
+        
+        let mut strict_host_check = self.state.is_strict_host_check();
+        if ui.checkbox(&mut strict_host_check, "Reject requests whose Host header doesn't match the URL")
+            .on_hover_text("When unchecked, mismatches are only logged as warnings")
+            .changed() {
+            self.state.set_strict_host_check(strict_host_check);
+        }
+        
+        let mut max_header_count = self.state.get_max_header_count();
+        ui.horizontal(|ui| {
+            ui.label("Max headers per message:");
+            if ui.add(egui::Slider::new(&mut max_header_count, 16..=MAX_HEADER_COUNT_LIMIT))
+                .on_hover_text("Requests over the limit get 431, upstream responses over it get 502")
+                .changed() {
+                self.state.set_max_header_count(max_header_count);
+            }
+        });
+        
+        let mut max_header_kb = self.state.get_max_header_bytes() / 1024;
+        ui.horizontal(|ui| {
+            ui.label("Max header size (KB):");
+            if ui.add(egui::Slider::new(&mut max_header_kb, 4..=256).logarithmic(true))
+                .on_hover_text("Total size of all headers on a request or upstream response")
+                .changed() {
+                self.state.set_max_header_bytes(max_header_kb * 1024);
+            }
+        });
+        
+        space(ui, 8.0);
+        let mut fail_closed = self.state.is_fail_closed();
+        if ui.checkbox(&mut fail_closed, "Block requests if the blocklist can't be checked")
+            .on_hover_text("Fail closed: an internal error stops traffic instead of letting it through unfiltered")
+            .changed() {
+            self.state.set_fail_closed(fail_closed);
+        }
+        
+        space(ui, 8.0);
+        let mut intranet_bypass = self.state.is_intranet_bypass_enabled();
+        if ui.checkbox(&mut intranet_bypass, "Let intranet hosts bypass filtering")
+            .on_hover_text("Private IPs and the names below are forwarded without blocking, stats or logging")
+            .changed() {
+            self.state.set_intranet_bypass(intranet_bypass);
+        }
+        if intranet_bypass {
+            ui.horizontal(|ui| {
+                ui.label("Bypass name:");
+                let response = ui.text_edit_singleline(&mut self.new_bypass_host);
+                
+                let add_pressed = ui.button("Add").clicked();
+                if (add_pressed || response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)))
+                    && !self.new_bypass_host.is_empty() {
+                    match self.state.add_bypass_host(&self.new_bypass_host) {
+                        Ok(_) => {
+                            self.new_bypass_host.clear();
+                            self.save_bypass_hosts();
+                        }
+                        Err(e) => self.state.append_log(format!("❌ {}", e)),
+                    }
+                }
+            });
+            for name in self.state.get_bypass_hosts() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("*.{}", name));
+                    if ui.button("❌").clicked() && self.state.remove_bypass_host(&name) {
+                        self.save_bypass_hosts();
+                    }
+                });
             }
         }
         
-        ui.add_space(16.0);
-        ui.separator();
-        ui.add_space(16.0);
-        
-        // Import/Export controls
-        ui.heading("Import/Export");
-        
+        space(ui, 8.0);
         ui.horizontal(|ui| {
-            if ui.button("Import Trackers").clicked() {
-                // This would require file dialog - not implemented yet
-                // In a real app, you'd use a native file dialog here
-                self.state.append_log("Import trackers requested - Not implemented yet".to_string());
+            ui.label("Decision script:");
+            ui.text_edit_singleline(&mut self.config.decision_script)
+                .on_hover_text("Asked about requests no rule matched: gets {\"host\", \"path\", \"referer\"} \
+                    as JSON on stdin and answers {\"block\": true} or {\"block\": false}");
+            if ui.button("📁").clicked() {
+                if let Some(path) = rfd::FileDialog::new().pick_file() {
+                    self.config.decision_script = path.display().to_string();
+                }
             }
-            
-            if ui.button("Export Trackers").clicked() {
-                // This would require file dialog - not implemented yet
-                self.state.append_log("Export trackers requested - Not implemented yet".to_string());
+            let active = self.state.get_decision_hook()
+                .map(|hook| hook.program().display().to_string())
+                .unwrap_or_default();
+            if active != self.config.decision_script && ui.button("Apply").clicked() {
+                self.state.set_decision_hook(decision_script(&self.config), DEFAULT_HOOK_TIMEOUT);
+                if let Err(e) = self.config.save(&self.profile.config_path) {
+                    self.state.append_log(format!("❌ Failed to save config: {}", e));
+                }
             }
         });
-    }
-
-    fn render_settings(&mut self, ui: &mut Ui) {
-        ui.heading("Proxy Settings");
-        ui.add_space(16.0);
         
-        // Proxy status
-        let enabled = self.state.is_proxy_enabled();
+        if ui.checkbox(&mut self.config.decision_cache_per_host, "Reuse script answers for the whole host")
+            .on_hover_text("Ask the decision script once per host each minute, rather than once per path. \
+                Fewer script runs, but the script can't treat paths differently.")
+            .changed() {
+            self.state.set_decision_cache_per_host(self.config.decision_cache_per_host);
+            if let Err(e) = self.config.save(&self.profile.config_path) {
+                self.state.append_log(format!("❌ Failed to save config: {}", e));
+            }
+        }
+        
         ui.horizontal(|ui| {
-            ui.label("Proxy Status:");
-            let status_text = if enabled {
-                RichText::new("Running").color(Color32::GREEN)
-            } else {
-                RichText::new("Stopped").color(Color32::RED)
-            };
-            ui.label(status_text);
+            ui.label("Block header prefix:");
+            ui.text_edit_singleline(&mut self.config.block_header_prefix)
+                .on_hover_text("Block responses carry <prefix>-Blocked and <prefix>-Reason headers, \
+                    e.g. X-DeTrack-Reason: suffix-match:doubleclick.net. Leave empty to send none.");
+            if self.config.block_header_prefix != self.state.get_block_header_prefix() && ui.button("Apply").clicked() {
+                match self.state.set_block_header_prefix(&self.config.block_header_prefix) {
+                    Ok(()) => {
+                        if let Err(e) = self.config.save(&self.profile.config_path) {
+                            self.state.append_log(format!("❌ Failed to save config: {}", e));
+                        }
+                    }
+                    Err(e) => self.state.append_log(format!("⚠️ {}", e)),
+                }
+            }
         });
         
-        ui.add_space(8.0);
+        space(ui, 16.0);
+        ui.separator();
+        space(ui, 16.0);
         
-        // Proxy controls
-        if ui.button(if enabled { "🚫 Stop Proxy" } else { "▶️ Start Proxy" }).clicked() {
-            if enabled {
-                self.state.disable_proxy();
-            } else {
-                self.state.enable_proxy();
+        // Privacy settings
+        ui.heading("Privacy Settings");
+        space(ui, 8.0);
+        
+        ui.checkbox(&mut self.privacy_mode, "Privacy mode")
+            .on_hover_text("Hide hosts in the log and statistics views, e.g. for screenshots");
+        ui.label("Hosts are shown as ***.<hash>.com. Blocking still uses the full host.");
+        
+        let mut strip_all = self.state.is_stripping_params_on_all_methods();
+        if ui.checkbox(&mut strip_all, "Strip tracking parameters from all requests")
+            .on_hover_text("By default only GET and HEAD are cleaned, so form submissions aren't altered")
+            .changed() {
+            self.state.set_strip_params_all_methods(strip_all);
+        }
+        
+        let mut normalize = self.state.is_header_normalization_enabled();
+        if ui.checkbox(&mut normalize, "Send a common User-Agent and Accept-Language")
+            .on_hover_text("Makes requests harder to fingerprint, but can break sites that check the browser")
+            .changed() {
+            self.state.set_header_normalization(normalize);
+        }
+        if normalize {
+            let mut user_agent = self.state.get_normalized_user_agent();
+            ui.horizontal(|ui| {
+                ui.label("User-Agent:");
+                if ui.text_edit_singleline(&mut user_agent).changed() {
+                    self.state.set_normalized_user_agent(&user_agent);
+                }
+            });
+            let mut accept_language = self.state.get_normalized_accept_language();
+            ui.horizontal(|ui| {
+                ui.label("Accept-Language:");
+                if ui.text_edit_singleline(&mut accept_language).changed() {
+                    self.state.set_normalized_accept_language(&accept_language);
+                }
+            });
+        }
+        
+        let mut stun_turn = self.state.is_stun_turn_blocking_enabled();
+        if ui.checkbox(&mut stun_turn, "Block STUN/TURN servers")
+            .on_hover_text("Refuse CONNECTs to the hosts below and to ports 3478/5349. Can break video calls.")
+            .changed() {
+            self.state.set_stun_turn_blocking(stun_turn);
+        }
+        if stun_turn {
+            ui.horizontal(|ui| {
+                ui.label("STUN/TURN host:");
+                let response = ui.text_edit_singleline(&mut self.new_stun_turn_host);
+                
+                let add_pressed = ui.button("Add").clicked();
+                if (add_pressed || response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)))
+                    && !self.new_stun_turn_host.is_empty() {
+                    match self.state.add_stun_turn_host(&self.new_stun_turn_host) {
+                        Ok(_) => self.new_stun_turn_host.clear(),
+                        Err(e) => self.state.append_log(format!("❌ {}", e)),
+                    }
+                }
+            });
+            for name in self.state.get_stun_turn_hosts() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("*.{}", name));
+                    if ui.button("❌").clicked() {
+                        self.state.remove_stun_turn_host(&name);
+                    }
+                });
             }
         }
         
-        ui.add_space(16.0);
+        const STATS_TTL_CHOICES: [(Option<i64>, &str); 5] = [
+            (None, "Never"),
+            (Some(1), "1 hour"),
+            (Some(6), "6 hours"),
+            (Some(24), "1 day"),
+            (Some(24 * 7), "7 days"),
+        ];
+        let ttl_hours = self.state.get_stats_ttl().map(|ttl| ttl.num_hours());
+        let selected = STATS_TTL_CHOICES
+            .iter()
+            .find(|(hours, _)| *hours == ttl_hours)
+            .map(|(_, label)| label.to_string())
+            .unwrap_or_else(|| format!("{} hours", ttl_hours.unwrap_or(0)));
+        ui.horizontal(|ui| {
+            ui.label("Forget domains not seen for:");
+            egui::ComboBox::from_id_salt("stats_ttl")
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    for (hours, label) in STATS_TTL_CHOICES {
+                        if ui.selectable_label(ttl_hours == hours, label).clicked() {
+                            self.state.set_stats_ttl(hours.map(chrono::Duration::hours));
+                        }
+                    }
+                });
+        }).response.on_hover_text("Keeps the statistics from growing without limit in long sessions");
+        
+        space(ui, 16.0);
         ui.separator();
-        ui.add_space(16.0);
+        space(ui, 16.0);
         
-        // Logging settings
-        ui.heading("Logging Settings");
-        ui.add_space(8.0);
+        // Connection settings
+        ui.heading("Connection Settings");
+        space(ui, 8.0);
         
-        let logging = self.state.is_logging_enabled();
+        ui.label(format!("Proxy Address: {}", self.state.proxy_address()));
+        ui.label("Configure your browser to use this address for HTTP/HTTPS proxy.");
+        let report_url = format!("http://{}/__detrack/report", self.state.proxy_address());
         ui.horizontal(|ui| {
-            ui.label("Logging Status:");
-            let status_text = if logging {
-                RichText::new("Enabled").color(Color32::GREEN)
-            } else {
-                RichText::new("Disabled").color(Color32::RED)
-            };
-            ui.label(status_text);
+            ui.label("Blocked domains report:");
+            ui.hyperlink_to(&report_url, &report_url);
         });
         
-        ui.add_space(8.0);
+        space(ui, 8.0);
         
-        // Logging controls
-        if ui.button(if logging { "📴 Disable Logging" } else { "📡 Enable Logging" }).clicked() {
-            if logging {
-                self.state.disable_logging();
-            } else {
-                self.state.enable_logging();
-            }
+        let mut restrict_ports = self.state.is_port_restriction_enabled();
+        if ui.checkbox(&mut restrict_ports, "Only connect to allowed ports")
+            .on_hover_text("Refuse requests and tunnels to other destination ports with 403")
+            .changed() {
+            self.state.set_port_restriction(restrict_ports);
         }
-        
-        if ui.button("💨 Clear Logs").clicked() {
-            self.state.clear_logs();
+        if restrict_ports {
+            ui.horizontal_wrapped(|ui| {
+                ui.label("Allowed ports:");
+                for port in self.state.get_allowed_ports() {
+                    if ui.small_button(format!("{} ❌", port)).on_hover_text("Remove").clicked() {
+                        self.state.remove_allowed_port(port);
+                    }
+                }
+                let response = ui.add(egui::TextEdit::singleline(&mut self.new_allowed_port).desired_width(60.0));
+                let add_pressed = ui.button("Add").clicked();
+                if add_pressed || response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    match self.new_allowed_port.trim().parse::<u16>() {
+                        Ok(port) if port > 0 => {
+                            self.state.add_allowed_port(port);
+                            self.new_allowed_port.clear();
+                        }
+                        _ => self.state.append_log(format!("❌ Invalid port: {}", self.new_allowed_port)),
+                    }
+                }
+            });
         }
         
-        ui.add_space(16.0);
-        ui.separator();
-        ui.add_space(16.0);
+        space(ui, 16.0);
         
-        // Connection settings
-        ui.heading("Connection Settings");
-        ui.add_space(8.0);
+        let mut max_tunnels = self.state.get_max_tunnels();
+        ui.horizontal(|ui| {
+            ui.label("Max open tunnels:");
+            if ui.add(egui::Slider::new(&mut max_tunnels, 16..=2048).logarithmic(true))
+                .on_hover_text("HTTPS/websocket tunnels beyond this are refused with 503")
+                .changed() {
+                self.state.set_max_tunnels(max_tunnels);
+            }
+        });
         
-        ui.label("Proxy Address: 127.0.0.1:8100");
-        ui.label("Configure your browser to use this address for HTTP/HTTPS proxy.");
+        let mut upstream_http2 = self.state.is_upstream_http2_enabled();
+        if ui.checkbox(&mut upstream_http2, "Use HTTP/2 to upstream servers when supported (experimental)")
+            .on_hover_text("Tries cleartext HTTP/2 (h2c) for plain HTTP requests and falls back to HTTP/1.1. HTTPS is tunneled unchanged.")
+            .changed() {
+            self.state.set_upstream_http2(upstream_http2);
+        }
         
-        ui.add_space(16.0);
+        space(ui, 16.0);
+        
+        if system_proxy::is_supported() {
+            if self.state.is_system_proxy_configured() {
+                ui.label(RichText::new("System proxy is pointed at DeTrack").color(palette(ui.ctx()).good));
+                if ui.button("↩ Restore previous system proxy").clicked() {
+                    if let Err(e) = self.state.restore_system_proxy() {
+                        self.state.append_log(format!("❌ {}", e));
+                    }
+                }
+            } else if ui.button("🖥️ Configure system proxy automatically").clicked() {
+                if let Err(e) = self.state.enable_system_proxy(&self.state.proxy_address()) {
+                    self.state.append_log(format!("❌ {}", e));
+                }
+            }
+            ui.label("The previous system proxy setting is restored when DeTrack exits. Changes are listed in the logs.");
+            
+            space(ui, 16.0);
+        }
         
         ui.collapsing("Browser Setup Instructions", |ui| {
             ui.heading("Chrome / Edge");
             ui.label("1. Open Settings -> Advanced -> System -> Open your computer's proxy settings");
             ui.label("2. In Windows, switch 'Use a proxy server' to ON");
-            ui.label("3. Set Address to 127.0.0.1 and Port to 8100");
+            ui.label(format!("3. Set Address to 127.0.0.1 and Port to {}", self.state.get_listen_port()));
             ui.label("4. Click Save");
             
-            ui.add_space(8.0);
+            space(ui, 8.0);
             
             ui.heading("Firefox");
             ui.label("1. Open Settings -> General -> Network Settings");
             ui.label("2. Select 'Manual proxy configuration'");
-            ui.label("3. Set HTTP Proxy to 127.0.0.1 and Port to 8100");
+            ui.label(format!("3. Set HTTP Proxy to 127.0.0.1 and Port to {}", self.state.get_listen_port()));
             ui.label("4. Check 'Also use this proxy for HTTPS'");
             ui.label("5. Click OK");
         });
@@ -390,46 +2172,46 @@ impl RequestViewerApp {
 
     fn render_about(&mut self, ui: &mut Ui) {
         ui.heading("About DeTrack Proxy");
-        ui.add_space(16.0);
+        space(ui, 16.0);
         
         ui.label("DeTrack Proxy is a privacy-focused HTTP/HTTPS proxy that blocks trackers and ads.");
         ui.label("Version: 0.1.0");
-        ui.add_space(8.0);
+        space(ui, 8.0);
         
         ui.horizontal(|ui| {
             ui.label("Source code:");
             ui.hyperlink("https://github.com/nonceferatu/DeTrack-proxy");
         });
         
-        ui.add_space(16.0);
+        space(ui, 16.0);
         ui.separator();
-        ui.add_space(16.0);
+        space(ui, 16.0);
         
         ui.heading("Features");
-        ui.add_space(8.0);
+        space(ui, 8.0);
         
         ui.label("• Block known trackers and ad servers");
         ui.label("• View and filter HTTP request logs");
         ui.label("• Customize blocking rules");
         ui.label("• Minimal performance impact");
         
-        ui.add_space(16.0);
+        space(ui, 16.0);
         ui.separator();
-        ui.add_space(16.0);
+        space(ui, 16.0);
         
         ui.heading("Setup Instructions");
-        ui.add_space(8.0);
+        space(ui, 8.0);
         
-        ui.label("1. Set your browser's HTTP and HTTPS proxy to 127.0.0.1:8100");
+        ui.label(format!("1. Set your browser's HTTP and HTTPS proxy to {}", self.state.proxy_address()));
         ui.label("2. Enable the proxy using the controls in the Dashboard tab");
         ui.label("3. Browse the web with reduced tracking!");
         
-        ui.add_space(16.0);
+        space(ui, 16.0);
         ui.separator();
-        ui.add_space(16.0);
+        space(ui, 16.0);
         
         ui.heading("Credits");
-        ui.add_space(8.0);
+        space(ui, 8.0);
         
         ui.label("DeTrack Proxy uses a curated list of known trackers and ad servers.");
         ui.label("Special thanks to the open source projects that made this possible.");
@@ -437,21 +2219,21 @@ impl RequestViewerApp {
 
     fn render_ai_tab(&mut self, ui: &mut Ui) {
         ui.heading("AI Tracker Detection");
-        ui.add_space(16.0);
+        space(ui, 16.0);
         
         // AI Status
         let enabled = self.state.is_ai_detection_enabled();
         ui.horizontal(|ui| {
             ui.label("AI Detection Status:");
             let status_text = if enabled {
-                RichText::new("Enabled").color(Color32::GREEN)
+                RichText::new("✔ Enabled").color(palette(ui.ctx()).good)
             } else {
-                RichText::new("Disabled").color(Color32::RED)
+                RichText::new("✖ Disabled").color(palette(ui.ctx()).bad)
             };
             ui.label(status_text);
         });
         
-        ui.add_space(8.0);
+        space(ui, 8.0);
         
         // AI Controls
         if ui.button(if enabled { "🔴 Disable AI" } else { "🟢 Enable AI" }).clicked() {
@@ -462,13 +2244,13 @@ impl RequestViewerApp {
             }
         }
         
-        ui.add_space(16.0);
+        space(ui, 16.0);
         ui.separator();
-        ui.add_space(16.0);
+        space(ui, 16.0);
         
         // AI Sensitivity
         ui.heading("AI Sensitivity");
-        ui.add_space(8.0);
+        space(ui, 8.0);
         
         let mut threshold = self.state.get_ai_confidence_threshold();
         ui.horizontal(|ui| {
@@ -488,13 +2270,88 @@ impl RequestViewerApp {
             }
         );
         
-        ui.add_space(16.0);
+        space(ui, 8.0);
+        
+        let mut sample_percent = self.state.get_ai_sample_percent();
+        ui.horizontal(|ui| {
+            ui.label("Analyze:");
+            if ui.add(egui::Slider::new(&mut sample_percent, 1..=100).suffix("% of requests"))
+                .on_hover_text("Lower values use less CPU on busy pages but find new trackers more slowly")
+                .changed() {
+                self.state.set_ai_sample_percent(sample_percent);
+            }
+        });
+        
+        let mut inspect_responses = self.state.is_response_inspection_enabled();
+        if ui.checkbox(&mut inspect_responses, "Detect tracking pixels in responses")
+            .on_hover_text("Reads the size of third-party images as they load and suggests hosts serving tiny (under 10x10) images")
+            .changed() {
+            self.state.set_response_inspection(inspect_responses);
+        }
+        
+        ui.horizontal(|ui| {
+            ui.label("When the AI flags a host the blocklist allows:");
+            let mut policy = self.state.get_ai_conflict_policy();
+            egui::ComboBox::from_id_salt("ai_conflict_policy")
+                .selected_text(policy.to_string())
+                .show_ui(ui, |ui| {
+                    for option in AiConflictPolicy::ALL {
+                        ui.selectable_value(&mut policy, option, option.to_string());
+                    }
+                });
+            if policy != self.state.get_ai_conflict_policy() {
+                self.state.set_ai_conflict_policy(policy);
+                self.config.ai_conflict_policy = policy;
+                if let Err(e) = self.config.save(&self.profile.config_path) {
+                    self.state.append_log(format!("❌ Failed to save config: {}", e));
+                }
+            }
+        }).response.on_hover_text("AI only suggests: add a suggestion, and let IP ranges and the decision script decide as usual. \
+            AI can block: block it and add a suggestion. \
+            Blocklist always wins: ignore the AI, so no suggestion is added.");
+        
+        let mut no_referer = self.state.is_ai_no_referer_heuristic_enabled();
+        if ui.checkbox(&mut no_referer, "Treat beacons without a Referer as third-party")
+            .on_hover_text("Some trackers strip the Referer to avoid referer-based rules. \
+                When checked, requests to paths like /pixel or /collect that send no Referer \
+                score as if they came from another site.")
+            .changed() {
+            self.state.set_ai_no_referer_heuristic(no_referer);
+        }
+        
+        ui.horizontal(|ui| {
+            let path = self.state.get_ai_model_path().map(|p| p.display().to_string()).unwrap_or_default();
+            ui.label(format!("Feedback saved to: {}", path));
+            if self.state.is_ai_model_unsaved() {
+                ui.label(RichText::new("⚠ last save failed").color(palette(ui.ctx()).warning));
+            }
+            if ui.button("📁 Change…").clicked() {
+                if let Some(path) = pick_ai_model_path() {
+                    match self.state.change_ai_model_path(path.clone()) {
+                        Ok(()) => self.remember_ai_model_path(&path),
+                        Err(e) => self.state.append_log(format!("❌ {}", e)),
+                    }
+                }
+            }
+        });
+        
+        let mut beacon_threshold = self.state.get_beacon_threshold();
+        ui.horizontal(|ui| {
+            ui.label("Beacon threshold (requests/min):");
+            if ui.add(egui::Slider::new(&mut beacon_threshold, 0..=60))
+                .on_hover_text("Suggest hosts that get the same request this often at a steady rate. 0 turns this off.")
+                .changed() {
+                self.state.set_beacon_threshold(beacon_threshold);
+            }
+        });
+        
+        space(ui, 16.0);
         ui.separator();
-        ui.add_space(16.0);
+        space(ui, 16.0);
         
         // AI Statistics
         ui.heading("AI Detection Statistics");
-        ui.add_space(8.0);
+        space(ui, 8.0);
         
         let (detections, false_positives, false_negatives) = self.state.get_ai_stats();
         
@@ -519,23 +2376,54 @@ impl RequestViewerApp {
             };
             ui.label(format!("{:.1}%", accuracy));
             ui.end_row();
+            
+            let (eligible, analyzed) = self.state.get_ai_sampling_counts();
+            ui.label("Effective Analysis Rate:");
+            if eligible > 0 {
+                ui.label(format!(
+                    "{:.1}% ({} of {} requests)",
+                    analyzed as f64 / eligible as f64 * 100.0,
+                    analyzed,
+                    eligible
+                ));
+            } else {
+                ui.label("No requests analyzed yet");
+            }
+            ui.end_row();
         });
         
         if ui.button("Reset Statistics").clicked() {
             self.state.reset_ai_stats();
         }
         
-        ui.add_space(16.0);
+        space(ui, 16.0);
         ui.separator();
-        ui.add_space(16.0);
+        space(ui, 16.0);
         
         // AI Suggested Trackers
         ui.heading("AI-Suggested Trackers");
-        ui.add_space(8.0);
+        space(ui, 8.0);
         
         let suggestions = self.state.get_ai_suggested_trackers();
         
-        ui.label(format!("Pending suggestions: {}", suggestions.len()));
+        ui.horizontal(|ui| {
+            ui.label(format!("Pending suggestions: {}", suggestions.len()));
+            if self.state.is_ai_suggestion_queue_full() {
+                ui.label(RichText::new(format!(
+                    "— queue full, showing the newest {}; older ones are dropped",
+                    suggestions.len()
+                )).color(palette(ui.ctx()).warning));
+            }
+        });
+        let mut suggestion_limit = self.state.get_ai_suggestion_limit();
+        ui.horizontal(|ui| {
+            ui.label("Keep at most:");
+            if ui.add(egui::DragValue::new(&mut suggestion_limit).range(1..=10_000))
+                .on_hover_text("When the queue is full, the oldest suggestion makes room for a new one")
+                .changed() {
+                self.state.set_ai_suggestion_limit(suggestion_limit);
+            }
+        });
         
         if suggestions.is_empty() {
             ui.label("No suggestions yet. AI will suggest trackers as it detects them.");
@@ -554,6 +2442,12 @@ impl RequestViewerApp {
                         if ui.button("❌ Reject").clicked() {
                             self.state.reject_ai_suggestion(domain);
                         }
+                        
+                        if ui.button("😴 Snooze")
+                            .on_hover_text(format!("Don't suggest this host for {} hours", AI_SNOOZE_HOURS))
+                            .clicked() {
+                            self.state.snooze_ai_suggestion(domain, chrono::Duration::hours(AI_SNOOZE_HOURS));
+                        }
                     });
                 }
             });
@@ -563,16 +2457,16 @@ impl RequestViewerApp {
             }
         }
         
-        ui.add_space(16.0);
+        space(ui, 16.0);
         ui.separator();
-        ui.add_space(16.0);
+        space(ui, 16.0);
         
         // Explanation of AI detection
         ui.heading("How AI Detection Works");
-        ui.add_space(8.0);
+        space(ui, 8.0);
         
         ui.label("The AI detection system uses fingerprinting and heuristics to identify trackers:");
-        ui.add_space(4.0);
+        space(ui, 4.0);
         
         egui::Grid::new("ai_features_grid").num_columns(2).spacing([20.0, 8.0]).show(ui, |ui| {
             ui.label("• Tracking Parameters");
@@ -596,12 +2490,77 @@ impl RequestViewerApp {
             ui.end_row();
         });
         
-        ui.add_space(8.0);
+        space(ui, 8.0);
         ui.label("When potential trackers are detected, they're added to the suggestion queue above for your review.");
     }
 }
 
+/// Table of per-host request statistics
+fn render_domain_rows(ui: &mut Ui, id: &str, stats: &[DomainStat], state: &SharedState, privacy_mode: bool) {
+    egui::Grid::new(id).num_columns(7).striped(true).spacing([24.0, 4.0]).show(ui, |ui| {
+        ui.label(RichText::new("Domain").strong());
+        ui.label(RichText::new("Requests").strong());
+        ui.label(RichText::new("Blocked").strong());
+        ui.label(RichText::new("Sent").strong());
+        ui.label(RichText::new("Received").strong());
+        ui.label(RichText::new("Last Seen").strong());
+        ui.label("");
+        ui.end_row();
+        
+        for stat in stats {
+            if privacy_mode {
+                ui.label(redact_host(&stat.domain));
+            } else {
+                ui.label(&stat.domain);
+            }
+            ui.label(format_count(stat.requests as u64));
+            if stat.blocked > 0 {
+                ui.label(RichText::new(format_count(stat.blocked as u64)).color(palette(ui.ctx()).bad));
+            } else {
+                ui.label("0");
+            }
+            ui.label(format_bytes(stat.request_bytes, byte_units(ui.ctx())));
+            ui.label(format_bytes(stat.response_bytes, byte_units(ui.ctx())));
+            ui.label(stat.last_seen.with_timezone(&chrono::Local).format("%H:%M:%S").to_string());
+            match state.explain_match(&stat.domain) {
+                Some(explanation) => {
+                    let hover = if privacy_mode {
+                        "Remove the matching entry from the blocklist".to_string()
+                    } else {
+                        format!("Blocked because {}. Removes {} from the blocklist.", explanation, explanation.rule)
+                    };
+                    if ui.small_button("✅ Unblock").on_hover_text(hover).clicked() {
+                        if let Err(e) = state.remove_tracker(&explanation.rule) {
+                            state.append_log(format!("❌ Error removing tracker: {}", e));
+                        }
+                    }
+                }
+                None => {
+                    if ui.small_button("🚫 Block").on_hover_text("Add to the blocklist").clicked() {
+                        if let Err(e) = state.add_tracker(&stat.domain) {
+                            state.append_log(format!("❌ Error adding tracker: {}", e));
+                        }
+                    }
+                }
+            }
+            ui.end_row();
+        }
+    });
+}
+
 impl App for RequestViewerApp {
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        if !self.exit_without_saving {
+            if let Err(e) = self.state.flush_ai_model() {
+                eprintln!("❌ {}", e);
+            }
+        }
+        // Undo automatic system proxy configuration
+        if let Err(e) = self.state.restore_system_proxy() {
+            eprintln!("❌ {}", e);
+        }
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut Frame) {
         // Load the logo texture if not already loaded
         if self.logo_texture.is_none() {
@@ -634,6 +2593,15 @@ impl App for RequestViewerApp {
 
         // Force a repaint to update UI frequently
         ctx.request_repaint_after(Duration::from_millis(500));
+
+        // Expire temporary allows here too, so expiry is logged even if the host isn't requested again
+        self.state.expire_temporary_allows();
+        self.update_window_title(ctx);
+
+        if self.restart_listener && !self.state.is_listener_running() {
+            self.restart_listener = false;
+            start_proxy(Arc::clone(&self.state));
+        }
         
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             ui.horizontal(|ui| {
@@ -642,15 +2610,22 @@ impl App for RequestViewerApp {
                     // For egui 0.31.1, we need to create a tuple of (TextureId, Vec2)
                     let image_source = (texture.id(), egui::vec2(32.0, 32.0));
                     ui.add(egui::Image::new(image_source));
-                    ui.add_space(8.0);
+                    space(ui, 8.0);
                 }
                 
                 ui.heading("DeTrack Proxy");
-                ui.add_space(32.0);
+                space(ui, 32.0);
                 
                 // Navigation tabs
                 ui.selectable_value(&mut self.selected_tab, Tab::Dashboard, "📊 Dashboard");
                 ui.selectable_value(&mut self.selected_tab, Tab::Logs, "📝 Logs");
+                let unseen = self.unseen_error_count();
+                let errors_label = if unseen > 0 {
+                    RichText::new(format!("⚠ Errors ({})", unseen)).color(palette(ui.ctx()).bad)
+                } else {
+                    RichText::new("⚠ Errors")
+                };
+                ui.selectable_value(&mut self.selected_tab, Tab::Errors, errors_label);
                 ui.selectable_value(&mut self.selected_tab, Tab::BlockList, "🚫 Blocklist");
                 ui.selectable_value(&mut self.selected_tab, Tab::AI, "🔍 AI");
                 ui.selectable_value(&mut self.selected_tab, Tab::Settings, "🔧 Settings");
@@ -658,8 +2633,9 @@ impl App for RequestViewerApp {
                 
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                     let enabled = self.state.is_proxy_enabled();
-                    let color = if enabled { Color32::GREEN } else { Color32::RED };
-                    let status = if enabled { "Running" } else { "Stopped" };
+                    let colors = palette(ui.ctx());
+                    let color = if enabled { colors.good } else { colors.bad };
+                    let status = if enabled { "✔ Running" } else { "✖ Stopped" };
                     ui.colored_label(color, status);
                     ui.label("Status:");
                 });
@@ -668,7 +2644,7 @@ impl App for RequestViewerApp {
         
         egui::TopBottomPanel::bottom("bottom_panel").show(ctx, |ui| {
             ui.horizontal(|ui| {
-                ui.label("Proxy Address: 127.0.0.1:8100");
+                ui.label(format!("Proxy Address: {}", self.state.proxy_address()));
                 
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                     // Show AI status if enabled
@@ -676,7 +2652,7 @@ impl App for RequestViewerApp {
                         // Show number of AI suggestions if any
                         let suggestions = self.state.get_ai_suggested_trackers();
                         if !suggestions.is_empty() {
-                            ui.label(RichText::new(format!("🤖 {} suggestions", suggestions.len()))
+                            ui.label(RichText::new(format!("🤖 {} suggestions", format_count(suggestions.len() as u64)))
                                 .color(Color32::LIGHT_BLUE));
                         } else {
                             ui.label(RichText::new("🤖 AI Active").color(Color32::LIGHT_BLUE));
@@ -684,52 +2660,292 @@ impl App for RequestViewerApp {
                     }
                     
                     // Get domain stats from logs (simple approach)
-                    let domain_count = match self.state.get_stats().len() {
+                    let domain_count = match self.state.get_cached_dashboard_stats().domains.len() {
                         0 => "No domains tracked yet".to_string(),
-                        count => format!("{} domains tracked", count),
+                        count => format!("{} domains tracked", format_count(count as u64)),
                     };
                     ui.label(domain_count);
                     
                     // Get request counts
                     let logs = self.state.get_logs();
                     if !logs.is_empty() {
-                        ui.label(format!("{} logs", logs.len()));
+                        ui.label(format!("{} logs", format_count(logs.len() as u64)));
                     }
                 });
             });
         });
         
+        self.render_setup_wizard(ctx);
+        self.check_unsaved_ai_model(ctx);
+        self.render_import_diff(ctx);
+        
         egui::CentralPanel::default().show(ctx, |ui| {
-            match self.selected_tab {
+            ui.add_enabled_ui(self.setup_step.is_none(), |ui| match self.selected_tab {
                 Tab::Dashboard => self.render_dashboard(ui),
                 Tab::Logs => self.render_logs(ui),
+                Tab::Errors => self.render_errors(ui),
                 Tab::BlockList => self.render_blocklist(ui),
                 Tab::Settings => self.render_settings(ui),
                 Tab::About => self.render_about(ui),
                 Tab::AI => self.render_ai_tab(ui),
-            }
+            });
         });
     }
 }
 
+/// The configured decision script, if any
+fn decision_script(config: &Config) -> Option<PathBuf> {
+    let script = config.decision_script.trim();
+    (!script.is_empty()).then(|| PathBuf::from(script))
+}
+
+/// Record in the top-level config which profile to start with
+fn remember_profile(name: Option<String>) -> std::io::Result<()> {
+    let mut config = Config::load(CONFIG_PATH)?.unwrap_or_default();
+    config.setup_complete = true;
+    config.profile = name;
+    config.save(CONFIG_PATH)
+}
+
+/// The profile named on the command line or in the config, and its settings.
+/// Falls back to the default profile if the named one can't be loaded.
+fn select_profile(name: Option<String>, default_config: Config) -> (Profile, Config) {
+    let Some(name) = name else {
+        return (Profile::default_profile(), default_config);
+    };
+    let loaded = Profile::named(Path::new(PROFILES_DIR), &name)
+        .and_then(|profile| Ok((profile.load_config()?, profile)));
+    match loaded {
+        Ok((Some(config), profile)) => (profile, config),
+        Ok((None, _)) => {
+            eprintln!("⚠️ Profile {} not found, using the default profile", name);
+            (Profile::default_profile(), default_config)
+        }
+        Err(e) => {
+            eprintln!("⚠️ Failed to load profile {}: {}, using the default profile", name, e);
+            (Profile::default_profile(), default_config)
+        }
+    }
+}
+
+/// Ask where AI feedback should be saved
+fn pick_ai_model_path() -> Option<PathBuf> {
+    rfd::FileDialog::new()
+        .set_file_name(AI_MODEL_PATH)
+        .add_filter("JSON", &["json"])
+        .save_file()
+}
+
+/// Run the proxy in a background thread with its own Tokio runtime.
+fn start_proxy(state: Arc<SharedState>) {
+    thread::spawn(move || {
+        let rt = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
+        if let Err(e) = rt.block_on(run_proxy(Arc::clone(&state))) {
+            eprintln!("❌ Proxy failed to start: {:?}", e);
+            state.append_log_entry(LogKind::Error, None, format!("❌ Proxy failed to start: {}", e));
+        }
+    });
+}
+
+/// Recompute the Dashboard aggregates in the background, so the UI reads
+/// them from the cache instead of locking the statistics on every repaint
+fn start_stats_refresher(state: Arc<SharedState>) {
+    thread::spawn(move || loop {
+        state.refresh_dashboard_stats();
+        thread::sleep(DASHBOARD_STATS_INTERVAL);
+    });
+}
+
+/// Move batched log entries into the log every [`LOG_DRAIN_INTERVAL`]
+fn start_log_drainer(state: Arc<SharedState>) {
+    thread::spawn(move || loop {
+        state.drain_log_queue();
+        thread::sleep(LOG_DRAIN_INTERVAL);
+    });
+}
+
+/// Run the proxy on this thread without a window until Ctrl-C (or SIGTERM
+/// on Unix), with the log on stdout.
+fn run_headless(state: Arc<SharedState>) {
+    state.set_headless(true);
+    let rt = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
+    rt.block_on(async {
+        let mut proxy = tokio::spawn(run_proxy(Arc::clone(&state)));
+        tokio::select! {
+            result = &mut proxy => {
+                if let Ok(Err(e)) = result {
+                    state.append_log_entry(LogKind::Error, None, format!("❌ Proxy failed to start: {}", e));
+                }
+                return;
+            }
+            _ = shutdown_signal() => {}
+        }
+
+        state.append_log("👋 Shutting down".to_string());
+        state.stop_listener();
+        let _ = proxy.await;
+    });
+
+    if let Err(e) = state.flush_ai_model() {
+        eprintln!("❌ {}", e);
+    }
+
+    if let Err(e) = state.restore_system_proxy() {
+        eprintln!("❌ {}", e);
+    }
+
+    // Entries still queued, such as the shutdown notice, would be lost
+    state.drain_log_queue();
+}
+
+/// Resolves on Ctrl-C, or on SIGTERM where there is one
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+                return;
+            }
+            Err(e) => eprintln!("⚠️ Can't listen for SIGTERM: {}", e),
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
+
 fn main() -> Result<(), eframe::Error> {
+    let cli = match cli::parse(std::env::args_os()) {
+        Ok(cli) => cli,
+        Err(e) => e.exit(),
+    };
+
+    // A missing config means this is the first run: the setup wizard picks
+    // the port and tracker list, and starts the proxy when it's done
+    let config = match Config::load(CONFIG_PATH) {
+        Ok(config) => config.unwrap_or_default(),
+        Err(e) => {
+            eprintln!("⚠️ Failed to read {}: {}, using defaults", CONFIG_PATH, e);
+            Config { setup_complete: true, ..Config::default() }
+        }
+    };
+    // A profile named on the command line wins over the one last used
+    let (profile, config) = select_profile(cli.profile.clone().or_else(|| config.profile.clone()), config);
+
+    // Environment overrides apply to this run only and are never saved
+    let overrides = match EnvOverrides::from_env() {
+        Ok(overrides) => overrides,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            std::process::exit(2);
+        }
+    };
+    let tracker_list = overrides.tracker_list.clone().unwrap_or_else(|| config.tracker_list.clone());
+
+    // Blocklist subcommands edit the list file and exit
+    if let Some(action) = cli.action {
+        let result = TrackerBlocker::new(&tracker_list).and_then(|mut blocker| action.run(&mut blocker));
+        match result {
+            Ok(output) => {
+                // Ignore write errors, e.g. `detrack list | head` closing the pipe
+                if !output.is_empty() {
+                    use std::io::Write;
+                    let _ = writeln!(std::io::stdout(), "{}", output);
+                }
+                std::process::exit(0);
+            }
+            Err(e) => {
+                eprintln!("❌ {}: {}", tracker_list, e);
+                std::process::exit(1);
+            }
+        }
+    }
+
     // Setup the tracker blocker and shared state
-    let blocker = TrackerBlocker::new("tracker_lists/test_trackers.txt")
+    let blocker = TrackerBlocker::new(&tracker_list)
         .expect("Failed to load tracker list");
     
-    // Print loaded trackers for debugging
-    blocker.print_loaded_trackers();
+    // Print loaded trackers for debugging, except in headless mode where
+    // stdout is the log
+    let headless = cli.headless || overrides.headless == Some(true);
+    if !headless {
+        blocker.print_loaded_trackers();
+    }
+    let skipped_lines = blocker.skipped_lines().len();
     
     let state = Arc::new(SharedState::new(blocker));
-
-    // Start proxy in background thread with Tokio runtime
-    let state_for_proxy = Arc::clone(&state);
-    thread::spawn(move || {
-        let rt = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
-        if let Err(e) = rt.block_on(run_proxy(state_for_proxy)) {
-            eprintln!("❌ Proxy failed to start: {:?}", e);
+    if skipped_lines > 0 {
+        state.append_log(format!("⚠️ Skipped {} malformed lines in {}", skipped_lines, tracker_list));
+    }
+    state.set_listen_port(overrides.listen_port.unwrap_or(config.listen_port));
+    state.set_blocked_cidrs(&config.blocked_cidrs);
+    state.set_allowlist_subdomains(config.allowlist_subdomains);
+    state.set_ai_conflict_policy(config.ai_conflict_policy);
+    state.set_decision_cache_per_host(config.decision_cache_per_host);
+    state.set_bypass_hosts(&config.bypass_hosts);
+    if let Err(e) = state.set_block_header_prefix(&config.block_header_prefix) {
+        state.append_log(format!("⚠️ {}", e));
+    }
+    state.mark_session_start();
+    if config.seed_empty_blocklist {
+        if let Err(e) = state.seed_empty_blocklist() {
+            state.append_log(format!("❌ {}", e));
         }
-    });
+    } else if state.is_blocklist_empty() {
+        state.append_log(format!("⚠️ {} is empty; nothing will be blocked", tracker_list));
+    }
+
+    if let Some(script) = decision_script(&config) {
+        state.set_decision_hook(Some(script), DEFAULT_HOOK_TIMEOUT);
+    }
+    if let Err(e) = state.load_allowlist(&profile.allowlist_path) {
+        state.append_log(format!("⚠️ {}", e));
+    }
+    if let Some(name) = &profile.name {
+        state.set_profile_name(Some(name.clone()));
+        state.append_log(format!("👤 Using profile {}", name));
+    }
+
+    if let Err(e) = state.set_timestamp_format(&config.log_timestamp_format, config.log_timestamps_utc) {
+        state.append_log(format!("⚠️ {}; using {}", e, DEFAULT_TIMESTAMP_FORMAT));
+    }
+
+    // Pick up AI weights learned in earlier sessions, and keep saving them
+    let _ = state.load_ai_model(&config.ai_model_path);
+    state.set_ai_model_path(Some(PathBuf::from(&config.ai_model_path)));
+
+    match overrides.ai_enabled {
+        Some(true) => state.enable_ai_detection(),
+        Some(false) => state.disable_ai_detection(),
+        None => {}
+    }
+    match overrides.blocking_enabled {
+        Some(true) => state.enable_proxy(),
+        Some(false) => state.disable_proxy(),
+        None => {}
+    }
+    for setting in overrides.describe() {
+        state.append_log(format!("🌱 {} set from the environment", setting));
+    }
+
+    state.set_log_batching(config.batch_log_appends);
+    start_log_drainer(Arc::clone(&state));
+
+    if headless {
+        run_headless(state);
+        return Ok(());
+    }
+
+    start_stats_refresher(Arc::clone(&state));
+
+    if config.setup_complete && config.start_proxy_on_launch {
+        start_proxy(Arc::clone(&state));
+    } else if config.setup_complete {
+        state.append_log("⏸ Proxy not started on launch; use Start Proxy to begin listening".to_string());
+    }
 
     // Launch the egui desktop app with correct options for the newer eframe version
     let mut native_options = eframe::NativeOptions::default();
@@ -759,13 +2975,16 @@ fn main() -> Result<(), eframe::Error> {
     native_options.viewport.min_inner_size = Some(egui::vec2(600.0, 400.0));
     
     // Set window title and other basic properties
-    native_options.viewport.title = Some("DeTrack Proxy".to_string());
+    native_options.viewport.title = Some(WINDOW_TITLE.to_string());
     
     eframe::run_native(
         "DeTrack Proxy",
         native_options,
-        Box::new(|_cc: &CreationContext| {
-            Ok(Box::new(RequestViewerApp::new(Arc::clone(&state))))
+        Box::new(|cc: &CreationContext| {
+            apply_density(&cc.egui_ctx, config.compact_ui);
+            apply_palette(&cc.egui_ctx, config.colorblind_palette);
+            apply_byte_units(&cc.egui_ctx, config.binary_byte_units);
+            Ok(Box::new(RequestViewerApp::new(Arc::clone(&state), profile, config)))
         }),
     )
 }
\ No newline at end of file
@@ -0,0 +1,121 @@
+//! Tracking pixel detection from the first bytes of image responses.
+//!
+//! Only image headers are parsed, so a response can be checked while it
+//! streams through without being decoded or buffered.
+
+use crate::inspect::{InspectableBody, Inspector};
+
+/// Images narrower and shorter than this are treated as tracking pixels
+pub const TRACKING_PIXEL_MAX_SIDE: u32 = 10;
+
+/// Most leading bytes kept while looking for image dimensions. JPEG frame
+/// headers can come after metadata, the other formats need far fewer.
+const SNIFF_LIMIT: usize = 2048;
+
+/// Width and height from a PNG, GIF, JPEG or WebP header, if recognised.
+pub fn image_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        // IHDR is always the first chunk
+        if data.get(12..16)? != b"IHDR" {
+            return None;
+        }
+        return Some((be32(data, 16)?, be32(data, 20)?));
+    }
+
+    if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        return Some((le16(data, 6)? as u32, le16(data, 8)? as u32));
+    }
+
+    if data.starts_with(b"\xff\xd8") {
+        return jpeg_dimensions(data);
+    }
+
+    if data.starts_with(b"RIFF") && data.get(8..12)? == b"WEBP" {
+        return match data.get(12..16)? {
+            b"VP8 " => Some(((le16(data, 26)? & 0x3fff) as u32, (le16(data, 28)? & 0x3fff) as u32)),
+            b"VP8L" => {
+                let bits = u32::from_le_bytes(data.get(21..25)?.try_into().ok()?);
+                Some(((bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1))
+            }
+            b"VP8X" => Some((le24(data, 24)? + 1, le24(data, 27)? + 1)),
+            _ => None,
+        };
+    }
+
+    None
+}
+
+/// Whether an image of this size is almost certainly a tracking pixel
+pub fn is_tracking_pixel((width, height): (u32, u32)) -> bool {
+    width < TRACKING_PIXEL_MAX_SIDE && height < TRACKING_PIXEL_MAX_SIDE
+}
+
+/// Walk JPEG segments up to the first start-of-frame marker.
+fn jpeg_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let mut pos = 2;
+    loop {
+        if *data.get(pos)? != 0xff {
+            return None;
+        }
+        let marker = *data.get(pos + 1)?;
+        match marker {
+            // Padding before a marker
+            0xff => pos += 1,
+            // Markers without a length
+            0x01 | 0xd0..=0xd7 => pos += 2,
+            // Start of frame, except DHT, JPG and DAC which share the range
+            0xc0..=0xcf if !matches!(marker, 0xc4 | 0xc8 | 0xcc) => {
+                let height = be16(data, pos + 5)? as u32;
+                let width = be16(data, pos + 7)? as u32;
+                return Some((width, height));
+            }
+            _ => pos += 2 + be16(data, pos + 2)? as usize,
+        }
+    }
+}
+
+fn be16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn be32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn le16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn le24(data: &[u8], at: usize) -> Option<u32> {
+    let bytes = data.get(at..at + 3)?;
+    Some(bytes[0] as u32 | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16)
+}
+
+type DimensionsCallback = Box<dyn FnOnce(Option<(u32, u32)>) + Send + Sync>;
+
+/// Reads image dimensions from the first bytes of a body
+struct DimensionSniffer {
+    on_dimensions: DimensionsCallback,
+}
+
+impl Inspector for DimensionSniffer {
+    fn inspect(&mut self, buffered: &[u8]) -> bool {
+        image_dimensions(buffered).is_some()
+    }
+
+    fn finish(self: Box<Self>, buffered: &[u8]) {
+        (self.on_dimensions)(image_dimensions(buffered));
+    }
+}
+
+/// Pass a response body through unchanged while reading image dimensions
+/// from its first bytes.
+///
+/// `on_dimensions` is called once, as soon as the dimensions are known or
+/// once it's clear they can't be found.
+pub fn sniff_dimensions<B>(
+    body: B,
+    on_dimensions: impl FnOnce(Option<(u32, u32)>) + Send + Sync + 'static,
+) -> InspectableBody<B> {
+    InspectableBody::new(body, SNIFF_LIMIT, DimensionSniffer { on_dimensions: Box::new(on_dimensions) })
+}
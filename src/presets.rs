@@ -0,0 +1,54 @@
+//! Curated blocklists built into the binary, so new users get a sensible
+//! starting point without hunting for lists.
+
+use crate::tracker_blocker::parse_trackers;
+
+/// An embedded tracker list that can be merged into the blocklist
+pub struct Preset {
+    pub name: &'static str,
+    pub description: &'static str,
+    content: &'static str,
+}
+
+impl Preset {
+    /// The preset's domains, sorted
+    pub fn domains(&self) -> Vec<String> {
+        let mut domains: Vec<String> = parse_trackers(self.content).into_iter().collect();
+        domains.sort();
+        domains
+    }
+
+    pub fn domain_count(&self) -> usize {
+        parse_trackers(self.content).len()
+    }
+}
+
+/// Presets from least to most aggressive
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "Minimal (ads only)",
+        description: "Ad networks and ad servers. Rarely breaks sites.",
+        content: include_str!("../tracker_lists/presets/minimal.txt"),
+    },
+    Preset {
+        name: "Balanced",
+        description: "Ads plus analytics and session recording.",
+        content: include_str!("../tracker_lists/presets/balanced.txt"),
+    },
+    Preset {
+        name: "Strict (ads+analytics+social)",
+        description: "Also social widgets and pixels, which can break share buttons and embeds.",
+        content: include_str!("../tracker_lists/presets/strict.txt"),
+    },
+];
+
+/// Name of the preset used to fill an empty blocklist
+pub const DEFAULT_PRESET_NAME: &str = "Balanced";
+
+/// Preset used to fill an empty blocklist
+pub fn default_preset() -> &'static Preset {
+    PRESETS
+        .iter()
+        .find(|preset| preset.name == DEFAULT_PRESET_NAME)
+        .expect("the default preset is one of PRESETS")
+}
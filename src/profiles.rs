@@ -0,0 +1,124 @@
+//! Named profiles, such as "home" and "work", each with its own settings,
+//! blocklist, allowlist and AI model.
+//!
+//! The default profile is the top-level config and the files it names.
+//! Other profiles live in their own directory under [`PROFILES_DIR`].
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::config::{Config, CONFIG_PATH};
+
+/// Directory holding one subdirectory per named profile
+pub const PROFILES_DIR: &str = "profiles";
+
+/// Name shown for the top-level config
+pub const DEFAULT_PROFILE: &str = "Default";
+
+/// Allowlist of the default profile
+pub const DEFAULT_ALLOWLIST_PATH: &str = "allowlist.txt";
+
+/// Where a profile's files are kept
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Profile {
+    /// `None` for the default profile
+    pub name: Option<String>,
+    pub config_path: PathBuf,
+    pub allowlist_path: PathBuf,
+}
+
+impl Profile {
+    /// The top-level config
+    pub fn default_profile() -> Self {
+        Self {
+            name: None,
+            config_path: PathBuf::from(CONFIG_PATH),
+            allowlist_path: PathBuf::from(DEFAULT_ALLOWLIST_PATH),
+        }
+    }
+
+    /// The profile called `name` under `root`, which may not exist yet
+    pub fn named(root: &Path, name: &str) -> io::Result<Self> {
+        let name = name.trim();
+        let valid = !name.is_empty()
+            && name.len() <= 64
+            && !name.eq_ignore_ascii_case(DEFAULT_PROFILE)
+            && name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == ' ');
+        if !valid {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid profile name: {:?}", name),
+            ));
+        }
+
+        let dir = root.join(name);
+        Ok(Self {
+            name: Some(name.to_string()),
+            config_path: dir.join("config.json"),
+            allowlist_path: dir.join("allowlist.txt"),
+        })
+    }
+
+    /// Name to show, with the default profile as [`DEFAULT_PROFILE`]
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or(DEFAULT_PROFILE)
+    }
+
+    fn dir(&self) -> &Path {
+        self.config_path.parent().unwrap_or(Path::new("."))
+    }
+
+    /// The profile's settings, or `None` if it hasn't been created
+    pub fn load_config(&self) -> io::Result<Option<Config>> {
+        Config::load(&self.config_path)
+    }
+
+    /// Create the profile from `template`: its settings are copied, and it
+    /// starts with a copy of the template's blocklist and a fresh AI model.
+    pub fn create(&self, template: &Config) -> io::Result<Config> {
+        if self.config_path.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("Profile {} already exists", self.display_name()),
+            ));
+        }
+        fs::create_dir_all(self.dir())?;
+
+        let tracker_list = self.dir().join("trackers.txt");
+        match fs::copy(&template.tracker_list, &tracker_list) {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => fs::write(&tracker_list, "")?,
+            Err(e) => return Err(e),
+        }
+
+        let config = Config {
+            setup_complete: true,
+            tracker_list: tracker_list.display().to_string(),
+            ai_model_path: self.dir().join("ai_model.json").display().to_string(),
+            profile: None,
+            ..template.clone()
+        };
+        config.save(&self.config_path)?;
+        Ok(config)
+    }
+}
+
+/// Names of the profiles under `root`, sorted. A missing `root` has none.
+pub fn list_profiles(root: &Path) -> io::Result<Vec<String>> {
+    let entries = match fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut names = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if entry.path().join("config.json").is_file() {
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    names.sort();
+    Ok(names)
+}
@@ -0,0 +1,32 @@
+use std::net::IpAddr;
+use std::sync::OnceLock;
+
+use publicsuffix::{List, Psl};
+
+/// Mozilla's Public Suffix List, bundled so lookups work offline.
+const PUBLIC_SUFFIX_LIST: &str = include_str!("../assets/public_suffix_list.dat");
+
+fn list() -> &'static List {
+    static LIST: OnceLock<List> = OnceLock::new();
+    LIST.get_or_init(|| {
+        PUBLIC_SUFFIX_LIST
+            .parse()
+            .expect("bundled public suffix list is valid")
+    })
+}
+
+/// Get the registrable domain (eTLD+1) of a host, e.g. `ads.example.co.uk`
+/// gives `example.co.uk`.
+///
+/// Returns `None` for IP addresses and for hosts that are themselves a
+/// public suffix.
+pub fn registrable_domain(host: &str) -> Option<String> {
+    let host = host.trim_end_matches('.').to_lowercase();
+    if host.parse::<IpAddr>().is_ok() {
+        return None;
+    }
+
+    list()
+        .domain(host.as_bytes())
+        .map(|domain| String::from_utf8_lossy(domain.as_bytes()).into_owned())
+}
@@ -1,32 +1,93 @@
-use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+use std::{
+    convert::Infallible,
+    future::Future,
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    sync::{atomic::{AtomicU64, Ordering}, Arc},
+    task::{Context, Poll},
+    time::Duration,
+};
 
 use bytes::Bytes;
 use http_body_util::{combinators::BoxBody, BodyExt, Empty, Full};
 use hyper::{
-    body::Incoming as Body, server::conn::http1 as server_http1, upgrade::Upgraded, Method,
-    Request, Response, StatusCode,
+    body::Incoming as Body, header::{HeaderMap, HeaderValue}, server::conn::http1 as server_http1,
+    upgrade::Upgraded, Method, Request, Response, StatusCode, Version,
 };
 use hyper::service::service_fn;
-use hyper_util::rt::TokioIo;
-use tokio::{io, net::{TcpListener, TcpStream}};
-use std::str::FromStr;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use tokio::{io::{self, AsyncRead, AsyncWrite, ReadBuf}, net::{TcpListener, TcpStream}};
 use hyper::Uri;
 
-use crate::shared_state::SharedState;
+use crate::control;
+use crate::inspect::CountingBody;
+use crate::pixel;
+use crate::public_suffix;
+use crate::shared_state::{ConnectionKind, SharedState, MAX_HEADER_COUNT_LIMIT};
+use crate::verdict::{RequestVerdict, VerdictReason};
+use crate::decision_hook::HookRequest;
+use crate::logging::{LogEvent, LogKind, RequestInfo};
 
 // Response body type alias
 type ResponseBody = BoxBody<Bytes, hyper::Error>;
 
+/// Bind the configured port and serve until `SharedState::stop_listener` is called.
 pub async fn run_proxy(state: Arc<SharedState>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let addr = SocketAddr::from(([127, 0, 0, 1], 8100));
-    let listener = TcpListener::bind(addr).await?;
+    if !state.try_claim_listener() {
+        return Err("the proxy is already listening".into());
+    }
+
+    let addr = SocketAddr::from(([127, 0, 0, 1], state.get_listen_port()));
+    let result = match TcpListener::bind(addr).await {
+        Ok(listener) => {
+            // Stopped with the listener, so restarts don't leave one running per start
+            let reaper = tokio::spawn(reap_stats(Arc::clone(&state)));
+            let result = serve(listener, Arc::clone(&state)).await;
+            reaper.abort();
+            result
+        }
+        Err(e) => Err(e.into()),
+    };
+    state.release_listener();
+    result
+}
+
+/// Longest host name accepted, the DNS limit
+const MAX_HOST_LEN: usize = 253;
+
+/// How often idle domains are pruned from the statistics
+const STATS_REAP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Periodically drop idle domains from the statistics, per the configured
+/// TTL, and recreate the tracker file if it has been deleted.
+async fn reap_stats(state: Arc<SharedState>) {
+    let mut interval = tokio::time::interval(STATS_REAP_INTERVAL);
+    loop {
+        interval.tick().await;
+        state.prune_expired_stats();
+        state.restore_missing_tracker_file();
+    }
+}
+
+/// Accept and proxy connections on an already-bound listener.
+///
+/// `run_proxy` binds the default address and hands off here; tests bind an
+/// ephemeral port and pass in their own `SharedState` to inspect afterwards.
+pub async fn serve(listener: TcpListener, state: Arc<SharedState>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let addr = listener.local_addr()?;
     println!("🚀 Listening on http://{}", addr);
     
     // Add startup log
     state.append_log(format!("🚀 Proxy server started on http://{}", addr));
 
     loop {
-        let (stream, _) = listener.accept().await?;
+        let (stream, client_addr) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = state.listener_stopped() => {
+                state.append_log(format!("⏹ Stopped listening on http://{}", addr));
+                return Ok(());
+            }
+        };
         let state_for_conn = Arc::clone(&state);
 
         tokio::spawn(async move {
@@ -38,19 +99,22 @@ pub async fn run_proxy(state: Arc<SharedState>) -> Result<(), Box<dyn std::error
             let service = service_fn(move |req| {
                 let state_for_req = Arc::clone(&state_for_conn);
                 async move {
-                    proxy(req, state_for_req).await
+                    proxy(req, client_addr, addr, state_for_req).await
                 }
             });
 
+            // Let hyper parse up to the highest configurable limit so
+            // `proxy` can apply the configured one and log rejections
             if let Err(err) = server_http1::Builder::new()
                 .preserve_header_case(true)
                 .title_case_headers(true)
+                .max_headers(MAX_HEADER_COUNT_LIMIT)
                 .serve_connection(io, service)
                 .with_upgrades()
                 .await
             {
                 eprintln!("❌ Connection error: {:?}", err);
-                state_for_error.append_log(format!("❌ Connection error: {:?}", err));
+                state_for_error.append_log_entry(LogKind::Error, None, format!("❌ Connection error: {:?}", err));
             }
         });
     }
@@ -58,62 +122,181 @@ pub async fn run_proxy(state: Arc<SharedState>) -> Result<(), Box<dyn std::error
 
 async fn proxy(
     mut req: Request<Body>,
+    client_addr: SocketAddr,
+    local_addr: SocketAddr,
     state: Arc<SharedState>,
 ) -> Result<Response<ResponseBody>, Infallible> {
+    if let Some(reason) = oversized_headers(req.headers(), &state) {
+        state.append_log_entry(
+            LogKind::Error,
+            req.uri().host(),
+            format!("⚠️ Rejected {} {}: {}", req.method(), req.uri(), reason),
+        );
+        return Ok(Response::builder()
+            .status(StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE)
+            .body(full("Request headers too large"))
+            .unwrap());
+    }
+
+    // Clients that don't know they're talking to a proxy send origin-form
+    // (`GET /path` plus a Host header); rebuild the absolute URI from Host
+    if req.method() != Method::CONNECT && req.uri().authority().is_none() {
+        match absolute_uri_from_host(&req) {
+            Some(uri) => *req.uri_mut() = uri,
+            None => {
+                state.append_log_entry(
+                    LogKind::Error,
+                    None,
+                    format!("⚠️ Rejected {} {}: no host in URI or Host header", req.method(), req.uri()),
+                );
+                return Ok(Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .body(full("Request must have an absolute URI or a Host header"))
+                    .unwrap());
+            }
+        }
+    }
+
+    // Requests for the proxy's own pages are answered here, not forwarded
+    if control::is_control_request(req.uri()) {
+        return Ok(control::handle(&req, client_addr, &state).map(|b| {
+            b.map_err(|never| match never {}).boxed()
+        }));
+    }
+
+    // Forwarding a request for the proxy's own address would loop back here
+    if targets_proxy(&req, local_addr) {
+        state.append_log_entry(
+            LogKind::Error,
+            None,
+            format!("🔁 Rejected {} {}: it targets the proxy itself", req.method(), req.uri()),
+        );
+        return Ok(Response::builder()
+            .status(StatusCode::MISDIRECTED_REQUEST)
+            .body(full(format!(
+                "This request is addressed to DeTrack itself. Use {} as your proxy, not as the destination.",
+                local_addr
+            )))
+            .unwrap());
+    }
+
+    // A Host header naming a different host than the URI suggests smuggling or misrouting
+    if req.method() != Method::CONNECT {
+        if let Some(header_host) = mismatched_host_header(&req) {
+            let message = format!(
+                "⚠️ Host header {} doesn't match request URI {}",
+                header_host,
+                req.uri()
+            );
+            let uri_host = req.uri().host().map(|h| h.to_string());
+            state.append_log_entry(LogKind::Error, uri_host.as_deref(), message);
+            if state.is_strict_host_check() {
+                return Ok(Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .body(full("Host header doesn't match request URI"))
+                    .unwrap());
+            }
+        }
+    }
+
     // Extract host for logging and store locally
     let host = req.uri().host().unwrap_or("unknown-host").to_string();
+    // Longer names can't be real, and only cost time in matching and logging
+    if host.len() > MAX_HOST_LEN {
+        state.append_log_entry(
+            LogKind::Error,
+            None,
+            format!("✂️ Rejected a request for a {}-character host name: {}", host.len(), host),
+        );
+        return Ok(Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .body(full("Host name too long"))
+            .unwrap());
+    }
     let method = req.method().clone();
     let path = req.uri().path().to_string();
     let is_connect = method == Method::CONNECT;
     
-    if state.is_logging_enabled() {
-        let log_entry = format!("{} {} {}", method, host, path);
-        state.append_log(log_entry);
-    }
+    // Allowed requests are logged once the verdict is known, and only if wanted
+    let log_allowed = state.is_logging_enabled() && state.is_allowed_logging_enabled();
+    let request_line = format!("{} {} {}", method, host, path);
 
-    // URL cleaning (before other checks)
-    if req.method() != Method::CONNECT {
-        if let Ok(blocker) = state.blocker.lock() {
-            let original_uri_str = req.uri().to_string();
-            let cleaned_uri_str = blocker.clean_url(&original_uri_str);
+    // Reject bogus tunnel targets before doing any work for them
+    let connect_addr = if is_connect {
+        match connect_target(req.uri()) {
+            Ok(addr) => Some(addr),
+            Err(reason) => {
+                state.append_log_entry(
+                    LogKind::Error,
+                    None,
+                    format!("⚠️ Rejected CONNECT to {}: {}", req.uri(), reason),
+                );
+                return Ok(Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .body(full(format!("Invalid CONNECT target: {}", reason)))
+                    .unwrap());
+            }
+        }
+    } else {
+        None
+    };
 
-            if original_uri_str != cleaned_uri_str {
-                if state.is_logging_enabled() {
-                    state.append_log(format!("🧹 Cleaned URL parameters: {} -> {}", original_uri_str, cleaned_uri_str));
-                }
+    // Ports outside the allowed set are refused, even for intranet hosts
+    let port = req.uri().port_u16().unwrap_or(if is_connect { 443 } else { 80 });
+    if !state.is_port_allowed(port) {
+        let message = format!("🔢 Blocked {} {}:{}: port {} isn't allowed", method, host, port, port);
+        state.append_log_entry_with_status(LogKind::Blocked, Some(&host), message, Some(403));
+        return Ok(block_response(StatusCode::FORBIDDEN, &state, &format!("port:{}", port))
+            .body(full(format!("Connections to port {} aren't allowed", port)))
+            .unwrap());
+    }
 
-                // Create a new request with the cleaned URI
-                if let Ok(cleaned_uri) = cleaned_uri_str.parse::<Uri>() {
-                    *req.uri_mut() = cleaned_uri;
+    // Intranet hosts go straight through: no filtering, stats or logging
+    if state.is_bypassed(&host) {
+        let Some(addr) = connect_addr else {
+            return Ok(forward(req, &host, &state, None).await);
+        };
+        if !state.try_acquire_tunnel() {
+            return Ok(too_many_tunnels(&state, &host));
+        }
+        let (guard, bytes) = ConnectionGuard::open(&state, ConnectionKind::Tunnel, &host);
+        spawn_tracked(guard, async move {
+            match hyper::upgrade::on(req).await {
+                Ok(upgraded) => {
+                    if let Err(e) = tunnel(upgraded, addr, None, bytes).await {
+                        eprintln!("❌ Tunnel error (intranet bypass): {}", e);
+                    }
                 }
+                Err(e) => eprintln!("❌ Upgrade error (intranet bypass): {}", e),
             }
-        }
+        });
+        return Ok(Response::new(empty()));
     }
 
     if !state.is_proxy_enabled() {
-        if is_connect {
-            if let Some(authority) = req.uri().authority() {
-                let addr = authority.to_string();
-                let req_clone = req;
-    
-                tokio::spawn(async move {
-                    match hyper::upgrade::on(req_clone).await {
-                        Ok(upgraded) => {
-                            if let Err(e) = tunnel(upgraded, addr).await {
-                                eprintln!("❌ Tunnel error (disabled proxy pass-through): {}", e);
-                            }
+        if log_allowed {
+            state.append_log_entry(LogKind::Request, Some(&host), request_line);
+        }
+
+        if let Some(addr) = connect_addr {
+            if !state.try_acquire_tunnel() {
+                return Ok(too_many_tunnels(&state, &host));
+            }
+            let req_clone = req;
+            let (guard, bytes) = ConnectionGuard::open(&state, ConnectionKind::Tunnel, &host);
+
+            spawn_tracked(guard, async move {
+                match hyper::upgrade::on(req_clone).await {
+                    Ok(upgraded) => {
+                        if let Err(e) = tunnel(upgraded, addr, None, bytes).await {
+                            eprintln!("❌ Tunnel error (disabled proxy pass-through): {}", e);
                         }
-                        Err(e) => eprintln!("❌ Upgrade error (disabled proxy pass-through): {}", e),
                     }
-                });
-    
-                return Ok(Response::new(empty()));
-            } else {
-                return Ok(Response::builder()
-                    .status(StatusCode::BAD_REQUEST)
-                    .body(full("CONNECT must be to a socket address"))
-                    .unwrap());
-            }
+                    Err(e) => eprintln!("❌ Upgrade error (disabled proxy pass-through): {}", e),
+                }
+            });
+
+            return Ok(Response::new(empty()));
         } else {
             // When proxy is disabled, return a service unavailable response
             state.record_request(&host, false); // Record as allowed since it's policy, not blocking
@@ -124,162 +307,640 @@ async fn proxy(
         }
     }
 
-    // Check for tracker blocking for HTTP requests
-    let is_blocked = match state.blocker.lock() {
-        Ok(blocker) => {
-            println!("Checking host: {}", host);
-            blocker.is_blocked(&host)
-        },
-        Err(e) => {
-            eprintln!("Failed to lock blocker: {:?}", e);
-            state.append_log(format!("⚠️ Failed to check blocker: {:?}", e));
-            false // Allow by default on error
-        }
+    // Kept with the log entry so the request can be replayed, e.g. as curl
+    let request_info = (!is_connect).then(|| request_info(&req, state.is_capturing_headers()));
+
+    // Decide on the request: allowlist, blocklist, AI and param stripping
+    let stun_turn = connect_addr.is_some()
+        && state.is_stun_turn_connect(&host, req.uri().port_u16().unwrap_or(443));
+    let referer = req.headers()
+        .get(hyper::header::REFERER)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string());
+    let mut verdict = if stun_turn {
+        RequestVerdict::block(VerdictReason::StunTurn)
+    } else {
+        let url_string = req.uri().to_string();
+        state.evaluate_request(&host, &url_string, referer.as_deref())
     };
 
-    if is_blocked {
-        // Record the blocked request in stats
-        state.record_request(&host, true);
-        
-        // Log blocked request
-        state.append_log(format!("🚫 Blocked request to tracker: {}", host));
-        
-        return Ok(Response::builder()
-            .status(StatusCode::FORBIDDEN)
-            .body(full(format!("🚫 Blocked request to tracker: {}", host)))
-            .unwrap());
+    // Trackers that rotate domains can still sit in a known address range.
+    // Only hosts no other rule decided on are resolved, so the allowlist
+    // and temporary allows still win. The checked addresses are the ones
+    // connected to, so the name can't resolve somewhere else in between.
+    let mut blocked_ip = None;
+    let mut resolved = None;
+    if verdict.is_undecided() && state.has_blocked_cidrs() {
+        let upstream = connect_addr.clone().unwrap_or_else(|| format!("{}:{}", host, port));
+        let addrs = resolve_upstream(&upstream).await;
+        blocked_ip = addrs.iter().flatten().map(SocketAddr::ip).find(|ip| state.is_ip_blocked(*ip));
+        resolved = Some(addrs);
+        if blocked_ip.is_some() {
+            verdict = RequestVerdict { cleaned_url: verdict.cleaned_url, ..RequestVerdict::block(VerdictReason::BlockedAddress) };
+        }
     }
 
-    // If not blocked by static list, check with AI detection
-    let ai_detected = if state.is_ai_detection_enabled() {
-        let url_string = req.uri().to_string();
-        
-        // Get referer header if available
-        let referer = req.headers()
-            .get(hyper::header::REFERER)
-            .and_then(|value| value.to_str().ok());
+    // The user's script only gets a say when no built-in rule did
+    if verdict.is_undecided() {
+        if let Some(hook) = state.get_decision_hook() {
+            let request = HookRequest {
+                host: host.clone(),
+                path: if is_connect { String::new() } else { path.clone() },
+                referer,
+            };
+            match hook.decide(&request).await {
+                Ok(true) => verdict = RequestVerdict { cleaned_url: verdict.cleaned_url, ..RequestVerdict::block(VerdictReason::Script) },
+                Ok(false) => {}
+                Err(e) => {
+                    if let Some(suppressed) = hook.note_failure() {
+                        let mut message = format!("🧩 Decision script failed for {}, allowing: {}", host, e);
+                        if suppressed > 0 {
+                            message.push_str(&format!(" ({} more failures since the last report)", suppressed));
+                        }
+                        state.append_log_entry(LogKind::Error, Some(&host), message);
+                    }
+                }
+            }
+        }
+    }
+
+    // URL cleaning, only for safe methods unless configured otherwise, so
+    // form submissions keep the query their action expects
+    let safe_method = method == Method::GET || method == Method::HEAD;
+    if !is_connect && (safe_method || state.is_stripping_params_on_all_methods()) {
+        if let Some(cleaned_uri_str) = &verdict.cleaned_url {
+            if state.is_logging_enabled() {
+                state.append_log_entry(
+                    LogKind::Request,
+                    Some(&host),
+                    format!("🧹 Cleaned URL parameters: {} -> {}", req.uri(), cleaned_uri_str),
+                );
+            }
+
+            // Create a new request with the cleaned URI
+            if let Ok(cleaned_uri) = cleaned_uri_str.parse::<Uri>() {
+                *req.uri_mut() = cleaned_uri;
+            }
+        }
+    }
+
+    let first_party = first_party_site(&req, &host);
+    if let Some(site) = &first_party {
+        state.record_site_contact(site, &host, verdict.is_blocked());
+        if verdict.is_undecided() {
+            state.record_unreviewed_third_party(&host);
+        }
+    }
+
+    if !is_connect {
+        state.record_path(&host, &path, verdict.is_blocked());
+    }
+
+    if verdict.is_blocked() {
+        // Record the blocked request in stats
+        state.record_request(&host, true);
         
-        // Check with AI detection
-        let is_tracker = if let Ok(mut tracker) = state.ai_tracker.lock() {
-            tracker.is_likely_tracker(&url_string, &host, referer)
+        let message = match verdict.reason {
+            VerdictReason::AiDetected => format!("🤖 AI detected and blocked tracker: {}", host),
+            VerdictReason::StunTurn => format!("📞 Blocked CONNECT to STUN/TURN server: {}", host),
+            VerdictReason::Script => format!("🧩 Decision script blocked: {}", host),
+            VerdictReason::BlockedAddress => format!(
+                "🌐 Blocked request to {}: it resolves to {}, in a blocked IP range",
+                host,
+                blocked_ip.map_or_else(String::new, |ip| ip.to_string()),
+            ),
+            VerdictReason::BlocklistUnavailable => format!("🚨 Blocked request to {}: the blocklist couldn't be checked", host),
+            _ => format!("🚫 Blocked request to tracker: {}", host),
+        };
+        // Failing closed is an error rather than a policy decision
+        let status = if verdict.reason == VerdictReason::BlocklistUnavailable {
+            StatusCode::SERVICE_UNAVAILABLE
         } else {
-            false
+            StatusCode::FORBIDDEN
         };
-        
-        if is_tracker {
-            // Add to suggested trackers list for user review
-            state.add_ai_suggested_tracker(&host);
-            
-            // Log the detection
-            state.append_log(format!("🤖 AI detected potential tracker: {}", host));
-        }
-        
-        is_tracker
-    } else {
-        false
-    };
 
-    // Optionally block AI-detected trackers immediately
-    // This could be controlled by a user setting in the future
-    let ai_block_immediately = false; // Set to true if you want immediate blocking
-    
-    if ai_detected && ai_block_immediately {
-        // Record the AI-blocked request in stats
-        state.record_request(&host, true);
+        // Log blocked request
+        state.append_log_entry_with_request(
+            LogKind::Blocked,
+            Some(&host),
+            message.clone(),
+            Some(status.as_u16()),
+            request_info,
+        );
         
-        return Ok(Response::builder()
-            .status(StatusCode::FORBIDDEN)
-            .body(full(format!("🤖 AI detected and blocked tracker: {}", host)))
+        let reason = match verdict.reason {
+            VerdictReason::Blocklisted => state
+                .explain_match(&host)
+                .map_or_else(|| verdict.reason.code().to_string(), |explanation| explanation.code()),
+            VerdictReason::BlockedAddress => match blocked_ip {
+                Some(ip) => format!("ip-range:{}", ip),
+                None => verdict.reason.code().to_string(),
+            },
+            _ => verdict.reason.code().to_string(),
+        };
+        return Ok(block_response(status, &state, &reason)
+            .body(full(message))
             .unwrap());
     }
 
     // Handle CONNECT method (for HTTPS tunneling)
-    if is_connect {
-        if let Some(authority) = req.uri().authority() {
-            let addr = authority.to_string();
-            let req_clone = req;
-            let state_for_spawn = Arc::clone(&state);
+    if let Some(addr) = connect_addr {
+        if !state.try_acquire_tunnel() {
+            return Ok(too_many_tunnels(&state, &host));
+        }
+        let req_clone = req;
+        let state_for_spawn = Arc::clone(&state);
+        let host_for_spawn = host.clone();
+        let (guard, bytes) = ConnectionGuard::open(&state, ConnectionKind::Tunnel, &host);
 
-            // Record the allowed request in stats
-            state.record_request(&host, false);
+        // Record the allowed request in stats
+        state.record_request(&host, false);
+        if log_allowed {
+            state.append_log_entry_with_status(LogKind::Request, Some(&host), request_line, Some(200));
+        }
 
-            tokio::spawn(async move {
-                match hyper::upgrade::on(req_clone).await {
-                    Ok(upgraded) => {
-                        if let Err(e) = tunnel(upgraded, addr.clone()).await {
+        spawn_tracked(guard, async move {
+            match hyper::upgrade::on(req_clone).await {
+                Ok(upgraded) => {
+                    let log_tunnel = state_for_spawn.is_event_logged(LogEvent::Tunnel);
+                    if log_tunnel {
+                        state_for_spawn.append_log_entry(LogKind::Info, Some(&host_for_spawn), format!("🔓 Tunnel opened to {}", addr));
+                    }
+                    match tunnel(upgraded, addr.clone(), resolved, bytes).await {
+                        Ok((from_client, from_server)) => {
+                            state_for_spawn.record_transfer(&host_for_spawn, from_client, from_server);
+                            if log_tunnel {
+                                state_for_spawn.append_log_entry(
+                                    LogKind::Info,
+                                    Some(&host_for_spawn),
+                                    format!("🔒 Tunnel to {} closed: {} bytes sent, {} bytes received", addr, from_client, from_server),
+                                );
+                            }
+                        }
+                        Err(e) => {
                             eprintln!("❌ Tunnel error: {}", e);
-                            state_for_spawn.append_log(format!("❌ Tunnel error with {}: {}", addr, e));
+                            state_for_spawn.append_log_entry(LogKind::Error, Some(&host_for_spawn), format!("❌ Tunnel error with {}: {}", addr, e));
                         }
                     }
-                    Err(e) => {
-                        eprintln!("❌ Upgrade error: {}", e);
-                        state_for_spawn.append_log(format!("❌ Upgrade error with {}: {}", addr, e));
-                    }
                 }
-            });
+                Err(e) => {
+                    eprintln!("❌ Upgrade error: {}", e);
+                    state_for_spawn.append_log_entry(LogKind::Error, Some(&host_for_spawn), format!("❌ Upgrade error with {}: {}", addr, e));
+                }
+            }
+        });
 
-            return Ok(Response::new(empty()));
-        } else {
-            return Ok(Response::builder()
-                .status(StatusCode::BAD_REQUEST)
-                .body(full("CONNECT must be to a socket address"))
-                .unwrap());
-        }
+        return Ok(Response::new(empty()));
     }
 
     // Normal HTTP forwarding
     // Record the allowed request in stats
     state.record_request(&host, false);
+    state.record_beacon_candidate(&host, &path);
     
+    let inspect_images = state.is_response_inspection_enabled() && first_party.is_some();
+    if state.is_header_normalization_enabled() {
+        normalize_fingerprint_headers(req.headers_mut(), &state);
+    }
+    let mut resp = forward(req, &host, &state, resolved).await;
+    if inspect_images && is_image(&resp) {
+        resp = watch_for_tracking_pixel(resp, &host, &state);
+    }
+    if log_allowed {
+        state.append_log_entry_with_request(
+            LogKind::Request,
+            Some(&host),
+            request_line,
+            Some(resp.status().as_u16()),
+            request_info,
+        );
+    }
+    Ok(resp)
+}
+
+/// Replace User-Agent and Accept-Language with the configured common values
+/// so requests look alike; an empty value drops the header.
+fn normalize_fingerprint_headers(headers: &mut HeaderMap, state: &SharedState) {
+    let normalized = [
+        (hyper::header::USER_AGENT, state.get_normalized_user_agent()),
+        (hyper::header::ACCEPT_LANGUAGE, state.get_normalized_accept_language()),
+    ];
+    for (name, value) in normalized {
+        match HeaderValue::from_str(&value) {
+            Ok(value) if !value.is_empty() => {
+                headers.insert(name, value);
+            }
+            _ => {
+                headers.remove(name);
+            }
+        }
+    }
+}
+
+/// Send a plain HTTP request to its upstream and return the response,
+/// or a 502 if the upstream can't be reached.
+///
+/// With upstream HTTP/2 enabled, upstreams that answer an h2c probe get the
+/// request over HTTP/2; everything else gets HTTP/1.1. `resolved` holds
+/// addresses already looked up for `host`, which are used as they are.
+async fn forward(
+    req: Request<Body>,
+    host: &str,
+    state: &Arc<SharedState>,
+    resolved: Option<std::io::Result<Vec<SocketAddr>>>,
+) -> Response<ResponseBody> {
     let port = req.uri().port_u16().unwrap_or(80);
     let addr = format!("{}:{}", host, port);
 
-    match TcpStream::connect(addr).await {
-        Ok(stream) => {
-            let io = TokioIo::new(stream);
-            let (mut sender, conn) = match hyper::client::conn::http1::Builder::new()
-                .preserve_header_case(true)
-                .title_case_headers(true)
-                .handshake(io)
-                .await
-            {
-                Ok(parts) => parts,
-                Err(e) => {
-                    state.append_log(format!("❌ Handshake failed with {}: {:?}", host, e));
-                    return Ok(Response::builder()
-                        .status(StatusCode::BAD_GATEWAY)
-                        .body(full("Handshake failed"))
-                        .unwrap())
-                }
-            };
+    let addrs = match resolved {
+        Some(addrs) => addrs,
+        None => resolve_upstream(&addr).await,
+    };
+    let addrs = match addrs {
+        Ok(addrs) => addrs,
+        Err(e) => {
+            state.append_log_entry(LogKind::Error, Some(host), format!("❌ Couldn't resolve {}: {}", host, e));
+            return Response::builder()
+                .status(StatusCode::BAD_GATEWAY)
+                .body(full("Target host name could not be resolved"))
+                .unwrap();
+        }
+    };
 
-            tokio::spawn(async move {
-                if let Err(err) = conn.await {
-                    eprintln!("Connection failed: {:?}", err);
-                }
-            });
+    let use_h2c = state.is_upstream_http2_enabled() && speaks_h2c(&addr, &addrs, state).await;
+    let request_bytes = req
+        .headers()
+        .get(hyper::header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok())
+        .unwrap_or(0);
 
-            match sender.send_request(req).await {
-                Ok(resp) => Ok(resp.map(|b| b.boxed())),
-                Err(e) => {
-                    state.append_log(format!("❌ Request failed with {}: {:?}", host, e));
-                    Ok(Response::builder()
-                        .status(StatusCode::BAD_GATEWAY)
-                        .body(full("Bad Gateway"))
-                        .unwrap())
-                }
+    let stream = match connect_upstream(&addrs).await {
+        Ok(stream) => stream,
+        Err(e) => {
+            state.append_log_entry(LogKind::Error, Some(host), format!("❌ Failed to connect to {}: {}", addr, e));
+            return Response::builder()
+                .status(StatusCode::BAD_GATEWAY)
+                .body(full("Target host refused or did not accept the connection"))
+                .unwrap();
+        }
+    };
+
+    let (guard, bytes) = ConnectionGuard::open(state, ConnectionKind::Forward, host);
+    let stream = Counted::new(stream, bytes);
+    let sent = if use_h2c {
+        send_h2c(req, stream, guard).await
+    } else {
+        send_http1(req, stream, guard).await
+    };
+
+    match sent {
+        Ok(resp) => match oversized_headers(resp.headers(), state) {
+            Some(reason) => {
+                state.append_log_entry(LogKind::Error, Some(host), format!("⚠️ Dropped response from {}: {}", host, reason));
+                Response::builder()
+                    .status(StatusCode::BAD_GATEWAY)
+                    .body(full("Upstream response headers too large"))
+                    .unwrap()
+            }
+            None => {
+                // Count what's actually streamed, since Content-Length may be missing
+                let state = Arc::clone(state);
+                let host = host.to_string();
+                resp.map(move |b| {
+                    CountingBody::new(b, move |response_bytes| {
+                        state.record_transfer(&host, request_bytes, response_bytes)
+                    })
+                    .boxed()
+                })
             }
+        },
+        Err(UpstreamError::Handshake(e)) => {
+            state.append_log_entry(LogKind::Error, Some(host), format!("❌ Handshake failed with {}: {:?}", host, e));
+            Response::builder()
+                .status(StatusCode::BAD_GATEWAY)
+                .body(full("Handshake failed"))
+                .unwrap()
         }
-        Err(e) => {
-            state.append_log(format!("❌ Failed to connect to {}: {:?}", host, e));
-            Ok(Response::builder()
+        Err(UpstreamError::Request(e)) => {
+            state.append_log_entry(LogKind::Error, Some(host), format!("❌ Request failed with {}: {:?}", host, e));
+            Response::builder()
                 .status(StatusCode::BAD_GATEWAY)
-                .body(full("Failed to connect to target host"))
-                .unwrap())
+                .body(full("Bad Gateway"))
+                .unwrap()
+        }
+    }
+}
+
+/// Resolve `addr`, a `host:port` pair, failing when the name has no
+/// addresses at all, e.g. a typo or a dead domain.
+async fn resolve_upstream(addr: &str) -> std::io::Result<Vec<SocketAddr>> {
+    let addrs: Vec<_> = tokio::net::lookup_host(addr).await?.collect();
+    if addrs.is_empty() {
+        return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "no addresses found"));
+    }
+    Ok(addrs)
+}
+
+/// Connect to the first of `addrs` that accepts
+async fn connect_upstream(addrs: &[SocketAddr]) -> std::io::Result<TcpStream> {
+    let mut last_error = None;
+    for addr in addrs {
+        match TcpStream::connect(addr).await {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.expect("at least one address was tried"))
+}
+
+/// Where sending a request upstream went wrong
+enum UpstreamError {
+    Handshake(hyper::Error),
+    Request(hyper::Error),
+}
+
+async fn send_http1(
+    req: Request<Body>,
+    stream: Counted<TcpStream>,
+    guard: ConnectionGuard,
+) -> Result<Response<Body>, UpstreamError> {
+    let (mut sender, conn) = hyper::client::conn::http1::Builder::new()
+        .preserve_header_case(true)
+        .title_case_headers(true)
+        .max_headers(MAX_HEADER_COUNT_LIMIT)
+        .handshake(TokioIo::new(stream))
+        .await
+        .map_err(UpstreamError::Handshake)?;
+
+    spawn_tracked(guard, async move {
+        if let Err(err) = conn.await {
+            eprintln!("Connection failed: {:?}", err);
+        }
+    });
+
+    sender.send_request(req).await.map_err(UpstreamError::Request)
+}
+
+async fn send_h2c(
+    mut req: Request<Body>,
+    stream: Counted<TcpStream>,
+    guard: ConnectionGuard,
+) -> Result<Response<Body>, UpstreamError> {
+    // Connection-specific headers are forbidden in HTTP/2
+    for name in HOP_BY_HOP_HEADERS {
+        req.headers_mut().remove(*name);
+    }
+    // TE is hop-by-hop too, but HTTP/2 allows it to ask for trailers
+    let te_trailers = req
+        .headers()
+        .get(hyper::header::TE)
+        .is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(b"trailers"));
+    if !te_trailers {
+        req.headers_mut().remove(hyper::header::TE);
+    }
+    *req.version_mut() = Version::HTTP_2;
+
+    let (mut sender, conn) = hyper::client::conn::http2::Builder::new(TokioExecutor::new())
+        .handshake(TokioIo::new(stream))
+        .await
+        .map_err(UpstreamError::Handshake)?;
+
+    spawn_tracked(guard, async move {
+        if let Err(err) = conn.await {
+            eprintln!("HTTP/2 connection failed: {:?}", err);
+        }
+    });
+
+    sender.send_request(req).await.map_err(UpstreamError::Request)
+}
+
+/// Headers that only apply to a single HTTP/1.1 connection
+const HOP_BY_HOP_HEADERS: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// How long an h2c probe may take before the upstream is treated as HTTP/1.1 only
+const H2C_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The HTTP/2 connection preface followed by an empty SETTINGS frame
+const H2C_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n\x00\x00\x00\x04\x00\x00\x00\x00\x00";
+
+/// Whether the upstream at `addr` speaks cleartext HTTP/2, probing it the
+/// first time it's seen.
+///
+/// The probe only sends the connection preface and checks that the answer
+/// starts with a SETTINGS frame, so upstreams never see an extra request.
+/// Probing on a separate connection means a real request is never lost to
+/// an upstream that turns out to be HTTP/1.1 only.
+async fn speaks_h2c(addr: &str, addrs: &[SocketAddr], state: &SharedState) -> bool {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    if let Some(known) = state.upstream_h2c_support(addr) {
+        return known;
+    }
+
+    let probe = async {
+        let mut stream = TcpStream::connect(addrs).await.ok()?;
+        stream.write_all(H2C_PREFACE).await.ok()?;
+        // A frame header: 3 bytes of length, then the type, 0x4 for SETTINGS
+        let mut header = [0u8; 9];
+        stream.read_exact(&mut header).await.ok()?;
+        Some(header[3] == 0x4)
+    };
+    let supported = matches!(tokio::time::timeout(H2C_PROBE_TIMEOUT, probe).await, Ok(Some(true)));
+
+    state.record_upstream_h2c_support(addr, supported);
+    if supported {
+        state.append_log(format!("⚡ Using HTTP/2 for {}", addr));
+    }
+    supported
+}
+
+/// Whether `req` is for the address the proxy is listening on, which
+/// happens when a client is given the proxy as its destination.
+fn targets_proxy(req: &Request<Body>, local_addr: SocketAddr) -> bool {
+    let Some(host) = req.uri().host() else {
+        return false;
+    };
+    let default_port = if req.method() == Method::CONNECT { 443 } else { 80 };
+    if req.uri().port_u16().unwrap_or(default_port) != local_addr.port() {
+        return false;
+    }
+
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    match host.parse::<IpAddr>() {
+        Ok(ip) => ip.is_loopback() || ip.is_unspecified() || ip == local_addr.ip(),
+        Err(_) => {
+            let host = host.trim_end_matches('.').to_ascii_lowercase();
+            host == "localhost" || host.ends_with(".localhost")
         }
     }
 }
 
+/// The referring site, if it differs from the site of `host`. Judged by
+/// the Referer, so requests without one have none.
+fn first_party_site(req: &Request<Body>, host: &str) -> Option<String> {
+    let referer_host = req
+        .headers()
+        .get(hyper::header::REFERER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|referer| referer.parse::<Uri>().ok())
+        .and_then(|uri| uri.host().map(|h| h.to_lowercase()))?;
+    let site = |h: &str| public_suffix::registrable_domain(h).unwrap_or_else(|| h.to_lowercase());
+    let referer_site = site(&referer_host);
+    (referer_site != site(host)).then_some(referer_site)
+}
+
+fn is_image<B>(resp: &Response<B>) -> bool {
+    resp.headers()
+        .get(hyper::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|content_type| content_type.trim_start().to_ascii_lowercase().starts_with("image/"))
+}
+
+/// Pass the response through, suggesting `host` for the blocklist if its
+/// image turns out to be a tracking pixel.
+fn watch_for_tracking_pixel(resp: Response<ResponseBody>, host: &str, state: &Arc<SharedState>) -> Response<ResponseBody> {
+    let host = host.to_string();
+    let state = Arc::clone(state);
+    resp.map(move |body| {
+        pixel::sniff_dimensions(body, move |dimensions| {
+            if let Some((width, height)) = dimensions.filter(|d| pixel::is_tracking_pixel(*d)) {
+                state.append_log_entry(
+                    LogKind::Ai,
+                    Some(&host),
+                    format!("🖼️ Tracking pixel detected: {} served a {}x{} image", host, width, height),
+                );
+                state.add_ai_suggested_tracker(&host);
+            }
+        })
+        .boxed()
+    })
+}
+
+/// What to keep of `req` for its log entry; headers only if `with_headers`.
+fn request_info(req: &Request<Body>, with_headers: bool) -> RequestInfo {
+    let headers = if with_headers {
+        req.headers()
+            .iter()
+            .map(|(name, value)| (name.to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned()))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    RequestInfo {
+        method: req.method().to_string(),
+        url: req.uri().to_string(),
+        headers,
+    }
+}
+
+/// Why `headers` exceed the configured count or size limit, if they do.
+fn oversized_headers(headers: &HeaderMap, state: &SharedState) -> Option<String> {
+    let count = headers.len();
+    let max_count = state.get_max_header_count();
+    if count > max_count {
+        return Some(format!("{} headers (limit {})", count, max_count));
+    }
+
+    // Counted as sent on the wire: `name: value\r\n`
+    let bytes: usize = headers
+        .iter()
+        .map(|(name, value)| name.as_str().len() + value.len() + 4)
+        .sum();
+    let max_bytes = state.get_max_header_bytes();
+    if bytes > max_bytes {
+        return Some(format!("{} header bytes (limit {})", bytes, max_bytes));
+    }
+    None
+}
+
+/// Absolute `http://` URI for an origin-form request, built from its Host header.
+fn absolute_uri_from_host(req: &Request<Body>) -> Option<Uri> {
+    let host = req.headers().get(hyper::header::HOST)?.to_str().ok()?.trim();
+    if host.is_empty() {
+        return None;
+    }
+    let path = req.uri().path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    format!("http://{}{}", host, path).parse().ok()
+}
+
+/// The Host header, if present and naming a different host or port than the URI.
+///
+/// Hosts are compared case-insensitively and a missing port means the
+/// scheme's default, so `Host: Example.com` matches `http://example.com:80/`.
+fn mismatched_host_header(req: &Request<Body>) -> Option<String> {
+    let header = req.headers().get(hyper::header::HOST)?;
+    let header = match header.to_str() {
+        Ok(header) => header.trim(),
+        Err(_) => return Some(String::from_utf8_lossy(header.as_bytes()).into_owned()),
+    };
+    let default_port = if req.uri().scheme_str() == Some("https") { 443 } else { 80 };
+
+    let uri_host = req.uri().host()?;
+    let uri_port = req.uri().port_u16().unwrap_or(default_port);
+
+    let matches = match header.parse::<hyper::http::uri::Authority>() {
+        Ok(authority) => {
+            authority.host().eq_ignore_ascii_case(uri_host)
+                && authority.port_u16().unwrap_or(default_port) == uri_port
+        }
+        Err(_) => false,
+    };
+
+    (!matches).then(|| header.to_string())
+}
+
+/// The `host:port` a CONNECT request asks to tunnel to.
+///
+/// Rejects targets that could never be connected to: a missing or empty
+/// host, a missing port, or port 0. (Non-numeric ports are already refused
+/// by the HTTP parser.)
+fn connect_target(uri: &Uri) -> Result<String, &'static str> {
+    let authority = uri.authority().ok_or("CONNECT must be to a socket address")?;
+    if authority.host().is_empty() {
+        return Err("missing host");
+    }
+    match authority.port_u16() {
+        None => Err("missing port"),
+        Some(0) => Err("port 0 is not allowed"),
+        Some(_) => Ok(authority.to_string()),
+    }
+}
+
+/// 503 for a CONNECT refused because the tunnel limit is reached.
+fn too_many_tunnels(state: &SharedState, host: &str) -> Response<ResponseBody> {
+    let max = state.get_max_tunnels();
+    state.append_log_entry(
+        LogKind::Error,
+        Some(host),
+        format!("⚠️ Refused tunnel to {}: {} tunnels already open", host, max),
+    );
+    Response::builder()
+        .status(StatusCode::SERVICE_UNAVAILABLE)
+        .body(full("Too many open tunnels"))
+        .unwrap()
+}
+
+/// Start of a block response, with `{prefix}-Blocked` and `{prefix}-Reason`
+/// headers so tools can tell DeTrack refused the request, and why.
+fn block_response(status: StatusCode, state: &SharedState, reason: &str) -> hyper::http::response::Builder {
+    let builder = Response::builder().status(status);
+    let prefix = state.get_block_header_prefix();
+    if prefix.is_empty() {
+        return builder;
+    }
+    let reason = HeaderValue::from_str(reason).unwrap_or_else(|_| HeaderValue::from_static("invalid"));
+    builder
+        .header(format!("{}-Blocked", prefix), "true")
+        .header(format!("{}-Reason", prefix), reason)
+}
+
 // Response helpers
 fn empty() -> ResponseBody {
     Empty::<Bytes>::new()
@@ -293,13 +954,107 @@ fn full<T: Into<Bytes>>(chunk: T) -> ResponseBody {
         .boxed()
 }
 
-async fn tunnel(upgraded: Upgraded, addr: String) -> std::io::Result<()> {
-    let mut server = TcpStream::connect(addr).await?;
-    let mut upgraded = TokioIo::new(upgraded);
+/// Relay bytes between the client and `addr` until either side closes,
+/// connecting to `resolved` when `addr` was already looked up.
+/// Returns how many bytes the client and the server sent.
+async fn tunnel(
+    upgraded: Upgraded,
+    addr: String,
+    resolved: Option<std::io::Result<Vec<SocketAddr>>>,
+    bytes: Arc<AtomicU64>,
+) -> std::io::Result<(u64, u64)> {
+    let addrs = match resolved {
+        Some(addrs) => addrs,
+        None => resolve_upstream(&addr).await,
+    }
+    .map_err(|e| std::io::Error::new(e.kind(), format!("couldn't resolve {}: {}", addr, e)))?;
+    let mut server = connect_upstream(&addrs)
+        .await
+        .map_err(|e| std::io::Error::new(e.kind(), format!("failed to connect to {}: {}", addr, e)))?;
+    let mut upgraded = Counted::new(TokioIo::new(upgraded), bytes);
     let (from_client, from_server) = io::copy_bidirectional(&mut upgraded, &mut server).await?;
     println!(
         "🔒 Tunnel closed: client sent {} bytes, server sent {} bytes",
         from_client, from_server
     );
-    Ok(())
-}
\ No newline at end of file
+    Ok((from_client, from_server))
+}
+
+/// Keeps a connection listed in `SharedState` for as long as its task runs.
+/// Dropping it (including when the task is aborted) unlists the connection
+/// and, for tunnels, frees the tunnel slot.
+struct ConnectionGuard {
+    state: Arc<SharedState>,
+    id: u64,
+    kind: ConnectionKind,
+}
+
+impl ConnectionGuard {
+    fn open(state: &Arc<SharedState>, kind: ConnectionKind, host: &str) -> (Self, Arc<AtomicU64>) {
+        let (id, bytes) = state.open_connection(kind, host);
+        (ConnectionGuard { state: Arc::clone(state), id, kind }, bytes)
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.state.connection_closed(self.id);
+        if self.kind == ConnectionKind::Tunnel {
+            self.state.release_tunnel();
+        }
+    }
+}
+
+/// Run a connection's task so `SharedState::close_connection` can abort it
+fn spawn_tracked<F>(guard: ConnectionGuard, task: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let state = Arc::clone(&guard.state);
+    let id = guard.id;
+    let handle = tokio::spawn(async move {
+        let _guard = guard;
+        task.await
+    });
+    state.set_connection_abort(id, handle.abort_handle());
+}
+
+/// Adds every byte read or written through `inner` to a shared counter
+struct Counted<T> {
+    inner: T,
+    bytes: Arc<AtomicU64>,
+}
+
+impl<T> Counted<T> {
+    fn new(inner: T, bytes: Arc<AtomicU64>) -> Self {
+        Counted { inner, bytes }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Counted<T> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        let read = buf.filled().len() - before;
+        self.bytes.fetch_add(read as u64, Ordering::Relaxed);
+        poll
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Counted<T> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = poll {
+            self.bytes.fetch_add(written as u64, Ordering::Relaxed);
+        }
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
@@ -1,8 +1,117 @@
-use std::sync::{Arc, Mutex};
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::atomic::{AtomicBool, AtomicU16, AtomicU64, AtomicUsize, Ordering};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use chrono::{DateTime, Utc};
-use crate::tracker_blocker::TrackerBlocker;
+use tokio::sync::Notify;
+use tokio::task::AbortHandle;
+use crate::blocklist_source::{BlocklistSource, ImportDiff, MatchExplanation};
+use crate::tracker_blocker::{matching_entry, normalize_host, parse_tracker_list, write_tracker_export, TrackerBlocker, UrlCleaner};
 use crate::ai_tracker::AITracker;
+use crate::beacon::{BeaconDetector, DEFAULT_BEACON_THRESHOLD};
+use crate::presets::{default_preset, Preset};
+use crate::decision_hook::DecisionHook;
+use crate::profiles::Profile;
+use crate::list_formats::parse_list;
+use crate::verdict::{AiConflictPolicy, HostStatus, HostVerdict, RequestVerdict, VerdictReason};
+use crate::system_proxy::{self, PreviousProxySettings};
+use crate::public_suffix;
+use crate::logging::{self, LogEntry, LogEvent, LogFormat, LogKind, RequestInfo, TimestampFormat};
+use crate::config::{Config, DEFAULT_LISTEN_PORT};
+
+/// How long [`SharedState::allow_once`] lets a host through
+pub const TEMPORARY_ALLOW_SECS: i64 = 120;
+
+/// Default for [`SharedState::set_max_tunnels`]
+pub const DEFAULT_MAX_TUNNELS: usize = 256;
+
+/// Most headers a request or response may carry unless configured otherwise
+pub const DEFAULT_MAX_HEADER_COUNT: usize = 100;
+
+/// Highest header count limit that can be configured; hyper's parser stops here
+pub const MAX_HEADER_COUNT_LIMIT: usize = 500;
+
+/// Most total header bytes a request or response may carry unless configured otherwise
+pub const DEFAULT_MAX_HEADER_BYTES: usize = 64 * 1024;
+
+/// Most hosts whose blocklist verdict is remembered before the cache is reset
+pub const VERDICT_CACHE_CAPACITY: usize = 4096;
+
+/// Intranet names bypassed unless edited; each also covers its subdomains
+pub const DEFAULT_BYPASS_HOSTS: &[&str] = &["localhost", "local", "internal"];
+
+/// Public STUN/TURN servers, often used to discover a visitor's real IP
+/// for fingerprinting; each also covers its subdomains
+pub const DEFAULT_STUN_TURN_HOSTS: &[&str] = &[
+    "stun.l.google.com",
+    "stun.services.mozilla.com",
+    "global.stun.twilio.com",
+    "global.turn.twilio.com",
+    "stun.cloudflare.com",
+    "turn.cloudflare.com",
+    "stun.stunprotocol.org",
+    "stun.ekiga.net",
+    "stun.voipbuster.com",
+    "relay.metered.ca",
+];
+
+/// User-Agent sent with header normalization unless edited: a current
+/// Firefox ESR on Windows, one of the most common browser profiles
+pub const DEFAULT_NORMALIZED_USER_AGENT: &str =
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:128.0) Gecko/20100101 Firefox/128.0";
+
+/// Accept-Language sent with header normalization unless edited
+pub const DEFAULT_NORMALIZED_ACCEPT_LANGUAGE: &str = "en-US,en;q=0.5";
+
+/// Standard STUN/TURN ports, plain and over TLS
+pub const STUN_TURN_PORTS: [u16; 2] = [3478, 5349];
+
+/// Prefix of the headers added to block responses, e.g. `X-DeTrack-Blocked`
+pub const DEFAULT_BLOCK_HEADER_PREFIX: &str = "X-DeTrack";
+
+/// Destination ports allowed when port restriction is on, unless configured otherwise
+pub const DEFAULT_ALLOWED_PORTS: [u16; 2] = [80, 443];
+
+/// Whether `host` is one of `names` or a subdomain of one
+fn matches_name_list(host: &str, names: &[String]) -> bool {
+    let host = host.to_ascii_lowercase();
+    names.iter().any(|name| {
+        host == *name || host.strip_suffix(name.as_str()).is_some_and(|rest| rest.ends_with('.'))
+    })
+}
+
+/// Whether `host` is allowlisted, counting subdomains of entries if `subdomains`
+fn allowlist_matches(allowlist: &HashSet<String>, host: &str, subdomains: bool) -> bool {
+    if subdomains {
+        matching_entry(allowlist, host).is_some()
+    } else {
+        allowlist.contains(host)
+    }
+}
+
+/// Whether `host` shares a registrable domain with an entry of `blocker`,
+/// filling `sites` with the listed registrable domains on first use
+fn site_is_listed(sites: &mut Option<HashSet<String>>, blocker: &dyn BlocklistSource, host: &str) -> bool {
+    let Some(site) = public_suffix::registrable_domain(host) else {
+        return blocker.contains(host);
+    };
+    sites
+        .get_or_insert_with(|| blocker.iter().filter_map(|entry| public_suffix::registrable_domain(&entry)).collect())
+        .contains(&site)
+}
+
+/// `name` as stored in a host list: lowercase, without a leading `*.`
+fn list_name(name: &str) -> Result<String, String> {
+    let name = name.trim().trim_start_matches("*.").trim_start_matches('.').to_ascii_lowercase();
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err(format!("'{}' is not a host name", name));
+    }
+    Ok(name)
+}
 
 /// Statistics for a specific domain
 #[derive(Clone, Debug)]
@@ -12,243 +121,2535 @@ pub struct DomainStat {
     pub blocked: usize,
     pub last_seen: DateTime<Utc>,
     pub bandwidth_saved: Arc<Mutex<u64>>,
+    /// Request body bytes sent to this host
+    pub request_bytes: u64,
+    /// Response body bytes received from this host
+    pub response_bytes: u64,
+}
+
+impl DomainStat {
+    /// Body bytes transferred in both directions
+    pub fn data_transferred(&self) -> u64 {
+        self.request_bytes + self.response_bytes
+    }
+}
+
+/// What an open connection carries
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionKind {
+    /// A CONNECT tunnel, usually HTTPS
+    Tunnel,
+    /// An upstream connection for a plain HTTP request
+    Forward,
+}
+
+/// An open tunnel or upstream connection
+#[derive(Clone, Debug)]
+pub struct ConnectionInfo {
+    pub id: u64,
+    pub kind: ConnectionKind,
+    pub host: String,
+    pub started: DateTime<Utc>,
+    /// Bytes transferred so far, in both directions
+    pub bytes: u64,
+}
+
+/// Bookkeeping for an open connection: its counters and how to close it
+struct OpenConnection {
+    kind: ConnectionKind,
+    host: String,
+    started: DateTime<Utc>,
+    bytes: Arc<AtomicU64>,
+    abort: Option<AbortHandle>,
+}
+
+/// Requests to one normalized path, across all hosts
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathStat {
+    /// The path with IDs replaced by `:id`, e.g. `/user/:id/collect`
+    pub path: String,
+    pub requests: usize,
+    pub blocked: usize,
+    /// Hosts seen serving the path, up to [`MAX_PATH_HOSTS`]
+    pub hosts: BTreeSet<String>,
+}
+
+/// Most distinct paths counted; later paths aren't recorded
+pub const MAX_PATH_STATS: usize = 2000;
+
+/// Most hosts remembered per path
+pub const MAX_PATH_HOSTS: usize = 100;
+
+/// Paths shown in the Dashboard's top paths
+pub const TOP_PATHS_SHOWN: usize = 20;
+
+/// `path` with segments that look like IDs replaced by `:id`, so that
+/// `/u/123/collect` and `/u/456/collect` count as one path.
+pub fn normalize_stat_path(path: &str) -> String {
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    let segments: Vec<&str> = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| if looks_like_id(segment) { ":id" } else { segment })
+        .collect();
+    format!("/{}", segments.join("/"))
+}
+
+fn looks_like_id(segment: &str) -> bool {
+    let digits = segment.chars().filter(|c| c.is_ascii_digit()).count();
+    let all_digits = digits == segment.len();
+    let hex = segment.len() >= 8 && segment.chars().all(|c| c.is_ascii_hexdigit() || c == '-');
+    let token = segment.len() >= 16
+        && digits > 0
+        && segment.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    all_digits || (digits > 0 && hex) || token
+}
+
+/// How a candidate blocklist would have treated the hosts seen so far
+#[derive(Clone, Debug)]
+pub struct BlocklistSimulation {
+    /// Valid entries in the candidate list
+    pub candidate_size: usize,
+    /// Candidate lines that aren't valid entries
+    pub malformed: usize,
+    /// Hosts seen that the candidate blocks and neither the current list
+    /// nor the allowlist covers, busiest first
+    pub newly_blocked: Vec<DomainStat>,
+    /// Hosts seen that both lists block
+    pub already_blocked: usize,
+    /// Allowlist entries covering hosts the candidate matches; those hosts
+    /// would stay allowed
+    pub allowlist_overlaps: Vec<String>,
+}
+
+impl BlocklistSimulation {
+    /// Requests to hosts the candidate would newly block
+    pub fn newly_blocked_requests(&self) -> usize {
+        self.newly_blocked.iter().map(|stat| stat.requests).sum()
+    }
+}
+
+/// Statistics for all hosts under one registrable domain (eTLD+1)
+#[derive(Clone, Debug)]
+pub struct DomainGroup {
+    /// The registrable domain, or the host itself for IPs and bare suffixes
+    pub domain: String,
+    pub requests: usize,
+    pub blocked: usize,
+    pub last_seen: DateTime<Utc>,
+    /// Body bytes transferred in both directions, across all hosts
+    pub data_transferred: u64,
+    /// Per-host statistics, busiest first
+    pub hosts: Vec<DomainStat>,
+}
+
+/// A third-party host contacted from a first-party site
+#[derive(Clone, Debug, PartialEq)]
+pub struct SiteContact {
+    pub host: String,
+    pub requests: usize,
+    pub blocked: usize,
+}
+
+/// One part of the protection score
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScoreFactor {
+    pub name: &'static str,
+    pub points: u32,
+    pub max: u32,
+    /// What to change to earn the missing points
+    pub hint: &'static str,
+}
+
+/// At-a-glance summary of how well DeTrack is set up, out of 100
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProtectionScore {
+    pub score: u32,
+    pub factors: Vec<ScoreFactor>,
+}
+
+impl ProtectionScore {
+    /// Factors short of their maximum, the biggest shortfall first
+    pub fn weak_factors(&self) -> Vec<&ScoreFactor> {
+        let mut weak: Vec<&ScoreFactor> = self.factors.iter().filter(|f| f.points < f.max).collect();
+        weak.sort_by_key(|f| std::cmp::Reverse(f.max - f.points));
+        weak
+    }
+}
+
+/// Aggregates shown on the Dashboard, computed in the background so
+/// repaints don't lock the statistics
+#[derive(Clone, Debug)]
+pub struct DashboardStats {
+    pub computed_at: DateTime<Utc>,
+    /// Per-host statistics, in no particular order
+    pub domains: Vec<DomainStat>,
+    pub groups: Vec<DomainGroup>,
+    pub sites: Vec<SiteSummary>,
+    pub protection: ProtectionScore,
+    /// Busiest paths first, at most [`TOP_PATHS_SHOWN`]
+    pub top_paths: Vec<PathStat>,
+}
+
+/// Entries kept in the log; the oldest are dropped beyond this
+const MAX_LOG_ENTRIES: usize = 10_000;
+
+/// Entries that can wait in the append queue while batching; further
+/// entries are dropped and counted until it's drained
+pub const LOG_QUEUE_CAPACITY: usize = 4096;
+
+/// How often queued log entries are moved into the log while batching
+pub const LOG_DRAIN_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How often the Dashboard aggregates are recomputed
+pub const DASHBOARD_STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Blocklist size that earns full points for the list
+const FULL_SCORE_BLOCKLIST_SIZE: usize = 1000;
+
+/// Share of requests blocked that earns full points; pages rarely get more
+const FULL_SCORE_BLOCK_RATE: f64 = 0.25;
+
+/// Share of third-party requests blocked that earns full points
+const FULL_SCORE_THIRD_PARTY_BLOCK_RATE: f64 = 0.5;
+
+/// Third parties contacted from one site during this session
+#[derive(Clone, Debug, PartialEq)]
+pub struct SiteSummary {
+    /// Registrable domain of the page that made the requests
+    pub site: String,
+    pub requests: usize,
+    pub blocked: usize,
+    /// Most blocked first
+    pub contacts: Vec<SiteContact>,
+}
+
+/// Connection speed assumed for time-saved estimates unless configured otherwise
+pub const DEFAULT_ASSUMED_SPEED_MBPS: u32 = 25;
+
+/// Latency each blocked request is assumed to have avoided
+const ASSUMED_ROUND_TRIP: Duration = Duration::from_millis(50);
+
+/// AI suggestions kept for review unless configured otherwise
+pub const DEFAULT_AI_SUGGESTION_LIMIT: usize = 200;
+
+/// Allowed third-party hosts kept for review; the oldest is dropped to make room
+pub const MAX_THIRD_PARTY_REVIEW: usize = 200;
+
+/// Most log entries that can be pinned at once
+pub const MAX_PINNED_LOGS: usize = 50;
+
+/// Most first-party sites tracked per session; later sites aren't summarized
+pub const MAX_SUMMARY_SITES: usize = 1000;
+
+/// Most upstreams whose h2c support is remembered before it's forgotten
+pub const MAX_PROBED_UPSTREAMS: usize = 1024;
+
+/// Allowed third-party hosts waiting to be blocked or dismissed
+#[derive(Default)]
+struct ThirdPartyReview {
+    /// Oldest first
+    hosts: Vec<String>,
+    /// Not queued again this session
+    dismissed: HashSet<String>,
 }
 
 /// Shared state between the proxy and the UI.
 /// This is safe to clone and pass around because of Arc.
+///
+/// Fields are private so that all locking goes through the methods below.
 #[derive(Clone)]
 pub struct SharedState {
     /// Whether the proxy is currently enabled.
-    pub proxy_enabled: Arc<Mutex<bool>>,
+    proxy_enabled: Arc<Mutex<bool>>,
+
+    /// Reject requests whose Host header disagrees with the URI, instead of just logging them
+    strict_host_check: Arc<Mutex<bool>>,
+
+    /// Forward intranet hosts directly, without filtering, stats or logging
+    intranet_bypass: Arc<AtomicBool>,
+
+    /// Names (and their subdomains) treated as intranet hosts
+    bypass_hosts: Arc<Mutex<Vec<String>>>,
+
+    /// Block CONNECTs to STUN/TURN servers
+    stun_turn_blocking: Arc<AtomicBool>,
+    /// Only connect to `allowed_ports`
+    port_restriction: Arc<AtomicBool>,
+    allowed_ports: Arc<Mutex<BTreeSet<u16>>>,
+
+    /// Hosts (and their subdomains) treated as STUN/TURN servers
+    stun_turn_hosts: Arc<Mutex<Vec<String>>>,
+
+    /// Port on 127.0.0.1 the proxy listens on (read when the proxy starts)
+    listen_port: Arc<AtomicU16>,
+
+    /// Prefix of the headers explaining block responses; empty for none
+    block_header_prefix: Arc<Mutex<String>>,
 
     /// Whether request logging is currently enabled.
-    pub log_enabled: Arc<Mutex<bool>>,
+    log_enabled: Arc<Mutex<bool>>,
+
+    /// Event categories whose log entries are dropped
+    disabled_log_events: Arc<Mutex<HashSet<LogEvent>>>,
 
     /// Request logs storage
-    pub logs: Arc<Mutex<Vec<String>>>,
+    logs: Arc<Mutex<Vec<LogEntry>>>,
+
+    /// Whether appends are queued and moved into `logs` in batches, so busy
+    /// request handlers don't contend with the UI for the log lock
+    log_batching: Arc<AtomicBool>,
+    /// Running without a window, so log entries are also printed to stdout
+    headless: Arc<AtomicBool>,
+    log_queue_sender: SyncSender<LogEntry>,
+    log_queue: Arc<Mutex<Receiver<LogEntry>>>,
+    /// Entries dropped because the queue was full, reported on the next drain
+    dropped_log_entries: Arc<AtomicU64>,
+
+    /// Copies of pinned log entries, kept apart so trimming the log doesn't drop them
+    pinned_logs: Arc<Mutex<Vec<LogEntry>>>,
+
+    /// Whether clearing the log also unpins entries
+    clear_unpins: Arc<AtomicBool>,
+
+    /// Where logs are archived before being cleared, if anywhere
+    log_archive_dir: Arc<Mutex<Option<PathBuf>>>,
+
+    /// Format log entries are echoed to stdout in
+    log_format: Arc<Mutex<LogFormat>>,
+    timestamp_format: Arc<Mutex<TimestampFormat>>,
+
+    /// Whether request headers are kept with request log entries
+    capture_headers: Arc<Mutex<bool>>,
+
+    /// Whether requests are also counted by path
+    path_stats_enabled: Arc<AtomicBool>,
+
+    /// Per normalized path statistics
+    path_stats: Arc<Mutex<HashMap<String, PathStat>>>,
+
+    /// Id given to the next log entry
+    next_log_id: Arc<AtomicU64>,
 
     /// The active tracker blocker instance.
-    pub blocker: Arc<Mutex<TrackerBlocker>>,
+    /// Read-locked for checks so concurrent requests don't serialize;
+    /// write-locked only to change the list.
+    blocker: Arc<RwLock<Box<dyn BlocklistSource>>>,
+
+    /// Strips tracking parameters from allowed URLs
+    url_cleaner: Arc<UrlCleaner>,
+
+    /// Memoized blocklist verdicts per host, cleared whenever the list changes
+    verdict_cache: Arc<Mutex<HashMap<String, bool>>>,
+
+    /// Lookups answered from `verdict_cache`
+    verdict_cache_hits: Arc<AtomicU64>,
+
+    /// Lookups that had to match against the blocklist
+    verdict_cache_misses: Arc<AtomicU64>,
+
+    /// Hosts that are never blocked, even if they match the blocklist
+    allowlist: Arc<Mutex<HashSet<String>>>,
+    /// File the allowlist is saved to on every change, if any
+    allowlist_path: Arc<Mutex<Option<PathBuf>>>,
+    /// Whether allowlisting a domain also allows its subdomains
+    allowlist_subdomains: Arc<AtomicBool>,
+    /// Named profile in use, `None` for the default
+    profile_name: Arc<Mutex<Option<String>>>,
+    /// User script consulted when no built-in rule matches
+    decision_hook: Arc<RwLock<Option<Arc<DecisionHook>>>>,
+    /// Cache the script's answers per host rather than per request
+    decision_cache_per_host: Arc<AtomicBool>,
+
+    /// Hosts allowed until the given time, e.g. to let one page load through
+    temporary_allows: Arc<Mutex<HashMap<String, DateTime<Utc>>>>,
 
     /// Statistics about requests
-    pub stats: Arc<Mutex<HashMap<String, DomainStat>>>,
+    stats: Arc<Mutex<HashMap<String, DomainStat>>>,
+
+    /// Third-party contacts per first-party site, this session only
+    site_contacts: Arc<Mutex<HashMap<String, HashMap<String, SiteContact>>>>,
+
+    /// Last Dashboard aggregates, `None` until computed or after a reset
+    dashboard_stats: Arc<RwLock<Option<Arc<DashboardStats>>>>,
+
+    /// Seconds a domain may go unseen before it's pruned from `stats`; 0 keeps everything
+    stats_ttl_secs: Arc<AtomicU64>,
 
     /// Total allowed requests
-    pub allowed_count: Arc<Mutex<usize>>,
+    allowed_count: Arc<AtomicUsize>,
 
     /// Total blocked requests
-    pub blocked_count: Arc<Mutex<usize>>,
+    blocked_count: Arc<AtomicUsize>,
+
+    /// Allowed count when the current session started
+    session_allowed_baseline: Arc<AtomicUsize>,
+
+    /// Blocked count when the current session started
+    session_blocked_baseline: Arc<AtomicUsize>,
 
     /// AI tracker for heuristic detection
-    pub ai_tracker: Arc<Mutex<AITracker>>,
+    ai_tracker: Arc<Mutex<AITracker>>,
+
+    /// What to do when the AI flags a host the blocklist doesn't list
+    ai_conflict_policy: Arc<Mutex<AiConflictPolicy>>,
+    
+    /// Percentage (1-100) of eligible requests the AI analyzes
+    ai_sample_percent: Arc<AtomicU64>,
+    
+    /// Requests that reached the AI step, sampled or not
+    ai_eligible_count: Arc<AtomicU64>,
+    
+    /// Requests the AI actually analyzed
+    ai_analyzed_count: Arc<AtomicU64>,
+    
+    /// Where the AI model is saved after each piece of feedback, if anywhere
+    ai_model_path: Arc<Mutex<Option<PathBuf>>>,
+
+    /// Set when the last AI model save failed, so feedback since then is only in memory
+    ai_model_unsaved: Arc<AtomicBool>,
     
     /// AI-suggested trackers pending user review
-    pub ai_suggested_trackers: Arc<Mutex<Vec<String>>>,
+    ai_suggested_trackers: Arc<Mutex<Vec<String>>>,
+
+    /// Most AI suggestions kept; the oldest is dropped to make room
+    ai_suggestion_limit: Arc<AtomicUsize>,
+
+    /// Third-party hosts that no rule caught, pending user review
+    third_party_review: Arc<Mutex<ThirdPartyReview>>,
 
     /// Total bandwidth saved by blocking trackers
-    pub bandwidth_saved: Arc<Mutex<u64>>, 
+    bandwidth_saved: Arc<AtomicU64>,
+
+    /// Whether the accept loop is running
+    listener_running: Arc<AtomicBool>,
+
+    /// Wakes the accept loop so it stops listening
+    listener_stop: Arc<Notify>,
+
+    /// CONNECT tunnels currently open
+    active_tunnels: Arc<AtomicUsize>,
+
+    /// Open tunnels and upstream connections by id
+    connections: Arc<Mutex<HashMap<u64, OpenConnection>>>,
+
+    /// Id for the next connection
+    next_connection_id: Arc<AtomicU64>,
+
+    /// Most tunnels allowed at once; further CONNECTs get a 503
+    max_tunnels: Arc<AtomicUsize>,
+
+    /// Check third-party image responses for tracking pixels as they stream through
+    response_inspection: Arc<Mutex<bool>>,
+
+    /// Strip tracking parameters from every method, not just GET and HEAD
+    strip_params_all_methods: Arc<Mutex<bool>>,
+
+    /// Replace User-Agent and Accept-Language on forwarded requests
+    header_normalization: Arc<AtomicBool>,
+
+    /// User-Agent sent when header normalization is on
+    normalized_user_agent: Arc<Mutex<String>>,
+
+    /// Accept-Language sent when header normalization is on
+    normalized_accept_language: Arc<Mutex<String>>,
+
+    /// Match blocklist entries by registrable domain instead of host suffix
+    registrable_matching: Arc<AtomicBool>,
+
+    /// Block requests when the blocklist can't be checked
+    fail_closed: Arc<AtomicBool>,
+
+    /// Registrable domains of the blocklist entries, built on first use
+    /// after each list change
+    blocked_sites: Arc<Mutex<Option<HashSet<String>>>>,
+
+    /// Recent request times per host and path, for spotting heartbeat beacons
+    beacons: Arc<Mutex<BeaconDetector>>,
+
+    /// Identical requests per minute that flag a beacon; 0 turns detection off
+    beacon_threshold: Arc<AtomicUsize>,
+
+    /// Try HTTP/2 without TLS (h2c) to upstreams before falling back to HTTP/1.1
+    upstream_http2: Arc<Mutex<bool>>,
+
+    /// Whether each upstream `host:port` answered an h2c probe
+    upstream_h2c_support: Arc<Mutex<HashMap<String, bool>>>,
+
+    /// Most headers allowed on a request or upstream response
+    max_header_count: Arc<AtomicUsize>,
+
+    /// Most total header bytes allowed on a request or upstream response
+    max_header_bytes: Arc<AtomicUsize>,
+
+    /// OS proxy settings replaced by `enable_system_proxy`, restored on exit
+    system_proxy_backup: Arc<Mutex<Option<PreviousProxySettings>>>,
 }
 
 impl SharedState {
-    pub fn new(blocker: TrackerBlocker) -> Self {
+    pub fn new(blocker: impl BlocklistSource + 'static) -> Self {
+        let (log_queue_sender, log_queue) = mpsc::sync_channel(LOG_QUEUE_CAPACITY);
         Self {
             proxy_enabled: Arc::new(Mutex::new(true)),
+            listen_port: Arc::new(AtomicU16::new(DEFAULT_LISTEN_PORT)),
+            block_header_prefix: Arc::new(Mutex::new(DEFAULT_BLOCK_HEADER_PREFIX.to_string())),
+            strict_host_check: Arc::new(Mutex::new(false)),
+            intranet_bypass: Arc::new(AtomicBool::new(true)),
+            bypass_hosts: Arc::new(Mutex::new(DEFAULT_BYPASS_HOSTS.iter().map(|h| h.to_string()).collect())),
+            stun_turn_blocking: Arc::new(AtomicBool::new(false)),
+            port_restriction: Arc::new(AtomicBool::new(false)),
+            allowed_ports: Arc::new(Mutex::new(DEFAULT_ALLOWED_PORTS.into_iter().collect())),
+            stun_turn_hosts: Arc::new(Mutex::new(DEFAULT_STUN_TURN_HOSTS.iter().map(|h| h.to_string()).collect())),
             log_enabled: Arc::new(Mutex::new(true)),
-            blocker: Arc::new(Mutex::new(blocker)),
+            disabled_log_events: Arc::new(Mutex::new(HashSet::from([LogEvent::Tunnel]))),
+            blocker: Arc::new(RwLock::new(Box::new(blocker))),
+            url_cleaner: Arc::new(UrlCleaner::default()),
+            verdict_cache: Arc::new(Mutex::new(HashMap::new())),
+            verdict_cache_hits: Arc::new(AtomicU64::new(0)),
+            verdict_cache_misses: Arc::new(AtomicU64::new(0)),
+            allowlist: Arc::new(Mutex::new(HashSet::new())),
+            allowlist_path: Arc::new(Mutex::new(None)),
+            allowlist_subdomains: Arc::new(AtomicBool::new(false)),
+            profile_name: Arc::new(Mutex::new(None)),
+            decision_hook: Arc::new(RwLock::new(None)),
+            decision_cache_per_host: Arc::new(AtomicBool::new(false)),
+            temporary_allows: Arc::new(Mutex::new(HashMap::new())),
             logs: Arc::new(Mutex::new(vec![])),
+            log_batching: Arc::new(AtomicBool::new(false)),
+            headless: Arc::new(AtomicBool::new(false)),
+            log_queue_sender,
+            log_queue: Arc::new(Mutex::new(log_queue)),
+            dropped_log_entries: Arc::new(AtomicU64::new(0)),
+            pinned_logs: Arc::new(Mutex::new(Vec::new())),
+            clear_unpins: Arc::new(AtomicBool::new(false)),
+            next_log_id: Arc::new(AtomicU64::new(1)),
+            log_archive_dir: Arc::new(Mutex::new(None)),
+            log_format: Arc::new(Mutex::new(LogFormat::default())),
+            timestamp_format: Arc::new(Mutex::new(TimestampFormat::default())),
+            capture_headers: Arc::new(Mutex::new(false)),
+            path_stats_enabled: Arc::new(AtomicBool::new(false)),
+            path_stats: Arc::new(Mutex::new(HashMap::new())),
             stats: Arc::new(Mutex::new(HashMap::new())),
-            allowed_count: Arc::new(Mutex::new(0)),
-            blocked_count: Arc::new(Mutex::new(0)),
+            site_contacts: Arc::new(Mutex::new(HashMap::new())),
+            dashboard_stats: Arc::new(RwLock::new(None)),
+            stats_ttl_secs: Arc::new(AtomicU64::new(0)),
+            allowed_count: Arc::new(AtomicUsize::new(0)),
+            blocked_count: Arc::new(AtomicUsize::new(0)),
+            session_allowed_baseline: Arc::new(AtomicUsize::new(0)),
+            session_blocked_baseline: Arc::new(AtomicUsize::new(0)),
             ai_tracker: Arc::new(Mutex::new(AITracker::new())),
+            ai_conflict_policy: Arc::new(Mutex::new(AiConflictPolicy::default())),
             ai_suggested_trackers: Arc::new(Mutex::new(Vec::new())),
-            bandwidth_saved: Arc::new(Mutex::new(0)),
+            ai_suggestion_limit: Arc::new(AtomicUsize::new(DEFAULT_AI_SUGGESTION_LIMIT)),
+            third_party_review: Arc::new(Mutex::new(ThirdPartyReview::default())),
+            ai_model_path: Arc::new(Mutex::new(None)),
+            ai_model_unsaved: Arc::new(AtomicBool::new(false)),
+            ai_sample_percent: Arc::new(AtomicU64::new(100)),
+            ai_eligible_count: Arc::new(AtomicU64::new(0)),
+            ai_analyzed_count: Arc::new(AtomicU64::new(0)),
+            bandwidth_saved: Arc::new(AtomicU64::new(0)),
+            system_proxy_backup: Arc::new(Mutex::new(None)),
+            listener_running: Arc::new(AtomicBool::new(false)),
+            listener_stop: Arc::new(Notify::new()),
+            active_tunnels: Arc::new(AtomicUsize::new(0)),
+            connections: Arc::new(Mutex::new(HashMap::new())),
+            next_connection_id: Arc::new(AtomicU64::new(1)),
+            max_tunnels: Arc::new(AtomicUsize::new(DEFAULT_MAX_TUNNELS)),
+            response_inspection: Arc::new(Mutex::new(false)),
+            strip_params_all_methods: Arc::new(Mutex::new(false)),
+            header_normalization: Arc::new(AtomicBool::new(false)),
+            normalized_user_agent: Arc::new(Mutex::new(DEFAULT_NORMALIZED_USER_AGENT.to_string())),
+            normalized_accept_language: Arc::new(Mutex::new(DEFAULT_NORMALIZED_ACCEPT_LANGUAGE.to_string())),
+            registrable_matching: Arc::new(AtomicBool::new(false)),
+            fail_closed: Arc::new(AtomicBool::new(false)),
+            blocked_sites: Arc::new(Mutex::new(None)),
+            beacons: Arc::new(Mutex::new(BeaconDetector::new())),
+            beacon_threshold: Arc::new(AtomicUsize::new(DEFAULT_BEACON_THRESHOLD)),
+            upstream_http2: Arc::new(Mutex::new(false)),
+            upstream_h2c_support: Arc::new(Mutex::new(HashMap::new())),
+            max_header_count: Arc::new(AtomicUsize::new(DEFAULT_MAX_HEADER_COUNT)),
+            max_header_bytes: Arc::new(AtomicUsize::new(DEFAULT_MAX_HEADER_BYTES)),
+        }
+    }
+
+    // Proxy toggle
+    pub fn enable_proxy(&self) {
+        if let Ok(mut enabled) = self.proxy_enabled.lock() {
+            *enabled = true;
+        }
+        self.append_log("▶️ Proxy enabled".to_string());
+    }
+
+    pub fn disable_proxy(&self) {
+        if let Ok(mut enabled) = self.proxy_enabled.lock() {
+            *enabled = false;
+        }
+        self.append_log("🛑 Proxy disabled".to_string());
+    }
+
+    /// Mark the accept loop as starting. Returns false if one is already running.
+    pub fn try_claim_listener(&self) -> bool {
+        self.listener_running
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+    }
+
+    /// Mark the accept loop as stopped.
+    pub fn release_listener(&self) {
+        self.listener_running.store(false, Ordering::Release);
+    }
+
+    pub fn is_listener_running(&self) -> bool {
+        self.listener_running.load(Ordering::Acquire)
+    }
+
+    /// Ask the running accept loop to stop listening.
+    pub fn stop_listener(&self) {
+        if self.is_listener_running() {
+            self.listener_stop.notify_one();
+        }
+    }
+
+    /// Resolves once `stop_listener` is called.
+    pub async fn listener_stopped(&self) {
+        self.listener_stop.notified().await
+    }
+
+    pub fn is_proxy_enabled(&self) -> bool {
+        self.proxy_enabled.lock().map(|v| *v).unwrap_or(false)
+    }
+
+    /// Choose between rejecting (strict) and logging (lenient) Host header mismatches.
+    pub fn set_strict_host_check(&self, strict: bool) {
+        if let Ok(mut current) = self.strict_host_check.lock() {
+            *current = strict;
+        }
+        self.append_log(if strict {
+            "🛡️ Requests with a mismatched Host header will be rejected".to_string()
+        } else {
+            "🛡️ Requests with a mismatched Host header will be logged".to_string()
+        });
+    }
+
+    pub fn is_strict_host_check(&self) -> bool {
+        self.strict_host_check.lock().map(|v| *v).unwrap_or(false)
+    }
+
+    /// Turn the intranet bypass on or off.
+    pub fn set_intranet_bypass(&self, enabled: bool) {
+        self.intranet_bypass.store(enabled, Ordering::Relaxed);
+        self.append_log(if enabled {
+            "🏠 Intranet hosts will bypass filtering".to_string()
+        } else {
+            "🏠 Intranet hosts will be filtered like any other".to_string()
+        });
+    }
+
+    pub fn is_intranet_bypass_enabled(&self) -> bool {
+        self.intranet_bypass.load(Ordering::Relaxed)
+    }
+
+    /// Whether `host` is an intranet host that skips filtering: a loopback or
+    /// private (RFC 1918 or IPv6 unique local) address, or a bypass name or
+    /// one of its subdomains.
+    pub fn is_bypassed(&self, host: &str) -> bool {
+        if !self.is_intranet_bypass_enabled() {
+            return false;
+        }
+
+        let host = host.trim_start_matches('[').trim_end_matches(']').trim_end_matches('.');
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return match ip {
+                IpAddr::V4(ip) => ip.is_private() || ip.is_loopback(),
+                IpAddr::V6(ip) => ip.is_loopback() || ip.segments()[0] & 0xfe00 == 0xfc00,
+            };
+        }
+
+        self.bypass_hosts.lock().map(|names| matches_name_list(host, &names)).unwrap_or(false)
+    }
+
+    /// Add a name to the bypass list. Returns false if it was already there.
+    pub fn add_bypass_host(&self, name: &str) -> Result<bool, String> {
+        let name = list_name(name)?;
+        let mut names = self.bypass_hosts.lock().map_err(|_| "Bypass list is unavailable".to_string())?;
+        if names.contains(&name) {
+            return Ok(false);
+        }
+        self.append_log(format!("🏠 Bypassing {}", name));
+        names.push(name);
+        Ok(true)
+    }
+
+    /// Remove a name from the bypass list. Returns false if it wasn't there.
+    pub fn remove_bypass_host(&self, name: &str) -> bool {
+        let removed = match self.bypass_hosts.lock() {
+            Ok(mut names) => {
+                let before = names.len();
+                names.retain(|n| n != name);
+                names.len() != before
+            }
+            Err(_) => false,
+        };
+        if removed {
+            self.append_log(format!("🏠 No longer bypassing {}", name));
+        }
+        removed
+    }
+
+    /// Replace the bypass list, e.g. with the one saved in the config.
+    /// Invalid names are logged and skipped.
+    pub fn set_bypass_hosts(&self, names: &[String]) {
+        let mut valid = Vec::new();
+        for name in names {
+            match list_name(name) {
+                Ok(name) if !valid.contains(&name) => valid.push(name),
+                Ok(_) => {}
+                Err(e) => self.append_log_entry(LogKind::Error, None, format!("⚠️ {}", e)),
+            }
+        }
+        if let Ok(mut current) = self.bypass_hosts.lock() {
+            *current = valid;
+        }
+    }
+
+    pub fn get_bypass_hosts(&self) -> Vec<String> {
+        self.bypass_hosts.lock().map(|names| names.clone()).unwrap_or_default()
+    }
+
+    /// Only forward to and tunnel to the allowed ports. Off by default so
+    /// local development servers keep working.
+    pub fn set_port_restriction(&self, enabled: bool) {
+        self.port_restriction.store(enabled, Ordering::Relaxed);
+        self.append_log(if enabled {
+            format!("🔢 Only connecting to ports {}", self.describe_allowed_ports())
+        } else {
+            "🔢 Connecting to any port".to_string()
+        });
+    }
+
+    pub fn is_port_restriction_enabled(&self) -> bool {
+        self.port_restriction.load(Ordering::Relaxed)
+    }
+
+    /// Whether the proxy may connect to destination `port`
+    pub fn is_port_allowed(&self, port: u16) -> bool {
+        !self.is_port_restriction_enabled()
+            || self.allowed_ports.lock().map(|ports| ports.contains(&port)).unwrap_or(false)
+    }
+
+    /// Returns false if the port was already allowed
+    pub fn add_allowed_port(&self, port: u16) -> bool {
+        let added = self.allowed_ports.lock().map(|mut ports| ports.insert(port)).unwrap_or(false);
+        if added {
+            self.append_log(format!("🔢 Allowing port {}", port));
+        }
+        added
+    }
+
+    /// Returns false if the port wasn't allowed
+    pub fn remove_allowed_port(&self, port: u16) -> bool {
+        let removed = self.allowed_ports.lock().map(|mut ports| ports.remove(&port)).unwrap_or(false);
+        if removed {
+            self.append_log(format!("🔢 No longer allowing port {}", port));
+        }
+        removed
+    }
+
+    /// Sorted
+    pub fn get_allowed_ports(&self) -> Vec<u16> {
+        self.allowed_ports.lock().map(|ports| ports.iter().copied().collect()).unwrap_or_default()
+    }
+
+    fn describe_allowed_ports(&self) -> String {
+        self.get_allowed_ports().iter().map(|port| port.to_string()).collect::<Vec<_>>().join(", ")
+    }
+
+    /// Block CONNECTs to the STUN/TURN list and to the standard STUN/TURN
+    /// ports. Off by default since it can break video calls.
+    pub fn set_stun_turn_blocking(&self, enabled: bool) {
+        self.stun_turn_blocking.store(enabled, Ordering::Relaxed);
+        self.append_log(if enabled {
+            "📞 Blocking CONNECTs to STUN/TURN servers".to_string()
+        } else {
+            "📞 Allowing CONNECTs to STUN/TURN servers".to_string()
+        });
+    }
+
+    pub fn is_stun_turn_blocking_enabled(&self) -> bool {
+        self.stun_turn_blocking.load(Ordering::Relaxed)
+    }
+
+    /// Whether a CONNECT to `host:port` should be blocked as STUN/TURN
+    /// traffic. Allowlisted hosts are never blocked.
+    pub fn is_stun_turn_connect(&self, host: &str, port: u16) -> bool {
+        if !self.is_stun_turn_blocking_enabled() || self.is_allowlisted(host) {
+            return false;
+        }
+        STUN_TURN_PORTS.contains(&port)
+            || self.stun_turn_hosts.lock().map(|names| matches_name_list(host, &names)).unwrap_or(false)
+    }
+
+    /// Add a host (and its subdomains) to the STUN/TURN list. Returns false
+    /// if it was already there.
+    pub fn add_stun_turn_host(&self, name: &str) -> Result<bool, String> {
+        let name = list_name(name)?;
+        let mut names = self.stun_turn_hosts.lock().map_err(|_| "STUN/TURN list is unavailable".to_string())?;
+        if names.contains(&name) {
+            return Ok(false);
+        }
+        self.append_log(format!("📞 Added STUN/TURN host {}", name));
+        names.push(name);
+        Ok(true)
+    }
+
+    /// Remove a host from the STUN/TURN list. Returns false if it wasn't there.
+    pub fn remove_stun_turn_host(&self, name: &str) -> bool {
+        let removed = match self.stun_turn_hosts.lock() {
+            Ok(mut names) => {
+                let before = names.len();
+                names.retain(|n| n != name);
+                names.len() != before
+            }
+            Err(_) => false,
+        };
+        if removed {
+            self.append_log(format!("📞 Removed STUN/TURN host {}", name));
+        }
+        removed
+    }
+
+    pub fn get_stun_turn_hosts(&self) -> Vec<String> {
+        self.stun_turn_hosts.lock().map(|names| names.clone()).unwrap_or_default()
+    }
+
+    pub fn set_listen_port(&self, port: u16) {
+        self.listen_port.store(port, Ordering::Relaxed);
+    }
+
+    pub fn get_listen_port(&self) -> u16 {
+        self.listen_port.load(Ordering::Relaxed)
+    }
+
+    /// Name block response headers `{prefix}-Blocked` and `{prefix}-Reason`,
+    /// or leave them out if `prefix` is empty
+    pub fn set_block_header_prefix(&self, prefix: &str) -> Result<(), String> {
+        let prefix = prefix.trim();
+        if !prefix.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(format!("Invalid header prefix: {:?}", prefix));
+        }
+        if let Ok(mut current) = self.block_header_prefix.lock() {
+            *current = prefix.to_string();
+        }
+        Ok(())
+    }
+
+    pub fn get_block_header_prefix(&self) -> String {
+        self.block_header_prefix.lock().map(|prefix| prefix.clone()).unwrap_or_default()
+    }
+
+    /// The `host:port` browsers should use as their proxy
+    pub fn proxy_address(&self) -> String {
+        format!("127.0.0.1:{}", self.get_listen_port())
+    }
+
+    // Log toggle
+    pub fn enable_logging(&self) {
+        if let Ok(mut enabled) = self.log_enabled.lock() {
+            *enabled = true;
+        }
+        self.append_log("📡 Logging enabled".to_string());
+    }
+
+    pub fn disable_logging(&self) {
+        if let Ok(mut enabled) = self.log_enabled.lock() {
+            *enabled = false;
+        }
+        self.append_log("📴 Logging disabled".to_string());
+    }
+
+    pub fn is_logging_enabled(&self) -> bool {
+        self.log_enabled.lock().map(|v| *v).unwrap_or(false)
+    }
+
+    /// Choose whether allowed requests are logged, or only blocks and errors.
+    pub fn set_log_allowed(&self, log_allowed: bool) {
+        self.set_event_logging(LogEvent::Request, log_allowed);
+    }
+
+    pub fn is_allowed_logging_enabled(&self) -> bool {
+        self.is_event_logged(LogEvent::Request)
+    }
+
+    /// Turn logging of one category of events on or off.
+    pub fn set_event_logging(&self, event: LogEvent, enabled: bool) {
+        if let Ok(mut disabled) = self.disabled_log_events.lock() {
+            if enabled {
+                disabled.remove(&event);
+            } else {
+                disabled.insert(event);
+            }
+        }
+        self.append_log(format!(
+            "📡 {} logging {}",
+            event.label(),
+            if enabled { "enabled" } else { "disabled" }
+        ));
+    }
+
+    /// Whether entries for `event` are kept. Tunnel events are off by default.
+    pub fn is_event_logged(&self, event: LogEvent) -> bool {
+        self.disabled_log_events
+            .lock()
+            .map(|disabled| !disabled.contains(&event))
+            .unwrap_or(true)
+    }
+
+    pub fn append_log(&self, entry: String) {
+        self.append_log_entry(LogKind::Info, None, entry);
+    }
+
+    /// Append a log entry about a particular kind of event, optionally tied to a host.
+    pub fn append_log_entry(&self, kind: LogKind, host: Option<&str>, message: String) {
+        self.append_log_entry_with_status(kind, host, message, None);
+    }
+
+    /// Append a request log entry along with the status sent to the client.
+    pub fn append_log_entry_with_status(
+        &self,
+        kind: LogKind,
+        host: Option<&str>,
+        message: String,
+        status: Option<u16>,
+    ) {
+        self.append_log_entry_with_request(kind, host, message, status, None);
+    }
+
+    /// Append a request log entry that keeps the request itself, e.g. for "Copy as curl".
+    pub fn append_log_entry_with_request(
+        &self,
+        kind: LogKind,
+        host: Option<&str>,
+        message: String,
+        status: Option<u16>,
+        request: Option<RequestInfo>,
+    ) {
+        if LogEvent::of_kind(kind).is_some_and(|event| !self.is_event_logged(event)) {
+            return;
+        }
+
+        if self.is_log_batching_enabled() {
+            let entry = self.new_log_entry(kind, host, message, status, request);
+            if let Err(TrySendError::Full(_)) = self.log_queue_sender.try_send(entry) {
+                self.dropped_log_entries.fetch_add(1, Ordering::Relaxed);
+            }
+            return;
+        }
+
+        let echo = self.is_headless().then(|| (self.get_log_format(), self.get_timestamp_format()));
+        let mut logs = match self.logs.lock() {
+            Ok(logs) => logs,
+            Err(_) => return, // Handle poisoned mutex
+        };
+        // Take the id under the lock so ids stay in log order
+        let entry = self.new_log_entry(kind, host, message, status, request);
+        if let Some((format, timestamps)) = &echo {
+            println!("{}", entry.format(*format, timestamps));
+        }
+        logs.push(entry);
+        
+        // Limit log size to prevent memory issues
+        if logs.len() > MAX_LOG_ENTRIES {
+            logs.remove(0); // Remove oldest log
+        }
+    }
+
+    /// A log entry with the next id, truncated to the logged length limits
+    fn new_log_entry(
+        &self,
+        kind: LogKind,
+        host: Option<&str>,
+        message: String,
+        status: Option<u16>,
+        request: Option<RequestInfo>,
+    ) -> LogEntry {
+        LogEntry {
+            id: self.next_log_id.fetch_add(1, Ordering::Relaxed),
+            timestamp: chrono::Local::now(),
+            kind,
+            host: host.map(|h| logging::truncate_for_log(h, logging::MAX_LOGGED_HOST_LEN).into_owned()),
+            message: match logging::truncate_for_log(&message, logging::MAX_LOG_MESSAGE_LEN) {
+                Cow::Borrowed(_) => message,
+                Cow::Owned(truncated) => truncated,
+            },
+            status,
+            request,
+        }
+    }
+
+    /// Queue log appends and move them into the log in batches, leaving the
+    /// request path with a channel send instead of the log lock. Queued
+    /// entries only show up once [`drain_log_queue`](Self::drain_log_queue)
+    /// runs, e.g. every [`LOG_DRAIN_INTERVAL`]. Turning it off drains the
+    /// queue right away.
+    pub fn set_log_batching(&self, enabled: bool) {
+        self.log_batching.store(enabled, Ordering::Relaxed);
+        if !enabled {
+            self.drain_log_queue();
+        }
+    }
+
+    pub fn is_log_batching_enabled(&self) -> bool {
+        self.log_batching.load(Ordering::Relaxed)
+    }
+
+    /// Echo log entries to stdout, for running without a window where the
+    /// console is the only place to read them
+    pub fn set_headless(&self, headless: bool) {
+        self.headless.store(headless, Ordering::Relaxed);
+    }
+
+    pub fn is_headless(&self) -> bool {
+        self.headless.load(Ordering::Relaxed)
+    }
+
+    /// Move queued log entries into the log under a single lock, returning
+    /// how many were moved
+    pub fn drain_log_queue(&self) -> usize {
+        let mut batch: Vec<LogEntry> = match self.log_queue.lock() {
+            Ok(queue) => queue.try_iter().collect(),
+            Err(_) => return 0,
+        };
+        let dropped = self.dropped_log_entries.swap(0, Ordering::Relaxed);
+        if batch.is_empty() && dropped == 0 {
+            return 0;
+        }
+        // Handlers on different threads can send slightly out of order
+        batch.sort_by_key(|entry| entry.id);
+        if dropped > 0 {
+            let message = format!("⚠️ Dropped {} log entries while the log queue was full", dropped);
+            batch.push(self.new_log_entry(LogKind::Error, None, message, None, None));
+        }
+
+        if self.is_headless() {
+            let format = self.get_log_format();
+            let timestamps = self.get_timestamp_format();
+            for entry in &batch {
+                println!("{}", entry.format(format, &timestamps));
+            }
+        }
+        let moved = batch.len();
+        if let Ok(mut logs) = self.logs.lock() {
+            logs.extend(batch);
+            let excess = logs.len().saturating_sub(MAX_LOG_ENTRIES);
+            logs.drain(..excess);
+        }
+        moved
+    }
+
+    /// Log entries formatted with their timestamp
+    pub fn get_logs(&self) -> Vec<String> {
+        let timestamps = self.get_timestamp_format();
+        self.get_log_entries()
+            .iter()
+            .map(|entry| entry.format(LogFormat::Emoji, &timestamps))
+            .collect()
+    }
+
+    /// The last `limit` error entries, newest first
+    pub fn get_recent_errors(&self, limit: usize) -> Vec<LogEntry> {
+        match self.logs.lock() {
+            Ok(logs) => logs
+                .iter()
+                .rev()
+                .filter(|entry| entry.kind == LogKind::Error)
+                .take(limit)
+                .cloned()
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    pub fn get_log_entries(&self) -> Vec<LogEntry> {
+        match self.logs.lock() {
+            Ok(logs) => logs.clone(),
+            Err(_) => vec![], // Return empty vector on error
+        }
+    }
+
+    /// Keep request headers with request log entries.
+    ///
+    /// Off by default since headers can carry cookies and credentials.
+    pub fn set_capture_headers(&self, capture: bool) {
+        if let Ok(mut current) = self.capture_headers.lock() {
+            *current = capture;
+        }
+    }
+
+    pub fn is_capturing_headers(&self) -> bool {
+        self.capture_headers.lock().map(|v| *v).unwrap_or(false)
+    }
+
+    /// Choose how log entries are echoed to stdout. The GUI always shows emoji.
+    pub fn set_log_format(&self, format: LogFormat) {
+        if let Ok(mut current) = self.log_format.lock() {
+            *current = format;
+        }
+    }
+
+    pub fn get_log_format(&self) -> LogFormat {
+        self.log_format.lock().map(|format| *format).unwrap_or_default()
+    }
+
+    /// Set the `strftime` pattern and timezone of log timestamps.
+    /// An invalid pattern leaves the current format in place.
+    pub fn set_timestamp_format(&self, pattern: &str, utc: bool) -> Result<(), String> {
+        let format = TimestampFormat::new(pattern, utc)?;
+        let mut current = self.timestamp_format.lock().map_err(|e| e.to_string())?;
+        *current = format;
+        Ok(())
+    }
+
+    pub fn get_timestamp_format(&self) -> TimestampFormat {
+        self.timestamp_format.lock().map(|format| format.clone()).unwrap_or_default()
+    }
+
+    /// Clear the log, first archiving it if an archive directory is set.
+    ///
+    /// If archiving fails the log is left untouched.
+    pub fn clear_logs(&self) {
+        let archive_dir = self.get_log_archive_dir();
+        let archived = match self.logs.lock() {
+            Ok(mut logs) => {
+                let archived = match &archive_dir {
+                    Some(dir) if !logs.is_empty() => match logging::archive_logs(&logs, dir) {
+                        Ok(path) => Some((logs.len(), path)),
+                        Err(e) => {
+                            drop(logs);
+                            self.append_log_entry(
+                                LogKind::Error,
+                                None,
+                                format!("❌ Failed to archive logs, not clearing them: {}", e),
+                            );
+                            return;
+                        }
+                    },
+                    _ => None,
+                };
+                logs.clear();
+                archived
+            }
+            Err(_) => None,
+        };
+
+        if self.is_clear_unpins_enabled() {
+            self.clear_pinned_logs();
+        }
+        if let Some((count, path)) = archived {
+            self.append_log(format!("📦 Archived {} log entries to {}", count, path.display()));
+        }
+        self.append_log("🧹 Logs cleared".to_string());
+    }
+
+    /// Keep the log entry with `id` in view, regardless of filters and clears
+    pub fn pin_log_entry(&self, id: u64) -> Result<(), String> {
+        let entry = self
+            .logs
+            .lock()
+            .map_err(|_| "Failed to lock logs".to_string())?
+            .iter()
+            .find(|entry| entry.id == id)
+            .cloned()
+            .ok_or_else(|| format!("No log entry {}", id))?;
+        let mut pinned = self.pinned_logs.lock().map_err(|_| "Failed to lock pinned logs".to_string())?;
+        if pinned.iter().any(|pinned| pinned.id == id) {
+            return Ok(());
+        }
+        if pinned.len() >= MAX_PINNED_LOGS {
+            return Err(format!("At most {} entries can be pinned", MAX_PINNED_LOGS));
+        }
+        pinned.push(entry);
+        pinned.sort_by_key(|entry| entry.id);
+        Ok(())
+    }
+
+    pub fn unpin_log_entry(&self, id: u64) {
+        if let Ok(mut pinned) = self.pinned_logs.lock() {
+            pinned.retain(|entry| entry.id != id);
+        }
+    }
+
+    pub fn is_log_entry_pinned(&self, id: u64) -> bool {
+        self.pinned_logs
+            .lock()
+            .map(|pinned| pinned.iter().any(|entry| entry.id == id))
+            .unwrap_or(false)
+    }
+
+    /// Pinned entries, oldest first
+    pub fn get_pinned_logs(&self) -> Vec<LogEntry> {
+        self.pinned_logs.lock().map(|pinned| pinned.clone()).unwrap_or_default()
+    }
+
+    pub fn clear_pinned_logs(&self) {
+        if let Ok(mut pinned) = self.pinned_logs.lock() {
+            pinned.clear();
+        }
+    }
+
+    /// Unpin everything when the log is cleared, rather than keeping pins
+    pub fn set_clear_unpins(&self, enabled: bool) {
+        self.clear_unpins.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn is_clear_unpins_enabled(&self) -> bool {
+        self.clear_unpins.load(Ordering::Relaxed)
+    }
+
+    /// Archive logs to `dir` whenever they are cleared, or don't archive if `None`.
+    pub fn set_log_archive_dir(&self, dir: Option<PathBuf>) {
+        if let Ok(mut archive_dir) = self.log_archive_dir.lock() {
+            *archive_dir = dir;
+        }
+    }
+
+    pub fn get_log_archive_dir(&self) -> Option<PathBuf> {
+        self.log_archive_dir.lock().ok().and_then(|dir| dir.clone())
+    }
+
+    // System proxy methods
+
+    /// Point the operating system proxy at `addr`, remembering what it replaced.
+    pub fn enable_system_proxy(&self, addr: &str) -> Result<(), String> {
+        let previous = system_proxy::enable(addr)
+            .map_err(|e| format!("Failed to configure system proxy: {}", e))?;
+
+        self.append_log(format!("🖥️ System proxy set to {} (previous setting: {})", addr, previous));
+
+        if let Ok(mut backup) = self.system_proxy_backup.lock() {
+            // Keep the user's original settings if we were already configured
+            if backup.is_none() {
+                *backup = Some(previous);
+            }
+        }
+        Ok(())
+    }
+
+    /// Put back the system proxy settings replaced by `enable_system_proxy`.
+    /// Does nothing if the system proxy was never changed.
+    pub fn restore_system_proxy(&self) -> Result<(), String> {
+        let previous = match self.system_proxy_backup.lock() {
+            Ok(mut backup) => backup.take(),
+            Err(_) => return Err("Failed to lock system proxy backup".to_string()),
+        };
+
+        if let Some(previous) = previous {
+            if let Err(e) = system_proxy::restore(&previous) {
+                self.append_log(format!(
+                    "❌ Failed to restore system proxy, please reset it manually to: {} ({})",
+                    previous, e
+                ));
+                if let Ok(mut backup) = self.system_proxy_backup.lock() {
+                    *backup = Some(previous);
+                }
+                return Err(format!("Failed to restore system proxy: {}", e));
+            }
+            self.append_log(format!("🖥️ System proxy restored ({})", previous));
+        }
+        Ok(())
+    }
+
+    pub fn is_system_proxy_configured(&self) -> bool {
+        self.system_proxy_backup.lock().map(|b| b.is_some()).unwrap_or(false)
+    }
+
+    // Tunnel limit methods
+
+    /// Reserve a slot for a new CONNECT tunnel.
+    ///
+    /// Returns `false` if `max_tunnels` are already open. Every successful
+    /// call must be paired with [`release_tunnel`](Self::release_tunnel).
+    pub fn try_acquire_tunnel(&self) -> bool {
+        let max = self.get_max_tunnels();
+        self.active_tunnels
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |active| {
+                (active < max).then_some(active + 1)
+            })
+            .is_ok()
+    }
+
+    /// Free the slot taken by [`try_acquire_tunnel`](Self::try_acquire_tunnel).
+    pub fn release_tunnel(&self) {
+        let _ = self.active_tunnels.fetch_update(Ordering::AcqRel, Ordering::Acquire, |active| {
+            Some(active.saturating_sub(1))
+        });
+    }
+
+    pub fn get_active_tunnels(&self) -> usize {
+        self.active_tunnels.load(Ordering::Acquire)
+    }
+
+    pub fn set_max_tunnels(&self, max: usize) {
+        self.max_tunnels.store(max.max(1), Ordering::Release);
+    }
+
+    pub fn get_max_tunnels(&self) -> usize {
+        self.max_tunnels.load(Ordering::Acquire)
+    }
+
+    /// Read the headers of third-party image responses to spot tracking pixels.
+    pub fn set_response_inspection(&self, enabled: bool) {
+        if let Ok(mut current) = self.response_inspection.lock() {
+            *current = enabled;
+        }
+    }
+
+    pub fn is_response_inspection_enabled(&self) -> bool {
+        self.response_inspection.lock().map(|v| *v).unwrap_or(false)
+    }
+
+    /// Strip tracking parameters from all requests, rather than only safe
+    /// (GET and HEAD) ones where the query can't be part of a form action.
+    pub fn set_strip_params_all_methods(&self, all: bool) {
+        if let Ok(mut current) = self.strip_params_all_methods.lock() {
+            *current = all;
+        }
+    }
+
+    pub fn is_stripping_params_on_all_methods(&self) -> bool {
+        self.strip_params_all_methods.lock().map(|v| *v).unwrap_or(false)
+    }
+
+    /// Send the same User-Agent and Accept-Language on every forwarded
+    /// request. Off by default since some sites gate on the User-Agent.
+    pub fn set_header_normalization(&self, enabled: bool) {
+        self.header_normalization.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn is_header_normalization_enabled(&self) -> bool {
+        self.header_normalization.load(Ordering::Relaxed)
+    }
+
+    /// User-Agent to send while normalization is on. Empty removes the header.
+    pub fn set_normalized_user_agent(&self, value: &str) {
+        if let Ok(mut current) = self.normalized_user_agent.lock() {
+            *current = value.trim().to_string();
+        }
+    }
+
+    pub fn get_normalized_user_agent(&self) -> String {
+        self.normalized_user_agent.lock().map(|v| v.clone()).unwrap_or_default()
+    }
+
+    /// Accept-Language to send while normalization is on. Empty removes the header.
+    pub fn set_normalized_accept_language(&self, value: &str) {
+        if let Ok(mut current) = self.normalized_accept_language.lock() {
+            *current = value.trim().to_string();
+        }
+    }
+
+    pub fn get_normalized_accept_language(&self) -> String {
+        self.normalized_accept_language.lock().map(|v| v.clone()).unwrap_or_default()
+    }
+
+    /// Try cleartext HTTP/2 (h2c) for forwarded requests, falling back to HTTP/1.1.
+    ///
+    /// Changing the setting forgets which upstreams were found to support it.
+    pub fn set_upstream_http2(&self, enabled: bool) {
+        if let Ok(mut current) = self.upstream_http2.lock() {
+            *current = enabled;
+        }
+        if let Ok(mut support) = self.upstream_h2c_support.lock() {
+            support.clear();
+        }
+    }
+
+    pub fn is_upstream_http2_enabled(&self) -> bool {
+        self.upstream_http2.lock().map(|v| *v).unwrap_or(false)
+    }
+
+    /// Whether `addr` is known to speak h2c, or `None` if it hasn't been probed
+    pub fn upstream_h2c_support(&self, addr: &str) -> Option<bool> {
+        self.upstream_h2c_support.lock().ok()?.get(addr).copied()
+    }
+
+    pub fn record_upstream_h2c_support(&self, addr: &str, supported: bool) {
+        if let Ok(mut support) = self.upstream_h2c_support.lock() {
+            // Upstreams are only probed again after a reset, so this is cheap
+            if support.len() >= MAX_PROBED_UPSTREAMS && !support.contains_key(addr) {
+                support.clear();
+            }
+            support.insert(addr.to_string(), supported);
+        }
+    }
+
+    /// Start tracking a connection. Returns its id and the counter to add
+    /// transferred bytes to.
+    pub fn open_connection(&self, kind: ConnectionKind, host: &str) -> (u64, Arc<AtomicU64>) {
+        let id = self.next_connection_id.fetch_add(1, Ordering::Relaxed);
+        let bytes = Arc::new(AtomicU64::new(0));
+        if let Ok(mut connections) = self.connections.lock() {
+            connections.insert(id, OpenConnection {
+                kind,
+                host: host.to_string(),
+                started: Utc::now(),
+                bytes: Arc::clone(&bytes),
+                abort: None,
+            });
+        }
+        (id, bytes)
+    }
+
+    /// Attach the handle `close_connection` uses to stop the connection's task.
+    pub fn set_connection_abort(&self, id: u64, abort: AbortHandle) {
+        if let Ok(mut connections) = self.connections.lock() {
+            if let Some(connection) = connections.get_mut(&id) {
+                connection.abort = Some(abort);
+            }
+        }
+    }
+
+    /// Stop tracking a connection that has ended.
+    pub fn connection_closed(&self, id: u64) {
+        if let Ok(mut connections) = self.connections.lock() {
+            connections.remove(&id);
+        }
+    }
+
+    /// Force a connection closed. Returns false if it had already ended.
+    pub fn close_connection(&self, id: u64) -> bool {
+        let connection = match self.connections.lock() {
+            Ok(mut connections) => connections.remove(&id),
+            Err(_) => None,
+        };
+        match connection {
+            Some(connection) => {
+                if let Some(abort) = connection.abort {
+                    abort.abort();
+                }
+                self.append_log_entry(
+                    LogKind::Info,
+                    Some(&connection.host),
+                    format!("✂️ Closed connection to {}", connection.host),
+                );
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Open connections, oldest first
+    pub fn get_connections(&self) -> Vec<ConnectionInfo> {
+        let mut connections: Vec<ConnectionInfo> = match self.connections.lock() {
+            Ok(connections) => connections
+                .iter()
+                .map(|(id, connection)| ConnectionInfo {
+                    id: *id,
+                    kind: connection.kind,
+                    host: connection.host.clone(),
+                    started: connection.started,
+                    bytes: connection.bytes.load(Ordering::Relaxed),
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        connections.sort_by_key(|connection| connection.id);
+        connections
+    }
+
+    /// Limit how many headers a request or upstream response may carry.
+    pub fn set_max_header_count(&self, max: usize) {
+        self.max_header_count.store(max.clamp(1, MAX_HEADER_COUNT_LIMIT), Ordering::Relaxed);
+    }
+
+    pub fn get_max_header_count(&self) -> usize {
+        self.max_header_count.load(Ordering::Relaxed)
+    }
+
+    /// Limit the total size of a request's or upstream response's headers.
+    pub fn set_max_header_bytes(&self, max: usize) {
+        self.max_header_bytes.store(max.max(1), Ordering::Relaxed);
+    }
+
+    pub fn get_max_header_bytes(&self) -> usize {
+        self.max_header_bytes.load(Ordering::Relaxed)
+    }
+
+    // Method to track bandwidth
+    pub fn track_bandwidth(&self, bytes: u64, blocked: bool) {
+        if blocked {
+            self.bandwidth_saved.fetch_add(bytes, Ordering::Relaxed);
+        }
+    }
+
+    // Method to get total bandwidth saved
+    pub fn get_bandwidth_saved(&self) -> u64 {
+        self.bandwidth_saved.load(Ordering::Relaxed)
+    }
+
+    /// Rough load time avoided by blocking: the saved bytes at `speed_mbps`,
+    /// plus one round trip per blocked request
+    pub fn estimated_time_saved(&self, speed_mbps: u32) -> Duration {
+        let bits = self.get_bandwidth_saved() as f64 * 8.0;
+        let transfer = bits / (speed_mbps.max(1) as f64 * 1_000_000.0);
+        let latency = self.get_blocked_count() as f64 * ASSUMED_ROUND_TRIP.as_secs_f64();
+        Duration::from_secs_f64(transfer + latency)
+    }
+    
+    
+    // Statistics methods
+    
+    pub fn record_request(&self, domain: &str, blocked: bool) {
+        // Update domain stats
+        if let Ok(mut stats) = self.stats.lock() {
+            let entry = stats.entry(domain.to_string()).or_insert_with(|| DomainStat {
+                domain: domain.to_string(),
+                requests: 0,
+                blocked: 0,
+                last_seen: Utc::now(),
+                bandwidth_saved: Arc::new(Mutex::new(0)), 
+                request_bytes: 0,
+                response_bytes: 0,
+            });
+            
+            entry.requests += 1;
+            if blocked {
+                entry.blocked += 1;
+            }
+            entry.last_seen = Utc::now();
+        }
+        
+        // Update global counters
+        if blocked {
+            self.blocked_count.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.allowed_count.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    
+    /// Count requests by path as well as by host
+    pub fn set_path_stats_enabled(&self, enabled: bool) {
+        self.path_stats_enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn is_path_stats_enabled(&self) -> bool {
+        self.path_stats_enabled.load(Ordering::Relaxed)
+    }
+
+    /// Count a request for `path` on `host`, if path statistics are on
+    pub fn record_path(&self, host: &str, path: &str, blocked: bool) {
+        if !self.is_path_stats_enabled() {
+            return;
+        }
+        let path = normalize_stat_path(path);
+        let Ok(mut paths) = self.path_stats.lock() else {
+            return;
+        };
+        if !paths.contains_key(&path) && paths.len() >= MAX_PATH_STATS {
+            return;
+        }
+        let stat = paths.entry(path.clone()).or_insert_with(|| PathStat {
+            path,
+            requests: 0,
+            blocked: 0,
+            hosts: BTreeSet::new(),
+        });
+        stat.requests += 1;
+        if blocked {
+            stat.blocked += 1;
+        }
+        if stat.hosts.len() < MAX_PATH_HOSTS {
+            stat.hosts.insert(host.to_lowercase());
+        }
+    }
+
+    /// The `limit` most requested paths, busiest first
+    pub fn get_top_paths(&self, limit: usize) -> Vec<PathStat> {
+        let Ok(paths) = self.path_stats.lock() else {
+            return Vec::new();
+        };
+        let mut top: Vec<&PathStat> = paths.values().collect();
+        top.sort_by(|a, b| b.requests.cmp(&a.requests).then_with(|| a.path.cmp(&b.path)));
+        top.into_iter().take(limit).cloned().collect()
+    }
+
+    /// Add body bytes sent to and received from `domain` to its statistics.
+    pub fn record_transfer(&self, domain: &str, request_bytes: u64, response_bytes: u64) {
+        if let Ok(mut stats) = self.stats.lock() {
+            if let Some(entry) = stats.get_mut(domain) {
+                entry.request_bytes += request_bytes;
+                entry.response_bytes += response_bytes;
+            }
+        }
+    }
+    
+    pub fn get_stats(&self) -> HashMap<String, DomainStat> {
+        match self.stats.lock() {
+            Ok(stats) => stats.clone(),
+            Err(_) => HashMap::new(), // Return empty map on error
+        }
+    }
+    
+    /// Remove domains not seen within `older_than`, returning how many were removed.
+    pub fn prune_stats(&self, older_than: chrono::Duration) -> usize {
+        let cutoff = Utc::now() - older_than;
+        match self.stats.lock() {
+            Ok(mut stats) => {
+                let before = stats.len();
+                stats.retain(|_, stat| stat.last_seen >= cutoff);
+                before - stats.len()
+            }
+            Err(_) => 0,
+        }
+    }
+
+    /// Forget domains idle for longer than `ttl`, or never if `None`.
+    pub fn set_stats_ttl(&self, ttl: Option<chrono::Duration>) {
+        let secs = ttl.map(|ttl| ttl.num_seconds().max(1) as u64).unwrap_or(0);
+        self.stats_ttl_secs.store(secs, Ordering::Relaxed);
+    }
+
+    pub fn get_stats_ttl(&self) -> Option<chrono::Duration> {
+        match self.stats_ttl_secs.load(Ordering::Relaxed) {
+            0 => None,
+            secs => Some(chrono::Duration::seconds(secs as i64)),
+        }
+    }
+
+    /// Apply the configured TTL with `prune_stats`, logging what was removed.
+    pub fn prune_expired_stats(&self) {
+        if let Some(ttl) = self.get_stats_ttl() {
+            let pruned = self.prune_stats(ttl);
+            if pruned > 0 {
+                self.append_log(format!("🧹 Pruned {} idle domains from statistics", pruned));
+            }
+        }
+    }
+    
+    /// Domain statistics with subdomains grouped under their registrable
+    /// domain, busiest group first. This is for display only.
+    pub fn get_grouped_stats(&self) -> Vec<DomainGroup> {
+        let mut groups: HashMap<String, DomainGroup> = HashMap::new();
+        
+        for stat in self.get_stats().into_values() {
+            let key = public_suffix::registrable_domain(&stat.domain)
+                .unwrap_or_else(|| stat.domain.clone());
+            
+            let group = groups.entry(key.clone()).or_insert_with(|| DomainGroup {
+                domain: key,
+                requests: 0,
+                blocked: 0,
+                last_seen: stat.last_seen,
+                data_transferred: 0,
+                hosts: Vec::new(),
+            });
+            
+            group.requests += stat.requests;
+            group.data_transferred += stat.data_transferred();
+            group.blocked += stat.blocked;
+            group.last_seen = group.last_seen.max(stat.last_seen);
+            group.hosts.push(stat);
+        }
+        
+        let mut groups: Vec<DomainGroup> = groups.into_values().collect();
+        for group in &mut groups {
+            group.hosts.sort_by(|a, b| b.requests.cmp(&a.requests).then_with(|| a.domain.cmp(&b.domain)));
+        }
+        groups.sort_by(|a, b| b.requests.cmp(&a.requests).then_with(|| a.domain.cmp(&b.domain)));
+        groups
+    }
+    
+    /// Record a request to third-party `host` made from a page on `site`.
+    pub fn record_site_contact(&self, site: &str, host: &str, blocked: bool) {
+        let Ok(mut sites) = self.site_contacts.lock() else {
+            return;
+        };
+        if !sites.contains_key(site) && sites.len() >= MAX_SUMMARY_SITES {
+            return;
+        }
+        let contact = sites
+            .entry(site.to_string())
+            .or_default()
+            .entry(host.to_string())
+            .or_insert_with(|| SiteContact { host: host.to_string(), requests: 0, blocked: 0 });
+        contact.requests += 1;
+        if blocked {
+            contact.blocked += 1;
+        }
+    }
+    
+    /// Composite 0-100 score from the blocklist size, AI detection, parameter
+    /// stripping, the block rate and how many third-party requests are blocked.
+    ///
+    /// Rates count as met before there's any traffic to measure.
+    pub fn get_protection_score(&self) -> ProtectionScore {
+        let scaled = |value: f64, full: f64, max: u32| ((value / full).min(1.0) * max as f64).round() as u32;
+
+        let blocklist_size = self.blocker.read().map(|blocker| blocker.len()).unwrap_or(0);
+        let blocked = self.get_blocked_count();
+        let total = blocked + self.get_allowed_count();
+        let block_rate = if total == 0 { 1.0 } else { blocked as f64 / total as f64 };
+        let (third_party, third_party_blocked) = self
+            .get_site_summary()
+            .iter()
+            .fold((0, 0), |(requests, blocked), site| (requests + site.requests, blocked + site.blocked));
+        let third_party_rate = if third_party == 0 {
+            1.0
+        } else {
+            third_party_blocked as f64 / third_party as f64
+        };
+
+        let factors = vec![
+            ScoreFactor {
+                name: "Blocklist size",
+                points: scaled(blocklist_size as f64, FULL_SCORE_BLOCKLIST_SIZE as f64, 25),
+                max: 25,
+                hint: "Add a preset or import a larger tracker list",
+            },
+            ScoreFactor {
+                name: "AI detection",
+                points: if self.is_ai_detection_enabled() { 20 } else { 0 },
+                max: 20,
+                hint: "Turn on AI detection to catch trackers missing from the list",
+            },
+            ScoreFactor {
+                name: "Parameter stripping",
+                points: if self.is_stripping_params_on_all_methods() { 15 } else { 8 },
+                max: 15,
+                hint: "Strip tracking parameters from form submissions too",
+            },
+            ScoreFactor {
+                name: "Block rate",
+                points: scaled(block_rate, FULL_SCORE_BLOCK_RATE, 20),
+                max: 20,
+                hint: "Few requests are blocked; make sure blocking is on and the list is current",
+            },
+            ScoreFactor {
+                name: "Third-party requests blocked",
+                points: scaled(third_party_rate, FULL_SCORE_THIRD_PARTY_BLOCK_RATE, 20),
+                max: 20,
+                hint: "Review the Sites Visited list and block third parties you don't need",
+            },
+        ];
+        ProtectionScore { score: factors.iter().map(|f| f.points).sum(), factors }
+    }
+
+    /// Sites visited this session with the third parties each contacted,
+    /// the site with the most blocked requests first.
+    pub fn get_site_summary(&self) -> Vec<SiteSummary> {
+        let Ok(sites) = self.site_contacts.lock() else {
+            return Vec::new();
+        };
+        let mut summary: Vec<SiteSummary> = sites
+            .iter()
+            .map(|(site, contacts)| {
+                let mut contacts: Vec<SiteContact> = contacts.values().cloned().collect();
+                contacts.sort_by(|a, b| {
+                    b.blocked.cmp(&a.blocked)
+                        .then_with(|| b.requests.cmp(&a.requests))
+                        .then_with(|| a.host.cmp(&b.host))
+                });
+                SiteSummary {
+                    site: site.clone(),
+                    requests: contacts.iter().map(|c| c.requests).sum(),
+                    blocked: contacts.iter().map(|c| c.blocked).sum(),
+                    contacts,
+                }
+            })
+            .collect();
+        summary.sort_by(|a, b| b.blocked.cmp(&a.blocked).then_with(|| a.site.cmp(&b.site)));
+        summary
+    }
+    
+    pub fn get_allowed_count(&self) -> usize {
+        self.allowed_count.load(Ordering::Relaxed)
+    }
+    
+    pub fn get_blocked_count(&self) -> usize {
+        self.blocked_count.load(Ordering::Relaxed)
+    }
+    
+    /// Start a new session: session counts are measured from the current totals.
+    /// Call this at startup once any saved statistics have been loaded.
+    pub fn mark_session_start(&self) {
+        self.session_allowed_baseline.store(self.get_allowed_count(), Ordering::Relaxed);
+        self.session_blocked_baseline.store(self.get_blocked_count(), Ordering::Relaxed);
+        if let Ok(mut sites) = self.site_contacts.lock() {
+            sites.clear();
+        }
+        self.invalidate_dashboard_stats();
+    }
+
+    /// Recompute the Dashboard aggregates and cache them
+    pub fn refresh_dashboard_stats(&self) -> Arc<DashboardStats> {
+        let stats = Arc::new(DashboardStats {
+            computed_at: Utc::now(),
+            domains: self.get_stats().into_values().collect(),
+            groups: self.get_grouped_stats(),
+            sites: self.get_site_summary(),
+            protection: self.get_protection_score(),
+            top_paths: self.get_top_paths(TOP_PATHS_SHOWN),
+        });
+        if let Ok(mut cached) = self.dashboard_stats.write() {
+            *cached = Some(Arc::clone(&stats));
+        }
+        stats
+    }
+
+    /// The cached Dashboard aggregates, computed now if there are none yet.
+    /// They lag by up to [`DASHBOARD_STATS_INTERVAL`] while a refresher runs.
+    pub fn get_cached_dashboard_stats(&self) -> Arc<DashboardStats> {
+        let cached = self.dashboard_stats.read().ok().and_then(|cached| cached.clone());
+        cached.unwrap_or_else(|| self.refresh_dashboard_stats())
+    }
+
+    fn invalidate_dashboard_stats(&self) {
+        if let Ok(mut cached) = self.dashboard_stats.write() {
+            *cached = None;
+        }
+    }
+    
+    /// Allowed requests since the session started
+    pub fn get_session_allowed_count(&self) -> usize {
+        let baseline = self.session_allowed_baseline.load(Ordering::Relaxed);
+        self.get_allowed_count().saturating_sub(baseline)
+    }
+    
+    /// Blocked requests since the session started
+    pub fn get_session_blocked_count(&self) -> usize {
+        let baseline = self.session_blocked_baseline.load(Ordering::Relaxed);
+        self.get_blocked_count().saturating_sub(baseline)
+    }
+    
+    pub fn reset_stats(&self) {
+        if let Ok(mut stats) = self.stats.lock() {
+            stats.clear();
+        }
+        if let Ok(mut paths) = self.path_stats.lock() {
+            paths.clear();
+        }
+        
+        self.allowed_count.store(0, Ordering::Relaxed);
+        self.blocked_count.store(0, Ordering::Relaxed);
+        
+        self.mark_session_start();
+        
+        self.append_log("📊 Statistics reset".to_string());
+    }
+    
+    // Tracker management methods
+    
+    pub fn add_tracker(&self, domain: &str) -> Result<(), String> {
+        if let Ok(mut blocker) = self.blocker.write() {
+            match blocker.add(domain) {
+                Ok(()) => {
+                    self.invalidate_verdict_cache();
+                    self.append_log_entry(LogKind::Info, Some(domain), format!("➕ Added tracker: {}", domain));
+                    Ok(())
+                },
+                Err(e) => Err(format!("Failed to add tracker: {}", e)),
+            }
+        } else {
+            Err("Failed to lock blocker".to_string())
+        }
+    }
+    
+    /// Merge a built-in preset into the blocklist, returning how many
+    /// domains were new.
+    pub fn apply_preset(&self, preset: &Preset) -> Result<usize, String> {
+        let added = self
+            .blocker
+            .write()
+            .map_err(|_| "Failed to lock blocker".to_string())?
+            .merge(&preset.domains())
+            .map_err(|e| format!("Failed to add preset: {}", e))?
+            .added
+            .len();
+        self.invalidate_verdict_cache();
+        self.append_log(format!("📦 Added {} trackers from the {} preset", added, preset.name));
+        Ok(added)
+    }
+    
+    /// Whether the blocklist has no entries, so nothing is blocked by it
+    pub fn is_blocklist_empty(&self) -> bool {
+        self.blocker.read().map(|blocker| blocker.is_empty()).unwrap_or(false)
+    }
+
+    /// Fill the blocklist with the default preset if it's empty, returning
+    /// whether it was.
+    pub fn seed_empty_blocklist(&self) -> Result<bool, String> {
+        if !self.is_blocklist_empty() {
+            return Ok(false);
+        }
+        let preset = default_preset();
+        self.apply_preset(preset)?;
+        self.append_log(format!("🌱 Blocklist was empty; filled it from the {} preset", preset.name));
+        Ok(true)
+    }
+
+    /// Match the candidate blocklist `content` against the hosts in the
+    /// statistics, without changing the live blocklist
+    pub fn simulate_blocklist(&self, content: &str) -> BlocklistSimulation {
+        let candidate = parse_tracker_list(content);
+        let seen: Vec<(String, DomainStat)> = self
+            .get_stats()
+            .into_values()
+            .map(|stat| (normalize_host(&stat.domain), stat))
+            .filter(|(host, _)| matching_entry(&candidate.trackers, host).is_some())
+            .collect();
+
+        let subdomains = self.is_allowlist_subdomains_enabled();
+        let allowlist: HashSet<String> = self.get_allowlist().into_iter().collect();
+        let mut newly_blocked = Vec::new();
+        let mut already_blocked = 0;
+        let blocker = self.blocker.read().ok();
+        for (host, stat) in seen {
+            // explain_match rather than contains, which logs every lookup
+            let blocked_now = blocker.as_ref().is_some_and(|blocker| blocker.explain_match(&host).is_some());
+            if blocked_now {
+                already_blocked += 1;
+            } else if !allowlist_matches(&allowlist, &host, subdomains) {
+                newly_blocked.push(stat);
+            }
+        }
+        drop(blocker);
+        newly_blocked.sort_by(|a, b| b.requests.cmp(&a.requests).then_with(|| a.domain.cmp(&b.domain)));
+
+        let mut allowlist_overlaps: Vec<String> = allowlist
+            .into_iter()
+            .filter(|entry| {
+                matching_entry(&candidate.trackers, entry).is_some()
+                    || (subdomains && candidate.trackers.iter().any(|tracker| {
+                        tracker.strip_suffix(entry.as_str()).is_some_and(|prefix| prefix.ends_with('.'))
+                    }))
+            })
+            .collect();
+        allowlist_overlaps.sort();
+
+        BlocklistSimulation {
+            candidate_size: candidate.trackers.len(),
+            malformed: candidate.skipped.len(),
+            newly_blocked,
+            already_blocked,
+            allowlist_overlaps,
+        }
+    }
+
+    /// Merge a tracker list file in any supported format into the blocklist,
+    /// skipping malformed lines
+    pub fn import_trackers(&self, path: &Path) -> Result<ImportDiff, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let parsed = parse_list(&content);
+        let domains: Vec<String> = parsed.trackers.into_iter().collect();
+        let mut diff = self
+            .blocker
+            .write()
+            .map_err(|_| "Failed to lock blocker".to_string())?
+            .merge(&domains)
+            .map_err(|e| format!("Failed to import {}: {}", path.display(), e))?;
+        diff.malformed = parsed.skipped.len();
+        diff.ignored = parsed.ignored;
+        diff.format = parsed.format;
+        self.invalidate_verdict_cache();
+        self.append_log(format!("📥 Imported {} as a {}: {}", path.display(), diff.format, diff.summary()));
+        Ok(diff)
+    }
+
+    /// Write the blocklist to `path` as a plain tracker list, returning how
+    /// many domains were written
+    pub fn export_trackers(&self, path: &Path) -> Result<usize, String> {
+        let trackers = self.get_trackers()?;
+        let domains: Vec<&str> = trackers.iter().map(String::as_str).collect();
+        write_tracker_export(path, &domains)
+            .map_err(|e| format!("Failed to export to {}: {}", path.display(), e))?;
+        self.append_log(format!("📤 Exported {} domains to {}", domains.len(), path.display()));
+        Ok(domains.len())
+    }
+
+    pub fn remove_tracker(&self, domain: &str) -> Result<(), String> {
+        if let Ok(mut blocker) = self.blocker.write() {
+            match blocker.remove(domain) {
+                Ok(()) => {
+                    self.invalidate_verdict_cache();
+                    self.append_log_entry(LogKind::Info, Some(domain), format!("➖ Removed tracker: {}", domain));
+                    Ok(())
+                },
+                Err(e) => Err(format!("Failed to remove tracker: {}", e)),
+            }
+        } else {
+            Err("Failed to lock blocker".to_string())
+        }
+    }
+    
+    /// Check whether `host` is on the blocklist. If the blocker can't be
+    /// locked, the answer follows the fail mode (see [`set_fail_closed`](Self::set_fail_closed)).
+    ///
+    /// Verdicts are cached per host so repeat requests skip list matching.
+    pub fn check_blocked(&self, host: &str) -> bool {
+        self.lookup_blocked(host).unwrap_or_else(|| self.is_fail_closed())
+    }
+
+    /// The blocklist entry that blocks `host`: the host itself or the
+    /// parent domain it's listed under.
+    pub fn blocking_entry(&self, host: &str) -> Option<String> {
+        self.explain_match(host).map(|explanation| explanation.rule)
+    }
+
+    /// Which blocklist entry blocks `host` and how, or `None` if it's allowed
+    pub fn explain_match(&self, host: &str) -> Option<MatchExplanation> {
+        self.blocker.read().ok()?.explain_match(&normalize_host(host))
+    }
+
+    /// List status of each of `hosts`, in order, taking each list's lock
+    /// once for the whole batch rather than once per host. Hosts are
+    /// matched the way requests are, including temporary allows and
+    /// registrable-domain matching.
+    pub fn classify_hosts(&self, hosts: &[String]) -> Vec<HostVerdict> {
+        // Expiring logs, so do it before any list is locked
+        self.expire_temporary_allows();
+        let registrable = self.is_registrable_matching_enabled();
+        let subdomains = self.is_allowlist_subdomains_enabled();
+        let blocker = self.blocker.read().ok();
+        let allowlist = self.allowlist.lock().ok();
+        let grants = self.temporary_allows.lock().ok();
+        let mut sites = if registrable { self.blocked_sites.lock().ok() } else { None };
+        hosts
+            .iter()
+            .map(|host| {
+                let normalized = normalize_host(host);
+                let rule = blocker.as_ref().and_then(|blocker| blocker.explain_match(&normalized));
+                let allowlisted = allowlist
+                    .as_ref()
+                    .is_some_and(|allowlist| allowlist_matches(allowlist, &normalized, subdomains));
+                let blocked = match (&blocker, sites.as_mut()) {
+                    (Some(blocker), Some(sites)) => site_is_listed(sites, &***blocker, &normalized),
+                    _ => rule.is_some(),
+                };
+                let status = if allowlisted {
+                    HostStatus::Allowlisted
+                } else if grants.as_ref().is_some_and(|grants| grants.contains_key(&normalized)) {
+                    HostStatus::TemporarilyAllowed
+                } else if blocked {
+                    HostStatus::Blocked
+                } else {
+                    HostStatus::Unlisted
+                };
+                HostVerdict { host: host.clone(), status, rule }
+            })
+            .collect()
+    }
+
+    /// Whether `host` is on the blocklist, or `None` if the blocker can't be locked
+    fn lookup_blocked(&self, host: &str) -> Option<bool> {
+        let key = normalize_host(host);
+        if let Some(blocked) = self.verdict_cache.lock().ok().and_then(|cache| cache.get(&key).copied()) {
+            self.verdict_cache_hits.fetch_add(1, Ordering::Relaxed);
+            return Some(blocked);
+        }
+        self.verdict_cache_misses.fetch_add(1, Ordering::Relaxed);
+
+        match self.blocker.read() {
+            Ok(blocker) => {
+                let blocked = if self.is_registrable_matching_enabled() {
+                    self.matches_registrable_domain(&**blocker, &key)
+                } else {
+                    blocker.contains(&key)
+                };
+                // Insert while still holding the read lock so a concurrent
+                // list change can't be overwritten by this stale verdict.
+                if let Ok(mut cache) = self.verdict_cache.lock() {
+                    if cache.len() >= VERDICT_CACHE_CAPACITY {
+                        cache.clear();
+                    }
+                    cache.insert(key, blocked);
+                }
+                Some(blocked)
+            }
+            Err(e) => {
+                let action = if self.is_fail_closed() { "blocking" } else { "allowing" };
+                eprintln!("🚨 Failed to lock blocker: {:?}", e);
+                self.append_log_entry(
+                    LogKind::Error,
+                    Some(host),
+                    format!("🚨 Blocklist unavailable, {} {}: {:?}", action, host, e),
+                );
+                None
+            }
+        }
+    }
+
+    /// Block requests when the blocklist can't be checked, instead of
+    /// letting them through unprotected.
+    pub fn set_fail_closed(&self, enabled: bool) {
+        self.fail_closed.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn is_fail_closed(&self) -> bool {
+        self.fail_closed.load(Ordering::Relaxed)
+    }
+
+    fn invalidate_verdict_cache(&self) {
+        if let Ok(mut cache) = self.verdict_cache.lock() {
+            cache.clear();
+        }
+        if let Ok(mut sites) = self.blocked_sites.lock() {
+            *sites = None;
+        }
+    }
+
+    /// Match whole sites rather than host suffixes: a listed domain blocks
+    /// every host with the same registrable domain (eTLD+1), and entries
+    /// that are themselves public suffixes, like `co.uk`, block nothing.
+    pub fn set_registrable_matching(&self, enabled: bool) {
+        self.registrable_matching.store(enabled, Ordering::Relaxed);
+        self.invalidate_verdict_cache();
+        self.append_log(if enabled {
+            "🌐 Blocklist entries match their whole registrable domain".to_string()
+        } else {
+            "🌐 Blocklist entries match themselves and their subdomains".to_string()
+        });
+    }
+
+    pub fn is_registrable_matching_enabled(&self) -> bool {
+        self.registrable_matching.load(Ordering::Relaxed)
+    }
+
+    /// Whether `host` shares a registrable domain with a listed entry.
+    /// Hosts without one, such as IP addresses, are matched as usual.
+    fn matches_registrable_domain(&self, blocker: &dyn BlocklistSource, host: &str) -> bool {
+        match self.blocked_sites.lock() {
+            Ok(mut sites) => site_is_listed(&mut sites, blocker, host),
+            Err(_) => blocker.contains(host),
+        }
+    }
+
+    /// Verdict cache hits and misses since startup
+    pub fn get_verdict_cache_stats(&self) -> (u64, u64) {
+        (
+            self.verdict_cache_hits.load(Ordering::Relaxed),
+            self.verdict_cache_misses.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Strip tracking parameters from `url`, returning it unchanged if it can't be parsed.
+    pub fn clean_url(&self, url: &str) -> String {
+        self.url_cleaner.clean_url(url)
+    }
+
+    /// Swap in a different tracker list, e.g. one picked during setup.
+    pub fn replace_blocker(&self, mut blocker: impl BlocklistSource + 'static) -> Result<(), String> {
+        let count = blocker.len();
+        if let Ok(mut current) = self.blocker.write() {
+            // IP ranges aren't stored with the list, so they carry over
+            for cidr in current.blocked_cidrs() {
+                let _ = blocker.add_blocked_cidr(&cidr);
+            }
+            *current = Box::new(blocker);
+            self.invalidate_verdict_cache();
+        } else {
+            return Err("Failed to lock blocker".to_string());
+        }
+        self.append_log(format!("📋 Loaded tracker list with {} entries", count));
+        Ok(())
+    }
+
+    /// Block every address in `cidr` once a host resolves to it
+    pub fn add_blocked_cidr(&self, cidr: &str) -> Result<(), String> {
+        let added = self
+            .blocker
+            .write()
+            .map_err(|_| "Failed to lock blocker".to_string())?
+            .add_blocked_cidr(cidr)
+            .map_err(|e| e.to_string())?;
+        if added {
+            self.append_log(format!("➕ Blocking IP range {}", cidr.trim()));
+        }
+        Ok(())
+    }
+
+    pub fn remove_blocked_cidr(&self, cidr: &str) -> Result<(), String> {
+        let removed = self
+            .blocker
+            .write()
+            .map_err(|_| "Failed to lock blocker".to_string())?
+            .remove_blocked_cidr(cidr)
+            .map_err(|e| e.to_string())?;
+        if removed {
+            self.append_log(format!("➖ No longer blocking IP range {}", cidr.trim()));
+        }
+        Ok(())
+    }
+
+    /// Replace the blocked IP ranges, skipping and logging invalid ones
+    pub fn set_blocked_cidrs(&self, cidrs: &[String]) {
+        for cidr in self.get_blocked_cidrs() {
+            let _ = self.remove_blocked_cidr(&cidr);
+        }
+        for cidr in cidrs {
+            if let Err(e) = self.add_blocked_cidr(cidr) {
+                self.append_log_entry(LogKind::Error, None, format!("⚠️ {}", e));
+            }
+        }
+    }
+
+    pub fn get_blocked_cidrs(&self) -> Vec<String> {
+        self.blocker.read().map(|blocker| blocker.blocked_cidrs()).unwrap_or_default()
+    }
+
+    /// Whether any IP ranges are blocked, so hosts need resolving first
+    pub fn has_blocked_cidrs(&self) -> bool {
+        self.blocker.read().map(|blocker| blocker.has_blocked_cidrs()).unwrap_or(false)
+    }
+
+    pub fn is_ip_blocked(&self, ip: IpAddr) -> bool {
+        self.blocker.read().map(|blocker| blocker.is_ip_blocked(ip)).unwrap_or(false)
+    }
+
+    /// Re-read the blocklist from its file, e.g. after editing it by hand.
+    /// Returns the entry counts before and after; on error the old list stays.
+    pub fn reload_trackers(&self) -> Result<(usize, usize), String> {
+        if self.restore_missing_tracker_file() {
+            let count = self.blocker.read().map(|blocker| blocker.len()).unwrap_or(0);
+            return Ok((count, count));
+        }
+
+        let mut blocker = self.blocker.write().map_err(|_| "Failed to lock blocker".to_string())?;
+        let before = blocker.len();
+        if let Err(e) = blocker.reload() {
+            drop(blocker);
+            self.append_log_entry(LogKind::Error, None, format!("❌ Failed to reload tracker list: {}", e));
+            return Err(format!("Failed to reload tracker list: {}", e));
+        }
+        let after = blocker.len();
+        self.invalidate_verdict_cache();
+        drop(blocker);
+
+        self.append_log(format!("🔄 Reloaded tracker list: {} → {} entries", before, after));
+        Ok((before, after))
+    }
+
+    pub fn get_trackers(&self) -> Result<Vec<String>, String> {
+        if let Ok(blocker) = self.blocker.read() {
+            let mut trackers: Vec<String> = blocker.iter().collect();
+            trackers.sort();
+            Ok(trackers)
+        } else {
+            Err("Failed to lock blocker".to_string())
         }
     }
 
-    // Proxy toggle
-    pub fn enable_proxy(&self) {
-        if let Ok(mut enabled) = self.proxy_enabled.lock() {
-            *enabled = true;
+    /// Recreate the blocklist file from memory if it was deleted, e.g. by a
+    /// cleanup script, so the next reload doesn't come back empty. Returns
+    /// whether it was missing.
+    pub fn restore_missing_tracker_file(&self) -> bool {
+        let Ok(mut blocker) = self.blocker.write() else {
+            return false;
+        };
+        let count = blocker.len();
+        match blocker.restore_if_missing() {
+            Ok(false) => false,
+            Ok(true) => {
+                drop(blocker);
+                self.append_log_entry(
+                    LogKind::Error,
+                    None,
+                    format!("⚠️ Tracker file was deleted; recreated it with the {} trackers in memory", count),
+                );
+                true
+            }
+            Err(e) => {
+                drop(blocker);
+                self.append_log_entry(LogKind::Error, None, format!("❌ Failed to recreate the tracker file: {}", e));
+                false
+            }
         }
-        self.append_log("▶️ Proxy enabled".to_string());
     }
 
-    pub fn disable_proxy(&self) {
-        if let Ok(mut enabled) = self.proxy_enabled.lock() {
-            *enabled = false;
+    // Allowlist methods
+
+    pub fn add_allowed_host(&self, domain: &str) {
+        let domain = normalize_host(domain);
+        if let Ok(mut allowlist) = self.allowlist.lock() {
+            allowlist.insert(domain.clone());
         }
-        self.append_log("🛑 Proxy disabled".to_string());
+        self.save_allowlist();
+        self.append_log_entry(LogKind::Info, Some(&domain), format!("✅ Added to allowlist: {}", domain));
     }
 
-    pub fn is_proxy_enabled(&self) -> bool {
-        self.proxy_enabled.lock().map(|v| *v).unwrap_or(false)
+    pub fn remove_allowed_host(&self, domain: &str) {
+        let domain = normalize_host(domain);
+        if let Ok(mut allowlist) = self.allowlist.lock() {
+            allowlist.remove(&domain);
+        }
+        self.save_allowlist();
+        self.append_log_entry(LogKind::Info, Some(&domain), format!("➖ Removed from allowlist: {}", domain));
     }
 
-    // Log toggle
-    pub fn enable_logging(&self) {
-        if let Ok(mut enabled) = self.log_enabled.lock() {
-            *enabled = true;
-        }
-        self.append_log("📡 Logging enabled".to_string());
+    pub fn is_allowlisted(&self, host: &str) -> bool {
+        let subdomains = self.is_allowlist_subdomains_enabled();
+        self.allowlist
+            .lock()
+            .map(|allowlist| allowlist_matches(&allowlist, &normalize_host(host), subdomains))
+            .unwrap_or(false)
     }
 
-    pub fn disable_logging(&self) {
-        if let Ok(mut enabled) = self.log_enabled.lock() {
-            *enabled = false;
-        }
-        self.append_log("📴 Logging disabled".to_string());
+    /// Let allowlisted domains cover their subdomains, the way blocklist
+    /// entries do, rather than only the exact host
+    pub fn set_allowlist_subdomains(&self, enabled: bool) {
+        self.allowlist_subdomains.store(enabled, Ordering::Relaxed);
+        self.invalidate_verdict_cache();
     }
 
-    pub fn is_logging_enabled(&self) -> bool {
-        self.log_enabled.lock().map(|v| *v).unwrap_or(false)
+    pub fn is_allowlist_subdomains_enabled(&self) -> bool {
+        self.allowlist_subdomains.load(Ordering::Relaxed)
     }
 
-    pub fn append_log(&self, entry: String) {
-        let mut logs = match self.logs.lock() {
-            Ok(logs) => logs,
-            Err(_) => return, // Handle poisoned mutex
+    pub fn get_allowlist(&self) -> Vec<String> {
+        let mut hosts: Vec<String> = match self.allowlist.lock() {
+            Ok(allowlist) => allowlist.iter().cloned().collect(),
+            Err(_) => Vec::new(),
         };
-        
-        // Add timestamp to log entry
-        let now = chrono::Local::now();
-        let timestamped_entry = format!("[{}] {}", now.format("%H:%M:%S"), entry);
-        
-        logs.push(timestamped_entry);
-        
-        // Limit log size to prevent memory issues
-        if logs.len() > 10000 {
-            logs.remove(0); // Remove oldest log
+        hosts.sort();
+        hosts
+    }
+
+    /// Replace the allowlist with the hosts in `path`, one per line, and save
+    /// changes there from now on. A missing file is an empty allowlist.
+    pub fn load_allowlist(&self, path: &Path) -> Result<(), String> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        let hosts: HashSet<String> = content
+            .lines()
+            .filter(|line| !line.trim().is_empty() && !line.trim().starts_with('#'))
+            .map(normalize_host)
+            .collect();
+
+        if let Ok(mut allowlist) = self.allowlist.lock() {
+            *allowlist = hosts;
+        }
+        if let Ok(mut current) = self.allowlist_path.lock() {
+            *current = Some(path.to_path_buf());
         }
+        Ok(())
     }
 
-    pub fn get_logs(&self) -> Vec<String> {
-        match self.logs.lock() {
-            Ok(logs) => logs.clone(),
-            Err(_) => vec![], // Return empty vector on error
+    fn save_allowlist(&self) {
+        let Some(path) = self.allowlist_path.lock().ok().and_then(|path| path.clone()) else {
+            return;
+        };
+        let mut content = self.get_allowlist().join("\n");
+        content.push('\n');
+        if let Err(e) = std::fs::write(&path, content) {
+            self.append_log_entry(LogKind::Error, None, format!("❌ Failed to save allowlist to {}: {}", path.display(), e));
         }
     }
 
-    pub fn clear_logs(&self) {
-        if let Ok(mut logs) = self.logs.lock() {
-            logs.clear();
+    // Profile methods
+
+    /// Switch to `profile`, whose settings are `config`: its blocklist,
+    /// allowlist, AI model and port replace the current ones. On error the
+    /// current profile stays in use.
+    pub fn load_profile(&self, profile: &Profile, config: &Config) -> Result<(), String> {
+        let blocker = TrackerBlocker::new(&config.tracker_list)
+            .map_err(|e| format!("Failed to load {}: {}", config.tracker_list, e))?;
+
+        // Keep what the outgoing profile learned before its model is replaced
+        if let Err(e) = self.flush_ai_model() {
+            self.append_log_entry(LogKind::Error, None, format!("❌ {}", e));
         }
-        self.append_log("🧹 Logs cleared".to_string());
+
+        self.replace_blocker(blocker)?;
+        self.set_blocked_cidrs(&config.blocked_cidrs);
+        self.set_allowlist_subdomains(config.allowlist_subdomains);
+        self.set_ai_conflict_policy(config.ai_conflict_policy);
+        self.set_decision_cache_per_host(config.decision_cache_per_host);
+        self.set_bypass_hosts(&config.bypass_hosts);
+        self.load_allowlist(&profile.allowlist_path)?;
+        if let Ok(mut tracker) = self.ai_tracker.lock() {
+            *tracker = AITracker::new();
+        }
+        let _ = self.load_ai_model(&config.ai_model_path);
+        self.set_ai_model_path(Some(PathBuf::from(&config.ai_model_path)));
+        self.set_listen_port(config.listen_port);
+        if let Err(e) = self.set_block_header_prefix(&config.block_header_prefix) {
+            self.append_log_entry(LogKind::Error, None, format!("⚠️ {}", e));
+        }
+
+        self.set_profile_name(profile.name.clone());
+        self.append_log(format!("👤 Using profile {}", profile.display_name()));
+        Ok(())
     }
 
-    // Method to track bandwidth
-    pub fn track_bandwidth(&self, bytes: u64, blocked: bool) {
-        if blocked {
-            if let Ok(mut saved) = self.bandwidth_saved.lock() {
-                *saved += bytes;
-            }
+    pub fn set_profile_name(&self, name: Option<String>) {
+        if let Ok(mut current) = self.profile_name.lock() {
+            *current = name;
         }
     }
 
-    // Method to get total bandwidth saved
-    pub fn get_bandwidth_saved(&self) -> u64 {
-        self.bandwidth_saved.lock().map(|s| *s).unwrap_or(0)
+    /// Name of the profile in use, `None` for the default
+    pub fn get_profile_name(&self) -> Option<String> {
+        self.profile_name.lock().ok().and_then(|name| name.clone())
     }
-    
-    
-    // Statistics methods
-    
-    pub fn record_request(&self, domain: &str, blocked: bool) {
-        // Update domain stats
-        if let Ok(mut stats) = self.stats.lock() {
-            let entry = stats.entry(domain.to_string()).or_insert_with(|| DomainStat {
-                domain: domain.to_string(),
-                requests: 0,
-                blocked: 0,
-                last_seen: Utc::now(),
-                bandwidth_saved: Arc::new(Mutex::new(0)), 
-            });
-            
-            entry.requests += 1;
-            if blocked {
-                entry.blocked += 1;
-            }
-            entry.last_seen = Utc::now();
+
+    /// Consult `program` about requests no built-in rule matched, or stop
+    /// with `None`. See [`crate::decision_hook`] for the protocol.
+    pub fn set_decision_hook(&self, program: Option<PathBuf>, timeout: Duration) {
+        let hook = program.map(|program| Arc::new(DecisionHook::new(program, timeout)));
+        if let Some(hook) = &hook {
+            hook.set_cache_per_host(self.is_decision_cache_per_host());
         }
-        
-        // Update global counters
-        if blocked {
-            if let Ok(mut count) = self.blocked_count.lock() {
-                *count += 1;
-            }
-        } else {
-            if let Ok(mut count) = self.allowed_count.lock() {
-                *count += 1;
-            }
+        let message = match &hook {
+            Some(hook) => format!("🧩 Consulting decision script {}", hook.program().display()),
+            None => "🧩 Decision script turned off".to_string(),
+        };
+        if let Ok(mut current) = self.decision_hook.write() {
+            *current = hook;
         }
+        self.append_log(message);
     }
-    
-    pub fn get_stats(&self) -> HashMap<String, DomainStat> {
-        match self.stats.lock() {
-            Ok(stats) => stats.clone(),
-            Err(_) => HashMap::new(), // Return empty map on error
+
+    pub fn get_decision_hook(&self) -> Option<Arc<DecisionHook>> {
+        self.decision_hook.read().ok().and_then(|hook| hook.clone())
+    }
+
+    /// Reuse the decision script's answer for every request to a host,
+    /// rather than only for the same host, path and referer
+    pub fn set_decision_cache_per_host(&self, enabled: bool) {
+        self.decision_cache_per_host.store(enabled, Ordering::Relaxed);
+        if let Some(hook) = self.get_decision_hook() {
+            hook.set_cache_per_host(enabled);
         }
     }
-    
-    pub fn get_allowed_count(&self) -> usize {
-        self.allowed_count.lock().map(|v| *v).unwrap_or(0)
+
+    pub fn is_decision_cache_per_host(&self) -> bool {
+        self.decision_cache_per_host.load(Ordering::Relaxed)
     }
-    
-    pub fn get_blocked_count(&self) -> usize {
-        self.blocked_count.lock().map(|v| *v).unwrap_or(0)
+
+    // Temporary allow methods
+
+    /// Allow `host` for a short while, long enough to reload a broken page.
+    pub fn allow_once(&self, host: &str) {
+        self.allow_temporarily(host, chrono::Duration::seconds(TEMPORARY_ALLOW_SECS));
     }
-    
-    pub fn reset_stats(&self) {
-        if let Ok(mut stats) = self.stats.lock() {
-            stats.clear();
-        }
-        
-        if let Ok(mut count) = self.allowed_count.lock() {
-            *count = 0;
-        }
-        
-        if let Ok(mut count) = self.blocked_count.lock() {
-            *count = 0;
+
+    /// Allow `host` until `duration` from now without touching the allowlist.
+    pub fn allow_temporarily(&self, host: &str, duration: chrono::Duration) {
+        let host = normalize_host(host);
+        if let Ok(mut grants) = self.temporary_allows.lock() {
+            grants.insert(host.clone(), Utc::now() + duration);
         }
-        
-        self.append_log("📊 Statistics reset".to_string());
+        self.append_log_entry(
+            LogKind::Info,
+            Some(&host),
+            format!("⏳ Temporarily allowed {} for {}s", host, duration.num_seconds()),
+        );
     }
-    
-    // Tracker management methods
-    
-    pub fn add_tracker(&self, domain: &str) -> Result<(), String> {
-        if let Ok(mut blocker) = self.blocker.lock() {
-            match blocker.add_tracker(domain) {
-                Ok(()) => {
-                    self.append_log(format!("➕ Added tracker: {}", domain));
-                    Ok(())
-                },
-                Err(e) => Err(format!("Failed to add tracker: {}", e)),
+
+    /// Whether `host` has an unexpired temporary grant.
+    pub fn is_temporarily_allowed(&self, host: &str) -> bool {
+        self.expire_temporary_allows();
+        self.temporary_allows
+            .lock()
+            .map(|grants| grants.contains_key(&normalize_host(host)))
+            .unwrap_or(false)
+    }
+
+    /// Drop expired temporary grants, logging each one.
+    pub fn expire_temporary_allows(&self) {
+        let now = Utc::now();
+        let expired: Vec<String> = match self.temporary_allows.lock() {
+            Ok(mut grants) => {
+                let expired = grants
+                    .iter()
+                    .filter(|(_, expiry)| **expiry <= now)
+                    .map(|(host, _)| host.clone())
+                    .collect::<Vec<_>>();
+                for host in &expired {
+                    grants.remove(host);
+                }
+                expired
             }
-        } else {
-            Err("Failed to lock blocker".to_string())
+            Err(_) => return,
+        };
+
+        for host in expired {
+            self.append_log_entry(LogKind::Info, Some(&host), format!("⌛ Temporary allow expired: {}", host));
         }
     }
-    
-    pub fn remove_tracker(&self, domain: &str) -> Result<(), String> {
-        if let Ok(mut blocker) = self.blocker.lock() {
-            match blocker.remove_tracker(domain) {
-                Ok(()) => {
-                    self.append_log(format!("➖ Removed tracker: {}", domain));
-                    Ok(())
-                },
-                Err(e) => Err(format!("Failed to remove tracker: {}", e)),
-            }
-        } else {
-            Err("Failed to lock blocker".to_string())
+
+    /// Active temporary grants with their expiry, soonest first
+    pub fn get_temporary_allows(&self) -> Vec<(String, DateTime<Utc>)> {
+        let mut grants: Vec<(String, DateTime<Utc>)> = match self.temporary_allows.lock() {
+            Ok(grants) => grants.iter().map(|(host, expiry)| (host.clone(), *expiry)).collect(),
+            Err(_) => Vec::new(),
+        };
+        grants.sort_by_key(|(_, expiry)| *expiry);
+        grants
+    }
+
+    // Request evaluation
+
+    /// Decide what to do with a request in one place.
+    ///
+    /// Consults, in order: the allowlist, temporary allows, the tracker
+    /// blocklist and the AI heuristics. Tracking parameters are stripped from
+    /// `url` regardless of the outcome and returned as `cleaned_url` if
+    /// anything changed.
+    pub fn evaluate_request(&self, host: &str, url: &str, referer: Option<&str>) -> RequestVerdict {
+        let mut verdict = self.evaluate_host(host, url, referer);
+
+        let cleaned = self.clean_url(url);
+        if cleaned != url {
+            verdict.cleaned_url = Some(cleaned);
         }
+
+        verdict
     }
-    
-    pub fn get_trackers(&self) -> Result<Vec<String>, String> {
-        if let Ok(blocker) = self.blocker.lock() {
-            Ok(blocker.get_trackers())
-        } else {
-            Err("Failed to lock blocker".to_string())
+
+    fn evaluate_host(&self, host: &str, url: &str, referer: Option<&str>) -> RequestVerdict {
+        if self.is_allowlisted(host) {
+            return RequestVerdict::allow(VerdictReason::Allowlisted);
+        }
+
+        if self.is_temporarily_allowed(host) {
+            self.append_log_entry(LogKind::Info, Some(host), format!("⏳ Temporary allow used: {}", host));
+            return RequestVerdict::allow(VerdictReason::TemporarilyAllowed);
+        }
+
+        match self.lookup_blocked(host) {
+            Some(true) => return RequestVerdict::block(VerdictReason::Blocklisted),
+            None if self.is_fail_closed() => return RequestVerdict::block(VerdictReason::BlocklistUnavailable),
+            _ => {}
+        }
+
+        // The blocklist allows the host; the policy decides if the AI can overrule it
+        if self.is_ai_detection_enabled() && self.should_sample_ai() && self.is_likely_tracker(url, host, referer) {
+            let policy = self.get_ai_conflict_policy();
+            let winner = if policy == AiConflictPolicy::AiCanBlock { "AI" } else { "blocklist" };
+            self.append_log_entry(
+                LogKind::Ai,
+                Some(host),
+                format!("⚖️ AI flagged {}, which the blocklist allows; {} wins ({})", host, winner, policy),
+            );
+
+            return match policy {
+                AiConflictPolicy::AiOnlySuggests => {
+                    // Add to suggested trackers list for user review
+                    self.add_ai_suggested_tracker(host);
+                    RequestVerdict::allow(VerdictReason::AiDetected)
+                }
+                AiConflictPolicy::AiCanBlock => {
+                    self.add_ai_suggested_tracker(host);
+                    RequestVerdict::block(VerdictReason::AiDetected)
+                }
+                AiConflictPolicy::BlocklistAlwaysWins => RequestVerdict::allow(VerdictReason::NoMatch),
+            };
         }
+
+        RequestVerdict::allow(VerdictReason::NoMatch)
     }
 
     // AI tracker methods
@@ -267,6 +2668,63 @@ impl SharedState {
         self.append_log("🤖 AI tracker detection disabled".to_string());
     }
     
+    /// What to do when the AI flags a host the blocklist allows
+    pub fn set_ai_conflict_policy(&self, policy: AiConflictPolicy) {
+        if let Ok(mut current) = self.ai_conflict_policy.lock() {
+            *current = policy;
+        }
+        self.append_log(format!("🤖 AI conflict policy: {}", policy));
+    }
+
+    pub fn get_ai_conflict_policy(&self) -> AiConflictPolicy {
+        self.ai_conflict_policy.lock().map(|policy| *policy).unwrap_or_default()
+    }
+
+    /// Score suspicious-path requests without a Referer as third-party.
+    pub fn set_ai_no_referer_heuristic(&self, enabled: bool) {
+        if let Ok(mut tracker) = self.ai_tracker.lock() {
+            tracker.set_no_referer_heuristic(enabled);
+        }
+    }
+    
+    pub fn is_ai_no_referer_heuristic_enabled(&self) -> bool {
+        self.ai_tracker
+            .lock()
+            .map(|tracker| tracker.is_no_referer_heuristic_enabled())
+            .unwrap_or(false)
+    }
+    
+    /// Identical requests to one host and path per minute that mark it as a
+    /// heartbeat beacon. 0 turns beacon detection off.
+    pub fn set_beacon_threshold(&self, per_minute: usize) {
+        self.beacon_threshold.store(per_minute, Ordering::Relaxed);
+    }
+
+    pub fn get_beacon_threshold(&self) -> usize {
+        self.beacon_threshold.load(Ordering::Relaxed)
+    }
+
+    /// Note an allowed request for beacon detection, suggesting the host as
+    /// a tracker if it's being pinged at a steady rate.
+    pub fn record_beacon_candidate(&self, host: &str, path: &str) {
+        let threshold = self.get_beacon_threshold();
+        if threshold == 0 {
+            return;
+        }
+        let detected = match self.beacons.lock() {
+            Ok(mut beacons) => beacons.record(host, path, Utc::now(), threshold),
+            Err(_) => None,
+        };
+        if let Some(count) = detected {
+            self.append_log_entry(
+                LogKind::Ai,
+                Some(host),
+                format!("📡 Beacon pattern detected: {}{} requested {} times in a minute at a steady rate", host, path, count),
+            );
+            self.add_ai_suggested_tracker(host);
+        }
+    }
+    
     pub fn is_ai_detection_enabled(&self) -> bool {
         if let Ok(tracker) = self.ai_tracker.lock() {
             tracker.is_enabled()
@@ -275,6 +2733,46 @@ impl SharedState {
         }
     }
     
+    /// Analyze only `percent` (1-100) of requests that reach the AI step.
+    pub fn set_ai_sample_percent(&self, percent: u64) {
+        self.ai_sample_percent.store(percent.clamp(1, 100), Ordering::Relaxed);
+    }
+    
+    pub fn get_ai_sample_percent(&self) -> u64 {
+        self.ai_sample_percent.load(Ordering::Relaxed)
+    }
+    
+    /// Requests that reached the AI step and how many of them were analyzed
+    pub fn get_ai_sampling_counts(&self) -> (u64, u64) {
+        (
+            self.ai_eligible_count.load(Ordering::Relaxed),
+            self.ai_analyzed_count.load(Ordering::Relaxed),
+        )
+    }
+    
+    /// Whether the next eligible request should be analyzed.
+    ///
+    /// Spreads samples evenly rather than randomly: at 10% every tenth
+    /// request is analyzed.
+    fn should_sample_ai(&self) -> bool {
+        let percent = self.get_ai_sample_percent();
+        let n = self.ai_eligible_count.fetch_add(1, Ordering::Relaxed);
+        let sampled = percent >= 100 || (n * percent) / 100 != ((n + 1) * percent) / 100;
+        if sampled {
+            self.ai_analyzed_count.fetch_add(1, Ordering::Relaxed);
+        }
+        sampled
+    }
+    
+    /// Ask the AI tracker whether a request looks like a tracker.
+    pub fn is_likely_tracker(&self, url: &str, host: &str, referer: Option<&str>) -> bool {
+        if let Ok(mut tracker) = self.ai_tracker.lock() {
+            tracker.is_likely_tracker(url, host, referer)
+        } else {
+            false
+        }
+    }
+    
     pub fn set_ai_confidence_threshold(&self, threshold: f32) {
         if let Ok(mut tracker) = self.ai_tracker.lock() {
             tracker.set_confidence_threshold(threshold);
@@ -291,14 +2789,49 @@ impl SharedState {
     }
     
     pub fn add_ai_suggested_tracker(&self, domain: &str) {
+        let snoozed = self.ai_tracker
+            .lock()
+            .map(|mut tracker| tracker.is_snoozed(domain, Utc::now()))
+            .unwrap_or(false);
+        if snoozed {
+            return;
+        }
         if let Ok(mut suggested) = self.ai_suggested_trackers.lock() {
             if !suggested.contains(&domain.to_string()) {
+                let limit = self.get_ai_suggestion_limit();
+                let overflow = (suggested.len() + 1).saturating_sub(limit);
+                for dropped in suggested.drain(..overflow) {
+                    self.append_log_entry(LogKind::Ai, Some(&dropped), format!("🤖 AI suggestion queue full, dropped oldest: {}", dropped));
+                }
                 suggested.push(domain.to_string());
-                self.append_log(format!("🤖 Added domain to AI suggestions: {}", domain));
+                self.append_log_entry(LogKind::Ai, Some(domain), format!("🤖 Added domain to AI suggestions: {}", domain));
             }
         }
     }
     
+    /// Keep at most `limit` (at least 1) AI suggestions, dropping the oldest
+    /// when a new one arrives. Lowering it drops the oldest right away.
+    pub fn set_ai_suggestion_limit(&self, limit: usize) {
+        let limit = limit.max(1);
+        self.ai_suggestion_limit.store(limit, Ordering::Relaxed);
+        if let Ok(mut suggested) = self.ai_suggested_trackers.lock() {
+            let overflow = suggested.len().saturating_sub(limit);
+            suggested.drain(..overflow);
+        }
+    }
+    
+    pub fn get_ai_suggestion_limit(&self) -> usize {
+        self.ai_suggestion_limit.load(Ordering::Relaxed)
+    }
+    
+    /// Whether the next new suggestion will push out the oldest one
+    pub fn is_ai_suggestion_queue_full(&self) -> bool {
+        self.ai_suggested_trackers
+            .lock()
+            .map(|suggested| suggested.len() >= self.get_ai_suggestion_limit())
+            .unwrap_or(false)
+    }
+    
     pub fn get_ai_suggested_trackers(&self) -> Vec<String> {
         if let Ok(suggested) = self.ai_suggested_trackers.lock() {
             suggested.clone()
@@ -327,11 +2860,70 @@ impl SharedState {
         if let Ok(mut tracker) = self.ai_tracker.lock() {
             tracker.report_false_negative(domain);
         }
+        self.persist_ai_model();
         
-        self.append_log(format!("✅ Approved AI-suggested tracker: {}", domain));
+        self.append_log_entry(LogKind::Ai, Some(domain), format!("✅ Approved AI-suggested tracker: {}", domain));
         Ok(())
     }
     
+    // Third-party review methods
+
+    /// Queue a third-party `host` that was allowed without any rule matching,
+    /// unless it's already queued, dismissed or suggested by the AI.
+    pub fn record_unreviewed_third_party(&self, host: &str) {
+        let host = host.to_lowercase();
+        let suggested = self
+            .ai_suggested_trackers
+            .lock()
+            .is_ok_and(|suggested| suggested.contains(&host));
+        if suggested {
+            return;
+        }
+        if let Ok(mut review) = self.third_party_review.lock() {
+            if review.dismissed.contains(&host) || review.hosts.contains(&host) {
+                return;
+            }
+            let overflow = (review.hosts.len() + 1).saturating_sub(MAX_THIRD_PARTY_REVIEW);
+            review.hosts.drain(..overflow);
+            review.hosts.push(host);
+        }
+    }
+
+    /// Allowed third-party hosts pending review, oldest first
+    pub fn get_third_party_review(&self) -> Vec<String> {
+        self.third_party_review
+            .lock()
+            .map(|review| review.hosts.clone())
+            .unwrap_or_default()
+    }
+
+    /// Block a host from the review queue
+    pub fn block_reviewed_host(&self, host: &str) -> Result<(), String> {
+        self.add_tracker(host)?;
+        if let Ok(mut review) = self.third_party_review.lock() {
+            review.hosts.retain(|h| h != host);
+        }
+        Ok(())
+    }
+
+    /// Drop a host from the review queue without blocking it, and don't
+    /// queue it again this session.
+    pub fn dismiss_reviewed_host(&self, host: &str) {
+        if let Ok(mut review) = self.third_party_review.lock() {
+            review.hosts.retain(|h| h != host);
+            review.dismissed.insert(host.to_string());
+        }
+    }
+
+    /// Dismiss every host in the review queue, so none is queued again
+    /// this session
+    pub fn clear_third_party_review(&self) {
+        if let Ok(mut review) = self.third_party_review.lock() {
+            let hosts = std::mem::take(&mut review.hosts);
+            review.dismissed.extend(hosts);
+        }
+    }
+
     pub fn reject_ai_suggestion(&self, domain: &str) {
         if let Ok(mut suggested) = self.ai_suggested_trackers.lock() {
             suggested.retain(|d| d != domain);
@@ -341,8 +2933,25 @@ impl SharedState {
         if let Ok(mut tracker) = self.ai_tracker.lock() {
             tracker.report_false_positive(domain);
         }
+        self.persist_ai_model();
         
-        self.append_log(format!("❌ Rejected AI-suggested tracker: {}", domain));
+        self.append_log_entry(LogKind::Ai, Some(domain), format!("❌ Rejected AI-suggested tracker: {}", domain));
+    }
+    
+    /// Drop a suggestion and don't suggest the host again for `duration`,
+    /// without telling the AI whether it was right.
+    pub fn snooze_ai_suggestion(&self, domain: &str, duration: chrono::Duration) {
+        if let Ok(mut suggested) = self.ai_suggested_trackers.lock() {
+            suggested.retain(|d| d != domain);
+        }
+        if let Ok(mut tracker) = self.ai_tracker.lock() {
+            tracker.snooze(domain, Utc::now() + duration);
+        }
+        self.append_log_entry(
+            LogKind::Ai,
+            Some(domain),
+            format!("😴 Snoozed AI suggestions for {} for {} hours", domain, duration.num_hours()),
+        );
     }
     
     pub fn get_ai_stats(&self) -> (usize, usize, usize) {
@@ -360,6 +2969,63 @@ impl SharedState {
         self.append_log("🤖 Reset AI tracker statistics".to_string());
     }
     
+    /// Save the AI model to `path` whenever feedback changes it, or never if `None`.
+    pub fn set_ai_model_path(&self, path: Option<PathBuf>) {
+        if let Ok(mut model_path) = self.ai_model_path.lock() {
+            *model_path = path;
+        }
+    }
+    
+    pub fn get_ai_model_path(&self) -> Option<PathBuf> {
+        self.ai_model_path.lock().ok().and_then(|path| path.clone())
+    }
+    
+    /// Save the AI model to `path` from now on, starting with a save right away.
+    pub fn change_ai_model_path(&self, path: PathBuf) -> Result<(), String> {
+        self.set_ai_model_path(Some(path.clone()));
+        self.persist_ai_model();
+        if self.is_ai_model_unsaved() {
+            return Err(format!("Couldn't save the AI model to {}", path.display()));
+        }
+        Ok(())
+    }
+    
+    /// Whether the last AI model save failed, leaving feedback unsaved
+    pub fn is_ai_model_unsaved(&self) -> bool {
+        self.ai_model_unsaved.load(Ordering::Relaxed)
+    }
+    
+    /// Retry the AI model save if the last one failed, e.g. before exiting.
+    /// The error says where the save failed and why.
+    pub fn flush_ai_model(&self) -> Result<(), String> {
+        if !self.is_ai_model_unsaved() {
+            return Ok(());
+        }
+        self.persist_ai_model();
+        if self.is_ai_model_unsaved() {
+            let path = self.get_ai_model_path().map(|p| p.display().to_string()).unwrap_or_default();
+            return Err(format!("AI feedback from this session couldn't be saved to {}", path));
+        }
+        Ok(())
+    }
+    
+    /// Save the AI model to the configured path, if any, so learned weights survive restarts.
+    fn persist_ai_model(&self) {
+        let Some(path) = self.get_ai_model_path() else {
+            return;
+        };
+        
+        let result = match self.ai_tracker.lock() {
+            Ok(tracker) => tracker.save(&path),
+            Err(_) => return,
+        };
+        self.ai_model_unsaved.store(result.is_err(), Ordering::Relaxed);
+        match result {
+            Ok(()) => self.append_log_entry(LogKind::Ai, None, format!("💾 Saved AI model to {}", path.display())),
+            Err(e) => self.append_log_entry(LogKind::Error, None, format!("❌ Failed to save AI model to {}: {}", path.display(), e)),
+        }
+    }
+    
     pub fn save_ai_model<P: AsRef<std::path::Path>>(&self, path: P) -> std::io::Result<()> {
         if let Ok(tracker) = self.ai_tracker.lock() {
             tracker.save(path)?;
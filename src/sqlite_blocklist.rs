@@ -0,0 +1,146 @@
+//! Blocklist stored in a SQLite database, for lists with hundreds of
+//! thousands of entries.
+//!
+//! Domains live in a single table keyed by domain, so exact and
+//! parent-domain lookups are index hits rather than a scan.
+
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+
+use rusqlite::{params, Connection};
+
+use crate::blocklist_source::{BlocklistSource, ImportDiff, MatchExplanation};
+use crate::tracker_blocker::{normalize_host, parse_trackers};
+
+pub struct SqliteBlocklist {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteBlocklist {
+    /// Open the database at `path`, creating it and its table if needed.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::from_connection(Connection::open(path).map_err(io::Error::other)?)
+    }
+
+    /// A database that only lives in memory, mainly for tests.
+    pub fn open_in_memory() -> io::Result<Self> {
+        Self::from_connection(Connection::open_in_memory().map_err(io::Error::other)?)
+    }
+
+    fn from_connection(conn: Connection) -> io::Result<Self> {
+        conn.execute_batch("CREATE TABLE IF NOT EXISTS trackers (domain TEXT PRIMARY KEY NOT NULL) WITHOUT ROWID")
+            .map_err(io::Error::other)?;
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    /// Import a text tracker list (one domain per line, `#` comments) into
+    /// the database, returning how many new domains were added.
+    pub fn import_list<P: AsRef<Path>>(&mut self, list: P) -> io::Result<usize> {
+        let content = std::fs::read_to_string(list)?;
+        let domains: Vec<String> = parse_trackers(&content).into_iter().collect();
+        self.merge(&domains).map(|diff| diff.added.len())
+    }
+
+    /// Find the listed domain that matches `host`, checking the host itself
+    /// and then each parent domain.
+    pub fn matching_tracker(&self, host: &str) -> Option<String> {
+        let host = normalize_host(host);
+        let conn = self.lock();
+        let mut lookup = conn
+            .prepare_cached("SELECT domain FROM trackers WHERE domain = ?1")
+            .ok()?;
+
+        let mut candidate = host.as_str();
+        loop {
+            if let Ok(domain) = lookup.query_row(params![candidate], |row| row.get(0)) {
+                return Some(domain);
+            }
+            match candidate.split_once('.') {
+                Some((_, parent)) => candidate = parent,
+                None => return None,
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Connection> {
+        // A panic mid-query leaves nothing half-written worth refusing over
+        self.conn.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl BlocklistSource for SqliteBlocklist {
+    fn contains(&self, host: &str) -> bool {
+        self.matching_tracker(host).is_some()
+    }
+
+    fn is_listed(&self, domain: &str) -> bool {
+        self.lock()
+            .query_row("SELECT 1 FROM trackers WHERE domain = ?1", params![domain], |_| Ok(()))
+            .is_ok()
+    }
+
+    fn explain_match(&self, host: &str) -> Option<MatchExplanation> {
+        self.matching_tracker(host).map(|rule| MatchExplanation::new(host, &rule))
+    }
+
+    fn add(&mut self, domain: &str) -> io::Result<()> {
+        self.lock()
+            .execute(
+                "INSERT OR IGNORE INTO trackers (domain) VALUES (?1)",
+                params![normalize_host(domain)],
+            )
+            .map(|_| ())
+            .map_err(io::Error::other)
+    }
+
+    fn merge(&mut self, domains: &[String]) -> io::Result<ImportDiff> {
+        let mut conn = self.lock();
+        let tx = conn.transaction().map_err(io::Error::other)?;
+        let mut added = Vec::new();
+        {
+            let mut insert = tx
+                .prepare("INSERT OR IGNORE INTO trackers (domain) VALUES (?1)")
+                .map_err(io::Error::other)?;
+            for domain in domains {
+                let domain = normalize_host(domain);
+                if insert.execute(params![domain]).map_err(io::Error::other)? > 0 {
+                    added.push(domain);
+                }
+            }
+        }
+        tx.commit().map_err(io::Error::other)?;
+        Ok(ImportDiff::new(added, domains.len()))
+    }
+
+    fn remove(&mut self, domain: &str) -> io::Result<()> {
+        self.lock()
+            .execute("DELETE FROM trackers WHERE domain = ?1", params![normalize_host(domain)])
+            .map(|_| ())
+            .map_err(io::Error::other)
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = String> + '_> {
+        let conn = self.lock();
+        let domains: Vec<String> = match conn.prepare("SELECT domain FROM trackers") {
+            Ok(mut stmt) => stmt
+                .query_map([], |row| row.get(0))
+                .map(|rows| rows.filter_map(Result::ok).collect())
+                .unwrap_or_default(),
+            Err(_) => Vec::new(),
+        };
+        Box::new(domains.into_iter())
+    }
+
+    /// The database is always current, so there is nothing to re-read.
+    fn reload(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.lock()
+            .query_row("SELECT COUNT(*) FROM trackers", [], |row| row.get::<_, i64>(0))
+            .map(|count| count as usize)
+            .unwrap_or(0)
+    }
+}
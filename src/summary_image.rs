@@ -0,0 +1,143 @@
+//! Renders a shareable PNG of the headline statistics, e.g. for posting
+//! "I blocked 10,432 trackers this week".
+
+use std::io;
+use std::path::Path;
+
+use ab_glyph::{point, Font, FontRef, GlyphId, PxScale, ScaleFont};
+use image::{Rgba, RgbaImage};
+
+use crate::shared_state::SharedState;
+
+/// Size of the rendered image in pixels
+pub const SUMMARY_IMAGE_SIZE: (u32, u32) = (800, 460);
+
+/// How many of the most blocked domains are listed
+pub const SUMMARY_TOP_DOMAINS: usize = 5;
+
+const BACKGROUND: [u8; 3] = [24, 26, 36];
+const TEXT: [u8; 3] = [230, 230, 235];
+const MUTED: [u8; 3] = [150, 150, 165];
+const ACCENT: [u8; 3] = [235, 80, 80];
+
+/// The numbers shown in a summary image
+#[derive(Clone, Debug, PartialEq)]
+pub struct Summary {
+    pub total: usize,
+    pub blocked: usize,
+    pub bandwidth_saved: u64,
+    /// Most blocked domains with their block counts, highest first
+    pub top_domains: Vec<(String, usize)>,
+}
+
+impl Summary {
+    /// Snapshot the all-time statistics.
+    pub fn from_state(state: &SharedState) -> Self {
+        let blocked = state.get_blocked_count();
+        let mut top_domains: Vec<(String, usize)> = state
+            .get_stats()
+            .into_values()
+            .filter(|stat| stat.blocked > 0)
+            .map(|stat| (stat.domain, stat.blocked))
+            .collect();
+        top_domains.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        top_domains.truncate(SUMMARY_TOP_DOMAINS);
+
+        Summary {
+            total: state.get_allowed_count() + blocked,
+            blocked,
+            bandwidth_saved: state.get_bandwidth_saved(),
+            top_domains,
+        }
+    }
+
+    /// Draw the summary using `font`, which must be a TrueType or OpenType font.
+    pub fn render(&self, font: &[u8]) -> io::Result<RgbaImage> {
+        let font = FontRef::try_from_slice(font)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let (width, height) = SUMMARY_IMAGE_SIZE;
+        let mut img = RgbaImage::from_pixel(width, height, Rgba([BACKGROUND[0], BACKGROUND[1], BACKGROUND[2], 255]));
+
+        draw_text(&mut img, &font, 26.0, 40.0, 30.0, MUTED, "DeTrack Proxy");
+        draw_text(&mut img, &font, 44.0, 40.0, 70.0, TEXT, &format!("I blocked {} trackers", group_digits(self.blocked)));
+
+        let rate = if self.total > 0 {
+            self.blocked as f64 / self.total as f64 * 100.0
+        } else {
+            0.0
+        };
+        let lines = [
+            format!("Requests seen: {}", group_digits(self.total)),
+            format!("Blocked: {} ({:.1}%)", group_digits(self.blocked), rate),
+            format!("Bandwidth saved: {:.2} MB", self.bandwidth_saved as f64 / 1_000_000.0),
+        ];
+        for (i, line) in lines.iter().enumerate() {
+            draw_text(&mut img, &font, 24.0, 40.0, 140.0 + i as f32 * 34.0, TEXT, line);
+        }
+
+        if !self.top_domains.is_empty() {
+            draw_text(&mut img, &font, 24.0, 40.0, 260.0, ACCENT, "Most blocked");
+            for (i, (domain, count)) in self.top_domains.iter().enumerate() {
+                let y = 296.0 + i as f32 * 30.0;
+                draw_text(&mut img, &font, 20.0, 60.0, y, TEXT, domain);
+                draw_text(&mut img, &font, 20.0, 620.0, y, MUTED, &group_digits(*count));
+            }
+        }
+
+        Ok(img)
+    }
+
+    /// Render the summary and write it to `path` as a PNG.
+    pub fn save_png<P: AsRef<Path>>(&self, font: &[u8], path: P) -> io::Result<()> {
+        self.render(font)?
+            .save_with_format(path, image::ImageFormat::Png)
+            .map_err(io::Error::other)
+    }
+}
+
+/// `10432` as `10,432`
+fn group_digits(n: usize) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    grouped
+}
+
+/// Draw one line of text with its top-left corner at (`x`, `y`).
+fn draw_text(img: &mut RgbaImage, font: &FontRef, size: f32, x: f32, y: f32, color: [u8; 3], text: &str) {
+    let scaled = font.as_scaled(PxScale::from(size));
+    let mut caret = point(x, y + scaled.ascent());
+    let mut previous: Option<GlyphId> = None;
+
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(previous) = previous {
+            caret.x += scaled.kern(previous, id);
+        }
+        let glyph = id.with_scale_and_position(size, caret);
+        caret.x += scaled.h_advance(id);
+        previous = Some(id);
+
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i64 + gx as i64;
+            let py = bounds.min.y as i64 + gy as i64;
+            if px < 0 || py < 0 || px >= img.width() as i64 || py >= img.height() as i64 {
+                return;
+            }
+            let pixel = img.get_pixel_mut(px as u32, py as u32);
+            for channel in 0..3 {
+                let blended = pixel[channel] as f32 * (1.0 - coverage) + color[channel] as f32 * coverage;
+                pixel[channel] = blended.round() as u8;
+            }
+        });
+    }
+}
@@ -0,0 +1,120 @@
+//! Optional configuration of the operating system proxy settings.
+//!
+//! Only Windows is supported: the WinINet settings in the current user's
+//! registry hive are pointed at DeTrack, and the previous values are kept so
+//! they can be put back when the app exits.
+
+use std::io;
+
+/// System proxy settings as they were before DeTrack changed them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PreviousProxySettings {
+    /// Whether a system proxy was enabled before DeTrack changed it
+    pub enabled: bool,
+    /// The previously configured proxy server, if any
+    pub server: Option<String>,
+}
+
+impl std::fmt::Display for PreviousProxySettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = if self.enabled { "enabled" } else { "disabled" };
+        match &self.server {
+            Some(server) => write!(f, "{}, server {}", state, server),
+            None => write!(f, "{}, no server", state),
+        }
+    }
+}
+
+/// Whether automatic configuration is available on this platform.
+pub fn is_supported() -> bool {
+    cfg!(windows)
+}
+
+/// Point the system proxy at `addr` and return the settings it replaced.
+pub fn enable(addr: &str) -> io::Result<PreviousProxySettings> {
+    platform::enable(addr)
+}
+
+/// Put back the settings returned by [`enable`].
+pub fn restore(previous: &PreviousProxySettings) -> io::Result<()> {
+    platform::restore(previous)
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::PreviousProxySettings;
+    use std::io;
+    use winreg::enums::{HKEY_CURRENT_USER, KEY_READ, KEY_WRITE};
+    use winreg::RegKey;
+
+    const INTERNET_SETTINGS: &str =
+        "Software\\Microsoft\\Windows\\CurrentVersion\\Internet Settings";
+
+    fn settings_key() -> io::Result<RegKey> {
+        RegKey::predef(HKEY_CURRENT_USER).open_subkey_with_flags(INTERNET_SETTINGS, KEY_READ | KEY_WRITE)
+    }
+
+    pub fn enable(addr: &str) -> io::Result<PreviousProxySettings> {
+        let key = settings_key()?;
+
+        let previous = PreviousProxySettings {
+            enabled: key.get_value::<u32, _>("ProxyEnable").unwrap_or(0) != 0,
+            server: key.get_value::<String, _>("ProxyServer").ok(),
+        };
+
+        key.set_value("ProxyServer", &addr.to_string())?;
+        key.set_value("ProxyEnable", &1u32)?;
+        notify_settings_changed();
+
+        Ok(previous)
+    }
+
+    pub fn restore(previous: &PreviousProxySettings) -> io::Result<()> {
+        let key = settings_key()?;
+
+        match &previous.server {
+            Some(server) => key.set_value("ProxyServer", server)?,
+            None => {
+                // The value may already be gone; that's what we want anyway
+                let _ = key.delete_value("ProxyServer");
+            }
+        }
+        key.set_value("ProxyEnable", &u32::from(previous.enabled))?;
+        notify_settings_changed();
+
+        Ok(())
+    }
+
+    /// Tell WinINet (and so browsers using system settings) to re-read the registry.
+    fn notify_settings_changed() {
+        use windows_sys::Win32::Networking::WinInet::{
+            InternetSetOptionW, INTERNET_OPTION_REFRESH, INTERNET_OPTION_SETTINGS_CHANGED,
+        };
+
+        unsafe {
+            InternetSetOptionW(std::ptr::null(), INTERNET_OPTION_SETTINGS_CHANGED, std::ptr::null(), 0);
+            InternetSetOptionW(std::ptr::null(), INTERNET_OPTION_REFRESH, std::ptr::null(), 0);
+        }
+    }
+}
+
+#[cfg(not(windows))]
+mod platform {
+    use super::PreviousProxySettings;
+    use std::io;
+
+    fn unsupported() -> io::Error {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            "automatic system proxy configuration is only supported on Windows",
+        )
+    }
+
+    pub fn enable(_addr: &str) -> io::Result<PreviousProxySettings> {
+        Err(unsupported())
+    }
+
+    pub fn restore(_previous: &PreviousProxySettings) -> io::Result<()> {
+        Err(unsupported())
+    }
+}
@@ -1,14 +1,151 @@
 use std::collections::HashSet;
 use std::fs;
 use std::io::{self}; 
+use std::net::IpAddr;
 use std::path::{Path, PathBuf};
 use chrono::Local;
 use url::Url;
 
+use crate::blocklist_source::{BlocklistSource, ImportDiff, MatchExplanation};
+use crate::ip_range::IpRange;
+use crate::list_formats::parse_list;
+
 pub struct TrackerBlocker {
     trackers: HashSet<String>,
     tracker_file_path: PathBuf,
+    cleaner: UrlCleaner,
+    /// Malformed lines skipped the last time the file was read
+    skipped_lines: Vec<SkippedLine>,
+    /// Address ranges blocked after resolution; not part of the file
+    blocked_ranges: Vec<IpRange>,
+}
+
+/// A tracker list line that isn't a valid entry
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SkippedLine {
+    /// 1-based line number
+    pub number: usize,
+    pub line: String,
+    pub reason: &'static str,
+}
+
+/// Entries read from a tracker list, and the lines that had to be skipped
+#[derive(Clone, Debug, Default)]
+pub struct ParsedTrackers {
+    pub trackers: HashSet<String>,
+    pub skipped: Vec<SkippedLine>,
+}
+
+/// Strips known tracking parameters from URLs
+pub struct UrlCleaner {
     tracking_params: HashSet<String>,
+    /// Prefixes from wildcard rules such as `utm_*`
+    tracking_param_prefixes: Vec<String>,
+}
+
+/// Canonical form of a host for blocklist matching: lowercase, without a
+/// port or the trailing dot of a fully qualified name.
+///
+/// Applied to both list entries and requested hosts, so `EXAMPLE.COM.`
+/// and `example.com:443` match an entry of `example.com`.
+pub fn normalize_host(host: &str) -> String {
+    let host = host.trim();
+    let host = match host.strip_prefix('[') {
+        // Bracketed IPv6 literal, possibly followed by a port
+        Some(rest) => rest.split(']').next().unwrap_or(rest),
+        // Only strip a port when there's a single colon, so bare IPv6 stays intact
+        None => match host.split_once(':') {
+            Some((name, port)) if !port.contains(':') => name,
+            _ => host,
+        },
+    };
+    host.trim_end_matches('.').to_lowercase()
+}
+
+/// Read one tracker list line: `None` for blank and comment lines, or the
+/// normalized entry, with inline `# comments` and a leading `*.` dropped.
+pub fn parse_tracker_line(line: &str) -> Option<Result<String, &'static str>> {
+    let line = line.split('#').next().unwrap_or_default().trim();
+    if line.is_empty() {
+        return None;
+    }
+    if line.contains("://") {
+        return Some(Err("URL instead of a host name"));
+    }
+    if line.contains(char::is_whitespace) {
+        return Some(Err("contains spaces"));
+    }
+
+    let host = normalize_host(line.trim_start_matches("*."));
+    let valid = !host.is_empty()
+        && host.len() <= 253
+        && host.split('.').all(|label| !label.is_empty())
+        && host.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'));
+    Some(if valid { Ok(host) } else { Err("not a valid host name") })
+}
+
+/// The entry in `trackers` matching a normalized host or one of its parent
+/// domains, if any
+pub fn matching_entry<'a>(trackers: &'a HashSet<String>, host: &str) -> Option<&'a str> {
+    let mut candidate = host;
+    loop {
+        if let Some(tracker) = trackers.get(candidate) {
+            return Some(tracker.as_str());
+        }
+        match candidate.split_once('.') {
+            Some((_, parent)) => candidate = parent,
+            None => return None,
+        }
+    }
+}
+
+/// Parse a tracker list, keeping valid entries and noting malformed lines
+pub fn parse_tracker_list(content: &str) -> ParsedTrackers {
+    let mut parsed = ParsedTrackers::default();
+    for (i, line) in content.lines().enumerate() {
+        match parse_tracker_line(line) {
+            Some(Ok(host)) => {
+                parsed.trackers.insert(host);
+            }
+            Some(Err(reason)) => parsed.skipped.push(SkippedLine {
+                number: i + 1,
+                line: line.trim().to_string(),
+                reason,
+            }),
+            None => {}
+        }
+    }
+    parsed
+}
+
+/// Parse a tracker list, ignoring comments, empty and malformed lines
+pub(crate) fn parse_trackers(content: &str) -> HashSet<String> {
+    parse_tracker_list(content).trackers
+}
+
+/// Write `domains` as an exported tracker list, in the order given
+pub fn write_tracker_export<P: AsRef<Path>>(export_file: P, domains: &[&str]) -> io::Result<()> {
+    let content = format!(
+        "# Exported tracker list from DeTrack Proxy\n\
+         # Exported: {}\n\
+         # Total domains: {}\n\
+         {}\n",
+        Local::now().format("%Y-%m-%d %H:%M:%S"),
+        domains.len(),
+        domains.join("\n")
+    );
+    fs::write(export_file, content)
+}
+
+/// Print skipped lines so whoever edited `path` can fix them
+fn report_skipped(path: &Path, skipped: &[SkippedLine]) {
+    if skipped.is_empty() {
+        return;
+    }
+    eprintln!("⚠️ Skipped {} malformed lines in {}", skipped.len(), path.display());
+    for line in skipped {
+        eprintln!("  line {}: '{}' ({})", line.number, line.line, line.reason);
+    }
 }
 
 impl TrackerBlocker {
@@ -39,33 +176,40 @@ impl TrackerBlocker {
             Err(e) => return Err(e),
         };
         
-        // Parse trackers, ignoring comments and empty lines
-        let trackers = content
-            .lines()
-            .filter(|line| {
-                let line = line.trim();
-                !line.is_empty() && !line.starts_with('#')
-            })
-            .map(|line| line.trim().to_lowercase())
-            .collect();
-        
-        // Predefined tracking parameters
-        let tracking_params = [
-            "utm_source", "utm_medium", "utm_campaign", "utm_term", "utm_content",
-            "fbclid", "gclid", "msclkid", "dclid", "twclid", 
-            "_ga", "_hsenc", "_openstat", "ref", "referrer", "source",
-            "mc_cid", "mc_eid", // Mailchimp
-            "wickedid", // Wicked Reports
-            "yclid", // Yandex
-        ].iter().map(|&s| s.to_string()).collect();
-
+        let parsed = parse_tracker_list(&content);
+        report_skipped(&file_path, &parsed.skipped);
         Ok(Self { 
-            trackers,
+            trackers: parsed.trackers,
             tracker_file_path: file_path,
-            tracking_params,
+            cleaner: UrlCleaner::default(),
+            skipped_lines: parsed.skipped,
+            blocked_ranges: Vec::new(),
         })
     }
 
+    /// Block every address in `cidr`. Returns whether the range is new.
+    pub fn add_blocked_cidr(&mut self, cidr: &str) -> io::Result<bool> {
+        let range: IpRange = cidr.parse()?;
+        if self.blocked_ranges.contains(&range) {
+            return Ok(false);
+        }
+        self.blocked_ranges.push(range);
+        Ok(true)
+    }
+
+    /// Stop blocking `cidr`, returning whether it was blocked
+    pub fn remove_blocked_cidr(&mut self, cidr: &str) -> io::Result<bool> {
+        let range: IpRange = cidr.parse()?;
+        let before = self.blocked_ranges.len();
+        self.blocked_ranges.retain(|r| *r != range);
+        Ok(self.blocked_ranges.len() < before)
+    }
+
+    /// Whether `ip` is in a blocked range
+    pub fn is_ip_blocked(&self, ip: IpAddr) -> bool {
+        self.blocked_ranges.iter().any(|range| range.contains(ip))
+    }
+
     /// Check if a host is blocked
     /// 
     /// # Behavior
@@ -76,29 +220,29 @@ impl TrackerBlocker {
             return false;
         }
         
-        let host = host.to_lowercase();
-        
-        // Exact match
-        if self.trackers.contains(&host) {
-            println!("🚫 Blocked exact match: {}", host);
-            return true;
-        }
-        
-        // Domain suffix matches
-        for tracker in &self.trackers {
-            if host.ends_with(&format!(".{}", tracker)) {
-                println!("🚫 Blocked domain suffix match: {} (matches {})", host, tracker);
-                return true;
-            }
-        }
-        
-        println!("✅ Allowed: {}", host);
-        false
+        // No printing here: the stdout lock would serialize concurrent
+        // readers of the blocklist, and the proxy logs the outcome anyway
+        self.explain_match(host).is_some()
+    }
+    
+    /// Which entry blocks `host` and how, or `None` if it's allowed
+    pub fn explain_match(&self, host: &str) -> Option<MatchExplanation> {
+        let host = normalize_host(host);
+        self.matching_tracker(&host).map(|rule| MatchExplanation::new(&host, rule))
+    }
+    
+    /// Find the tracker entry that matches a normalized host, if any
+    ///
+    /// Looks up the host itself and then each parent domain (`a.b.c`, `b.c`,
+    /// `c`), so the cost depends on the number of labels rather than the
+    /// size of the list.
+    pub fn matching_tracker(&self, host: &str) -> Option<&str> {
+        matching_entry(&self.trackers, host)
     }
     
     /// Add a new tracker to the list
     pub fn add_tracker(&mut self, domain: &str) -> io::Result<()> {
-        let domain = domain.trim().to_lowercase();
+        let domain = normalize_host(domain);
         
         // Don't add if it already exists
         if self.trackers.contains(&domain) {
@@ -114,7 +258,7 @@ impl TrackerBlocker {
     
     /// Remove a tracker from the list
     pub fn remove_tracker(&mut self, domain: &str) -> io::Result<()> {
-        let domain = domain.trim().to_lowercase();
+        let domain = normalize_host(domain);
         
         // Remove from in-memory set
         self.trackers.remove(&domain);
@@ -150,6 +294,11 @@ impl TrackerBlocker {
         trackers
     }
     
+    /// Malformed lines skipped the last time the list file was read
+    pub fn skipped_lines(&self) -> &[SkippedLine] {
+        &self.skipped_lines
+    }
+    
     /// Get the number of trackers
     pub fn tracker_count(&self) -> usize {
         self.trackers.len()
@@ -169,58 +318,176 @@ impl TrackerBlocker {
         println!("==============================");
     }
     
-    /// Import trackers from another file
-    pub fn import_trackers<P: AsRef<Path>>(&mut self, import_file: P) -> io::Result<usize> {
-        let content = fs::read_to_string(import_file)?;
-        
-        let mut added_count = 0;
-        
-        for line in content.lines() {
-            let line = line.trim();
-            if line.is_empty() || line.starts_with('#') {
-                continue;
-            }
-            
-            let domain = line.to_lowercase();
-            if !self.trackers.contains(&domain) {
-                self.trackers.insert(domain);
-                added_count += 1;
+    /// Import trackers from another file in any format
+    /// [`parse_list`] recognizes, skipping malformed lines
+    pub fn import_trackers<P: AsRef<Path>>(&mut self, import_file: P) -> io::Result<ImportDiff> {
+        let content = fs::read_to_string(&import_file)?;
+        let parsed = parse_list(&content);
+        report_skipped(import_file.as_ref(), &parsed.skipped);
+        let domains: Vec<String> = parsed.trackers.into_iter().collect();
+        let mut diff = self.merge_trackers(&domains)?;
+        diff.malformed = parsed.skipped.len();
+        diff.ignored = parsed.ignored;
+        diff.format = parsed.format;
+        Ok(diff)
+    }
+    
+    /// Add several trackers, saving the file once. Returns which were new.
+    pub fn merge_trackers(&mut self, domains: &[String]) -> io::Result<ImportDiff> {
+        let mut added = Vec::new();
+        for domain in domains {
+            let domain = normalize_host(domain);
+            if self.trackers.insert(domain.clone()) {
+                added.push(domain);
             }
         }
         
         // Only save if we added any
-        if added_count > 0 {
+        if !added.is_empty() {
             self.save_trackers()?;
         }
         
-        Ok(added_count)
+        Ok(ImportDiff::new(added, domains.len()))
     }
     
     /// Export trackers to another file
     pub fn export_trackers<P: AsRef<Path>>(&self, export_file: P) -> io::Result<usize> {
-        let mut sorted_trackers: Vec<&String> = self.trackers.iter().collect();
+        let mut sorted_trackers: Vec<&str> = self.trackers.iter().map(|s| s.as_str()).collect();
         sorted_trackers.sort();
-        
-        // Prepare file content
-        let content = format!(
-            "# Exported tracker list from DeTrack Proxy\n\
-             # Exported: {}\n\
-             # Total domains: {}\n\
-             {}\n",
-            Local::now().format("%Y-%m-%d %H:%M:%S"),
-            sorted_trackers.len(),
-            sorted_trackers.iter().map(|s| s.as_str()).collect::<Vec<&str>>().join("\n")
-        );
-        
-        // Write to file
-        fs::write(export_file, content)?;
-        
+        write_tracker_export(export_file, &sorted_trackers)?;
         Ok(sorted_trackers.len())
     }
 
     /// Check if a parameter is a tracking parameter
     pub fn is_tracking_parameter(&self, param_name: &str) -> bool {
-        self.tracking_params.contains(&param_name.to_lowercase())
+        self.cleaner.is_tracking_parameter(param_name)
+    }
+
+    /// Names of the tracking parameters `clean_url` would remove from `url_str`
+    pub fn stripped_params(&self, url_str: &str) -> Vec<String> {
+        self.cleaner.stripped_params(url_str)
+    }
+
+    /// Clean URL by removing tracking parameters
+    pub fn clean_url(&self, url_str: &str) -> String {
+        self.cleaner.clean_url(url_str)
+    }
+}
+
+impl BlocklistSource for TrackerBlocker {
+    fn contains(&self, host: &str) -> bool {
+        self.is_blocked(host)
+    }
+
+    fn is_listed(&self, domain: &str) -> bool {
+        self.trackers.contains(domain)
+    }
+
+    fn add(&mut self, domain: &str) -> io::Result<()> {
+        self.add_tracker(domain)
+    }
+
+    fn merge(&mut self, domains: &[String]) -> io::Result<ImportDiff> {
+        self.merge_trackers(domains)
+    }
+
+    fn explain_match(&self, host: &str) -> Option<MatchExplanation> {
+        TrackerBlocker::explain_match(self, host)
+    }
+
+    fn remove(&mut self, domain: &str) -> io::Result<()> {
+        self.remove_tracker(domain)
+    }
+
+    fn add_blocked_cidr(&mut self, cidr: &str) -> io::Result<bool> {
+        TrackerBlocker::add_blocked_cidr(self, cidr)
+    }
+
+    fn remove_blocked_cidr(&mut self, cidr: &str) -> io::Result<bool> {
+        TrackerBlocker::remove_blocked_cidr(self, cidr)
+    }
+
+    fn blocked_cidrs(&self) -> Vec<String> {
+        self.blocked_ranges.iter().map(|range| range.to_string()).collect()
+    }
+
+    fn has_blocked_cidrs(&self) -> bool {
+        !self.blocked_ranges.is_empty()
+    }
+
+    fn is_ip_blocked(&self, ip: IpAddr) -> bool {
+        TrackerBlocker::is_ip_blocked(self, ip)
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = String> + '_> {
+        Box::new(self.trackers.iter().cloned())
+    }
+
+    /// A deleted file is recreated from the list in memory rather than
+    /// read as empty, so deleting it never unblocks everything.
+    fn reload(&mut self) -> io::Result<()> {
+        if self.restore_if_missing()? {
+            return Ok(());
+        }
+        let parsed = parse_tracker_list(&fs::read_to_string(&self.tracker_file_path)?);
+        report_skipped(&self.tracker_file_path, &parsed.skipped);
+        self.trackers = parsed.trackers;
+        self.skipped_lines = parsed.skipped;
+        Ok(())
+    }
+
+    fn restore_if_missing(&mut self) -> io::Result<bool> {
+        if self.tracker_file_path.try_exists()? {
+            return Ok(false);
+        }
+        eprintln!(
+            "⚠️ {} was deleted, recreating it with {} trackers",
+            self.tracker_file_path.display(),
+            self.trackers.len()
+        );
+        self.save_trackers()?;
+        Ok(true)
+    }
+
+    fn len(&self) -> usize {
+        self.tracker_count()
+    }
+}
+
+impl UrlCleaner {
+    /// Check if a parameter is a tracking parameter
+    pub fn is_tracking_parameter(&self, param_name: &str) -> bool {
+        let name = param_name.to_lowercase();
+        self.tracking_params.contains(&name)
+            || self.tracking_param_prefixes.iter().any(|prefix| name.starts_with(prefix.as_str()))
+    }
+
+    /// Add a parameter rule: an exact name, or a prefix ending in `*`
+    /// like `utm_*`
+    pub fn add_tracking_parameter(&mut self, rule: &str) {
+        let rule = rule.trim().to_lowercase();
+        match rule.strip_suffix('*') {
+            Some(prefix) if !prefix.is_empty() && !self.tracking_param_prefixes.iter().any(|p| p == prefix) => {
+                self.tracking_param_prefixes.push(prefix.to_string());
+            }
+            Some(_) => {}
+            None if !rule.is_empty() => {
+                self.tracking_params.insert(rule);
+            }
+            None => {}
+        }
+    }
+
+    /// Names of the tracking parameters `clean_url` would remove from `url_str`
+    pub fn stripped_params(&self, url_str: &str) -> Vec<String> {
+        match Url::parse(url_str) {
+            Ok(parsed_url) => parsed_url
+                .query_pairs()
+                .filter(|(key, _)| self.is_tracking_parameter(key))
+                .map(|(key, _)| key.into_owned())
+                .collect(),
+            Err(_) => Vec::new(),
+        }
     }
 
     /// Clean URL by removing tracking parameters
@@ -269,11 +536,32 @@ impl Default for TrackerBlocker {
         Self::new("trackers.txt").unwrap_or_else(|_| Self {
             trackers: HashSet::new(),
             tracker_file_path: PathBuf::from("trackers.txt"),
-            tracking_params: [
-                "utm_source", "utm_medium", "utm_campaign", "utm_term", "utm_content",
-                "fbclid", "gclid", "msclkid", "dclid", "twclid", 
-                "_ga", "_hsenc", "_openstat", "ref", "referrer", "source",
-            ].iter().map(|&s| s.to_string()).collect(),
+            cleaner: UrlCleaner::default(),
+            skipped_lines: Vec::new(),
+            blocked_ranges: Vec::new(),
         })
     }
-}
\ No newline at end of file
+}
+
+impl Default for UrlCleaner {
+    fn default() -> Self {
+        // Predefined tracking parameters
+        let tracking_params = [
+            "utm_source", "utm_medium", "utm_campaign", "utm_term", "utm_content",
+            "fbclid", "gclid", "msclkid", "dclid", "twclid", 
+            "_ga", "_hsenc", "_openstat", "ref", "referrer", "source",
+            "mc_cid", "mc_eid", // Mailchimp
+            "wickedid", // Wicked Reports
+            "yclid", // Yandex
+        ].iter().map(|&s| s.to_string()).collect();
+
+        let mut cleaner = Self { tracking_params, tracking_param_prefixes: Vec::new() };
+        for rule in [
+            "utm_*", // All UTM variants, e.g. utm_source_platform
+            "_hs*", // HubSpot
+        ] {
+            cleaner.add_tracking_parameter(rule);
+        }
+        cleaner
+    }
+}
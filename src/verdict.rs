@@ -0,0 +1,140 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::blocklist_source::MatchExplanation;
+
+/// What the proxy should do with a request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VerdictAction {
+    Allow,
+    Block,
+}
+
+/// Why a verdict was reached.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VerdictReason {
+    /// The host is on the user's allowlist.
+    Allowlisted,
+    /// The host has an unexpired temporary allow grant.
+    TemporarilyAllowed,
+    /// The host matched the tracker blocklist.
+    Blocklisted,
+    /// AI heuristics flagged the request as a likely tracker.
+    AiDetected,
+    /// A CONNECT to a STUN/TURN server, with STUN/TURN blocking on.
+    StunTurn,
+    /// The blocklist couldn't be checked and the proxy fails closed.
+    BlocklistUnavailable,
+    /// The host resolves into a blocked IP range.
+    BlockedAddress,
+    /// The user's decision script said to block.
+    Script,
+    /// No rule matched.
+    NoMatch,
+}
+
+/// Combined decision for a single request, as returned by
+/// `SharedState::evaluate_request`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestVerdict {
+    pub action: VerdictAction,
+    pub reason: VerdictReason,
+    /// The URL with tracking parameters stripped, if any were removed.
+    pub cleaned_url: Option<String>,
+}
+
+impl RequestVerdict {
+    pub fn allow(reason: VerdictReason) -> Self {
+        Self { action: VerdictAction::Allow, reason, cleaned_url: None }
+    }
+
+    pub fn block(reason: VerdictReason) -> Self {
+        Self { action: VerdictAction::Block, reason, cleaned_url: None }
+    }
+
+    pub fn is_blocked(&self) -> bool {
+        self.action == VerdictAction::Block
+    }
+
+    /// Whether no block rule and no allow rule decided the request, so
+    /// later checks such as IP ranges still apply. An AI flag the policy
+    /// only turns into a suggestion doesn't count as a decision.
+    pub fn is_undecided(&self) -> bool {
+        !self.is_blocked() && !matches!(self.reason, VerdictReason::Allowlisted | VerdictReason::TemporarilyAllowed)
+    }
+}
+
+impl VerdictReason {
+    /// Short machine-readable name, e.g. for response headers
+    pub fn code(&self) -> &'static str {
+        match self {
+            VerdictReason::Allowlisted => "allowlisted",
+            VerdictReason::TemporarilyAllowed => "temporarily-allowed",
+            VerdictReason::Blocklisted => "blocklisted",
+            VerdictReason::AiDetected => "ai-detected",
+            VerdictReason::StunTurn => "stun-turn",
+            VerdictReason::BlocklistUnavailable => "blocklist-unavailable",
+            VerdictReason::BlockedAddress => "ip-range",
+            VerdictReason::Script => "script",
+            VerdictReason::NoMatch => "no-match",
+        }
+    }
+}
+
+/// What happens when the AI flags a host the blocklist doesn't list.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AiConflictPolicy {
+    /// Allow the request and queue the host as a suggestion. IP ranges and
+    /// the decision script still apply, as for unmatched hosts.
+    #[default]
+    AiOnlySuggests,
+    /// Block the request, and queue the host as a suggestion.
+    AiCanBlock,
+    /// Go by the blocklist alone: no suggestion, and the request counts as
+    /// unmatched, so a decision script still gets a say.
+    BlocklistAlwaysWins,
+}
+
+impl AiConflictPolicy {
+    pub const ALL: [AiConflictPolicy; 3] = [
+        AiConflictPolicy::AiOnlySuggests,
+        AiConflictPolicy::AiCanBlock,
+        AiConflictPolicy::BlocklistAlwaysWins,
+    ];
+}
+
+impl fmt::Display for AiConflictPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AiConflictPolicy::AiOnlySuggests => "AI only suggests",
+            AiConflictPolicy::AiCanBlock => "AI can block",
+            AiConflictPolicy::BlocklistAlwaysWins => "blocklist always wins",
+        })
+    }
+}
+
+/// Where a host stands with the lists, as returned by
+/// `SharedState::classify_hosts`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HostStatus {
+    /// On the allowlist, which wins over the blocklist.
+    Allowlisted,
+    /// Allowed for now by a temporary allow, which also wins over the
+    /// blocklist.
+    TemporarilyAllowed,
+    /// Matched by the blocklist.
+    Blocked,
+    /// On neither list.
+    Unlisted,
+}
+
+/// List status of one host in a batch.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HostVerdict {
+    pub host: String,
+    pub status: HostStatus,
+    /// The blocklist entry matching the host, even when the allowlist wins.
+    pub rule: Option<MatchExplanation>,
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use common::temp_path;
+use detrack_proxy::ai_tracker::AITracker;
+
+/// Analyze a third-party request that the default weights flag as a tracker.
+fn analyze_third_party(tracker: &mut AITracker, host: &str) -> bool {
+    let url = format!("https://{}/collect/pixel?utm_source=x&uid=123", host);
+    tracker.is_likely_tracker(&url, host, Some("https://news.example/"))
+}
+
+#[test]
+fn false_positives_on_third_party_requests_lower_its_weight() {
+    let mut tracker = AITracker::new();
+    let initial = tracker.feature_weights().third_party_weight;
+
+    for i in 0..10 {
+        let host = format!("cdn{}.widgets.test", i);
+        // Once the weights have dropped enough the request stops being flagged
+        analyze_third_party(&mut tracker, &host);
+        tracker.report_false_positive(&host);
+    }
+
+    let weight = tracker.feature_weights().third_party_weight;
+    assert!(weight < initial, "{} should be below {}", weight, initial);
+    assert!(weight >= 0.0);
+}
+
+#[test]
+fn confirmed_trackers_raise_the_weights_that_fired() {
+    let mut tracker = AITracker::new();
+    let initial = tracker.feature_weights().clone();
+
+    assert!(analyze_third_party(&mut tracker, "px.ads.test"));
+    tracker.report_false_negative("px.ads.test");
+
+    let weights = tracker.feature_weights();
+    assert!(weights.third_party_weight > initial.third_party_weight);
+    // Features that weren't present are left alone
+    assert_eq!(weights.numeric_id_weight, initial.numeric_id_weight);
+}
+
+#[test]
+fn learned_weights_survive_save_and_load() {
+    let path = temp_path("ai_model.json");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let mut tracker = AITracker::new();
+    assert!(analyze_third_party(&mut tracker, "cdn.widgets.test"));
+    tracker.report_false_positive("cdn.widgets.test");
+
+    tracker.save(&path).unwrap();
+    let loaded = AITracker::load(&path).unwrap();
+
+    assert_eq!(loaded.feature_weights(), tracker.feature_weights());
+}
+
+#[test]
+fn no_referer_heuristic_raises_beacon_scores_only() {
+    let mut tracker = AITracker::new();
+    let pixel = "https://cdn.example/pixel";
+    let page = "https://cdn.example/index.html";
+    let pixel_before = tracker.score(pixel, "cdn.example", None);
+    let page_before = tracker.score(page, "cdn.example", None);
+
+    tracker.set_no_referer_heuristic(true);
+    let pixel_after = tracker.score(pixel, "cdn.example", None);
+    let page_after = tracker.score(page, "cdn.example", None);
+
+    assert!(pixel_after > page_after, "{} should exceed {}", pixel_after, page_after);
+    assert!(pixel_after > pixel_before);
+    // Entropy is summed in hash order, so scores can differ in the last bit
+    assert!((page_after - page_before).abs() < 1e-6);
+    // A same-site Referer still counts as first-party
+    let same_site = tracker.score(pixel, "cdn.example", Some("https://cdn.example/"));
+    assert!((same_site - pixel_before).abs() < 1e-6);
+}
+
+#[test]
+fn snoozed_hosts_are_not_suggested_until_expiry() {
+    use chrono::{Duration, Utc};
+
+    let state = common::state_with(&[]);
+    state.add_ai_suggested_tracker("beacon.test");
+    state.snooze_ai_suggestion("beacon.test", Duration::hours(1));
+    assert!(state.get_ai_suggested_trackers().is_empty());
+
+    state.add_ai_suggested_tracker("beacon.test");
+    assert!(state.get_ai_suggested_trackers().is_empty());
+
+    // An expired snooze lets the host be suggested again
+    state.snooze_ai_suggestion("beacon.test", Duration::zero());
+    state.add_ai_suggested_tracker("beacon.test");
+    assert_eq!(state.get_ai_suggested_trackers(), vec!["beacon.test"]);
+
+    let mut tracker = AITracker::new();
+    let now = Utc::now();
+    tracker.snooze("a.test", now + Duration::minutes(5));
+    assert!(tracker.is_snoozed("a.test", now));
+    assert!(!tracker.is_snoozed("a.test", now + Duration::minutes(6)));
+    assert!(tracker.snoozed_domains().is_empty());
+}
+
+#[test]
+fn pathologically_long_input_is_capped() {
+    let host = format!("{}.test", "ab.".repeat(3_333));
+    let capped: String = host.chars().take(253).collect();
+    assert_eq!(AITracker::calculate_entropy(&host), AITracker::calculate_entropy(&capped));
+
+    let tracker = AITracker::new();
+    let url = format!("https://{}/pixel?{}", host, "x=1&".repeat(5_000));
+    let started = std::time::Instant::now();
+    let score = tracker.score(&url, &host, Some("https://news.example/"));
+    assert!(score.is_finite());
+    assert!(started.elapsed() < std::time::Duration::from_millis(500));
+}
@@ -0,0 +1,38 @@
+use chrono::{Duration, TimeZone, Utc};
+use detrack_proxy::beacon::BeaconDetector;
+
+#[test]
+fn steady_identical_requests_are_flagged_once() {
+    let mut detector = BeaconDetector::new();
+    let start = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+
+    let results: Vec<Option<usize>> = (0..8)
+        .map(|i| detector.record("metrics.test", "/ping", start + Duration::seconds(i * 10), 6))
+        .collect();
+
+    assert_eq!(results[..5], [None; 5]);
+    assert_eq!(results[5], Some(6));
+    assert_eq!(results[6..], [None, None]);
+}
+
+#[test]
+fn irregular_requests_are_not_beacons() {
+    let mut detector = BeaconDetector::new();
+    let start = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+
+    // A burst of page loads followed by a long pause
+    for secs in [0, 1, 2, 3, 4, 50, 51] {
+        assert_eq!(detector.record("cdn.test", "/app.js", start + Duration::seconds(secs), 6), None);
+    }
+}
+
+#[test]
+fn requests_outside_the_window_are_not_counted() {
+    let mut detector = BeaconDetector::new();
+    let start = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+
+    // Steady, but only one request every 30 seconds
+    for i in 0..10 {
+        assert_eq!(detector.record("slow.test", "/ping", start + Duration::seconds(i * 30), 6), None);
+    }
+}
@@ -0,0 +1,182 @@
+mod common;
+
+use std::collections::HashSet;
+use std::io;
+use std::sync::Arc;
+
+use common::{get, spawn_proxy, spawn_upstream};
+use detrack_proxy::blocklist_source::BlocklistSource;
+use detrack_proxy::shared_state::SharedState;
+use hyper::StatusCode;
+
+/// A blocklist that only lives in memory and matches hosts exactly.
+struct MemoryList(HashSet<String>);
+
+impl BlocklistSource for MemoryList {
+    fn contains(&self, host: &str) -> bool {
+        self.0.contains(host)
+    }
+
+    fn add(&mut self, domain: &str) -> io::Result<()> {
+        self.0.insert(domain.to_string());
+        Ok(())
+    }
+
+    fn remove(&mut self, domain: &str) -> io::Result<()> {
+        self.0.remove(domain);
+        Ok(())
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = String> + '_> {
+        Box::new(self.0.iter().cloned())
+    }
+
+    fn reload(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+#[tokio::test]
+async fn proxy_works_with_a_custom_source() {
+    let list = MemoryList(["tracker.test".to_string()].into_iter().collect());
+    let state = Arc::new(SharedState::new(list));
+    state.disable_ai_detection();
+    let proxy = spawn_proxy(state.clone()).await;
+    let upstream = spawn_upstream().await;
+
+    let blocked = get(proxy, "http://tracker.test/pixel.gif").await;
+    let allowed = get(proxy, &format!("http://{}/page", upstream)).await;
+
+    assert_eq!(blocked.status, StatusCode::FORBIDDEN);
+    assert_eq!(allowed.status, StatusCode::OK);
+
+    state.add_tracker("ads.example").unwrap();
+    assert_eq!(state.get_trackers().unwrap(), vec!["ads.example", "tracker.test"]);
+}
+
+/// A blocklist that matches parent domains but only implements the
+/// required methods.
+struct ParentMatchingList(HashSet<String>);
+
+impl BlocklistSource for ParentMatchingList {
+    fn contains(&self, host: &str) -> bool {
+        self.0.iter().any(|domain| host == domain || host.ends_with(&format!(".{}", domain)))
+    }
+
+    fn add(&mut self, domain: &str) -> io::Result<()> {
+        self.0.insert(domain.to_string());
+        Ok(())
+    }
+
+    fn remove(&mut self, domain: &str) -> io::Result<()> {
+        self.0.remove(domain);
+        Ok(())
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = String> + '_> {
+        Box::new(self.0.iter().cloned())
+    }
+
+    fn reload(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+#[test]
+fn default_explain_match_reports_the_most_specific_entry() {
+    let list = ParentMatchingList(["tracker.test", "cdn.tracker.test"].map(String::from).into_iter().collect());
+
+    let explanation = list.explain_match("img.cdn.tracker.test").unwrap();
+    assert_eq!(explanation.rule, "cdn.tracker.test");
+    assert_eq!(list.explain_match("tracker.test").unwrap().rule, "tracker.test");
+    assert!(list.explain_match("example.test").is_none());
+}
+
+#[test]
+fn default_merge_adds_subdomains_of_listed_entries() {
+    let mut list = ParentMatchingList(["tracker.test"].map(String::from).into_iter().collect());
+
+    let diff = list.merge(&["cdn.tracker.test".to_string(), "tracker.test".to_string()]).unwrap();
+
+    assert_eq!(diff.added, ["cdn.tracker.test"]);
+    assert_eq!(list.len(), 2);
+}
+
+/// A blocklist whose lock gets poisoned the first time it's written to.
+struct PanickingList;
+
+impl BlocklistSource for PanickingList {
+    fn contains(&self, _host: &str) -> bool {
+        false
+    }
+
+    fn add(&mut self, _domain: &str) -> io::Result<()> {
+        panic!("simulated blocklist failure");
+    }
+
+    fn remove(&mut self, _domain: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = String> + '_> {
+        Box::new(std::iter::empty())
+    }
+
+    fn reload(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        0
+    }
+}
+
+/// Shared state whose blocklist lock is poisoned.
+fn state_with_broken_blocklist() -> Arc<SharedState> {
+    let state = Arc::new(SharedState::new(PanickingList));
+    state.disable_ai_detection();
+    let poisoner = Arc::clone(&state);
+    assert!(std::thread::spawn(move || poisoner.add_tracker("ads.example")).join().is_err());
+    state
+}
+
+#[tokio::test]
+async fn lock_failure_allows_requests_when_failing_open() {
+    let state = state_with_broken_blocklist();
+    let proxy = spawn_proxy(state.clone()).await;
+    let upstream = spawn_upstream().await;
+
+    let resp = get(proxy, &format!("http://{}/page", upstream)).await;
+
+    assert_eq!(resp.status, StatusCode::OK);
+    assert!(state
+        .get_log_entries()
+        .iter()
+        .any(|entry| entry.message.starts_with("🚨 Blocklist unavailable, allowing")));
+}
+
+#[tokio::test]
+async fn lock_failure_blocks_requests_when_failing_closed() {
+    let state = state_with_broken_blocklist();
+    state.set_fail_closed(true);
+    let proxy = spawn_proxy(state.clone()).await;
+    let upstream = spawn_upstream().await;
+
+    let resp = get(proxy, &format!("http://{}/page", upstream)).await;
+
+    assert_eq!(resp.status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(state.get_blocked_count(), 1);
+    assert!(state.check_blocked("anything.example"));
+    assert!(state
+        .get_log_entries()
+        .iter()
+        .any(|entry| entry.message.starts_with("🚨 Blocklist unavailable, blocking")));
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use common::blocker_with;
+use detrack_proxy::cli::{self, Action, Cli};
+
+#[test]
+fn no_arguments_start_the_app() {
+    assert_eq!(cli::parse(["detrack"]).unwrap(), Cli { headless: false, profile: None, action: None });
+    assert!(cli::parse(["detrack", "--headless"]).unwrap().headless);
+}
+
+#[test]
+fn subcommands_are_parsed() {
+    let action = |args: &[&str]| cli::parse(args.iter().copied()).unwrap().action;
+
+    assert_eq!(action(&["detrack", "add", "ads.test"]), Some(Action::Add("ads.test".into())));
+    assert_eq!(action(&["detrack", "remove", "ads.test"]), Some(Action::Remove("ads.test".into())));
+    assert_eq!(action(&["detrack", "list"]), Some(Action::List));
+    assert_eq!(action(&["detrack", "import", "in.txt"]), Some(Action::Import("in.txt".into())));
+    assert_eq!(action(&["detrack", "export", "out.txt"]), Some(Action::Export("out.txt".into())));
+    assert!(cli::parse(["detrack", "add"]).is_err());
+    assert!(cli::parse(["detrack", "frobnicate"]).is_err());
+    assert_eq!(cli::parse(["detrack", "list", "--profile", "work"]).unwrap().profile.as_deref(), Some("work"));
+}
+
+#[test]
+fn actions_edit_the_tracker_file() {
+    let mut blocker = blocker_with(&["tracker.test"]);
+
+    assert_eq!(Action::Add("Ads.Test".into()).run(&mut blocker).unwrap(), "➕ Added ads.test");
+    assert!(Action::Add("ads.test".into()).run(&mut blocker).unwrap().contains("already"));
+    assert_eq!(Action::List.run(&mut blocker).unwrap(), "ads.test\ntracker.test");
+    assert_eq!(Action::Remove("tracker.test".into()).run(&mut blocker).unwrap(), "➖ Removed tracker.test");
+    assert!(Action::Remove("tracker.test".into()).run(&mut blocker).unwrap().contains("isn't"));
+
+    let exported = common::temp_path("exported.txt");
+    std::fs::create_dir_all(exported.parent().unwrap()).unwrap();
+    Action::Export(exported.clone()).run(&mut blocker).unwrap();
+
+    let mut other = blocker_with(&[]);
+    let message = Action::Import(exported).run(&mut other).unwrap();
+    assert!(message.starts_with("📥 Imported 1 new domains"), "{}", message);
+    assert_eq!(other.get_trackers(), vec!["ads.test"]);
+}
@@ -0,0 +1,161 @@
+//! Helpers shared by the integration tests: a temporary tracker list, a
+//! mock upstream server and a proxy instance bound to an ephemeral port.
+#![allow(dead_code)]
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Incoming;
+use hyper::header::HeaderMap;
+use hyper::service::service_fn;
+use hyper::{Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use tokio::net::{TcpListener, TcpStream};
+
+use detrack_proxy::run_proxy::serve;
+use detrack_proxy::shared_state::SharedState;
+use detrack_proxy::tracker_blocker::TrackerBlocker;
+
+static FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Path to a fresh, not yet existing file in the system temp directory.
+pub fn temp_path(name: &str) -> PathBuf {
+    let n = FILE_COUNTER.fetch_add(1, Ordering::SeqCst);
+    std::env::temp_dir()
+        .join(format!("detrack-test-{}-{}", std::process::id(), n))
+        .join(name)
+}
+
+/// Write `trackers` to a temporary list and load a blocker from it.
+pub fn blocker_with(trackers: &[&str]) -> TrackerBlocker {
+    let path = temp_path("trackers.txt");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, trackers.join("\n")).unwrap();
+    TrackerBlocker::new(&path).expect("failed to create tracker blocker")
+}
+
+/// Shared state backed by a temporary tracker list.
+pub fn state_with(trackers: &[&str]) -> Arc<SharedState> {
+    Arc::new(SharedState::new(blocker_with(trackers)))
+}
+
+/// Start the proxy on an ephemeral port and return its address. The
+/// intranet bypass is turned off, since test upstreams listen on loopback
+/// addresses that would otherwise skip filtering.
+pub async fn spawn_proxy(state: Arc<SharedState>) -> SocketAddr {
+    state.set_intranet_bypass(false);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let _ = serve(listener, state).await;
+    });
+    addr
+}
+
+/// Start a mock upstream that echoes the request line and headers back as
+/// the response body.
+pub async fn spawn_upstream() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = match listener.accept().await {
+                Ok(conn) => conn,
+                Err(_) => return,
+            };
+            tokio::spawn(async move {
+                let service = service_fn(|req: Request<Incoming>| async move {
+                    Ok::<_, Infallible>(Response::new(Full::new(Bytes::from(echo(&req)))))
+                });
+                let _ = hyper::server::conn::http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await;
+            });
+        }
+    });
+    addr
+}
+
+fn echo(req: &Request<Incoming>) -> String {
+    let mut body = format!("upstream: {} {}\n", req.method(), req.uri());
+    for (name, value) in req.headers() {
+        body.push_str(&format!("{}: {}\n", name, value.to_str().unwrap_or("")));
+    }
+    body
+}
+
+/// Response as seen by the client.
+pub struct ProxyResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+/// Send a request through the proxy on a fresh connection.
+pub async fn send(proxy: SocketAddr, req: Request<Full<Bytes>>) -> ProxyResponse {
+    let stream = TcpStream::connect(proxy).await.unwrap();
+    let (mut sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+        .await
+        .unwrap();
+    tokio::spawn(async move {
+        let _ = conn.await;
+    });
+
+    let resp = sender.send_request(req).await.unwrap();
+    let status = resp.status();
+    let headers = resp.headers().clone();
+    let body = resp.into_body().collect().await.unwrap().to_bytes();
+    ProxyResponse {
+        status,
+        headers,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    }
+}
+
+/// Send an absolute-form `GET` for `uri` through the proxy.
+pub async fn get(proxy: SocketAddr, uri: &str) -> ProxyResponse {
+    let host = uri
+        .parse::<hyper::Uri>()
+        .ok()
+        .and_then(|u| u.authority().map(|a| a.to_string()))
+        .unwrap_or_default();
+    let req = Request::get(uri)
+        .header(hyper::header::HOST, host)
+        .body(Full::new(Bytes::new()))
+        .unwrap();
+    send(proxy, req).await
+}
+
+/// Write `raw` to the proxy as-is and return the status line of the reply.
+///
+/// For requests a well-behaved client library refuses to build, such as
+/// malformed CONNECT targets.
+pub async fn send_raw(proxy: SocketAddr, raw: &str) -> String {
+    open_raw(proxy, raw).await.1
+}
+
+/// Like [`send_raw`], but keep the connection open, e.g. to hold a tunnel.
+pub async fn open_raw(proxy: SocketAddr, raw: &str) -> (TcpStream, String) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut stream = TcpStream::connect(proxy).await.unwrap();
+    stream.write_all(raw.as_bytes()).await.unwrap();
+
+    let mut reply = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !reply.windows(2).any(|w| w == b"\r\n") {
+        let n = stream.read(&mut buf).await.unwrap();
+        if n == 0 {
+            break;
+        }
+        reply.extend_from_slice(&buf[..n]);
+    }
+
+    let status = String::from_utf8_lossy(&reply).lines().next().unwrap_or_default().to_string();
+    (stream, status)
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use common::temp_path;
+use detrack_proxy::config::{Config, EnvOverrides, DEFAULT_LISTEN_PORT};
+use detrack_proxy::verdict::AiConflictPolicy;
+
+#[test]
+fn missing_config_means_first_run() {
+    let path = temp_path("detrack_config.json");
+
+    assert_eq!(Config::load(&path).unwrap(), None);
+}
+
+#[test]
+fn config_round_trips() {
+    let path = temp_path("detrack_config.json");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let config = Config {
+        setup_complete: true,
+        listen_port: 9100,
+        tracker_list: "tracker_lists/trackers.txt".to_string(),
+        start_proxy_on_launch: false,
+        compact_ui: true,
+        colorblind_palette: true,
+        ai_model_path: "models/ai.json".to_string(),
+        assumed_speed_mbps: 100,
+        log_timestamp_format: "%Y-%m-%d %H:%M:%S".to_string(),
+        log_timestamps_utc: true,
+        profile: Some("work".to_string()),
+        decision_script: "scripts/decide.sh".to_string(),
+        seed_empty_blocklist: true,
+        blocked_cidrs: vec!["203.0.113.0/24".to_string()],
+        allowlist_subdomains: true,
+        binary_byte_units: true,
+        block_header_prefix: "X-Privacy".to_string(),
+        batch_log_appends: true,
+        ai_conflict_policy: AiConflictPolicy::AiCanBlock,
+        decision_cache_per_host: true,
+        bypass_hosts: vec!["corp.example".to_string()],
+    };
+
+    config.save(&path).unwrap();
+
+    assert_eq!(Config::load(&path).unwrap(), Some(config));
+}
+
+#[test]
+fn missing_fields_use_defaults() {
+    let path = temp_path("detrack_config.json");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, r#"{ "setup_complete": true }"#).unwrap();
+
+    let config = Config::load(&path).unwrap().unwrap();
+
+    assert!(config.setup_complete);
+    assert_eq!(config.listen_port, DEFAULT_LISTEN_PORT);
+    assert!(config.start_proxy_on_launch);
+    assert!(!config.compact_ui);
+}
+
+#[test]
+fn environment_overrides_are_parsed() {
+    let overrides = EnvOverrides::from_vars([
+        ("DETRACK_PORT", "8200"),
+        ("DETRACK_BLOCKLIST", "/etc/trackers.txt"),
+        ("DETRACK_AI", "off"),
+        ("DETRACK_BLOCKING", "TRUE"),
+        ("PATH", "/usr/bin"),
+    ])
+    .unwrap();
+
+    assert_eq!(overrides.listen_port, Some(8200));
+    assert_eq!(overrides.tracker_list.as_deref(), Some("/etc/trackers.txt"));
+    assert_eq!(overrides.ai_enabled, Some(false));
+    assert_eq!(overrides.blocking_enabled, Some(true));
+    assert_eq!(overrides.describe()[0], "DETRACK_PORT=8200");
+}
+
+#[test]
+fn unset_or_empty_variables_are_not_overrides() {
+    let overrides = EnvOverrides::from_vars([("DETRACK_PORT", " ")]).unwrap();
+
+    assert_eq!(overrides, EnvOverrides::default());
+    assert!(overrides.describe().is_empty());
+}
+
+#[test]
+fn invalid_override_values_are_errors() {
+    assert!(EnvOverrides::from_vars([("DETRACK_PORT", "80000")]).is_err());
+    assert!(EnvOverrides::from_vars([("DETRACK_AI", "maybe")]).is_err());
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use common::{open_raw, send_raw, spawn_proxy, spawn_upstream, state_with};
+
+async fn connect_status(target: &str) -> String {
+    let state = state_with(&[]);
+    let proxy = spawn_proxy(state).await;
+    send_raw(proxy, &format!("CONNECT {} HTTP/1.1\r\nHost: {}\r\n\r\n", target, target)).await
+}
+
+#[tokio::test]
+async fn connect_to_port_zero_is_rejected() {
+    let status = connect_status("example.com:0").await;
+    assert!(status.starts_with("HTTP/1.1 400"), "status was: {}", status);
+}
+
+#[tokio::test]
+async fn connect_without_port_is_rejected() {
+    let status = connect_status("example.com").await;
+    assert!(status.starts_with("HTTP/1.1 400"), "status was: {}", status);
+}
+
+#[tokio::test]
+async fn connect_with_empty_host_is_rejected() {
+    let status = connect_status(":443").await;
+    assert!(status.starts_with("HTTP/1.1 400"), "status was: {}", status);
+}
+
+#[tokio::test]
+async fn connect_with_non_numeric_port_is_rejected() {
+    let status = connect_status("example.com:https").await;
+    assert!(status.starts_with("HTTP/1.1 400"), "status was: {}", status);
+}
+
+#[tokio::test]
+async fn rejected_connect_is_logged_and_not_counted() {
+    let state = state_with(&[]);
+    let proxy = spawn_proxy(state.clone()).await;
+
+    let status = send_raw(proxy, "CONNECT example.com:0 HTTP/1.1\r\nHost: example.com:0\r\n\r\n").await;
+
+    assert!(status.starts_with("HTTP/1.1 400"), "status was: {}", status);
+    assert!(state.get_logs().iter().any(|log| log.contains("Rejected CONNECT")));
+    assert_eq!(state.get_allowed_count(), 0);
+}
+
+#[tokio::test]
+async fn valid_connect_is_accepted() {
+    let state = state_with(&[]);
+    let proxy = spawn_proxy(state.clone()).await;
+
+    let status = send_raw(proxy, "CONNECT 127.0.0.1:9 HTTP/1.1\r\nHost: 127.0.0.1:9\r\n\r\n").await;
+
+    assert!(status.starts_with("HTTP/1.1 200"), "status was: {}", status);
+    assert_eq!(state.get_allowed_count(), 1);
+}
+
+#[tokio::test]
+async fn connect_beyond_tunnel_limit_gets_service_unavailable() {
+    let state = state_with(&[]);
+    state.set_max_tunnels(1);
+    let upstream = spawn_upstream().await;
+    let proxy = spawn_proxy(state.clone()).await;
+    let connect = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n\r\n", upstream);
+
+    let (first, status) = open_raw(proxy, &connect).await;
+    assert!(status.starts_with("HTTP/1.1 200"), "status was: {}", status);
+    assert_eq!(state.get_active_tunnels(), 1);
+
+    let status = send_raw(proxy, &connect).await;
+    assert!(status.starts_with("HTTP/1.1 503"), "status was: {}", status);
+
+    // Closing the first tunnel frees its slot
+    drop(first);
+    for _ in 0..50 {
+        if state.get_active_tunnels() == 0 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert_eq!(state.get_active_tunnels(), 0);
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use bytes::Bytes;
+use common::{get, send, spawn_proxy, state_with};
+use http_body_util::Full;
+use hyper::{Request, StatusCode};
+
+#[tokio::test]
+async fn report_lists_blocked_domains() {
+    let state = state_with(&["tracker.test"]);
+    let proxy = spawn_proxy(state.clone()).await;
+    get(proxy, "http://tracker.test/pixel.gif").await;
+    get(proxy, "http://tracker.test/pixel.gif").await;
+
+    let resp = get(proxy, &format!("http://{}/__detrack/report", proxy)).await;
+
+    assert_eq!(resp.status, StatusCode::OK);
+    assert!(resp.headers["content-type"].to_str().unwrap().starts_with("text/html"));
+    assert!(resp.body.contains("<td>tracker.test</td><td>2</td>"), "body was: {}", resp.body);
+    // Control requests aren't counted as proxied requests
+    assert_eq!(state.get_allowed_count(), 0);
+}
+
+#[tokio::test]
+async fn unknown_control_endpoint_is_not_found() {
+    let state = state_with(&[]);
+    let proxy = spawn_proxy(state).await;
+
+    let resp = get(proxy, &format!("http://{}/__detrack/nope", proxy)).await;
+
+    assert_eq!(resp.status, StatusCode::NOT_FOUND);
+    assert_eq!(resp.headers["content-type"], "application/json");
+}
+
+#[tokio::test]
+async fn wrong_method_gets_json_error() {
+    let state = state_with(&[]);
+    let proxy = spawn_proxy(state).await;
+
+    let req = Request::post(format!("http://{}/__detrack/report", proxy))
+        .header(hyper::header::HOST, proxy.to_string())
+        .body(Full::new(Bytes::new()))
+        .unwrap();
+    let resp = send(proxy, req).await;
+
+    assert_eq!(resp.status, StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(resp.headers["allow"], "GET, HEAD");
+    let body: serde_json::Value = serde_json::from_str(&resp.body).unwrap();
+    assert_eq!(body, serde_json::json!({ "error": "Method not allowed", "code": 405 }));
+}
+
+#[tokio::test]
+async fn blocklist_endpoint_returns_both_lists() {
+    let state = state_with(&["tracker.test", "ads.test"]);
+    state.add_allowed_host("cdn.test");
+    let proxy = spawn_proxy(state).await;
+
+    let resp = get(proxy, &format!("http://{}/__detrack/blocklist", proxy)).await;
+
+    assert_eq!(resp.status, StatusCode::OK);
+    assert_eq!(resp.headers["content-type"], "application/json");
+    let body: serde_json::Value = serde_json::from_str(&resp.body).unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!({ "blocklist": ["ads.test", "tracker.test"], "allowlist": ["cdn.test"] })
+    );
+}
+
+#[tokio::test]
+async fn blocklist_add_round_trips() {
+    let state = state_with(&[]);
+    let proxy = spawn_proxy(state.clone()).await;
+
+    let req = Request::post(format!("http://{}/__detrack/blocklist/add?domain=tracker.test", proxy))
+        .header(hyper::header::HOST, proxy.to_string())
+        .header(hyper::header::ORIGIN, "moz-extension://abc")
+        .body(Full::new(Bytes::new()))
+        .unwrap();
+    let resp = send(proxy, req).await;
+    assert_eq!(resp.status, StatusCode::OK);
+
+    let resp = get(proxy, &format!("http://{}/__detrack/blocklist", proxy)).await;
+    let body: serde_json::Value = serde_json::from_str(&resp.body).unwrap();
+    assert_eq!(body["blocklist"], serde_json::json!(["tracker.test"]));
+    assert!(state.check_blocked("tracker.test"));
+}
+
+#[tokio::test]
+async fn web_pages_cannot_change_the_blocklist() {
+    let state = state_with(&["tracker.test"]);
+    let proxy = spawn_proxy(state.clone()).await;
+
+    let req = Request::post(format!("http://{}/__detrack/blocklist/remove?domain=tracker.test", proxy))
+        .header(hyper::header::HOST, proxy.to_string())
+        .header(hyper::header::ORIGIN, "https://evil.example")
+        .body(Full::new(Bytes::new()))
+        .unwrap();
+    let resp = send(proxy, req).await;
+
+    assert_eq!(resp.status, StatusCode::FORBIDDEN);
+    assert!(state.check_blocked("tracker.test"));
+}
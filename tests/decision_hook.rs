@@ -0,0 +1,163 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use common::{get, send, spawn_proxy, spawn_upstream, state_with, temp_path};
+use detrack_proxy::decision_hook::{DecisionHook, HookRequest, DEFAULT_HOOK_TIMEOUT, MAX_CONCURRENT_RUNS};
+use detrack_proxy::verdict::AiConflictPolicy;
+use http_body_util::Full;
+use hyper::{Request, StatusCode};
+
+/// Write an executable shell script with `body`
+fn script(body: &str) -> PathBuf {
+    let path = temp_path("decide.sh");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+fn request(host: &str) -> HookRequest {
+    HookRequest { host: host.to_string(), path: "/".to_string(), referer: None }
+}
+
+#[tokio::test]
+async fn script_can_block_requests_no_rule_matched() {
+    let decide = script(r#"if grep -q '"host":"127.0.0.1"' ; then echo '{"block": true}'; else echo '{"block": false}'; fi"#);
+    let state = state_with(&[]);
+    state.set_decision_hook(Some(decide), DEFAULT_HOOK_TIMEOUT);
+    let upstream = spawn_upstream().await;
+    let proxy = spawn_proxy(state.clone()).await;
+
+    let resp = get(proxy, &format!("http://{}/pixel", upstream)).await;
+
+    assert_eq!(resp.status, StatusCode::FORBIDDEN);
+    assert!(state.get_logs().iter().any(|log| log.contains("Decision script blocked: 127.0.0.1")));
+}
+
+/// Send a request the AI flags, under `policy`, with a script that blocks
+/// everything. Returns the response status and body, and the AI suggestions.
+async fn ai_flagged_request_with_script(policy: AiConflictPolicy) -> (StatusCode, String, Vec<String>) {
+    let state = state_with(&[]);
+    state.enable_ai_detection();
+    state.set_ai_confidence_threshold(0.1);
+    state.set_ai_conflict_policy(policy);
+    state.set_decision_hook(Some(script(r#"echo '{"block": true}'"#)), DEFAULT_HOOK_TIMEOUT);
+    let upstream = spawn_upstream().await;
+    let proxy = spawn_proxy(state.clone()).await;
+
+    let req = Request::get(format!("http://{}/collect/pixel?utm_source=x", upstream))
+        .header("host", upstream.to_string())
+        .header("referer", "http://news.example.org/")
+        .body(Full::new(Bytes::new()))
+        .unwrap();
+    let resp = send(proxy, req).await;
+    (resp.status, resp.body, state.get_ai_suggested_trackers())
+}
+
+#[tokio::test]
+async fn the_script_still_decides_when_the_ai_only_suggests() {
+    let (status, body, suggested) = ai_flagged_request_with_script(AiConflictPolicy::AiOnlySuggests).await;
+
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(body.contains("Decision script blocked"), "body was: {}", body);
+    assert_eq!(suggested.len(), 1);
+}
+
+#[tokio::test]
+async fn the_ai_blocks_before_the_script_when_it_can_block() {
+    let (status, body, suggested) = ai_flagged_request_with_script(AiConflictPolicy::AiCanBlock).await;
+
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(body.contains("AI detected and blocked"), "body was: {}", body);
+    assert_eq!(suggested.len(), 1);
+}
+
+#[tokio::test]
+async fn the_script_decides_when_the_blocklist_always_wins() {
+    let (status, body, suggested) = ai_flagged_request_with_script(AiConflictPolicy::BlocklistAlwaysWins).await;
+
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(body.contains("Decision script blocked"), "body was: {}", body);
+    assert!(suggested.is_empty());
+}
+
+#[tokio::test]
+async fn slow_scripts_time_out() {
+    let hook = DecisionHook::new(script("sleep 5"), Duration::from_millis(100));
+
+    let started = Instant::now();
+    assert!(hook.decide(&request("a.test")).await.is_err());
+    assert!(started.elapsed() < Duration::from_secs(2));
+}
+
+#[tokio::test]
+async fn answers_are_cached() {
+    let runs = temp_path("runs");
+    std::fs::create_dir_all(runs.parent().unwrap()).unwrap();
+    let hook = DecisionHook::new(
+        script(&format!("echo run >> {}\necho '{{\"block\": false}}'", runs.display())),
+        DEFAULT_HOOK_TIMEOUT,
+    );
+
+    assert_eq!(hook.decide(&request("a.test")).await, Ok(false));
+    assert_eq!(hook.decide(&request("a.test")).await, Ok(false));
+    assert_eq!(std::fs::read_to_string(&runs).unwrap().lines().count(), 1);
+}
+
+#[tokio::test]
+async fn invalid_answers_are_errors() {
+    let hook = DecisionHook::new(script("echo maybe"), DEFAULT_HOOK_TIMEOUT);
+    assert!(hook.decide(&request("a.test")).await.is_err());
+}
+
+#[tokio::test]
+async fn answers_can_be_cached_per_host() {
+    let runs = temp_path("runs");
+    std::fs::create_dir_all(runs.parent().unwrap()).unwrap();
+    let hook = DecisionHook::new(
+        script(&format!("echo run >> {}\necho '{{\"block\": true}}'", runs.display())),
+        DEFAULT_HOOK_TIMEOUT,
+    );
+    hook.set_cache_per_host(true);
+
+    let other_path = HookRequest { path: "/pixel.gif".to_string(), ..request("a.test") };
+    assert_eq!(hook.decide(&request("a.test")).await, Ok(true));
+    assert_eq!(hook.decide(&other_path).await, Ok(true));
+    assert_eq!(std::fs::read_to_string(&runs).unwrap().lines().count(), 1);
+}
+
+#[tokio::test]
+async fn concurrent_runs_are_limited() {
+    let hook = Arc::new(DecisionHook::new(
+        script("sleep 0.3\necho '{\"block\": false}'"),
+        Duration::from_secs(10),
+    ));
+
+    // Twice as many requests as runs allowed take two rounds
+    let started = Instant::now();
+    let mut runs = tokio::task::JoinSet::new();
+    for i in 0..MAX_CONCURRENT_RUNS * 2 {
+        let hook = Arc::clone(&hook);
+        runs.spawn(async move { hook.decide(&request(&format!("{}.test", i))).await });
+    }
+    while let Some(result) = runs.join_next().await {
+        assert_eq!(result.unwrap(), Ok(false));
+    }
+    assert!(started.elapsed() >= Duration::from_millis(600), "took {:?}", started.elapsed());
+}
+
+#[test]
+fn repeated_failures_are_logged_once_per_interval() {
+    let hook = DecisionHook::new(script("exit 1"), DEFAULT_HOOK_TIMEOUT);
+
+    assert_eq!(hook.note_failure(), Some(0));
+    assert_eq!(hook.note_failure(), None);
+    assert_eq!(hook.note_failure(), None);
+}
@@ -0,0 +1,29 @@
+use detrack_proxy::humanize::{format_bytes, format_count, ByteUnits};
+
+#[test]
+fn counts_switch_suffix_at_each_thousand() {
+    assert_eq!(format_count(0), "0");
+    assert_eq!(format_count(999), "999");
+    assert_eq!(format_count(1_000), "1.0K");
+    assert_eq!(format_count(1_234), "1.2K");
+    assert_eq!(format_count(999_949), "999.9K");
+    // Would round to 1000.0K
+    assert_eq!(format_count(999_950), "1.0M");
+    assert_eq!(format_count(3_400_000), "3.4M");
+    assert_eq!(format_count(2_000_000_000), "2.0B");
+    assert_eq!(format_count(u64::MAX), "18446744.1T");
+}
+
+#[test]
+fn bytes_use_decimal_or_binary_units() {
+    assert_eq!(format_bytes(999, ByteUnits::Decimal), "999 B");
+    assert_eq!(format_bytes(1_000, ByteUnits::Decimal), "1.0 KB");
+    assert_eq!(format_bytes(1_048_576, ByteUnits::Decimal), "1.0 MB");
+    assert_eq!(format_bytes(2_500_000_000, ByteUnits::Decimal), "2.5 GB");
+
+    assert_eq!(format_bytes(1_000, ByteUnits::Binary), "1000 B");
+    assert_eq!(format_bytes(1_023, ByteUnits::Binary), "1023 B");
+    assert_eq!(format_bytes(1_024, ByteUnits::Binary), "1.0 KiB");
+    assert_eq!(format_bytes(1_048_576, ByteUnits::Binary), "1.0 MiB");
+    assert_eq!(format_bytes(1_048_575, ByteUnits::Binary), "1.0 MiB");
+}
@@ -0,0 +1,77 @@
+use std::convert::Infallible;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use detrack_proxy::inspect::{InspectableBody, Inspector};
+use http_body_util::BodyExt;
+use hyper::body::{Body, Frame};
+
+/// A body of `count` chunks of `size` bytes each, made up as it's read
+struct Chunks {
+    count: usize,
+    size: usize,
+}
+
+impl Body for Chunks {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Result<Frame<Bytes>, Infallible>>> {
+        if self.count == 0 {
+            return Poll::Ready(None);
+        }
+        self.count -= 1;
+        Poll::Ready(Some(Ok(Frame::data(Bytes::from(vec![b'x'; self.size])))))
+    }
+}
+
+/// Records the largest buffer it was shown and what it finished with
+#[derive(Clone, Default)]
+struct Recorder {
+    largest: Arc<Mutex<usize>>,
+    finished_with: Arc<Mutex<Option<usize>>>,
+}
+
+impl Inspector for Recorder {
+    fn inspect(&mut self, buffered: &[u8]) -> bool {
+        let mut largest = self.largest.lock().unwrap();
+        *largest = (*largest).max(buffered.len());
+        false
+    }
+
+    fn finish(self: Box<Self>, buffered: &[u8]) {
+        *self.finished_with.lock().unwrap() = Some(buffered.len());
+    }
+}
+
+#[tokio::test]
+async fn large_body_streams_through_with_bounded_buffer() {
+    const CAP: usize = 4096;
+    let recorder = Recorder::default();
+    // 1 MiB in 1000-byte chunks
+    let mut body = InspectableBody::new(Chunks { count: 1024, size: 1000 }, CAP, recorder.clone());
+
+    let mut passed_through = 0;
+    while let Some(frame) = body.frame().await {
+        passed_through += frame.unwrap().into_data().unwrap().len();
+        assert!(body.buffered_len() <= CAP);
+    }
+
+    assert_eq!(passed_through, 1024 * 1000);
+    assert!(!body.is_inspecting());
+    assert!(*recorder.largest.lock().unwrap() <= CAP);
+    assert_eq!(*recorder.finished_with.lock().unwrap(), Some(CAP));
+}
+
+#[tokio::test]
+async fn short_body_is_inspected_in_full_when_it_ends() {
+    let recorder = Recorder::default();
+    let body = InspectableBody::new(Chunks { count: 3, size: 10 }, 4096, recorder.clone());
+
+    let collected = body.collect().await.unwrap().to_bytes();
+
+    assert_eq!(collected.len(), 30);
+    assert_eq!(*recorder.finished_with.lock().unwrap(), Some(30));
+}
@@ -0,0 +1,29 @@
+use std::net::IpAddr;
+
+use detrack_proxy::ip_range::IpRange;
+
+fn ip(text: &str) -> IpAddr {
+    text.parse().unwrap()
+}
+
+#[test]
+fn addresses_inside_and_outside_a_range() {
+    let range: IpRange = "203.0.113.0/24".parse().unwrap();
+    assert!(range.contains(ip("203.0.113.7")));
+    assert!(range.contains(ip("::ffff:203.0.113.7")));
+    assert!(!range.contains(ip("203.0.114.7")));
+    assert!(!range.contains(ip("2001:db8::1")));
+
+    let v6: IpRange = "2001:db8::/32".parse().unwrap();
+    assert!(v6.contains(ip("2001:db8:ffff::1")));
+    assert!(!v6.contains(ip("2001:db9::1")));
+}
+
+#[test]
+fn ranges_are_normalized_and_validated() {
+    assert_eq!("10.1.2.3/8".parse::<IpRange>().unwrap().to_string(), "10.0.0.0/8");
+    assert_eq!("192.0.2.1".parse::<IpRange>().unwrap().to_string(), "192.0.2.1/32");
+    assert_eq!("0.0.0.0/0".parse::<IpRange>().unwrap().to_string(), "0.0.0.0/0");
+    assert!("10.0.0.0/33".parse::<IpRange>().is_err());
+    assert!("tracker.test/24".parse::<IpRange>().is_err());
+}
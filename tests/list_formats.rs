@@ -0,0 +1,75 @@
+mod common;
+
+use common::blocker_with;
+use detrack_proxy::list_formats::{detect_format, parse_list, ListFormat};
+
+const HOSTS: &str = include_str!("fixtures/hosts.txt");
+const UBLOCK_FILTERS: &str = include_str!("fixtures/ublock_filters.txt");
+const UBLOCK_BACKUP: &str = include_str!("fixtures/ublock_backup.json");
+const PIHOLE_TELEPORTER: &str = include_str!("fixtures/pihole_teleporter.json");
+
+fn sorted(list: &detrack_proxy::list_formats::ImportedList) -> Vec<&str> {
+    let mut trackers: Vec<&str> = list.trackers.iter().map(String::as_str).collect();
+    trackers.sort();
+    trackers
+}
+
+#[test]
+fn formats_are_detected_from_content() {
+    assert_eq!(detect_format("ads.example.com\n# comment\n"), ListFormat::Plain);
+    assert_eq!(detect_format(HOSTS), ListFormat::Hosts);
+    assert_eq!(detect_format(UBLOCK_FILTERS), ListFormat::Adblock);
+    assert_eq!(detect_format("||ads.example.com^\n"), ListFormat::Adblock);
+    assert_eq!(detect_format(UBLOCK_BACKUP), ListFormat::UblockBackup);
+    assert_eq!(detect_format(PIHOLE_TELEPORTER), ListFormat::PiholeTeleporter);
+}
+
+#[test]
+fn hosts_files_skip_local_names() {
+    let list = parse_list(HOSTS);
+    assert_eq!(list.format, ListFormat::Hosts);
+    assert_eq!(
+        sorted(&list),
+        vec!["ads.example.com", "metrics.example.net", "pixel.example.org", "tracker.example.net"]
+    );
+    assert!(list.skipped.is_empty());
+}
+
+#[test]
+fn adblock_lists_keep_only_domain_rules() {
+    let list = parse_list(UBLOCK_FILTERS);
+    assert_eq!(sorted(&list), vec!["ads.example.com", "metrics.example.net", "tracker.example.net"]);
+    // $script, the exception, the cosmetic filter and the path rule
+    assert_eq!(list.ignored, 4);
+}
+
+#[test]
+fn ublock_backups_use_filters_and_dynamic_block_rules() {
+    let list = parse_list(UBLOCK_BACKUP);
+    assert_eq!(list.format, ListFormat::UblockBackup);
+    assert_eq!(sorted(&list), vec!["ads.example.com", "beacon.example.com"]);
+    assert_eq!(list.ignored, 4);
+}
+
+#[test]
+fn pihole_exports_use_enabled_exact_deny_entries() {
+    let list = parse_list(PIHOLE_TELEPORTER);
+    assert_eq!(list.format, ListFormat::PiholeTeleporter);
+    assert_eq!(sorted(&list), vec!["ads.example.com", "tracker.example.net"]);
+    assert_eq!(list.ignored, 3);
+}
+
+#[test]
+fn import_reports_the_detected_format() {
+    let mut blocker = blocker_with(&["ads.example.com"]);
+    let path = common::temp_path("filters.txt");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, UBLOCK_FILTERS).unwrap();
+
+    let diff = blocker.import_trackers(&path).unwrap();
+
+    assert_eq!(diff.format, ListFormat::Adblock);
+    assert_eq!(diff.added, vec!["metrics.example.net", "tracker.example.net"]);
+    assert_eq!(diff.summary(), "Added 2 new domains, skipped 1 duplicates and 4 unsupported rules");
+    assert!(blocker.is_blocked("cdn.tracker.example.net"));
+}
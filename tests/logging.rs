@@ -0,0 +1,206 @@
+mod common;
+
+use detrack_proxy::logging::{coalesce, group_by_host, redact_host, GroupOrder, LogEntry, LogFormat, LogKind, RequestInfo, TimestampFormat};
+
+#[test]
+fn redaction_hides_domain_but_keeps_suffix() {
+    let redacted = redact_host("ads.example.co.uk");
+    assert!(redacted.starts_with("***."), "{}", redacted);
+    assert!(redacted.ends_with(".co.uk"), "{}", redacted);
+    assert!(!redacted.contains("example"), "{}", redacted);
+}
+
+#[test]
+fn redaction_is_stable_per_site() {
+    let a = redact_host("a.example.com");
+    let b = redact_host("b.example.com");
+    assert_eq!(a, b);
+    assert_ne!(redact_host("example.com"), redact_host("example.org"));
+    assert_eq!(redact_host("example.com"), redact_host("EXAMPLE.com"));
+}
+
+#[test]
+fn redaction_of_ip_hides_address() {
+    let redacted = redact_host("192.168.1.10");
+    assert!(redacted.starts_with("ip-"), "{}", redacted);
+    assert!(!redacted.contains("192"), "{}", redacted);
+}
+
+fn entry(id: u64, kind: LogKind, message: &str) -> LogEntry {
+    LogEntry {
+        id,
+        timestamp: chrono::Local::now(),
+        kind,
+        host: Some("example.com".to_string()),
+        message: message.to_string(),
+        status: None,
+        request: None,
+    }
+}
+
+#[test]
+fn entries_are_grouped_by_host() {
+    let on = |id, host: &str| LogEntry { host: Some(host.to_string()), ..entry(id, LogKind::Request, "GET") };
+    let entries = vec![
+        on(1, "a.test"),
+        on(2, "a.test"),
+        on(3, "b.test"),
+        LogEntry { host: None, ..entry(4, LogKind::Info, "▶️ Proxy enabled") },
+    ];
+
+    let by_count = group_by_host(entries.clone(), GroupOrder::Count);
+    let hosts: Vec<&str> = by_count.iter().map(|g| g.host.as_str()).collect();
+    assert_eq!(hosts, vec!["a.test", "b.test"]);
+    let ids: Vec<u64> = by_count[0].entries.iter().map(|e| e.id).collect();
+    assert_eq!(ids, vec![2, 1]);
+
+    let by_recency = group_by_host(entries, GroupOrder::Recency);
+    let hosts: Vec<&str> = by_recency.iter().map(|g| g.host.as_str()).collect();
+    assert_eq!(hosts, vec!["b.test", "a.test"]);
+}
+
+#[test]
+fn consecutive_repeats_are_merged_with_a_count() {
+    let entries = vec![
+        entry(1, LogKind::Request, "GET example.com /"),
+        entry(2, LogKind::Request, "GET example.com /"),
+        entry(3, LogKind::Request, "GET example.com /"),
+        entry(4, LogKind::Blocked, "GET example.com /"),
+        entry(5, LogKind::Request, "GET example.com /"),
+    ];
+
+    let merged: Vec<(u64, usize)> = coalesce(entries).iter().map(|(e, n)| (e.id, *n)).collect();
+
+    // Runs keep their latest entry; a different kind breaks the run
+    assert_eq!(merged, vec![(3, 3), (4, 1), (5, 1)]);
+}
+
+#[test]
+fn clearing_archives_logs_when_enabled() {
+    use std::io::Read;
+
+    let state = common::state_with(&[]);
+    let dir = common::temp_path("logs");
+    state.set_log_archive_dir(Some(dir.clone()));
+    state.append_log("first".to_string());
+    state.append_log("second".to_string());
+
+    state.clear_logs();
+
+    let archives: Vec<_> = std::fs::read_dir(&dir).unwrap().map(|e| e.unwrap().path()).collect();
+    assert_eq!(archives.len(), 1);
+    let mut content = String::new();
+    flate2::read::GzDecoder::new(std::fs::File::open(&archives[0]).unwrap())
+        .read_to_string(&mut content)
+        .unwrap();
+    let messages: Vec<String> = content
+        .lines()
+        .map(|line| serde_json::from_str::<LogEntry>(line).unwrap().message)
+        .collect();
+    assert_eq!(messages, vec!["first", "second"]);
+    assert!(state.get_logs().iter().all(|log| !log.contains("first")));
+}
+
+#[test]
+fn plain_format_replaces_emoji_with_tags() {
+    let blocked = entry(1, LogKind::Blocked, "🚫 Blocked: example.com");
+    let info = entry(2, LogKind::Info, "▶️ Proxy enabled");
+
+    let timestamps = TimestampFormat::default();
+    assert!(blocked.format(LogFormat::Plain, &timestamps).ends_with("] [BLOCK] Blocked: example.com"));
+    assert!(info.format(LogFormat::Plain, &timestamps).ends_with("] [INFO] Proxy enabled"));
+    assert_eq!(info.format(LogFormat::Emoji, &timestamps), info.to_string());
+}
+
+#[test]
+fn timestamps_use_the_configured_format() {
+    let info = entry(1, LogKind::Info, "▶️ Proxy enabled");
+    let full = TimestampFormat::new("%Y-%m-%d %H:%M:%S", true).unwrap();
+
+    let expected = info.timestamp.with_timezone(&chrono::Utc).format("[%Y-%m-%d %H:%M:%S]").to_string();
+    assert!(info.format(LogFormat::Emoji, &full).starts_with(&expected));
+}
+
+#[test]
+fn invalid_timestamp_formats_are_rejected() {
+    assert!(TimestampFormat::new("%Q", false).is_err());
+    assert!(TimestampFormat::new("", false).is_err());
+
+    let state = common::state_with(&[]);
+    state.set_timestamp_format("%Y-%m-%d %H:%M", false).unwrap();
+    assert!(state.set_timestamp_format("%H:%", false).is_err());
+    assert_eq!(state.get_timestamp_format().pattern(), "%Y-%m-%d %H:%M");
+}
+
+#[test]
+fn request_is_rendered_as_a_quoted_curl_command() {
+    let get = RequestInfo {
+        method: "GET".to_string(),
+        url: "http://example.com/a?b=1&c=2".to_string(),
+        headers: vec![],
+    };
+    let post = RequestInfo {
+        method: "POST".to_string(),
+        url: "http://example.com/form".to_string(),
+        headers: vec![("X-Note".to_string(), "it's".to_string())],
+    };
+
+    assert_eq!(get.to_curl(), "curl 'http://example.com/a?b=1&c=2'");
+    assert_eq!(post.to_curl(), r#"curl -X POST 'http://example.com/form' -H 'X-Note: it'\''s'"#);
+}
+
+#[test]
+fn long_text_is_truncated_on_a_character_boundary() {
+    use detrack_proxy::logging::truncate_for_log;
+
+    assert_eq!(truncate_for_log("short", 10), "short");
+    assert_eq!(truncate_for_log("abcdefghij", 4), "abcd… [truncated 6 bytes]");
+    // "é" is two bytes, so the cut falls back before it
+    assert_eq!(truncate_for_log("aéb", 2), "a… [truncated 3 bytes]");
+}
+
+#[test]
+fn pinned_entries_survive_clearing() {
+    let state = common::state_with(&[]);
+    state.append_log("keep me".to_string());
+    let id = state.get_log_entries().last().unwrap().id;
+
+    state.pin_log_entry(id).unwrap();
+    state.pin_log_entry(id).unwrap();
+    state.clear_logs();
+
+    let pinned = state.get_pinned_logs();
+    assert_eq!(pinned.len(), 1);
+    assert_eq!(pinned[0].message, "keep me");
+    assert!(state.is_log_entry_pinned(id));
+    assert!(state.pin_log_entry(id).is_err(), "the entry is gone from the log");
+
+    state.set_clear_unpins(true);
+    state.clear_logs();
+    assert!(state.get_pinned_logs().is_empty());
+}
+
+#[test]
+fn batched_appends_show_up_once_drained() {
+    use detrack_proxy::shared_state::LOG_QUEUE_CAPACITY;
+
+    let state = common::state_with(&[]);
+    let before = state.get_log_entries().len();
+    state.set_log_batching(true);
+    for i in 0..LOG_QUEUE_CAPACITY + 5 {
+        state.append_log(format!("entry {}", i));
+    }
+    assert_eq!(state.get_log_entries().len(), before, "nothing is added until drained");
+
+    assert_eq!(state.drain_log_queue(), LOG_QUEUE_CAPACITY + 1);
+    let entries = state.get_log_entries();
+    assert_eq!(entries[before].message, "entry 0");
+    assert!(entries.windows(2).all(|pair| pair[0].id < pair[1].id));
+    let last = entries.last().unwrap();
+    assert_eq!(last.message, "⚠️ Dropped 5 log entries while the log queue was full");
+
+    // Turning batching off flushes what's queued
+    state.append_log("queued".to_string());
+    state.set_log_batching(false);
+    assert_eq!(state.get_log_entries().last().unwrap().message, "queued");
+}
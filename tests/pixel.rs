@@ -0,0 +1,105 @@
+mod common;
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+
+use bytes::Bytes;
+use common::{send, spawn_proxy, state_with};
+use detrack_proxy::pixel::{image_dimensions, is_tracking_pixel};
+use http_body_util::Full;
+use hyper::body::Incoming;
+use hyper::header::{CONTENT_TYPE, HOST, REFERER};
+use hyper::{Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+
+/// The classic 43-byte transparent 1x1 GIF
+const PIXEL_GIF: &[u8] = b"GIF89a\x01\x00\x01\x00\x80\x00\x00\xff\xff\xff\x00\x00\x00!\xf9\x04\x01\x00\x00\x00\x00,\x00\x00\x00\x00\x01\x00\x01\x00\x00\x02\x02D\x01\x00;";
+
+fn png_header(width: u32, height: u32) -> Vec<u8> {
+    let mut data = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+    data.extend_from_slice(&width.to_be_bytes());
+    data.extend_from_slice(&height.to_be_bytes());
+    data
+}
+
+#[test]
+fn dimensions_are_read_from_image_headers() {
+    assert_eq!(image_dimensions(PIXEL_GIF), Some((1, 1)));
+    assert_eq!(image_dimensions(&png_header(640, 480)), Some((640, 480)));
+
+    // SOI, an APP0 segment to skip, then a baseline frame header
+    let jpeg = b"\xff\xd8\xff\xe0\x00\x04ab\xff\xc0\x00\x11\x08\x00\x02\x00\x03";
+    assert_eq!(image_dimensions(jpeg), Some((3, 2)));
+
+    assert_eq!(image_dimensions(b"<html>"), None);
+    assert_eq!(image_dimensions(&png_header(1, 1)[..20]), None);
+}
+
+#[test]
+fn only_tiny_images_count_as_pixels() {
+    assert!(is_tracking_pixel((1, 1)));
+    assert!(is_tracking_pixel((9, 2)));
+    assert!(!is_tracking_pixel((10, 1)));
+    assert!(!is_tracking_pixel((640, 480)));
+}
+
+/// An upstream serving `body` as `image/gif` for every request.
+async fn spawn_image_upstream(body: &'static [u8]) -> SocketAddr {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let service = hyper::service::service_fn(move |_req: Request<Incoming>| async move {
+                    let resp = Response::builder()
+                        .header(CONTENT_TYPE, "image/gif")
+                        .body(Full::new(Bytes::from_static(body)))
+                        .unwrap();
+                    Ok::<_, Infallible>(resp)
+                });
+                let _ = hyper::server::conn::http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await;
+            });
+        }
+    });
+    addr
+}
+
+fn image_request(upstream: SocketAddr, referer: &str) -> Request<Full<Bytes>> {
+    Request::get(format!("http://{}/img.gif", upstream))
+        .header(HOST, upstream.to_string())
+        .header(REFERER, referer)
+        .body(Full::new(Bytes::new()))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn third_party_pixel_is_suggested_and_passed_through() {
+    let state = state_with(&[]);
+    state.disable_ai_detection();
+    state.set_response_inspection(true);
+    let upstream = spawn_image_upstream(PIXEL_GIF).await;
+    let proxy = spawn_proxy(state.clone()).await;
+
+    let resp = send(proxy, image_request(upstream, "http://news.example/article")).await;
+
+    assert_eq!(resp.status, StatusCode::OK);
+    assert!(resp.body.starts_with("GIF89a"));
+    assert_eq!(state.get_ai_suggested_trackers(), vec!["127.0.0.1".to_string()]);
+    assert!(state.get_logs().iter().any(|l| l.contains("served a 1x1 image")));
+}
+
+#[tokio::test]
+async fn first_party_images_are_not_inspected() {
+    let state = state_with(&[]);
+    state.disable_ai_detection();
+    state.set_response_inspection(true);
+    let upstream = spawn_image_upstream(PIXEL_GIF).await;
+    let proxy = spawn_proxy(state.clone()).await;
+
+    let resp = send(proxy, image_request(upstream, &format!("http://{}/", upstream))).await;
+
+    assert_eq!(resp.status, StatusCode::OK);
+    assert!(state.get_ai_suggested_trackers().is_empty());
+}
@@ -0,0 +1,39 @@
+mod common;
+
+use common::state_with;
+use detrack_proxy::presets::{default_preset, DEFAULT_PRESET_NAME, PRESETS};
+
+#[test]
+fn presets_grow_from_minimal_to_strict() {
+    assert_eq!(PRESETS.len(), 3);
+    for pair in PRESETS.windows(2) {
+        let (smaller, larger) = (pair[0].domains(), pair[1].domains());
+        assert!(!smaller.is_empty());
+        assert!(smaller.len() < larger.len(), "{} vs {}", pair[0].name, pair[1].name);
+        for domain in &smaller {
+            assert!(larger.contains(domain), "{} is missing {}", pair[1].name, domain);
+        }
+    }
+    assert_eq!(PRESETS[0].domain_count(), PRESETS[0].domains().len());
+}
+
+#[test]
+fn default_preset_is_balanced() {
+    assert_eq!(default_preset().name, DEFAULT_PRESET_NAME);
+}
+
+#[test]
+fn applying_a_preset_merges_new_domains_only() {
+    let state = state_with(&["doubleclick.net", "example.org"]);
+    let preset = &PRESETS[0];
+
+    let added = state.apply_preset(preset).unwrap();
+    assert_eq!(added, preset.domain_count() - 1);
+
+    let trackers = state.get_trackers().unwrap();
+    assert!(trackers.contains(&"example.org".to_string()));
+    assert_eq!(trackers.len(), preset.domain_count() + 1);
+    assert!(state.check_blocked("adnxs.com"));
+
+    assert_eq!(state.apply_preset(preset).unwrap(), 0);
+}
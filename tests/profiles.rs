@@ -0,0 +1,57 @@
+mod common;
+
+use common::{state_with, temp_path};
+use detrack_proxy::config::Config;
+use detrack_proxy::profiles::{list_profiles, Profile};
+
+#[test]
+fn new_profiles_copy_the_template_blocklist() {
+    let root = temp_path("profiles");
+    let list = temp_path("trackers.txt");
+    std::fs::create_dir_all(list.parent().unwrap()).unwrap();
+    std::fs::write(&list, "tracker.test\n").unwrap();
+    let template = Config { tracker_list: list.display().to_string(), listen_port: 8200, ..Config::default() };
+
+    let work = Profile::named(&root, "work").unwrap();
+    let config = work.create(&template).unwrap();
+
+    assert_eq!(config.listen_port, 8200);
+    assert!(config.setup_complete);
+    assert!(config.tracker_list.starts_with(&root.join("work").display().to_string()));
+    assert_eq!(std::fs::read_to_string(&config.tracker_list).unwrap(), "tracker.test\n");
+    assert_eq!(work.load_config().unwrap(), Some(config));
+    assert!(work.create(&template).is_err());
+    assert_eq!(list_profiles(&root).unwrap(), vec!["work"]);
+}
+
+#[test]
+fn profile_names_are_validated() {
+    let root = temp_path("profiles");
+
+    assert!(Profile::named(&root, "home").is_ok());
+    assert!(Profile::named(&root, "").is_err());
+    assert!(Profile::named(&root, "../escape").is_err());
+    assert!(Profile::named(&root, "default").is_err());
+    assert!(list_profiles(&root).unwrap().is_empty());
+}
+
+#[test]
+fn loading_a_profile_swaps_blocklist_and_allowlist() {
+    let state = state_with(&["tracker.test"]);
+    let root = temp_path("profiles");
+    let home = Profile::named(&root, "home").unwrap();
+    let config = home.create(&Config::default()).unwrap();
+    std::fs::write(&config.tracker_list, "ads.example\n").unwrap();
+    std::fs::write(&home.allowlist_path, "cdn.example\n").unwrap();
+
+    state.load_profile(&home, &config).unwrap();
+
+    assert!(state.check_blocked("ads.example"));
+    assert!(!state.check_blocked("tracker.test"));
+    assert!(state.is_allowlisted("cdn.example"));
+    assert_eq!(state.get_profile_name().as_deref(), Some("home"));
+
+    state.add_allowed_host("news.example");
+    let saved = std::fs::read_to_string(&home.allowlist_path).unwrap();
+    assert_eq!(saved, "cdn.example\nnews.example\n");
+}
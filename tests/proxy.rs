@@ -0,0 +1,768 @@
+mod common;
+
+use bytes::Bytes;
+use common::{get, send, send_raw, spawn_proxy, spawn_upstream, state_with};
+use detrack_proxy::logging::{LogClass, LogKind};
+use http_body_util::Full;
+use hyper::header::HOST;
+use hyper::{Request, StatusCode};
+
+#[tokio::test]
+async fn allowed_request_is_forwarded() {
+    let state = state_with(&["tracker.test"]);
+    let upstream = spawn_upstream().await;
+    let proxy = spawn_proxy(state.clone()).await;
+
+    let resp = get(proxy, &format!("http://{}/hello", upstream)).await;
+
+    assert_eq!(resp.status, StatusCode::OK);
+    assert!(resp.body.starts_with("upstream: GET"), "body was: {}", resp.body);
+    assert!(resp.body.contains("/hello"));
+    assert_eq!(state.get_allowed_count(), 1);
+    assert_eq!(state.get_blocked_count(), 0);
+}
+
+#[tokio::test]
+async fn blocked_host_gets_forbidden() {
+    let state = state_with(&["tracker.test"]);
+    let proxy = spawn_proxy(state.clone()).await;
+
+    let resp = get(proxy, "http://tracker.test/pixel.gif").await;
+
+    assert_eq!(resp.status, StatusCode::FORBIDDEN);
+    assert_eq!(state.get_blocked_count(), 1);
+    assert_eq!(state.get_allowed_count(), 0);
+}
+
+#[tokio::test]
+async fn subdomain_of_blocked_host_gets_forbidden() {
+    let state = state_with(&["tracker.test"]);
+    let proxy = spawn_proxy(state.clone()).await;
+
+    let resp = get(proxy, "http://cdn.tracker.test/").await;
+
+    assert_eq!(resp.status, StatusCode::FORBIDDEN);
+    assert_eq!(state.get_blocked_count(), 1);
+}
+
+#[tokio::test]
+async fn stats_are_recorded_per_domain() {
+    let state = state_with(&["tracker.test"]);
+    let upstream = spawn_upstream().await;
+    let proxy = spawn_proxy(state.clone()).await;
+
+    get(proxy, &format!("http://{}/a", upstream)).await;
+    get(proxy, &format!("http://{}/b", upstream)).await;
+    get(proxy, "http://tracker.test/").await;
+
+    assert_eq!(state.get_allowed_count(), 2);
+    assert_eq!(state.get_blocked_count(), 1);
+
+    let stats = state.get_stats();
+    let upstream_stat = &stats[&upstream.ip().to_string()];
+    assert_eq!(upstream_stat.requests, 2);
+    assert_eq!(upstream_stat.blocked, 0);
+    let tracker_stat = &stats["tracker.test"];
+    assert_eq!(tracker_stat.requests, 1);
+    assert_eq!(tracker_stat.blocked, 1);
+}
+
+#[tokio::test]
+async fn allowed_requests_are_not_logged_when_disabled() {
+    let state = state_with(&["tracker.test"]);
+    state.set_log_allowed(false);
+    let upstream = spawn_upstream().await;
+    let proxy = spawn_proxy(state.clone()).await;
+
+    get(proxy, &format!("http://{}/quiet", upstream)).await;
+    get(proxy, "http://tracker.test/loud").await;
+
+    let logs = state.get_logs();
+    assert!(!logs.iter().any(|l| l.contains("/quiet")), "logs: {:?}", logs);
+    assert!(logs.iter().any(|l| l.contains("Blocked request to tracker: tracker.test")));
+}
+
+#[tokio::test]
+async fn request_log_entries_carry_the_response_status() {
+    let state = state_with(&["tracker.test"]);
+    let upstream = spawn_upstream().await;
+    let proxy = spawn_proxy(state.clone()).await;
+
+    get(proxy, &format!("http://{}/ok", upstream)).await;
+    get(proxy, "http://tracker.test/").await;
+
+    let entries = state.get_log_entries();
+    let allowed = entries.iter().find(|e| e.message.contains("/ok")).expect("request logged");
+    assert_eq!(allowed.status, Some(200));
+    assert_eq!(allowed.class(), LogClass::Allowed);
+    let blocked = entries.iter().find(|e| e.kind == LogKind::Blocked).expect("block logged");
+    assert_eq!(blocked.status, Some(403));
+    assert_eq!(blocked.class(), LogClass::Blocked);
+}
+
+#[tokio::test]
+async fn origin_form_request_uses_host_header() {
+    let state = state_with(&["tracker.test"]);
+    let upstream = spawn_upstream().await;
+    let proxy = spawn_proxy(state.clone()).await;
+
+    let req = Request::get("/origin?x=1")
+        .header(HOST, upstream.to_string())
+        .body(Full::new(Bytes::new()))
+        .unwrap();
+    let resp = send(proxy, req).await;
+
+    assert_eq!(resp.status, StatusCode::OK);
+    assert!(resp.body.contains("/origin?x=1"), "body was: {}", resp.body);
+    assert_eq!(state.get_allowed_count(), 1);
+}
+
+#[tokio::test]
+async fn origin_form_request_to_tracker_is_blocked() {
+    let state = state_with(&["tracker.test"]);
+    let proxy = spawn_proxy(state.clone()).await;
+
+    let req = Request::get("/pixel.gif")
+        .header(HOST, "tracker.test")
+        .body(Full::new(Bytes::new()))
+        .unwrap();
+    let resp = send(proxy, req).await;
+
+    assert_eq!(resp.status, StatusCode::FORBIDDEN);
+    assert_eq!(state.get_blocked_count(), 1);
+}
+
+#[tokio::test]
+async fn origin_form_request_without_host_is_rejected() {
+    let state = state_with(&[]);
+    let proxy = spawn_proxy(state.clone()).await;
+
+    let status = send_raw(proxy, "GET /nowhere HTTP/1.0\r\n\r\n").await;
+
+    assert!(status.contains(" 400 "), "status was: {}", status);
+}
+
+/// An absolute-form GET for `uri` with an explicit Host header.
+fn get_with_host(uri: &str, host: &str) -> Request<Full<Bytes>> {
+    Request::get(uri)
+        .header(HOST, host)
+        .body(Full::new(Bytes::new()))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn matching_host_header_is_accepted_in_strict_mode() {
+    let state = state_with(&[]);
+    state.set_strict_host_check(true);
+    let upstream = spawn_upstream().await;
+    let proxy = spawn_proxy(state.clone()).await;
+
+    let uri = format!("http://{}/same", upstream);
+    let resp = send(proxy, get_with_host(&uri, &upstream.to_string().to_uppercase())).await;
+
+    assert_eq!(resp.status, StatusCode::OK);
+    assert!(!state.get_logs().iter().any(|l| l.contains("doesn't match")));
+}
+
+#[tokio::test]
+async fn mismatched_host_header_is_rejected_in_strict_mode() {
+    let state = state_with(&[]);
+    state.set_strict_host_check(true);
+    let upstream = spawn_upstream().await;
+    let proxy = spawn_proxy(state.clone()).await;
+
+    let uri = format!("http://{}/smuggle", upstream);
+    let resp = send(proxy, get_with_host(&uri, "internal.example")).await;
+
+    assert_eq!(resp.status, StatusCode::BAD_REQUEST);
+    assert_eq!(state.get_allowed_count(), 0);
+}
+
+#[tokio::test]
+async fn mismatched_host_header_is_logged_in_lenient_mode() {
+    let state = state_with(&[]);
+    let upstream = spawn_upstream().await;
+    let proxy = spawn_proxy(state.clone()).await;
+
+    let uri = format!("http://{}/smuggle", upstream);
+    let resp = send(proxy, get_with_host(&uri, "internal.example")).await;
+
+    assert_eq!(resp.status, StatusCode::OK);
+    assert!(state.get_logs().iter().any(|l| l.contains("Host header internal.example doesn't match")));
+}
+
+#[tokio::test]
+async fn request_over_header_limits_is_rejected() {
+    let state = state_with(&[]);
+    state.set_max_header_count(20);
+    state.set_max_header_bytes(4096);
+    let upstream = spawn_upstream().await;
+    let proxy = spawn_proxy(state.clone()).await;
+    let uri = format!("http://{}/", upstream);
+
+    let mut many = get_with_host(&uri, &upstream.to_string());
+    for i in 0..30 {
+        let name: hyper::header::HeaderName = format!("x-filler-{}", i).parse().unwrap();
+        many.headers_mut().insert(name, "1".parse().unwrap());
+    }
+    let mut large = get_with_host(&uri, &upstream.to_string());
+    large.headers_mut().insert("x-filler", "a".repeat(8192).parse().unwrap());
+
+    assert_eq!(send(proxy, many).await.status, StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE);
+    assert_eq!(send(proxy, large).await.status, StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE);
+    assert_eq!(send(proxy, get_with_host(&uri, &upstream.to_string())).await.status, StatusCode::OK);
+    assert!(state.get_logs().iter().any(|l| l.contains("headers (limit 20)")));
+}
+
+#[tokio::test]
+async fn upstream_response_over_header_limit_is_dropped() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut buf = [0u8; 4096];
+        let _ = stream.read(&mut buf).await;
+        let mut reply = String::from("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n");
+        for i in 0..40 {
+            reply.push_str(&format!("X-Filler-{}: 1\r\n", i));
+        }
+        reply.push_str("\r\n");
+        let _ = stream.write_all(reply.as_bytes()).await;
+    });
+
+    let state = state_with(&[]);
+    state.set_max_header_count(20);
+    let proxy = spawn_proxy(state.clone()).await;
+
+    let resp = get(proxy, &format!("http://{}/", upstream)).await;
+
+    assert_eq!(resp.status, StatusCode::BAD_GATEWAY);
+    assert!(state.get_logs().iter().any(|l| l.contains("Dropped response")));
+}
+
+#[tokio::test]
+async fn request_entries_keep_the_request_for_replay() {
+    let state = state_with(&["tracker.test"]);
+    state.disable_ai_detection();
+    let proxy = spawn_proxy(state.clone()).await;
+
+    get(proxy, "http://tracker.test/pixel.gif?id=1").await;
+    state.set_capture_headers(true);
+    get(proxy, "http://tracker.test/other").await;
+
+    let entries = state.get_log_entries();
+    let requests: Vec<_> = entries.iter().filter_map(|e| e.request.as_ref()).collect();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].to_curl(), "curl 'http://tracker.test/pixel.gif?id=1'");
+    assert!(requests[1].headers.iter().any(|(name, value)| name == "host" && value == "tracker.test"));
+}
+
+/// An upstream that only speaks cleartext HTTP/2 and reports the version it saw.
+async fn spawn_h2c_upstream() -> std::net::SocketAddr {
+    use hyper::body::Incoming;
+    use hyper_util::rt::{TokioExecutor, TokioIo};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let service = hyper::service::service_fn(|req: Request<Incoming>| async move {
+                    let body = format!("{:?} {}", req.version(), req.method());
+                    Ok::<_, std::convert::Infallible>(hyper::Response::new(Full::new(Bytes::from(body))))
+                });
+                let _ = hyper::server::conn::http2::Builder::new(TokioExecutor::new())
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await;
+            });
+        }
+    });
+    addr
+}
+
+#[tokio::test]
+async fn upstream_http2_is_used_when_enabled_and_supported() {
+    let state = state_with(&[]);
+    state.set_upstream_http2(true);
+    let upstream = spawn_h2c_upstream().await;
+    let proxy = spawn_proxy(state.clone()).await;
+
+    let resp = get(proxy, &format!("http://{}/page", upstream)).await;
+
+    assert_eq!(resp.status, StatusCode::OK);
+    assert_eq!(resp.body, "HTTP/2.0 GET");
+    assert_eq!(state.upstream_h2c_support(&upstream.to_string()), Some(true));
+}
+
+#[tokio::test]
+async fn te_headers_other_than_trailers_are_dropped_for_http2() {
+    let state = state_with(&[]);
+    state.set_upstream_http2(true);
+    let upstream = spawn_h2c_upstream().await;
+    let proxy = spawn_proxy(state.clone()).await;
+
+    let req = Request::get(format!("http://{}/page", upstream))
+        .header(HOST, upstream.to_string())
+        .header("te", "gzip")
+        .body(Full::new(Bytes::new()))
+        .unwrap();
+    let resp = send(proxy, req).await;
+
+    assert_eq!(resp.status, StatusCode::OK);
+    assert_eq!(resp.body, "HTTP/2.0 GET");
+}
+
+#[tokio::test]
+async fn upstream_http2_falls_back_to_http1() {
+    let state = state_with(&[]);
+    state.set_upstream_http2(true);
+    let upstream = spawn_upstream().await;
+    let proxy = spawn_proxy(state.clone()).await;
+
+    let resp = get(proxy, &format!("http://{}/page", upstream)).await;
+
+    assert_eq!(resp.status, StatusCode::OK);
+    assert!(resp.body.starts_with("upstream: GET"), "body was: {}", resp.body);
+    assert_eq!(state.upstream_h2c_support(&upstream.to_string()), Some(false));
+}
+
+#[tokio::test]
+async fn stopped_listener_closes_the_port() {
+    use detrack_proxy::run_proxy::run_proxy;
+
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let state = state_with(&[]);
+    state.set_listen_port(port);
+    let server = tokio::spawn(run_proxy(state.clone()));
+
+    let addr = std::net::SocketAddr::from(([127, 0, 0, 1], port));
+    let mut connected = false;
+    for _ in 0..50 {
+        if tokio::net::TcpStream::connect(addr).await.is_ok() {
+            connected = true;
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    assert!(connected);
+    assert!(state.is_listener_running());
+
+    state.stop_listener();
+    server.await.unwrap().unwrap();
+
+    assert!(!state.is_listener_running());
+    assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+}
+
+#[tokio::test]
+async fn closing_a_tunnel_frees_its_slot() {
+    use detrack_proxy::shared_state::ConnectionKind;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // An upstream that accepts and holds connections open
+    let upstream = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut held = Vec::new();
+        while let Ok((stream, _)) = upstream.accept().await {
+            held.push(stream);
+        }
+    });
+
+    let state = state_with(&[]);
+    let proxy = spawn_proxy(state.clone()).await;
+
+    let mut client = tokio::net::TcpStream::connect(proxy).await.unwrap();
+    client
+        .write_all(format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n\r\n", upstream_addr).as_bytes())
+        .await
+        .unwrap();
+    let mut buf = [0u8; 1024];
+    let n = client.read(&mut buf).await.unwrap();
+    assert!(String::from_utf8_lossy(&buf[..n]).starts_with("HTTP/1.1 200"));
+    client.write_all(b"hello").await.unwrap();
+
+    let mut connections = Vec::new();
+    for _ in 0..50 {
+        connections = state.get_connections();
+        if connections.first().is_some_and(|c| c.bytes > 0) {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    assert_eq!(connections.len(), 1);
+    assert_eq!(connections[0].kind, ConnectionKind::Tunnel);
+    assert_eq!(connections[0].host, "127.0.0.1");
+    assert_eq!(connections[0].bytes, 5);
+    assert_eq!(state.get_active_tunnels(), 1);
+
+    assert!(state.close_connection(connections[0].id));
+    assert!(!state.close_connection(connections[0].id));
+
+    // The aborted tunnel drops the client's connection and its slot
+    let n = client.read(&mut buf).await.unwrap_or(0);
+    assert_eq!(n, 0);
+    assert!(state.get_connections().is_empty());
+    assert_eq!(state.get_active_tunnels(), 0);
+}
+
+#[tokio::test]
+async fn tracking_params_are_stripped_from_safe_methods_only() {
+    let upstream = spawn_upstream().await;
+    let state = state_with(&[]);
+    state.disable_ai_detection();
+    let proxy = spawn_proxy(state.clone()).await;
+    let uri = format!("http://{}/form?id=7&utm_source=mail", upstream);
+
+    let resp = get(proxy, &uri).await;
+    assert!(resp.body.starts_with(&format!("upstream: GET http://{}/form?id=7\n", upstream)));
+
+    let post = || {
+        Request::post(uri.as_str())
+            .header(HOST, upstream.to_string())
+            .body(Full::new(Bytes::new()))
+            .unwrap()
+    };
+    let resp = send(proxy, post()).await;
+    assert!(resp.body.starts_with(&format!("upstream: POST {}\n", uri)));
+
+    state.set_strip_params_all_methods(true);
+    let resp = send(proxy, post()).await;
+    assert!(resp.body.starts_with(&format!("upstream: POST http://{}/form?id=7\n", upstream)));
+}
+
+#[tokio::test]
+async fn response_bytes_are_counted_per_domain() {
+    let upstream = spawn_upstream().await;
+    let state = state_with(&[]);
+    state.disable_ai_detection();
+    let proxy = spawn_proxy(state.clone()).await;
+
+    let req = Request::post(format!("http://{}/upload", upstream))
+        .header(HOST, upstream.to_string())
+        .body(Full::new(Bytes::from_static(b"hello")))
+        .unwrap();
+    let resp = send(proxy, req).await;
+
+    // The count is recorded once the body has streamed through
+    let mut stat = None;
+    for _ in 0..50 {
+        stat = state.get_stats().get("127.0.0.1").cloned();
+        if stat.as_ref().is_some_and(|s| s.response_bytes > 0) {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    let stat = stat.unwrap();
+    assert_eq!(stat.request_bytes, 5);
+    assert_eq!(stat.response_bytes, resp.body.len() as u64);
+}
+
+#[tokio::test]
+async fn stun_turn_connects_are_blocked_only_when_enabled() {
+    let state = state_with(&[]);
+    let proxy = spawn_proxy(state.clone()).await;
+    let connect = |target: &str| format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n\r\n", target);
+
+    assert!(!state.is_stun_turn_connect("stun.l.google.com", 443));
+
+    state.set_stun_turn_blocking(true);
+    let status = send_raw(proxy, &connect("stun.l.google.com:443")).await;
+    assert!(status.contains(" 403 "), "status was: {}", status);
+    let status = send_raw(proxy, &connect("127.0.0.1:3478")).await;
+    assert!(status.contains(" 403 "), "status was: {}", status);
+    assert_eq!(state.get_blocked_count(), 2);
+    assert!(state
+        .get_log_entries()
+        .iter()
+        .any(|entry| entry.message.starts_with("📞 Blocked CONNECT to STUN/TURN server: stun.l.google.com")));
+
+    // The list is editable and the allowlist still wins
+    assert!(state.add_stun_turn_host("*.turn.example").unwrap());
+    assert!(state.is_stun_turn_connect("relay.turn.example", 443));
+    assert!(state.remove_stun_turn_host("stun.l.google.com"));
+    assert!(!state.is_stun_turn_connect("stun.l.google.com", 443));
+    state.add_allowed_host("turn.example");
+    assert!(!state.is_stun_turn_connect("turn.example", 3478));
+}
+
+#[tokio::test]
+async fn fingerprint_headers_are_normalized_only_when_enabled() {
+    let state = state_with(&[]);
+    let upstream = spawn_upstream().await;
+    let proxy = spawn_proxy(state.clone()).await;
+    let request = || {
+        Request::get(format!("http://{}/", upstream))
+            .header(HOST, upstream.to_string())
+            .header("user-agent", "Quirky/1.0")
+            .header("accept-language", "de-CH")
+            .body(Full::new(Bytes::new()))
+            .unwrap()
+    };
+
+    let resp = send(proxy, request()).await;
+    assert!(resp.body.contains("user-agent: Quirky/1.0"), "body was: {}", resp.body);
+    assert!(resp.body.contains("accept-language: de-CH"), "body was: {}", resp.body);
+
+    state.set_header_normalization(true);
+    state.set_normalized_accept_language("");
+    let resp = send(proxy, request()).await;
+    assert!(
+        resp.body.contains(&format!("user-agent: {}", state.get_normalized_user_agent())),
+        "body was: {}",
+        resp.body
+    );
+    assert!(!resp.body.contains("accept-language"), "body was: {}", resp.body);
+}
+
+#[tokio::test]
+async fn chunked_response_bytes_are_counted_as_streamed() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // An upstream that answers without Content-Length, in three chunks
+    let upstream = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut stream, _) = upstream.accept().await.unwrap();
+        let mut buf = [0u8; 4096];
+        let _ = stream.read(&mut buf).await.unwrap();
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        for chunk in ["hello ", "chunked ", "world"] {
+            stream.write_all(format!("{:x}\r\n{}\r\n", chunk.len(), chunk).as_bytes()).await.unwrap();
+            stream.flush().await.unwrap();
+        }
+        stream.write_all(b"0\r\n\r\n").await.unwrap();
+    });
+
+    let state = state_with(&[]);
+    state.disable_ai_detection();
+    let proxy = spawn_proxy(state.clone()).await;
+
+    let resp = get(proxy, &format!("http://{}/stream", upstream_addr)).await;
+    assert_eq!(resp.body, "hello chunked world");
+    assert!(resp.headers.get("content-length").is_none());
+
+    let mut response_bytes = 0;
+    for _ in 0..50 {
+        response_bytes = state.get_stats().get("127.0.0.1").map_or(0, |s| s.response_bytes);
+        if response_bytes > 0 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    assert_eq!(response_bytes, "hello chunked world".len() as u64);
+}
+
+#[tokio::test]
+async fn third_party_requests_are_summarized_by_referring_site() {
+    let state = state_with(&["tracker.test"]);
+    state.disable_ai_detection();
+    let upstream = spawn_upstream().await;
+    let proxy = spawn_proxy(state.clone()).await;
+    let from_page = |uri: String, host: String, referer: &str| {
+        Request::get(uri)
+            .header(HOST, host)
+            .header("referer", referer)
+            .body(Full::new(Bytes::new()))
+            .unwrap()
+    };
+
+    let page = "http://www.news.test/story";
+    send(proxy, from_page("http://tracker.test/pixel.gif".into(), "tracker.test".into(), page)).await;
+    send(proxy, from_page("http://tracker.test/pixel.gif".into(), "tracker.test".into(), page)).await;
+    send(proxy, from_page(format!("http://{}/", upstream), upstream.to_string(), page)).await;
+    // First-party requests aren't part of the summary
+    send(proxy, from_page(format!("http://{}/", upstream), upstream.to_string(), "http://127.0.0.1/")).await;
+
+    let summary = state.get_site_summary();
+    assert_eq!(summary.len(), 1);
+    assert_eq!(summary[0].site, "news.test");
+    assert_eq!((summary[0].requests, summary[0].blocked), (3, 2));
+    assert_eq!(summary[0].contacts[0].host, "tracker.test");
+    assert_eq!(summary[0].contacts[0].blocked, 2);
+    assert_eq!(summary[0].contacts[1].host, "127.0.0.1");
+    assert_eq!(summary[0].contacts[1].blocked, 0);
+    // Only the allowed third party is queued for review
+    assert_eq!(state.get_third_party_review(), vec!["127.0.0.1".to_string()]);
+
+    state.mark_session_start();
+    assert!(state.get_site_summary().is_empty());
+}
+
+#[tokio::test]
+async fn unresolvable_host_gets_bad_gateway() {
+    let state = state_with(&[]);
+    let proxy = spawn_proxy(state.clone()).await;
+
+    // .invalid never resolves
+    let resp = get(proxy, "http://no-such-host.invalid/").await;
+
+    assert_eq!(resp.status, StatusCode::BAD_GATEWAY);
+    assert!(resp.body.contains("could not be resolved"), "body was: {}", resp.body);
+    assert!(state.get_logs().iter().any(|l| l.contains("Couldn't resolve no-such-host.invalid")));
+}
+
+#[tokio::test]
+async fn closed_port_gets_bad_gateway() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let closed = listener.local_addr().unwrap();
+    drop(listener);
+
+    let state = state_with(&[]);
+    let proxy = spawn_proxy(state.clone()).await;
+
+    let resp = get(proxy, &format!("http://{}/", closed)).await;
+
+    assert_eq!(resp.status, StatusCode::BAD_GATEWAY);
+    assert!(resp.body.contains("refused"), "body was: {}", resp.body);
+    assert!(state.get_logs().iter().any(|l| l.contains(&format!("Failed to connect to {}", closed))));
+}
+
+#[tokio::test]
+async fn requests_for_the_proxy_itself_are_rejected() {
+    let state = state_with(&[]);
+    let proxy = spawn_proxy(state.clone()).await;
+
+    let resp = get(proxy, &format!("http://{}/", proxy)).await;
+    assert_eq!(resp.status, StatusCode::MISDIRECTED_REQUEST);
+    assert!(resp.body.contains("addressed to DeTrack itself"), "body was: {}", resp.body);
+
+    let resp = get(proxy, &format!("http://localhost:{}/page", proxy.port())).await;
+    assert_eq!(resp.status, StatusCode::MISDIRECTED_REQUEST);
+
+    let status = send_raw(proxy, &format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n\r\n", proxy)).await;
+    assert!(status.starts_with("HTTP/1.1 421"), "status was: {}", status);
+    assert_eq!(state.get_allowed_count(), 0);
+    assert!(state.get_logs().iter().any(|log| log.contains("targets the proxy itself")));
+}
+
+#[tokio::test]
+async fn allowed_ports_are_forwarded_when_ports_are_restricted() {
+    let state = state_with(&[]);
+    let upstream = spawn_upstream().await;
+    state.set_port_restriction(true);
+    state.add_allowed_port(upstream.port());
+    let proxy = spawn_proxy(state.clone()).await;
+
+    let resp = get(proxy, &format!("http://{}/", upstream)).await;
+
+    assert_eq!(resp.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn other_ports_are_forbidden_when_ports_are_restricted() {
+    let state = state_with(&[]);
+    let upstream = spawn_upstream().await;
+    state.set_port_restriction(true);
+    let proxy = spawn_proxy(state.clone()).await;
+
+    let resp = get(proxy, &format!("http://{}/", upstream)).await;
+    assert_eq!(resp.status, StatusCode::FORBIDDEN);
+    assert!(resp.body.contains(&format!("port {}", upstream.port())), "body was: {}", resp.body);
+
+    let status = send_raw(proxy, &format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n\r\n", upstream)).await;
+    assert!(status.starts_with("HTTP/1.1 403"), "status was: {}", status);
+    assert_eq!(state.get_allowed_ports(), vec![80, 443]);
+}
+
+#[tokio::test]
+async fn hosts_resolving_into_a_blocked_range_are_blocked() {
+    let state = state_with(&[]);
+    state.disable_ai_detection();
+    let upstream = spawn_upstream().await;
+    let proxy = spawn_proxy(state.clone()).await;
+
+    state.add_blocked_cidr("10.0.0.0/8").unwrap();
+    let resp = get(proxy, &format!("http://{}/", upstream)).await;
+    assert_eq!(resp.status, StatusCode::OK);
+
+    state.add_blocked_cidr("127.0.0.0/8").unwrap();
+    let resp = get(proxy, &format!("http://{}/", upstream)).await;
+    assert_eq!(resp.status, StatusCode::FORBIDDEN);
+    assert!(resp.body.contains("blocked IP range"), "body was: {}", resp.body);
+    assert_eq!(state.get_blocked_count(), 1);
+}
+
+#[tokio::test]
+async fn hosts_the_ai_only_suggests_are_still_checked_against_blocked_ranges() {
+    let state = state_with(&[]);
+    state.enable_ai_detection();
+    state.set_ai_confidence_threshold(0.1);
+    let upstream = spawn_upstream().await;
+    let proxy = spawn_proxy(state.clone()).await;
+    state.add_blocked_cidr("127.0.0.0/8").unwrap();
+
+    let req = Request::get(format!("http://{}/collect/pixel?utm_source=x", upstream))
+        .header(HOST, upstream.to_string())
+        .header("referer", "http://news.example.org/")
+        .body(Full::new(Bytes::new()))
+        .unwrap();
+    let resp = send(proxy, req).await;
+
+    assert_eq!(state.get_ai_suggested_trackers(), [upstream.ip().to_string()]);
+    assert_eq!(resp.status, StatusCode::FORBIDDEN);
+    assert!(resp.body.contains("blocked IP range"), "body was: {}", resp.body);
+}
+
+#[tokio::test]
+async fn allowlisted_hosts_in_a_blocked_range_are_still_allowed() {
+    let state = state_with(&[]);
+    state.disable_ai_detection();
+    let upstream = spawn_upstream().await;
+    let proxy = spawn_proxy(state.clone()).await;
+    state.add_blocked_cidr("127.0.0.0/8").unwrap();
+    state.add_allowed_host(&upstream.ip().to_string());
+
+    let resp = get(proxy, &format!("http://{}/", upstream)).await;
+
+    assert_eq!(resp.status, StatusCode::OK);
+    assert_eq!(state.get_blocked_count(), 0);
+    assert_eq!(state.get_allowed_count(), 1);
+}
+
+#[tokio::test]
+async fn overlong_host_names_are_rejected_with_a_truncated_log() {
+    let state = state_with(&["tracker.test"]);
+    let proxy = spawn_proxy(state.clone()).await;
+    let host = format!("{}.tracker.test", "a".repeat(10_000));
+
+    let started = std::time::Instant::now();
+    let resp = get(proxy, &format!("http://{}/", host)).await;
+
+    assert_eq!(resp.status, StatusCode::BAD_REQUEST);
+    assert!(started.elapsed() < std::time::Duration::from_secs(2));
+    let entry = state
+        .get_log_entries()
+        .into_iter()
+        .find(|entry| entry.message.contains("-character host name"))
+        .expect("rejection was logged");
+    assert!(entry.message.contains("[truncated"), "message was: {}", entry.message);
+    assert!(entry.message.len() < 1200);
+    assert_eq!(state.get_blocked_count() + state.get_allowed_count(), 0);
+}
+
+#[tokio::test]
+async fn block_responses_say_why() {
+    let state = state_with(&["tracker.test"]);
+    let upstream = spawn_upstream().await;
+    let proxy = spawn_proxy(state.clone()).await;
+
+    let blocked = get(proxy, "http://cdn.tracker.test/").await;
+    assert_eq!(blocked.headers["x-detrack-blocked"], "true");
+    assert_eq!(blocked.headers["x-detrack-reason"], "suffix-match:tracker.test");
+
+    let allowed = get(proxy, &format!("http://{}/", upstream)).await;
+    assert_eq!(allowed.status, StatusCode::OK);
+    assert!(!allowed.headers.contains_key("x-detrack-blocked"));
+    assert!(!allowed.headers.contains_key("x-detrack-reason"));
+
+    state.set_block_header_prefix("X-Privacy").unwrap();
+    let renamed = get(proxy, "http://tracker.test/").await;
+    assert_eq!(renamed.headers["x-privacy-reason"], "exact-match:tracker.test");
+    assert!(state.set_block_header_prefix("X Bad").is_err());
+}
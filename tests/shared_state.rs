@@ -0,0 +1,682 @@
+mod common;
+
+use common::state_with;
+use detrack_proxy::verdict::{AiConflictPolicy, VerdictAction, VerdictReason};
+
+#[test]
+fn blocklisted_host_is_blocked() {
+    let state = state_with(&["tracker.test"]);
+    state.disable_ai_detection();
+
+    let verdict = state.evaluate_request("cdn.tracker.test", "http://cdn.tracker.test/", None);
+
+    assert_eq!(verdict.action, VerdictAction::Block);
+    assert_eq!(verdict.reason, VerdictReason::Blocklisted);
+    assert!(verdict.is_blocked());
+}
+
+#[test]
+fn unmatched_host_is_allowed() {
+    let state = state_with(&["tracker.test"]);
+    state.disable_ai_detection();
+
+    let verdict = state.evaluate_request("example.com", "http://example.com/", None);
+
+    assert_eq!(verdict.action, VerdictAction::Allow);
+    assert_eq!(verdict.reason, VerdictReason::NoMatch);
+    assert_eq!(verdict.cleaned_url, None);
+}
+
+#[test]
+fn allowlist_overrides_blocklist() {
+    let state = state_with(&["tracker.test"]);
+    state.disable_ai_detection();
+    state.add_allowed_host("tracker.test");
+
+    let verdict = state.evaluate_request("tracker.test", "http://tracker.test/", None);
+
+    assert_eq!(verdict.action, VerdictAction::Allow);
+    assert_eq!(verdict.reason, VerdictReason::Allowlisted);
+}
+
+#[test]
+fn allowlist_matches_exact_hosts_by_default() {
+    let state = state_with(&["tracker.test"]);
+    state.disable_ai_detection();
+    state.add_allowed_host("tracker.test");
+
+    assert!(state.is_allowlisted("tracker.test"));
+    assert!(!state.is_allowlisted("cdn.tracker.test"));
+    let verdict = state.evaluate_request("cdn.tracker.test", "http://cdn.tracker.test/", None);
+    assert_eq!(verdict.reason, VerdictReason::Blocklisted);
+}
+
+#[test]
+fn allowlist_and_temporary_allows_ignore_case_ports_and_trailing_dots() {
+    let state = state_with(&["allowed.test", "granted.test"]);
+    state.disable_ai_detection();
+    state.add_allowed_host("Allowed.Test.");
+    state.allow_temporarily("GRANTED.test.", chrono::Duration::seconds(60));
+
+    assert_eq!(state.get_allowlist(), ["allowed.test"]);
+    assert!(state.is_allowlisted("allowed.test"));
+    assert!(state.is_allowlisted("allowed.test.:443"));
+    assert!(state.is_temporarily_allowed("granted.test"));
+    assert!(state.is_temporarily_allowed("Granted.Test."));
+    let verdict = state.evaluate_request("allowed.test.", "http://allowed.test./", None);
+    assert_eq!(verdict.reason, VerdictReason::Allowlisted);
+}
+
+#[test]
+fn allowlist_can_cover_subdomains() {
+    let state = state_with(&["tracker.test"]);
+    state.disable_ai_detection();
+    state.add_allowed_host("tracker.test");
+    state.set_allowlist_subdomains(true);
+
+    assert!(state.is_allowlisted("a.cdn.tracker.test"));
+    assert!(!state.is_allowlisted("othertracker.test"));
+    let verdict = state.evaluate_request("cdn.tracker.test", "http://cdn.tracker.test/", None);
+    assert_eq!(verdict.action, VerdictAction::Allow);
+    assert_eq!(verdict.reason, VerdictReason::Allowlisted);
+}
+
+#[test]
+fn tracking_params_are_stripped() {
+    let state = state_with(&[]);
+    state.disable_ai_detection();
+
+    let verdict = state.evaluate_request(
+        "example.com",
+        "http://example.com/page?id=7&utm_source=news&fbclid=abc",
+        None,
+    );
+
+    assert!(!verdict.is_blocked());
+    assert_eq!(verdict.cleaned_url.as_deref(), Some("http://example.com/page?id=7"));
+}
+
+#[test]
+fn blocked_verdict_still_reports_cleaned_url() {
+    let state = state_with(&["tracker.test"]);
+    state.disable_ai_detection();
+
+    let verdict = state.evaluate_request("tracker.test", "http://tracker.test/?gclid=1", None);
+
+    assert!(verdict.is_blocked());
+    assert_eq!(verdict.cleaned_url.as_deref(), Some("http://tracker.test/"));
+}
+
+#[test]
+fn ai_detection_suggests_without_blocking() {
+    let state = state_with(&[]);
+    state.enable_ai_detection();
+    state.set_ai_confidence_threshold(0.1);
+
+    let verdict = state.evaluate_request(
+        "metrics.example.net",
+        "http://metrics.example.net/collect/pixel?utm_source=x",
+        Some("http://news.example.org/"),
+    );
+
+    assert_eq!(verdict.action, VerdictAction::Allow);
+    assert_eq!(verdict.reason, VerdictReason::AiDetected);
+    assert_eq!(state.get_ai_suggested_trackers(), vec!["metrics.example.net".to_string()]);
+}
+
+/// Evaluate a request the AI flags but the blocklist doesn't list, under `policy`
+fn ai_conflict(policy: AiConflictPolicy) -> (detrack_proxy::verdict::RequestVerdict, Vec<String>, String) {
+    let state = state_with(&["other.test"]);
+    state.enable_ai_detection();
+    state.set_ai_confidence_threshold(0.1);
+    state.set_ai_conflict_policy(policy);
+
+    let verdict = state.evaluate_request(
+        "metrics.example.net",
+        "http://metrics.example.net/collect/pixel?utm_source=x",
+        Some("http://news.example.org/"),
+    );
+    let conflict = state
+        .get_log_entries()
+        .into_iter()
+        .rev()
+        .find(|entry| entry.message.starts_with("⚖️"))
+        .map(|entry| entry.message)
+        .unwrap_or_default();
+    (verdict, state.get_ai_suggested_trackers(), conflict)
+}
+
+#[test]
+fn ai_conflicts_follow_the_policy() {
+    let (verdict, suggested, log) = ai_conflict(AiConflictPolicy::AiOnlySuggests);
+    assert_eq!((verdict.action, verdict.reason), (VerdictAction::Allow, VerdictReason::AiDetected));
+    assert_eq!(suggested, ["metrics.example.net"]);
+    assert!(log.contains("blocklist wins (AI only suggests)"), "{}", log);
+
+    let (verdict, suggested, log) = ai_conflict(AiConflictPolicy::AiCanBlock);
+    assert_eq!((verdict.action, verdict.reason), (VerdictAction::Block, VerdictReason::AiDetected));
+    assert_eq!(suggested, ["metrics.example.net"]);
+    assert!(log.contains("AI wins (AI can block)"), "{}", log);
+
+    let (verdict, suggested, log) = ai_conflict(AiConflictPolicy::BlocklistAlwaysWins);
+    assert_eq!((verdict.action, verdict.reason), (VerdictAction::Allow, VerdictReason::NoMatch));
+    assert!(suggested.is_empty());
+    assert!(log.contains("blocklist wins (blocklist always wins)"), "{}", log);
+}
+
+#[test]
+fn session_counts_start_from_baseline() {
+    let state = state_with(&[]);
+    state.record_request("a.example", false);
+    state.record_request("b.example", true);
+
+    state.mark_session_start();
+    state.record_request("c.example", true);
+
+    assert_eq!(state.get_blocked_count(), 2);
+    assert_eq!(state.get_session_blocked_count(), 1);
+    assert_eq!(state.get_allowed_count(), 1);
+    assert_eq!(state.get_session_allowed_count(), 0);
+
+    state.reset_stats();
+    assert_eq!(state.get_session_blocked_count(), 0);
+}
+
+#[test]
+fn grouped_stats_collapse_subdomains() {
+    let state = state_with(&[]);
+    state.record_request("a.ads.com", true);
+    state.record_request("b.ads.com", true);
+    state.record_request("c.ads.com", false);
+    state.record_request("www.example.co.uk", false);
+    state.record_request("static.example.co.uk", false);
+    state.record_request("192.168.1.10", false);
+
+    let groups = state.get_grouped_stats();
+
+    assert_eq!(groups.len(), 3);
+    assert_eq!(groups[0].domain, "ads.com");
+    assert_eq!(groups[0].requests, 3);
+    assert_eq!(groups[0].blocked, 2);
+    assert_eq!(groups[0].hosts.len(), 3);
+
+    let uk = groups.iter().find(|g| g.domain == "example.co.uk").unwrap();
+    assert_eq!(uk.requests, 2);
+
+    let ip = groups.iter().find(|g| g.domain == "192.168.1.10").unwrap();
+    assert_eq!(ip.hosts.len(), 1);
+}
+
+#[test]
+fn temporary_allow_overrides_blocklist_until_expiry() {
+    let state = state_with(&["tracker.test"]);
+    state.disable_ai_detection();
+
+    state.allow_temporarily("tracker.test", chrono::Duration::seconds(60));
+    let verdict = state.evaluate_request("tracker.test", "http://tracker.test/", None);
+    assert_eq!(verdict.action, VerdictAction::Allow);
+    assert_eq!(verdict.reason, VerdictReason::TemporarilyAllowed);
+
+    state.allow_temporarily("tracker.test", chrono::Duration::zero());
+    let verdict = state.evaluate_request("tracker.test", "http://tracker.test/", None);
+    assert_eq!(verdict.action, VerdictAction::Block);
+    assert!(state.get_temporary_allows().is_empty());
+    assert!(state.get_logs().iter().any(|log| log.contains("Temporary allow expired: tracker.test")));
+}
+
+#[test]
+fn ai_sampling_analyzes_the_configured_share_of_requests() {
+    let state = state_with(&[]);
+    state.set_ai_sample_percent(10);
+
+    for i in 0..100 {
+        let host = format!("site{}.example", i);
+        state.evaluate_request(&host, &format!("http://{}/", host), None);
+    }
+
+    assert_eq!(state.get_ai_sampling_counts(), (100, 10));
+}
+
+#[test]
+fn verdict_cache_is_invalidated_when_the_list_changes() {
+    let state = state_with(&["tracker.test"]);
+
+    assert!(!state.check_blocked("ads.example"));
+    assert!(!state.check_blocked("ads.example"));
+    assert_eq!(state.get_verdict_cache_stats(), (1, 1));
+
+    state.add_tracker("ads.example").unwrap();
+    assert!(state.check_blocked("ads.example"));
+
+    state.remove_tracker("ads.example").unwrap();
+    assert!(!state.check_blocked("ads.example"));
+    assert_eq!(state.get_verdict_cache_stats(), (1, 3));
+}
+
+#[test]
+fn prune_stats_removes_only_idle_domains() {
+    let state = state_with(&[]);
+    state.record_request("old.example", false);
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    state.record_request("new.example", true);
+
+    assert_eq!(state.prune_stats(chrono::Duration::milliseconds(25)), 1);
+
+    let stats = state.get_stats();
+    assert!(stats.contains_key("new.example"));
+    assert!(!stats.contains_key("old.example"));
+    // Totals are kept; only the per-domain breakdown is pruned
+    assert_eq!(state.get_allowed_count(), 1);
+}
+
+#[test]
+fn private_addresses_and_intranet_names_are_bypassed() {
+    let state = state_with(&[]);
+
+    assert!(state.is_bypassed("10.1.2.3"));
+    assert!(state.is_bypassed("192.168.0.10"));
+    assert!(state.is_bypassed("127.0.0.1"));
+    assert!(state.is_bypassed("[::1]"));
+    assert!(state.is_bypassed("printer.local"));
+    assert!(state.is_bypassed("localhost"));
+    assert!(!state.is_bypassed("8.8.8.8"));
+    assert!(!state.is_bypassed("notlocal"));
+    assert!(!state.is_bypassed("example.com"));
+
+    state.set_intranet_bypass(false);
+    assert!(!state.is_bypassed("10.1.2.3"));
+    assert!(!state.is_bypassed("printer.local"));
+}
+
+#[test]
+fn bypass_list_is_editable() {
+    let state = state_with(&[]);
+
+    assert_eq!(state.add_bypass_host("*.corp.example"), Ok(true));
+    assert_eq!(state.add_bypass_host("corp.example"), Ok(false));
+    assert!(state.is_bypassed("wiki.corp.example"));
+
+    assert!(state.remove_bypass_host("local"));
+    assert!(!state.is_bypassed("printer.local"));
+
+    state.set_bypass_hosts(&["*.Lab.example".to_string(), "not a host".to_string()]);
+    assert_eq!(state.get_bypass_hosts(), ["lab.example"]);
+}
+
+#[test]
+fn disabled_event_categories_are_not_logged() {
+    use detrack_proxy::logging::{LogEvent, LogKind};
+
+    let state = state_with(&[]);
+    state.set_event_logging(LogEvent::Error, false);
+    state.append_log_entry(LogKind::Error, Some("a.test"), "upstream failed".to_string());
+    state.append_log_entry(LogKind::Blocked, Some("b.test"), "blocked".to_string());
+
+    let entries = state.get_log_entries();
+    assert!(!entries.iter().any(|e| e.kind == LogKind::Error));
+    assert!(entries.iter().any(|e| e.kind == LogKind::Blocked));
+    assert!(!state.is_event_logged(LogEvent::Tunnel));
+}
+
+#[test]
+fn reload_trackers_picks_up_external_edits() {
+    use detrack_proxy::shared_state::SharedState;
+    use detrack_proxy::tracker_blocker::TrackerBlocker;
+
+    let path = common::temp_path("trackers.txt");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, "old.test\n").unwrap();
+    let state = SharedState::new(TrackerBlocker::new(&path).unwrap());
+    assert!(state.check_blocked("old.test"));
+
+    std::fs::write(&path, "new.test\nother.test\n").unwrap();
+    assert_eq!(state.reload_trackers(), Ok((1, 2)));
+    assert!(!state.check_blocked("old.test"));
+    assert!(state.check_blocked("new.test"));
+
+    // A failed reload keeps the current list
+    std::fs::remove_file(&path).unwrap();
+    std::fs::create_dir(&path).unwrap();
+    assert!(state.reload_trackers().is_err());
+    assert!(state.check_blocked("new.test"));
+}
+
+#[test]
+fn deleted_tracker_file_is_recreated_from_memory() {
+    use detrack_proxy::shared_state::SharedState;
+    use detrack_proxy::tracker_blocker::TrackerBlocker;
+
+    let path = common::temp_path("trackers.txt");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, "a.test\nb.test\n").unwrap();
+    let state = SharedState::new(TrackerBlocker::new(&path).unwrap());
+
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(state.reload_trackers(), Ok((2, 2)));
+    assert!(state.check_blocked("a.test"));
+    assert!(state.check_blocked("b.test"));
+    assert!(state.get_logs().iter().any(|log| log.contains("Tracker file was deleted")));
+
+    // The recreated file holds the whole list
+    let reloaded = TrackerBlocker::new(&path).unwrap();
+    assert_eq!(reloaded.get_trackers(), vec!["a.test", "b.test"]);
+    assert!(!state.restore_missing_tracker_file());
+}
+
+#[test]
+fn registrable_matching_respects_public_suffixes() {
+    let state = state_with(&["example.co.uk", "co.uk", "user.github.io"]);
+
+    // Plain suffix matching lets a public suffix entry block everything under it
+    assert!(state.check_blocked("shop.co.uk"));
+
+    state.set_registrable_matching(true);
+    assert!(state.check_blocked("example.co.uk"));
+    assert!(state.check_blocked("ads.example.co.uk"));
+    assert!(!state.check_blocked("notexample.co.uk"));
+    assert!(!state.check_blocked("shop.co.uk"));
+    assert!(state.check_blocked("cdn.user.github.io"));
+    assert!(!state.check_blocked("other.github.io"));
+}
+
+#[test]
+fn registrable_matching_covers_the_whole_site() {
+    let state = state_with(&["ads.example.com"]);
+    assert!(!state.check_blocked("www.example.com"));
+
+    state.set_registrable_matching(true);
+    assert!(state.check_blocked("www.example.com"));
+
+    // The site set follows list changes
+    state.remove_tracker("ads.example.com").unwrap();
+    assert!(!state.check_blocked("www.example.com"));
+}
+
+#[test]
+fn blocking_entry_names_the_listed_parent() {
+    let state = state_with(&["tracker.test", "ads.example.com"]);
+
+    assert_eq!(state.blocking_entry("cdn.eu.tracker.test").as_deref(), Some("tracker.test"));
+    assert_eq!(state.blocking_entry("ads.example.com").as_deref(), Some("ads.example.com"));
+    assert_eq!(state.blocking_entry("www.example.com"), None);
+
+    // Unblocking from a stats row removes the entry that matched
+    state.remove_tracker("tracker.test").unwrap();
+    assert_eq!(state.blocking_entry("cdn.eu.tracker.test"), None);
+    assert!(!state.check_blocked("cdn.eu.tracker.test"));
+}
+
+#[test]
+fn ai_suggestion_queue_drops_the_oldest_when_full() {
+    let state = state_with(&[]);
+    state.set_ai_suggestion_limit(3);
+
+    for host in ["a.test", "b.test", "c.test"] {
+        state.add_ai_suggested_tracker(host);
+    }
+    assert!(state.is_ai_suggestion_queue_full());
+    state.add_ai_suggested_tracker("b.test");
+    assert_eq!(state.get_ai_suggested_trackers(), vec!["a.test", "b.test", "c.test"]);
+
+    state.add_ai_suggested_tracker("d.test");
+    assert_eq!(state.get_ai_suggested_trackers(), vec!["b.test", "c.test", "d.test"]);
+
+    state.set_ai_suggestion_limit(1);
+    assert_eq!(state.get_ai_suggested_trackers(), vec!["d.test"]);
+    state.reject_ai_suggestion("d.test");
+    assert!(!state.is_ai_suggestion_queue_full());
+}
+
+#[test]
+fn failed_ai_model_saves_are_retried_on_flush() {
+    let state = state_with(&[]);
+    // A directory can't be written as a file
+    let dir = common::temp_path("models");
+    std::fs::create_dir_all(&dir).unwrap();
+    state.set_ai_model_path(Some(dir.clone()));
+
+    state.add_ai_suggested_tracker("a.test");
+    state.reject_ai_suggestion("a.test");
+    assert!(state.is_ai_model_unsaved());
+    assert!(state.flush_ai_model().is_err());
+
+    let file = dir.join("ai_model.json");
+    state.change_ai_model_path(file.clone()).unwrap();
+    assert!(!state.is_ai_model_unsaved());
+    assert!(file.exists());
+    assert!(state.flush_ai_model().is_ok());
+    assert!(state
+        .get_log_entries()
+        .iter()
+        .any(|entry| entry.message.starts_with("❌ Failed to save AI model")));
+}
+
+#[test]
+fn time_saved_counts_transfer_and_latency() {
+    let state = state_with(&[]);
+    assert_eq!(state.estimated_time_saved(25), std::time::Duration::ZERO);
+
+    // 1 MB at 8 Mbit/s takes a second to transfer
+    state.track_bandwidth(1_000_000, true);
+    assert_eq!(state.estimated_time_saved(8).as_millis(), 1000);
+
+    state.record_request("tracker.test", true);
+    assert_eq!(state.estimated_time_saved(8).as_millis(), 1050);
+}
+
+#[test]
+fn protection_score_reports_what_holds_it_back() {
+    let state = state_with(&["tracker.test"]);
+    state.disable_ai_detection();
+
+    let protection = state.get_protection_score();
+    assert_eq!(protection.factors.iter().map(|f| f.max).sum::<u32>(), 100);
+    let weak: Vec<&str> = protection.weak_factors().iter().map(|f| f.name).collect();
+    assert_eq!(weak, vec!["Blocklist size", "AI detection", "Parameter stripping"]);
+
+    state.enable_ai_detection();
+    state.set_strip_params_all_methods(true);
+    assert!(state.get_protection_score().score > protection.score);
+
+    // Allowing everything drags the block rate down
+    for _ in 0..10 {
+        state.record_request("news.example", false);
+    }
+    let weak: Vec<&str> = state.get_protection_score().weak_factors().iter().map(|f| f.name).collect();
+    assert!(weak.contains(&"Block rate"));
+}
+
+#[test]
+fn recent_errors_are_newest_first_and_limited() {
+    use detrack_proxy::logging::LogKind;
+
+    let state = state_with(&[]);
+    state.append_log_entry(LogKind::Error, Some("a.test"), "❌ first".to_string());
+    state.append_log_entry(LogKind::Request, Some("b.test"), "GET b.test /".to_string());
+    state.append_log_entry(LogKind::Error, Some("c.test"), "❌ second".to_string());
+    state.append_log_entry(LogKind::Error, Some("d.test"), "❌ third".to_string());
+
+    let errors: Vec<String> = state.get_recent_errors(2).into_iter().map(|e| e.message).collect();
+    assert_eq!(errors, vec!["❌ third", "❌ second"]);
+    assert_eq!(state.get_recent_errors(10).len(), 3);
+}
+
+#[test]
+fn third_party_review_queue_dedups_and_forgets_decided_hosts() {
+    let state = state_with(&[]);
+    state.record_unreviewed_third_party("cdn.test");
+    state.record_unreviewed_third_party("stats.test");
+    state.record_unreviewed_third_party("CDN.test");
+    assert_eq!(state.get_third_party_review(), vec!["cdn.test", "stats.test"]);
+
+    state.block_reviewed_host("stats.test").unwrap();
+    assert!(state.check_blocked("stats.test"));
+    state.dismiss_reviewed_host("cdn.test");
+    assert!(state.get_third_party_review().is_empty());
+
+    // Dismissed hosts stay out for the session
+    state.record_unreviewed_third_party("cdn.test");
+    assert!(state.get_third_party_review().is_empty());
+}
+
+#[test]
+fn empty_blocklist_is_seeded_from_the_default_preset() {
+    let state = state_with(&[]);
+    assert!(state.is_blocklist_empty());
+
+    assert!(state.seed_empty_blocklist().unwrap());
+    assert!(!state.is_blocklist_empty());
+    let seeded = state.get_trackers().unwrap().len();
+
+    // A list with entries is left alone
+    assert!(!state.seed_empty_blocklist().unwrap());
+    assert_eq!(state.get_trackers().unwrap().len(), seeded);
+}
+
+#[test]
+fn dashboard_stats_are_served_from_the_cache_until_refreshed() {
+    let state = state_with(&["tracker.test"]);
+    state.record_request("tracker.test", true);
+    let first = state.get_cached_dashboard_stats();
+    assert_eq!(first.domains.len(), 1);
+
+    state.record_request("cdn.test", false);
+    assert_eq!(state.get_cached_dashboard_stats().domains.len(), 1);
+
+    let refreshed = state.refresh_dashboard_stats();
+    assert_eq!(refreshed.domains.len(), 2);
+    assert!(refreshed.computed_at >= first.computed_at);
+
+    // Resetting doesn't leave stale figures behind
+    state.reset_stats();
+    assert!(state.get_cached_dashboard_stats().domains.is_empty());
+}
+
+#[test]
+fn paths_are_normalized_before_counting() {
+    use detrack_proxy::shared_state::normalize_stat_path;
+
+    assert_eq!(normalize_stat_path("/u/123456/collect"), "/u/:id/collect");
+    assert_eq!(normalize_stat_path("/s/5f3a9c2e1b/pixel.gif"), "/s/:id/pixel.gif");
+    assert_eq!(normalize_stat_path("/v/550e8400-e29b-41d4-a716-446655440000/"), "/v/:id");
+    assert_eq!(normalize_stat_path("/collect?v=2&tid=UA-1"), "/collect");
+    assert_eq!(normalize_stat_path("/about/facebook"), "/about/facebook");
+    assert_eq!(normalize_stat_path(""), "/");
+}
+
+#[test]
+fn top_paths_aggregate_across_hosts_when_enabled() {
+    let state = state_with(&[]);
+    state.record_path("a.test", "/collect", true);
+    assert!(state.get_top_paths(10).is_empty());
+
+    state.set_path_stats_enabled(true);
+    state.record_path("a.test", "/collect", true);
+    state.record_path("b.test", "/collect", false);
+    state.record_path("a.test", "/u/123/tr", true);
+
+    let top = state.get_top_paths(10);
+    assert_eq!(top.len(), 2);
+    assert_eq!(top[0].path, "/collect");
+    assert_eq!((top[0].requests, top[0].blocked, top[0].hosts.len()), (2, 1, 2));
+    assert_eq!(state.get_top_paths(1).len(), 1);
+
+    state.reset_stats();
+    assert!(state.get_top_paths(10).is_empty());
+}
+
+#[test]
+fn simulating_a_list_reports_newly_blocked_hosts_without_changing_the_blocklist() {
+    let state = state_with(&["ads.test"]);
+    state.add_allowed_host("cdn.metrics.test");
+    for _ in 0..3 {
+        state.record_request("pixel.metrics.test", false);
+    }
+    state.record_request("ads.test", true);
+    state.record_request("news.test", false);
+
+    let simulation = state.simulate_blocklist("metrics.test\nads.test\nnot a host\n");
+
+    assert_eq!(simulation.candidate_size, 2);
+    assert_eq!(simulation.malformed, 1);
+    let newly: Vec<&str> = simulation.newly_blocked.iter().map(|stat| stat.domain.as_str()).collect();
+    assert_eq!(newly, ["pixel.metrics.test"]);
+    assert_eq!(simulation.newly_blocked_requests(), 3);
+    assert_eq!(simulation.already_blocked, 1);
+    assert_eq!(simulation.allowlist_overlaps, ["cdn.metrics.test"]);
+    assert!(!state.check_blocked("pixel.metrics.test"));
+}
+
+#[test]
+fn hosts_are_classified_in_one_batch() {
+    use detrack_proxy::blocklist_source::MatchKind;
+    use detrack_proxy::verdict::HostStatus;
+
+    let state = state_with(&["tracker.test", "cdn.test"]);
+    state.add_allowed_host("cdn.test");
+    let hosts: Vec<String> = ["pixel.Tracker.test", "cdn.test", "news.test"].map(String::from).to_vec();
+
+    let verdicts = state.classify_hosts(&hosts);
+
+    let statuses: Vec<HostStatus> = verdicts.iter().map(|v| v.status).collect();
+    assert_eq!(statuses, [HostStatus::Blocked, HostStatus::Allowlisted, HostStatus::Unlisted]);
+    assert_eq!(verdicts[0].host, "pixel.Tracker.test");
+    let rule = verdicts[0].rule.as_ref().unwrap();
+    assert_eq!((rule.rule.as_str(), rule.kind), ("tracker.test", MatchKind::Subdomain));
+    // The allowlist wins, but the blocklist entry is still reported
+    assert_eq!(verdicts[1].rule.as_ref().unwrap().kind, MatchKind::Exact);
+    assert_eq!(verdicts[2].rule, None);
+}
+
+#[test]
+fn classified_hosts_match_the_way_requests_do() {
+    use detrack_proxy::verdict::HostStatus;
+
+    let state = state_with(&["ads.example.com", "tracker.test"]);
+    state.disable_ai_detection();
+    state.set_registrable_matching(true);
+    state.allow_temporarily("tracker.test", chrono::Duration::seconds(60));
+    let hosts: Vec<String> = ["www.example.com", "tracker.test"].map(String::from).to_vec();
+
+    let statuses: Vec<HostStatus> = state.classify_hosts(&hosts).iter().map(|v| v.status).collect();
+
+    assert_eq!(statuses, [HostStatus::Blocked, HostStatus::TemporarilyAllowed]);
+    for (host, status) in hosts.iter().zip(statuses) {
+        let verdict = state.evaluate_request(host, &format!("http://{}/", host), None);
+        assert_eq!(verdict.is_blocked(), status == HostStatus::Blocked, "{}", host);
+    }
+}
+
+#[test]
+fn exported_trackers_can_be_imported() {
+    let path = common::temp_path("export.txt");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let source = state_with(&["tracker.test", "ads.test"]);
+
+    assert_eq!(source.export_trackers(&path).unwrap(), 2);
+
+    let target = state_with(&["ads.test"]);
+    let diff = target.import_trackers(&path).unwrap();
+    assert_eq!(diff.added, vec!["tracker.test"]);
+    assert_eq!(target.get_trackers().unwrap(), vec!["ads.test", "tracker.test"]);
+}
+
+#[test]
+fn simulating_a_list_leaves_out_allowlisted_hosts() {
+    let state = state_with(&[]);
+    state.set_allowlist_subdomains(true);
+    state.add_allowed_host("metrics.test");
+    state.record_request("pixel.metrics.test", false);
+    state.record_request("ads.test", false);
+
+    let simulation = state.simulate_blocklist("pixel.metrics.test\nads.test\n");
+
+    let newly: Vec<&str> = simulation.newly_blocked.iter().map(|stat| stat.domain.as_str()).collect();
+    assert_eq!(newly, ["ads.test"]);
+    assert_eq!(simulation.allowlist_overlaps, ["metrics.test"]);
+
+    state.set_allowlist_subdomains(false);
+    let simulation = state.simulate_blocklist("pixel.metrics.test\nads.test\n");
+    assert_eq!(simulation.newly_blocked.len(), 2);
+    assert!(simulation.allowlist_overlaps.is_empty());
+}
@@ -0,0 +1,39 @@
+#![cfg(feature = "sqlite")]
+
+mod common;
+
+use common::temp_path;
+use detrack_proxy::blocklist_source::BlocklistSource;
+use detrack_proxy::sqlite_blocklist::SqliteBlocklist;
+
+#[test]
+fn imported_list_matches_hosts_and_subdomains() {
+    let list = temp_path("trackers.txt");
+    std::fs::create_dir_all(list.parent().unwrap()).unwrap();
+    std::fs::write(&list, "# comment\ntracker.test\n\nAds.Example.com\ntracker.test\n").unwrap();
+
+    let mut db = SqliteBlocklist::open_in_memory().unwrap();
+    assert_eq!(db.import_list(&list).unwrap(), 2);
+
+    assert_eq!(db.len(), 2);
+    assert!(db.contains("tracker.test"));
+    assert_eq!(db.matching_tracker("cdn.ads.example.com").as_deref(), Some("ads.example.com"));
+    assert!(!db.contains("notatracker.test"));
+}
+
+#[test]
+fn add_and_remove_persist_in_the_database() {
+    let path = temp_path("trackers.db");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+    let mut db = SqliteBlocklist::open(&path).unwrap();
+    db.add("tracker.test").unwrap();
+    db.add("pixel.example").unwrap();
+    db.remove("pixel.example").unwrap();
+    drop(db);
+
+    let db = SqliteBlocklist::open(&path).unwrap();
+    assert!(db.contains("a.tracker.test"));
+    assert!(!db.contains("pixel.example"));
+    assert_eq!(db.len(), 1);
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use common::temp_path;
+use detrack_proxy::summary_image::{Summary, SUMMARY_IMAGE_SIZE};
+
+fn ui_font() -> Vec<u8> {
+    egui::FontDefinitions::default().font_data["Ubuntu-Light"].font.to_vec()
+}
+
+#[test]
+fn summary_is_saved_as_png() {
+    let summary = Summary {
+        total: 12_000,
+        blocked: 10_432,
+        bandwidth_saved: 5_000_000,
+        top_domains: vec![("tracker.test".to_string(), 9_000)],
+    };
+    let path = temp_path("summary.png");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+    summary.save_png(&ui_font(), &path).unwrap();
+
+    let img = image::open(&path).unwrap().to_rgba8();
+    assert_eq!(img.dimensions(), SUMMARY_IMAGE_SIZE);
+    // Some text was drawn over the background
+    let background = *img.get_pixel(0, 0);
+    assert!(img.pixels().any(|p| *p != background));
+}
+
+#[test]
+fn summary_lists_most_blocked_domains_first() {
+    let state = common::state_with(&[]);
+    state.record_request("a.test", true);
+    state.record_request("b.test", true);
+    state.record_request("b.test", true);
+    state.record_request("c.test", false);
+
+    let summary = Summary::from_state(&state);
+
+    assert_eq!(summary.total, 4);
+    assert_eq!(summary.blocked, 3);
+    assert_eq!(summary.top_domains, vec![("b.test".to_string(), 2), ("a.test".to_string(), 1)]);
+}
+
+#[test]
+fn invalid_font_is_an_error() {
+    let summary = Summary { total: 0, blocked: 0, bandwidth_saved: 0, top_domains: Vec::new() };
+    assert!(summary.render(b"not a font").is_err());
+}
@@ -0,0 +1,214 @@
+mod common;
+
+use common::blocker_with;
+use detrack_proxy::tracker_blocker::UrlCleaner;
+
+#[test]
+fn matching_tracker_checks_each_parent_domain() {
+    let blocker = blocker_with(&["tracker.test", "ads.example.com"]);
+
+    assert_eq!(blocker.matching_tracker("tracker.test"), Some("tracker.test"));
+    assert_eq!(blocker.matching_tracker("a.b.tracker.test"), Some("tracker.test"));
+    assert_eq!(blocker.matching_tracker("cdn.ads.example.com"), Some("ads.example.com"));
+    assert_eq!(blocker.matching_tracker("example.com"), None);
+}
+
+#[test]
+fn matching_tracker_requires_a_label_boundary() {
+    let blocker = blocker_with(&["tracker.test"]);
+
+    assert_eq!(blocker.matching_tracker("notatracker.test"), None);
+    assert!(!blocker.is_blocked("notatracker.test"));
+}
+
+#[test]
+fn stripped_params_lists_tracking_params_only() {
+    let blocker = blocker_with(&[]);
+
+    assert_eq!(
+        blocker.stripped_params("https://example.com/page?id=7&utm_source=mail&fbclid=abc"),
+        vec!["utm_source", "fbclid"]
+    );
+    assert!(blocker.stripped_params("https://example.com/page?id=7").is_empty());
+}
+
+#[test]
+fn wildcard_rules_match_parameter_prefixes() {
+    let cleaner = UrlCleaner::default();
+
+    assert!(cleaner.is_tracking_parameter("utm_source_platform"));
+    assert!(cleaner.is_tracking_parameter("UTM_ID"));
+    assert!(cleaner.is_tracking_parameter("_hsmi"));
+    assert!(cleaner.is_tracking_parameter("_hsenc_xyz"));
+    assert_eq!(
+        cleaner.clean_url("https://example.com/search?q=rust&utm_source_platform=x&_hsmi=1"),
+        "https://example.com/search?q=rust"
+    );
+}
+
+#[test]
+fn wildcard_rules_keep_other_parameters() {
+    let mut cleaner = UrlCleaner::default();
+    cleaner.add_tracking_parameter("vendor_*");
+
+    assert!(cleaner.is_tracking_parameter("vendor_click"));
+    assert!(!cleaner.is_tracking_parameter("q"));
+    assert!(!cleaner.is_tracking_parameter("utm"));
+    assert!(!cleaner.is_tracking_parameter("vendor"));
+}
+
+#[test]
+fn hosts_are_normalized_before_matching() {
+    let blocker = blocker_with(&["example.com"]);
+
+    assert!(blocker.is_blocked("example.com."));
+    assert!(blocker.is_blocked("EXAMPLE.COM"));
+    assert!(blocker.is_blocked("example.com:443"));
+    assert!(blocker.is_blocked("Cdn.Example.Com.:80"));
+    assert!(!blocker.is_blocked("example.co"));
+}
+
+#[test]
+fn entries_are_normalized_when_added() {
+    let mut blocker = blocker_with(&["Tracker.Test."]);
+    blocker.add_tracker("ADS.example:8080").unwrap();
+
+    assert!(blocker.is_blocked("tracker.test"));
+    assert!(blocker.is_blocked("ads.example"));
+    assert!(blocker.get_trackers().contains(&"ads.example".to_string()));
+}
+
+#[test]
+fn normalize_host_keeps_ipv6_literals() {
+    use detrack_proxy::tracker_blocker::normalize_host;
+
+    assert_eq!(normalize_host("[::1]:443"), "::1");
+    assert_eq!(normalize_host("::1"), "::1");
+    assert_eq!(normalize_host("10.0.0.1:80"), "10.0.0.1");
+}
+
+#[test]
+fn inline_comments_are_dropped() {
+    use detrack_proxy::tracker_blocker::parse_tracker_line;
+
+    assert_eq!(parse_tracker_line("example.com # note"), Some(Ok("example.com".to_string())));
+    assert_eq!(parse_tracker_line("example.com#note"), Some(Ok("example.com".to_string())));
+    assert_eq!(parse_tracker_line("   # only a comment"), None);
+    assert_eq!(parse_tracker_line(""), None);
+}
+
+#[test]
+fn urls_are_rejected() {
+    use detrack_proxy::tracker_blocker::parse_tracker_line;
+
+    assert!(parse_tracker_line("https://example.com/ads").unwrap().is_err());
+    assert!(parse_tracker_line("http://example.com").unwrap().is_err());
+}
+
+#[test]
+fn lines_with_spaces_are_rejected() {
+    use detrack_proxy::tracker_blocker::parse_tracker_line;
+
+    assert!(parse_tracker_line("example.com other.com").unwrap().is_err());
+    assert!(parse_tracker_line("0.0.0.0 example.com").unwrap().is_err());
+}
+
+#[test]
+fn invalid_host_names_are_rejected() {
+    use detrack_proxy::tracker_blocker::parse_tracker_line;
+
+    assert!(parse_tracker_line("example..com").unwrap().is_err());
+    assert!(parse_tracker_line("ads/banner").unwrap().is_err());
+    assert!(parse_tracker_line("||example.com^").unwrap().is_err());
+    assert!(parse_tracker_line(&format!("{}.com", "a".repeat(260))).unwrap().is_err());
+    // Wildcards, IPs and underscores are fine
+    assert_eq!(parse_tracker_line("*.Ads.Example"), Some(Ok("ads.example".to_string())));
+    assert_eq!(parse_tracker_line("10.0.0.1"), Some(Ok("10.0.0.1".to_string())));
+    assert_eq!(parse_tracker_line("_dmarc.example.com"), Some(Ok("_dmarc.example.com".to_string())));
+}
+
+#[test]
+fn malformed_lines_are_skipped_and_counted_at_load() {
+    let blocker = blocker_with(&[
+        "# header",
+        "tracker.test",
+        "ads.example # added by hand",
+        "https://bad.example/path",
+        "two words.example",
+    ]);
+
+    assert_eq!(blocker.get_trackers(), vec!["ads.example", "tracker.test"]);
+    let skipped = blocker.skipped_lines();
+    assert_eq!(skipped.len(), 2);
+    assert_eq!(skipped[0].number, 4);
+    assert_eq!(skipped[0].line, "https://bad.example/path");
+    assert_eq!(skipped[1].number, 5);
+}
+
+#[test]
+fn import_reports_new_domains_and_duplicates() {
+    let mut blocker = blocker_with(&["tracker.test"]);
+    let list = common::temp_path("import.txt");
+    std::fs::create_dir_all(list.parent().unwrap()).unwrap();
+    std::fs::write(&list, "tracker.test\nzeta.example\nAds.Example\nnot a domain\n").unwrap();
+
+    let diff = blocker.import_trackers(&list).unwrap();
+
+    assert_eq!(diff.added, vec!["ads.example", "zeta.example"]);
+    assert_eq!(diff.duplicates, 1);
+    assert_eq!(diff.malformed, 1);
+    assert_eq!(diff.summary(), "Added 2 new domains, skipped 1 duplicates and 1 malformed lines");
+}
+
+#[test]
+fn explain_match_reports_exact_matches() {
+    use detrack_proxy::blocklist_source::MatchKind;
+    let blocker = blocker_with(&["doubleclick.net"]);
+
+    let explanation = blocker.explain_match("DoubleClick.net.").unwrap();
+    assert_eq!(explanation.host, "doubleclick.net");
+    assert_eq!(explanation.rule, "doubleclick.net");
+    assert_eq!(explanation.kind, MatchKind::Exact);
+    assert_eq!(explanation.to_string(), "doubleclick.net is on the blocklist");
+}
+
+#[test]
+fn explain_match_reports_subdomain_matches() {
+    use detrack_proxy::blocklist_source::MatchKind;
+    let blocker = blocker_with(&["doubleclick.net", "ads.doubleclick.net"]);
+
+    let explanation = blocker.explain_match("stats.g.doubleclick.net").unwrap();
+    assert_eq!(explanation.rule, "doubleclick.net");
+    assert_eq!(explanation.kind, MatchKind::Subdomain);
+    assert_eq!(explanation.to_string(), "stats.g.doubleclick.net is a subdomain of doubleclick.net");
+    // The most specific entry wins
+    assert_eq!(blocker.explain_match("x.ads.doubleclick.net").unwrap().rule, "ads.doubleclick.net");
+}
+
+#[test]
+fn explain_match_reports_top_level_domain_matches() {
+    use detrack_proxy::blocklist_source::MatchKind;
+    let blocker = blocker_with(&["zip"]);
+
+    let explanation = blocker.explain_match("download.zip").unwrap();
+    assert_eq!(explanation.rule, "zip");
+    assert_eq!(explanation.kind, MatchKind::TopLevelDomain);
+}
+
+#[test]
+fn explain_match_treats_wildcard_entries_as_subdomain_rules() {
+    use detrack_proxy::blocklist_source::MatchKind;
+    let blocker = blocker_with(&["*.tracker.test"]);
+
+    let explanation = blocker.explain_match("cdn.tracker.test").unwrap();
+    assert_eq!(explanation.rule, "tracker.test");
+    assert_eq!(explanation.kind, MatchKind::Subdomain);
+}
+
+#[test]
+fn explain_match_is_none_for_allowed_hosts() {
+    let blocker = blocker_with(&["doubleclick.net"]);
+
+    assert_eq!(blocker.explain_match("example.com"), None);
+    assert_eq!(blocker.explain_match("notdoubleclick.net"), None);
+}